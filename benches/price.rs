@@ -0,0 +1,20 @@
+use alloy::primitives::U256;
+use criterion::{criterion_group, criterion_main, Criterion};
+use std::hint::black_box;
+use std::str::FromStr;
+use uniswap_indexer::price::{calculate_pair_prices, sqrt_pair_prices_bigdecimal, sqrt_pair_prices_integer};
+
+// USDC/WETH at 2500, the shape of a typical mainnet swap
+fn price_paths(c: &mut Criterion) {
+    let sqrt = U256::from_str("1584563250285286751870879006720000").unwrap();
+    let diff = 6 - 18;
+
+    let mut group = c.benchmark_group("sqrt_price_x96");
+    group.bench_function("integer", |b| b.iter(|| sqrt_pair_prices_integer(black_box(sqrt), black_box(diff))));
+    group.bench_function("bigdecimal", |b| b.iter(|| sqrt_pair_prices_bigdecimal(black_box(sqrt), black_box(diff))));
+    group.bench_function("calculate_pair_prices", |b| b.iter(|| calculate_pair_prices(black_box(sqrt), black_box(diff))));
+    group.finish();
+}
+
+criterion_group!(benches, price_paths);
+criterion_main!(benches);
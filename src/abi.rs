@@ -0,0 +1,194 @@
+use alloy::sol;
+
+sol! {
+    event Swap(
+        address indexed sender,
+        address indexed recipient,
+        int256 amount0,
+        int256 amount1,
+        uint160 sqrtPriceX96,
+        uint128 liquidity,
+        int24 tick,
+    );
+
+    event Mint(
+        address sender,
+        address indexed owner,
+        int24 indexed tickLower,
+        int24 indexed tickUpper,
+        uint128 amount,
+        uint256 amount0,
+        uint256 amount1,
+    );
+
+    event Burn(
+        address indexed owner,
+        int24 indexed tickLower,
+        int24 indexed tickUpper,
+        uint128 amount,
+        uint256 amount0,
+        uint256 amount1,
+    );
+
+    event Collect(
+        address indexed owner,
+        address recipient,
+        int24 indexed tickLower,
+        int24 indexed tickUpper,
+        uint128 amount0,
+        uint128 amount1,
+    );
+
+    event Flash(
+        address indexed sender,
+        address indexed recipient,
+        uint256 amount0,
+        uint256 amount1,
+        uint256 paid0,
+        uint256 paid1,
+    );
+
+    event Initialize(uint160 sqrtPriceX96, int24 tick);
+
+    event SetFeeProtocol(
+        uint8 feeProtocol0Old,
+        uint8 feeProtocol1Old,
+        uint8 feeProtocol0New,
+        uint8 feeProtocol1New,
+    );
+
+    event CollectProtocol(
+        address indexed sender,
+        address indexed recipient,
+        uint128 amount0,
+        uint128 amount1,
+    );
+
+    // Factory: new pool deployed
+    event PoolCreated(
+        address indexed token0,
+        address indexed token1,
+        uint24 indexed fee,
+        int24 tickSpacing,
+        address pool,
+    );
+
+    // Interface: get token from Pull
+    #[sol(rpc)]
+    interface IUniswapV3Pool {
+        function token0() external view returns (address);
+        function token1() external view returns (address);
+        function fee() external view returns (uint24);
+        function tickSpacing() external view returns (int24);
+        function liquidity() external view returns (uint128);
+    }
+
+    // Interface: existing pool of a pair at a fee tier
+    #[sol(rpc)]
+    interface IUniswapV3Factory {
+        function getPool(address tokenA, address tokenB, uint24 fee) external view returns (address pool);
+    }
+
+    // Interface: get decimals
+    #[sol(rpc)]
+    interface IERC20 {
+        function decimals() external view returns (uint8);
+        function symbol() external view returns (string);
+        function name() external view returns (string);
+    }
+
+    // Interface: Chainlink USD price feed
+    #[sol(rpc)]
+    interface AggregatorV3Interface {
+        function decimals() external view returns (uint8);
+        function latestRoundData() external view returns (uint80 roundId, int256 answer, uint256 startedAt, uint256 updatedAt, uint80 answeredInRound);
+    }
+}
+
+// Uniswap V2 pair events, kept apart because the names clash with V3
+pub mod uniswap_v2 {
+    alloy::sol! {
+        event Swap(
+            address indexed sender,
+            uint256 amount0In,
+            uint256 amount1In,
+            uint256 amount0Out,
+            uint256 amount1Out,
+            address indexed to,
+        );
+
+        event Sync(uint112 reserve0, uint112 reserve1);
+    }
+}
+
+// Uniswap V4: every pool lives in the PoolManager singleton, keyed by PoolId
+pub mod uniswap_v4 {
+    alloy::sol! {
+        event Swap(
+            bytes32 indexed id,
+            address indexed sender,
+            int128 amount0,
+            int128 amount1,
+            uint160 sqrtPriceX96,
+            uint128 liquidity,
+            int24 tick,
+            uint24 fee,
+        );
+
+        // PoolId -> PoolKey lookup (keyed by the first 25 bytes of the id)
+        #[sol(rpc)]
+        interface IPositionManager {
+            function poolKeys(bytes25 poolId) external view returns (
+                address currency0,
+                address currency1,
+                uint24 fee,
+                int24 tickSpacing,
+                address hooks
+            );
+        }
+    }
+}
+
+// PancakeSwap V3 fork: Swap carries the protocol fees on top of the Uniswap layout
+pub mod pancake_v3 {
+    alloy::sol! {
+        event Swap(
+            address indexed sender,
+            address indexed recipient,
+            int256 amount0,
+            int256 amount1,
+            uint160 sqrtPriceX96,
+            uint128 liquidity,
+            int24 tick,
+            uint128 protocolFeesToken0,
+            uint128 protocolFeesToken1,
+        );
+    }
+}
+
+// NonfungiblePositionManager: liquidity changes per NFT position
+pub mod position_manager {
+    alloy::sol! {
+        event IncreaseLiquidity(uint256 indexed tokenId, uint128 liquidity, uint256 amount0, uint256 amount1);
+        event DecreaseLiquidity(uint256 indexed tokenId, uint128 liquidity, uint256 amount0, uint256 amount1);
+        event Collect(uint256 indexed tokenId, address recipient, uint256 amount0, uint256 amount1);
+
+        #[sol(rpc)]
+        interface INonfungiblePositionManager {
+            function positions(uint256 tokenId) external view returns (
+                uint96 nonce,
+                address operator,
+                address token0,
+                address token1,
+                uint24 fee,
+                int24 tickLower,
+                int24 tickUpper,
+                uint128 liquidity,
+                uint256 feeGrowthInside0LastX128,
+                uint256 feeGrowthInside1LastX128,
+                uint128 tokensOwed0,
+                uint128 tokensOwed1
+            );
+        }
+    }
+}
@@ -0,0 +1,275 @@
+use alloy::{
+    providers::Provider,
+    rpc::types::{Filter, Log},
+    transports::TransportError,
+};
+use eyre::Result;
+use futures_util::stream::{self, StreamExt};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+use tracing::{debug, info, warn};
+
+use crate::blocks::BlockTimes;
+use crate::config::IndexerConfig;
+use crate::indexer::{log_pool, pool_filters, LogHandler};
+use crate::metrics;
+use crate::pool::{PoolInfo, PoolRef};
+
+// Blocks per eth_getLogs call to start with, halved whenever the node refuses a range
+pub const DEFAULT_CHUNK_SIZE: u64 = 2000;
+// Chunks fetched at once. Results are handled in block order, so at most this many
+// chunks of at most MAX_CHUNK_LOGS logs each are held in memory
+pub const DEFAULT_PARALLELISM: usize = 4;
+pub const MAX_CHUNK_LOGS: usize = 10_000;
+// Other RPC errors are retried with exponential backoff before the backfill fails
+pub const MAX_RETRIES: u32 = 5;
+const RETRY_BASE_DELAY: Duration = Duration::from_millis(500);
+// A progress line every this many chunks
+pub const PROGRESS_EVERY_CHUNKS: u64 = 10;
+
+// BACKFILL_FROM_BLOCK..=BACKFILL_TO_BLOCK, replayed before the live subscription
+#[derive(Debug, Clone, Copy)]
+pub struct BackfillRange {
+    pub from: u64,
+    // None: the head at startup
+    pub to: Option<u64>,
+    pub chunk_size: u64,
+    pub parallelism: usize,
+}
+
+impl BackfillRange {
+    pub fn new(from: u64, to: Option<u64>) -> Self {
+        Self { from, to, chunk_size: DEFAULT_CHUNK_SIZE, parallelism: DEFAULT_PARALLELISM }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct BackfillStats {
+    // Last block of the range, the live stream can continue after it
+    pub last_block: u64,
+    pub logs: u64,
+}
+
+// Counters shared by the fetch workers and the ingesting loop
+struct Progress {
+    started: Instant,
+    total_blocks: u64,
+    rows_before: u64,
+    blocks_fetched: AtomicU64,
+    logs_fetched: AtomicU64,
+    blocks_done: AtomicU64,
+    logs_decoded: AtomicU64,
+    chunks_done: AtomicU64,
+}
+
+impl Progress {
+    fn new(from: u64, to: u64) -> Self {
+        Self {
+            started: Instant::now(),
+            total_blocks: (to + 1).saturating_sub(from),
+            rows_before: metrics::ROWS_INSERTED.get(),
+            blocks_fetched: AtomicU64::new(0),
+            logs_fetched: AtomicU64::new(0),
+            blocks_done: AtomicU64::new(0),
+            logs_decoded: AtomicU64::new(0),
+            chunks_done: AtomicU64::new(0),
+        }
+    }
+
+    // Structured fields for log processors, the message for humans
+    fn report(&self, message: &str) {
+        let elapsed = self.started.elapsed().as_secs_f64();
+        let blocks_done = self.blocks_done.load(Ordering::Relaxed);
+        let blocks_per_sec = if elapsed > 0.0 { blocks_done as f64 / elapsed } else { 0.0 };
+        let remaining = self.total_blocks.saturating_sub(blocks_done);
+        let eta = (blocks_per_sec > 0.0).then(|| Duration::from_secs_f64(remaining as f64 / blocks_per_sec));
+        let rows_inserted = metrics::ROWS_INSERTED.get().saturating_sub(self.rows_before);
+        let logs_decoded = self.logs_decoded.load(Ordering::Relaxed);
+
+        info!(
+            blocks_done,
+            total_blocks = self.total_blocks,
+            blocks_fetched = self.blocks_fetched.load(Ordering::Relaxed),
+            logs_fetched = self.logs_fetched.load(Ordering::Relaxed),
+            logs_decoded,
+            rows_inserted,
+            blocks_per_sec,
+            eta_secs = eta.map(|d| d.as_secs()),
+            "{} {}/{} blocks ({:.1}%), {} logs, {} rows inserted, {:.0} blocks/s, ETA {}",
+            message,
+            blocks_done,
+            self.total_blocks,
+            blocks_done as f64 * 100.0 / self.total_blocks.max(1) as f64,
+            logs_decoded,
+            rows_inserted,
+            blocks_per_sec,
+            eta.map_or("-".to_string(), |d| format!("{}s", d.as_secs())),
+        );
+    }
+}
+
+// Node errors that mean "ask for fewer blocks", the wording differs per provider
+fn range_too_large(e: &TransportError) -> bool {
+    let msg = e.to_string().to_lowercase();
+    [
+        "more than 10000 results",
+        "query returned more than",
+        "block range",
+        "range is too large",
+        "response size",
+        "limit exceeded",
+    ]
+    .iter()
+    .any(|pattern| msg.contains(pattern))
+}
+
+// Logs of every filter in from..=to, in chain order
+pub async fn logs_in_range<P: Provider>(provider: &P, filters: &[Filter], from: u64, to: u64) -> Result<Vec<Log>, TransportError> {
+    let mut logs = Vec::new();
+    for filter in filters {
+        logs.extend(provider.get_logs(&filter.clone().from_block(from).to_block(to)).await?);
+    }
+    // Filters can overlap (firehose and the configured pools), each log is kept once
+    logs.sort_by_key(|log| (log.block_number, log.log_index));
+    logs.dedup_by_key(|log| (log.block_number, log.log_index));
+    Ok(logs)
+}
+
+// One chunk, split in halves while the node refuses the range or it holds more than
+// MAX_CHUNK_LOGS. Splits lower `chunk_size` so later chunks start smaller
+async fn fetch_chunk<P: Provider>(
+    provider: &P,
+    filters: &[Filter],
+    from: u64,
+    to: u64,
+    chunk_size: &AtomicU64,
+    progress: &Progress,
+    block_times: &BlockTimes,
+) -> Result<Vec<Log>> {
+    // Stack of ranges still to fetch, the lower half on top so logs stay in order
+    let mut pending = vec![(from, to)];
+    let mut logs = Vec::new();
+    let mut attempt = 0;
+
+    while let Some((start, end)) = pending.pop() {
+        let split = match logs_in_range(provider, filters, start, end).await {
+            Ok(chunk) if chunk.len() > MAX_CHUNK_LOGS && end > start => true,
+            Ok(chunk) => {
+                progress.blocks_fetched.fetch_add(end - start + 1, Ordering::Relaxed);
+                progress.logs_fetched.fetch_add(chunk.len() as u64, Ordering::Relaxed);
+                logs.extend(chunk);
+                attempt = 0;
+                false
+            }
+            Err(e) if end > start && range_too_large(&e) => {
+                metrics::RPC_ERRORS.with_label_values(&["get_logs"]).inc();
+                warn!("⚠️ Range {}..={} too large, splitting it: {}", start, end, e);
+                true
+            }
+            Err(e) if attempt < MAX_RETRIES => {
+                metrics::RPC_ERRORS.with_label_values(&["get_logs"]).inc();
+                let delay = RETRY_BASE_DELAY * 2u32.pow(attempt);
+                attempt += 1;
+                warn!("⚠️ get_logs {}..={} failed (attempt {}), retrying in {:?}: {}", start, end, attempt, delay, e);
+                tokio::time::sleep(delay).await;
+                pending.push((start, end));
+                false
+            }
+            Err(e) => {
+                metrics::RPC_ERRORS.with_label_values(&["get_logs"]).inc();
+                return Err(e.into());
+            }
+        };
+
+        if split {
+            let mid = start + (end - start) / 2;
+            pending.push((mid + 1, end));
+            pending.push((start, mid));
+            chunk_size.fetch_min(mid - start + 1, Ordering::Relaxed);
+        }
+    }
+
+    // Headers for the whole chunk in one go instead of one request per log later
+    block_times.prefetch(&logs).await;
+    Ok(logs)
+}
+
+// Historical logs go through the same LogHandler as the live stream, so rows
+// are decoded, filtered and batched identically. With `resume`, only logs of
+// those pools past their checkpoint are replayed
+pub async fn backfill<P: Provider>(
+    provider: &P,
+    handler: &mut LogHandler,
+    pools: &mut HashMap<PoolRef, PoolInfo>,
+    config: &IndexerConfig,
+    range: BackfillRange,
+    resume: Option<&HashMap<PoolRef, u64>>,
+) -> Result<BackfillStats> {
+    // "latest" is resolved once, blocks mined during the backfill are left to the live stream
+    let to = match range.to {
+        Some(to) => to,
+        None => provider.get_block_number().await?,
+    };
+
+    let filters = pool_filters(pools, config);
+    if filters.is_empty() {
+        warn!("⚠️ Nothing to backfill, no pools configured");
+        return Ok(BackfillStats { last_block: to, logs: 0 });
+    }
+
+    info!("⏪ Backfilling blocks {}..={} ({} chunks at a time)", range.from, to, range.parallelism);
+    let chunk_size = AtomicU64::new(range.chunk_size.max(1));
+    let mut next = range.from;
+    let ranges = std::iter::from_fn(|| {
+        if next > to {
+            return None;
+        }
+        let end = next.saturating_add(chunk_size.load(Ordering::Relaxed) - 1).min(to);
+        let chunk = (next, end);
+        next = end.saturating_add(1);
+        Some(chunk)
+    });
+
+    // Fetched concurrently, yielded in range order
+    let progress = Progress::new(range.from, to);
+    let block_times = handler.block_times.clone();
+    let (filters, chunk_hint, shared, block_times) = (&filters, &chunk_size, &progress, &*block_times);
+    let mut chunks = stream::iter(ranges)
+        .map(|(start, end)| async move {
+            (start, end, fetch_chunk(provider, filters, start, end, chunk_hint, shared, block_times).await)
+        })
+        .buffered(range.parallelism.max(1));
+
+    let mut handled = 0;
+    while let Some((start, end, logs)) = chunks.next().await {
+        let logs = logs?;
+        let count = logs.len();
+        for log in logs {
+            if let Some(resume) = resume {
+                let checkpoint = log_pool(&log, config).and_then(|pool| resume.get(&pool));
+                let past_checkpoint = match (checkpoint, log.block_number) {
+                    (Some(&checkpoint), Some(block)) => block > checkpoint,
+                    _ => false,
+                };
+                if !past_checkpoint {
+                    continue;
+                }
+            }
+            if !handler.handle(pools, log).await {
+                eyre::bail!("Writer channel closed during backfill");
+            }
+            handled += 1;
+            progress.logs_decoded.fetch_add(1, Ordering::Relaxed);
+        }
+        debug!("⏪ Blocks {}..={}: {} logs", start, end, count);
+
+        progress.blocks_done.fetch_add(end - start + 1, Ordering::Relaxed);
+        if progress.chunks_done.fetch_add(1, Ordering::Relaxed) % PROGRESS_EVERY_CHUNKS == PROGRESS_EVERY_CHUNKS - 1 {
+            progress.report("⏳ Backfill");
+        }
+    }
+
+    progress.report("✅ Backfill done:");
+    Ok(BackfillStats { last_block: to, logs: handled })
+}
@@ -0,0 +1,78 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+use crate::records::IndexedEvent;
+
+// Default for BATCH_MAX_AGE_SECONDS
+pub const DEFAULT_BATCH_MAX_AGE: Duration = Duration::from_secs(5);
+// ASYNC_INSERT: most rows one insert carries, the writer otherwise sends what it has right away
+pub const ASYNC_INSERT_BATCH_SIZE: usize = 1000;
+
+// The writer's buffer: flushed once it holds BATCH_SIZE rows or its oldest row has waited
+// BATCH_MAX_AGE_SECONDS, whichever comes first. A quiet pool's rows don't sit in memory for hours
+#[derive(Debug)]
+pub struct Batch {
+    pub events: Vec<IndexedEvent>,
+    max_rows: usize,
+    max_age: Duration,
+    // When the first row of the current batch arrived, None while empty
+    oldest: Option<Instant>,
+}
+
+impl Batch {
+    pub fn new(max_rows: usize, max_age: Duration) -> Self {
+        let max_rows = max_rows.max(1);
+        Self { events: Vec::with_capacity(max_rows), max_rows, max_age, oldest: None }
+    }
+
+    pub fn push(&mut self, event: IndexedEvent, now: Instant) {
+        self.oldest.get_or_insert(now);
+        self.events.push(event);
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.events.len() >= self.max_rows
+    }
+
+    // When the batch has to go out even if it isn't full, None while empty
+    pub fn deadline(&self) -> Option<Instant> {
+        if self.events.is_empty() {
+            return None;
+        }
+        self.oldest.map(|oldest| oldest + self.max_age)
+    }
+
+    pub fn is_due(&self, now: Instant) -> bool {
+        self.is_full() || self.deadline().is_some_and(|deadline| now >= deadline)
+    }
+
+    // The rows to flush; the age restarts with the next push
+    pub fn take(&mut self) -> Vec<IndexedEvent> {
+        self.oldest = None;
+        std::mem::replace(&mut self.events, Vec::with_capacity(self.max_rows))
+    }
+}
+
+// Last version handed out by next_insert_version
+static INSERT_VERSION: AtomicU64 = AtomicU64::new(0);
+
+// Wall-clock micros, strictly above every earlier version of this process even if the clock
+// steps back. Another process (a parallel backfill) can tie: ReplacingMergeTree then keeps
+// either row, which are the same swap
+pub fn next_insert_version() -> u64 {
+    let now = chrono::Utc::now().timestamp_micros().max(0) as u64;
+    let previous = INSERT_VERSION.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |last| Some(now.max(last + 1))).unwrap_or_default();
+    now.max(previous + 1)
+}
+
+// uniswap_swaps is a ReplacingMergeTree(insert_version) on (chain_id, pool_address, tx_hash,
+// log_index): a swap written twice (replayed, gap-filled or backfilled again) collapses to the
+// copy of the newest batch. Stamped once per batch, after the derived columns are filled in
+pub fn stamp_insert_version(batch: &mut [IndexedEvent]) {
+    let version = next_insert_version();
+    for event in batch {
+        if let IndexedEvent::Swap(swap) = event {
+            swap.insert_version = version;
+        }
+    }
+}
@@ -0,0 +1,101 @@
+use alloy::{
+    eips::BlockNumberOrTag,
+    providers::{DynProvider, Provider},
+    rpc::types::Log,
+};
+use eyre::Result;
+use futures_util::stream::{self, StreamExt};
+use lru::LruCache;
+use std::collections::BTreeSet;
+use std::num::NonZeroUsize;
+use std::sync::Mutex;
+use tracing::warn;
+
+use crate::metrics;
+use crate::rpc::http_provider;
+
+// Default for BLOCK_CACHE_SIZE, a few parallel backfill chunks worth of blocks
+pub const BLOCK_CACHE_SIZE: usize = 10_000;
+// Header requests in flight during a prefetch
+pub const HEADER_FETCH_CONCURRENCY: usize = 16;
+
+// Block timestamps shared by the live stream and backfill, bounded so a long run
+// doesn't grow forever
+pub struct BlockTimes {
+    // None when replaying, logs without a timestamp stay without one
+    provider: Option<DynProvider>,
+    cache: Mutex<LruCache<u64, u64>>,
+}
+
+impl BlockTimes {
+    pub fn new(http_url: &str, capacity: usize) -> Result<Self> {
+        Ok(Self { provider: Some(http_provider(http_url)?), ..Self::offline(capacity) })
+    }
+
+    pub fn offline(capacity: usize) -> Self {
+        let capacity = NonZeroUsize::new(capacity).unwrap_or(NonZeroUsize::MIN);
+        Self { provider: None, cache: Mutex::new(LruCache::new(capacity)) }
+    }
+
+    fn cached(&self, number: u64) -> Option<u64> {
+        let ts = self.cache.lock().unwrap().get(&number).copied();
+        match ts {
+            Some(_) => metrics::BLOCK_CACHE_HITS.inc(),
+            None => metrics::BLOCK_CACHE_MISSES.inc(),
+        }
+        ts
+    }
+
+    async fn fetch(&self, number: u64) -> Option<u64> {
+        let provider = self.provider.as_ref()?;
+        let block = match provider.get_block_by_number(BlockNumberOrTag::Number(number)).await {
+            Ok(Some(block)) => block,
+            Ok(None) => {
+                warn!("⚠️ Block {} not found", number);
+                return None;
+            }
+            Err(e) => {
+                metrics::RPC_ERRORS.with_label_values(&["block"]).inc();
+                warn!("⚠️ Failed to fetch block {}: {:?}", number, e);
+                return None;
+            }
+        };
+
+        let ts = block.header.timestamp;
+        self.cache.lock().unwrap().put(number, ts);
+        Some(ts)
+    }
+
+    // Block timestamp (seconds) of a log: from the log itself when the provider
+    // sets it, otherwise from the block header
+    pub async fn resolve(&self, log: &Log) -> Option<u64> {
+        if let Some(ts) = log.block_timestamp {
+            return Some(ts);
+        }
+
+        let number = log.block_number?;
+        if let Some(ts) = self.cached(number) {
+            return Some(ts);
+        }
+        self.fetch(number).await
+    }
+
+    // Headers of every block in `logs` that isn't cached yet, fetched concurrently
+    // so the following resolve() calls are all hits
+    pub async fn prefetch(&self, logs: &[Log]) {
+        let missing: BTreeSet<u64> = {
+            let cache = self.cache.lock().unwrap();
+            logs.iter()
+                .filter(|log| log.block_timestamp.is_none())
+                .filter_map(|log| log.block_number)
+                .filter(|number| !cache.contains(number))
+                .collect()
+        };
+
+        stream::iter(missing)
+            .map(|number| self.fetch(number))
+            .buffer_unordered(HEADER_FETCH_CONCURRENCY)
+            .for_each(|_| async {})
+            .await;
+    }
+}
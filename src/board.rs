@@ -0,0 +1,62 @@
+use alloy::primitives::Address;
+use std::sync::atomic::{AtomicI64, AtomicU64, Ordering};
+use std::time::Duration;
+
+use crate::pool::PoolSpec;
+
+// Default for REFERENCE_MAX_AGE_SECONDS
+pub const DEFAULT_REFERENCE_MAX_AGE: Duration = Duration::from_secs(300);
+
+// Latest USD price of the reference pool's base token (WETH in WETH/USDC), written by the
+// decoder on every reference swap and read by every other pool's. Two atomics instead of a
+// lock: a reader can pair a price with the next swap's time, both are one swap apart
+#[derive(Debug)]
+pub struct PriceBoard {
+    price: AtomicU64,
+    // Block time of that price in ms, 0 before the first reference swap
+    updated_at: AtomicI64,
+}
+
+impl PriceBoard {
+    pub const fn new() -> Self {
+        Self { price: AtomicU64::new(0), updated_at: AtomicI64::new(0) }
+    }
+
+    // Older swaps than the current price (a backfill behind the live stream) don't replace it
+    pub fn update(&self, price: f64, timestamp: i64) {
+        if !price.is_finite() || price <= 0.0 || timestamp < self.updated_at.load(Ordering::Acquire) {
+            return;
+        }
+        self.price.store(price.to_bits(), Ordering::Release);
+        self.updated_at.store(timestamp, Ordering::Release);
+    }
+
+    // The price for a row at `timestamp` (ms), None when it is further than max_age from it
+    pub fn get(&self, timestamp: i64, max_age: Duration) -> Option<f64> {
+        let updated_at = self.updated_at.load(Ordering::Acquire);
+        if updated_at == 0 || updated_at.abs_diff(timestamp) > max_age.as_millis() as u64 {
+            return None;
+        }
+        Some(f64::from_bits(self.price.load(Ordering::Acquire)))
+    }
+}
+
+impl Default for PriceBoard {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// One reference pool per process
+pub static BOARD: PriceBoard = PriceBoard::new();
+
+// REFERENCE_POOL: a stable-quoted pool whose base token prices the other pools holding it,
+// indexed even when POOL_ADDRESSES doesn't list it
+#[derive(Debug, Clone)]
+pub struct ReferencePool {
+    pub spec: PoolSpec,
+    // REFERENCE_TOKEN, the reference pool's non-stable token
+    pub token: Address,
+    // REFERENCE_MAX_AGE_SECONDS
+    pub max_age: Duration,
+}
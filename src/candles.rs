@@ -0,0 +1,155 @@
+use std::collections::{BTreeMap, HashMap};
+use std::time::Duration;
+use tracing::warn;
+
+use crate::records::{CandleRecord, IndexedEvent, SCHEMA_VERSION};
+
+pub const CANDLE_MS: i64 = 60_000;
+// Default for CANDLE_GRACE_SECONDS
+pub const DEFAULT_CANDLE_GRACE: Duration = Duration::from_secs(30);
+// Flat candles carried forward in a row at most, a longer silence jumps to the next swap
+pub const MAX_FILLED_MINUTES: i64 = 1440;
+
+// CANDLES_1M / CANDLE_GRACE_SECONDS / CANDLES_FILL_GAPS
+#[derive(Debug, Clone, Default)]
+pub struct CandleSettings {
+    pub enabled: bool,
+    // A minute stays open this long past its end for late logs
+    pub grace: Duration,
+    // Minutes without swaps get a flat candle at the previous close
+    pub fill_gaps: bool,
+}
+
+#[derive(Debug, Clone)]
+struct Candle {
+    open: f64,
+    high: f64,
+    low: f64,
+    close: f64,
+    volume0: f64,
+    volume1: f64,
+    volume_usd: Option<f64>,
+    swaps: u64,
+}
+
+impl Candle {
+    fn flat(price: f64) -> Self {
+        Self { open: price, high: price, low: price, close: price, volume0: 0.0, volume1: 0.0, volume_usd: None, swaps: 0 }
+    }
+}
+
+#[derive(Debug, Default)]
+struct PoolCandles {
+    chain_id: u64,
+    open: BTreeMap<i64, Candle>,
+    // Minute and close of the last candle written
+    emitted: Option<(i64, f64)>,
+}
+
+// 1-minute OHLCV of price_usd per pool, applied by the writer. A minute is written once
+// the stream's block time is `grace` past its end, a swap for a minute already written
+// is dropped with a warning. Pools without a price_usd get no candles
+#[derive(Debug, Default)]
+pub struct Candles {
+    settings: CandleSettings,
+    pools: HashMap<String, PoolCandles>,
+    now: i64,
+}
+
+impl Candles {
+    pub fn new(settings: CandleSettings) -> Self {
+        Self { settings, ..Self::default() }
+    }
+
+    // The uniswap_candles_1m rows the batch finishes
+    pub fn apply(&mut self, batch: &[IndexedEvent]) -> Vec<IndexedEvent> {
+        let mut rows = Vec::new();
+        if !self.settings.enabled {
+            return rows;
+        }
+
+        for event in batch {
+            let IndexedEvent::Swap(swap) = event else { continue };
+            let Some(price) = swap.price_usd else { continue };
+            let at = swap.timestamp.timestamp_millis();
+            let minute = at - at.rem_euclid(CANDLE_MS);
+
+            let pool = self.pools.entry(swap.pool_address.clone()).or_default();
+            pool.chain_id = swap.chain_id;
+            if pool.emitted.is_some_and(|(m, _)| minute <= m) {
+                warn!("⚠️ Swap {}#{} is past its candle's grace period, left out", swap.tx_hash, swap.log_index);
+                continue;
+            }
+
+            let candle = pool.open.entry(minute).or_insert_with(|| Candle::flat(price));
+            candle.high = candle.high.max(price);
+            candle.low = candle.low.min(price);
+            candle.close = price;
+            candle.volume0 += swap.amount0.unwrap_or_default().abs();
+            candle.volume1 += swap.amount1.unwrap_or_default().abs();
+            if let Some(volume) = swap.volume_usd {
+                candle.volume_usd = Some(candle.volume_usd.unwrap_or_default() + volume);
+            }
+            candle.swaps += 1;
+            self.now = self.now.max(at);
+        }
+
+        let ready = self.now - CANDLE_MS - self.settings.grace.as_millis() as i64;
+        for (address, pool) in &mut self.pools {
+            let chain_id = pool.chain_id;
+            finish(pool, ready, self.settings.fill_gaps, |minute, candle| rows.push(candle_row(chain_id, address, minute, candle)));
+        }
+        rows
+    }
+}
+
+// Candles of minutes starting at or before `ready`, in minute order
+fn finish(pool: &mut PoolCandles, ready: i64, fill_gaps: bool, mut emit: impl FnMut(i64, &Candle)) {
+    let mut filled = 0;
+    loop {
+        let next_open = pool.open.keys().next().copied();
+        let next_fill = pool.emitted.filter(|_| fill_gaps).map(|(m, _)| m + CANDLE_MS);
+        let Some(minute) = [next_open, next_fill].into_iter().flatten().min() else { break };
+        if minute > ready {
+            break;
+        }
+
+        let candle = match pool.open.remove(&minute) {
+            Some(candle) => {
+                filled = 0;
+                candle
+            }
+            None => {
+                let (_, close) = pool.emitted.unwrap_or_default();
+                filled += 1;
+                if filled > MAX_FILLED_MINUTES {
+                    // Skip to just before the next swap's minute, or the last ready one
+                    let resume = next_open.map_or(ready - ready.rem_euclid(CANDLE_MS), |m| m - CANDLE_MS);
+                    pool.emitted = Some((resume, close));
+                    filled = 0;
+                    continue;
+                }
+                Candle::flat(close)
+            }
+        };
+        pool.emitted = Some((minute, candle.close));
+        emit(minute, &candle);
+    }
+}
+
+fn candle_row(chain_id: u64, pool: &str, minute: i64, candle: &Candle) -> IndexedEvent {
+    IndexedEvent::Candle(CandleRecord {
+        chain_id,
+        schema_version: SCHEMA_VERSION,
+        pool_address: pool.to_string(),
+        minute,
+        open: candle.open,
+        high: candle.high,
+        low: candle.low,
+        close: candle.close,
+        volume0: candle.volume0,
+        volume1: candle.volume1,
+        volume_usd: candle.volume_usd,
+        swaps: candle.swaps,
+    })
+}
@@ -0,0 +1,115 @@
+use alloy::primitives::Address;
+use bigdecimal::{BigDecimal, Zero};
+use std::collections::HashMap;
+use std::sync::{OnceLock, RwLock};
+use std::time::Duration;
+use tracing::{info, warn};
+
+use crate::abi::AggregatorV3Interface;
+use crate::metrics;
+use crate::price::adjust_signed_amount;
+use crate::rpc::http_provider;
+
+// Defaults for CHAINLINK_POLL_SECONDS / CHAINLINK_MAX_AGE_SECONDS, ETH/USD's heartbeat is an hour
+pub const DEFAULT_POLL_INTERVAL: Duration = Duration::from_secs(60);
+pub const DEFAULT_MAX_AGE: Duration = Duration::from_secs(3600);
+
+// CHAINLINK_FEEDS: token -> its USD aggregator
+#[derive(Debug, Clone)]
+pub struct ChainlinkFeeds {
+    pub feeds: HashMap<Address, Address>,
+    pub poll: Duration,
+    // A round older than this (or newer, for backfilled swaps) counts as missing
+    pub max_age: Duration,
+}
+
+// Latest answer of a feed, in USD
+#[derive(Debug, Clone, PartialEq)]
+pub struct FeedPrice {
+    pub usd: BigDecimal,
+    // updatedAt of the round, unix seconds
+    pub updated_at: u64,
+}
+
+// Written by the poller, read by the decoders: a lock held for a map lookup, never across a call
+#[derive(Debug)]
+pub struct PriceCache {
+    prices: RwLock<HashMap<Address, FeedPrice>>,
+    max_age: u64,
+}
+
+impl PriceCache {
+    pub fn new(max_age: Duration) -> Self {
+        Self { prices: RwLock::new(HashMap::new()), max_age: max_age.as_secs() }
+    }
+
+    pub fn insert(&self, token: Address, price: FeedPrice) {
+        self.prices.write().unwrap_or_else(|e| e.into_inner()).insert(token, price);
+    }
+
+    // USD price of the token at `at` (unix seconds), None when the round is too far from it
+    pub fn get(&self, token: Address, at: u64) -> Option<BigDecimal> {
+        let prices = self.prices.read().unwrap_or_else(|e| e.into_inner());
+        let price = prices.get(&token)?;
+        (price.updated_at.abs_diff(at) <= self.max_age).then(|| price.usd.clone())
+    }
+}
+
+static CACHE: OnceLock<PriceCache> = OnceLock::new();
+
+// None until the poller runs, and for tokens without a fresh feed
+pub fn usd_price(token: Address, at: u64) -> Option<BigDecimal> {
+    CACHE.get()?.get(token, at)
+}
+
+// Starts the poller. Reads go through the throttled HTTP provider, one round of
+// latestRoundData per feed every poll interval
+pub fn spawn(http_url: &str, feeds: &ChainlinkFeeds) {
+    let cache = CACHE.get_or_init(|| PriceCache::new(feeds.max_age));
+    let http_url = http_url.to_string();
+    let feeds = feeds.clone();
+    info!("🔗 Polling {} Chainlink feed(s) every {:?}", feeds.feeds.len(), feeds.poll);
+    tokio::spawn(async move { poll(&http_url, &feeds, cache).await });
+}
+
+async fn poll(http_url: &str, feeds: &ChainlinkFeeds, cache: &PriceCache) {
+    let mut decimals: HashMap<Address, u8> = HashMap::new();
+    let mut interval = tokio::time::interval(feeds.poll);
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    loop {
+        interval.tick().await;
+        for (&token, &feed) in &feeds.feeds {
+            match read_feed(http_url, feed, &mut decimals).await {
+                Ok(Some(price)) => cache.insert(token, price),
+                Ok(None) => {
+                    metrics::FEED_ERRORS.inc();
+                    warn!("⚠️ Chainlink feed {:?} for {:?} answered a non-positive price", feed, token);
+                }
+                // The previous round stays cached until it goes stale
+                Err(e) => {
+                    metrics::FEED_ERRORS.inc();
+                    warn!("⚠️ Chainlink feed {:?} for {:?} failed: {}", feed, token, e);
+                }
+            }
+        }
+    }
+}
+
+async fn read_feed(http_url: &str, feed: Address, decimals: &mut HashMap<Address, u8>) -> eyre::Result<Option<FeedPrice>> {
+    let aggregator = AggregatorV3Interface::new(feed, http_provider(http_url)?);
+    let feed_decimals = match decimals.get(&feed) {
+        Some(d) => *d,
+        None => {
+            let d = aggregator.decimals().call().await?;
+            decimals.insert(feed, d);
+            d
+        }
+    };
+
+    let round = aggregator.latestRoundData().call().await?;
+    let usd = adjust_signed_amount(round.answer, feed_decimals);
+    if usd <= BigDecimal::zero() {
+        return Ok(None);
+    }
+    Ok(Some(FeedPrice { usd, updated_at: round.updatedAt.saturating_to() }))
+}
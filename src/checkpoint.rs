@@ -0,0 +1,183 @@
+use clickhouse::{Client, Row};
+use eyre::{Result, WrapErr};
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::PathBuf;
+use tracing::{info, warn};
+
+use crate::postgres::Postgres;
+use crate::records::IndexedEvent;
+
+pub const CHECKPOINT_TABLE: &str = "indexer_checkpoints";
+
+// CHECKPOINT_STORE: where the last inserted block per pool is kept
+#[derive(Debug, Clone)]
+pub enum CheckpointStore {
+    // indexer_checkpoints, in the SINK database
+    Table,
+    // "<pool_address> <block_number>" per line
+    File(PathBuf),
+}
+
+// The database of CheckpointStore::Table
+#[derive(Clone)]
+pub enum CheckpointDb {
+    ClickHouse(Box<Client>),
+    Postgres(Postgres),
+}
+
+#[derive(Debug, Serialize, Row)]
+struct CheckpointRow {
+    chain_id: u64,
+    pool_address: String,
+    block_number: u64,
+    updated_at: i64,
+}
+
+// Highest block per pool whose rows are all in the sink, the resume backfill starts after it.
+// Only advanced after a successful flush; a pool whose batch failed stops advancing until restart, so
+// the resume backfill replays the rows that were dropped
+pub struct Checkpoints {
+    store: CheckpointStore,
+    db: Option<CheckpointDb>,
+    chain_id: u64,
+    blocks: HashMap<String, u64>,
+    // Last block per pool in the sink, which the next batch may still add rows to
+    tail: HashMap<String, u64>,
+    frozen: HashSet<String>,
+}
+
+impl Checkpoints {
+    // `db` is None with SINK=file, which only has the file store
+    pub async fn load(store: CheckpointStore, db: Option<CheckpointDb>, chain_id: u64) -> Result<Self> {
+        let blocks = match (&store, &db) {
+            (CheckpointStore::Table, None) => eyre::bail!("CHECKPOINT_STORE needs a database sink"),
+            (CheckpointStore::Table, Some(CheckpointDb::Postgres(postgres))) => postgres.load_checkpoints(chain_id).await?,
+            (CheckpointStore::Table, Some(CheckpointDb::ClickHouse(client))) => {
+                client
+                    .query(&format!(
+                        "CREATE TABLE IF NOT EXISTS {} (chain_id UInt64, pool_address String, block_number UInt64, updated_at DateTime64(3)) \
+                         ENGINE = ReplacingMergeTree(block_number) ORDER BY (chain_id, pool_address)",
+                        CHECKPOINT_TABLE
+                    ))
+                    .execute()
+                    .await
+                    .wrap_err_with(|| format!("Failed to create {}", CHECKPOINT_TABLE))?;
+
+                let rows: Vec<(String, u64)> = client
+                    .query(&format!("SELECT pool_address, max(block_number) FROM {} WHERE chain_id = ? GROUP BY pool_address", CHECKPOINT_TABLE))
+                    .bind(chain_id)
+                    .fetch_all()
+                    .await
+                    .wrap_err_with(|| format!("Failed to read {}", CHECKPOINT_TABLE))?;
+                rows.into_iter().collect()
+            }
+            (CheckpointStore::File(path), _) => match fs::read_to_string(path) {
+                Ok(contents) => parse_checkpoint_file(&contents)?,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => HashMap::new(),
+                Err(e) => return Err(e).wrap_err_with(|| format!("Failed to read {}", path.display())),
+            },
+        };
+
+        info!("📍 Loaded {} checkpoint(s)", blocks.len());
+        Ok(Self { store, db, chain_id, blocks, tail: HashMap::new(), frozen: HashSet::new() })
+    }
+
+    pub fn get(&self, pool_address: &str) -> Option<u64> {
+        self.blocks.get(pool_address).copied()
+    }
+
+    // Highest block per pool in a batch that is about to be flushed
+    pub fn touched(batch: &[IndexedEvent]) -> HashMap<String, u64> {
+        let mut touched: HashMap<String, u64> = HashMap::new();
+        for (pool, block) in batch.iter().filter_map(IndexedEvent::checkpoint) {
+            let entry = touched.entry(pool.to_string()).or_default();
+            *entry = (*entry).max(block);
+        }
+        touched
+    }
+
+    pub fn freeze(&mut self, touched: HashMap<String, u64>) {
+        for pool in touched.into_keys() {
+            if self.frozen.insert(pool.clone()) {
+                warn!("⚠️ Checkpoint of {} frozen at {:?} after a failed flush", pool, self.get(&pool));
+            }
+        }
+    }
+
+    // After a successful flush. A pool's rows reach the writer in log order, so every block
+    // before its last one in the batch is complete; that one may go on in the next batch and
+    // waits in `tail` for a later block or `finish`. A failed save only leaves the stored
+    // checkpoint behind
+    pub async fn advance(&mut self, touched: HashMap<String, u64>) {
+        let mut complete = HashMap::new();
+        for (pool, block) in touched {
+            if self.frozen.contains(&pool) {
+                continue;
+            }
+            let tail = self.tail.entry(pool.clone()).or_default();
+            *tail = (*tail).max(block);
+            complete.insert(pool, block.saturating_sub(1));
+        }
+        self.save_changed(complete).await;
+    }
+
+    // Once the writer's channel is closed and everything is flushed: nothing can join the
+    // pools' last blocks any more
+    pub async fn finish(&mut self) {
+        let tail = std::mem::take(&mut self.tail).into_iter().filter(|(pool, _)| !self.frozen.contains(pool)).collect();
+        self.save_changed(tail).await;
+    }
+
+    async fn save_changed(&mut self, blocks: HashMap<String, u64>) {
+        let mut changed = Vec::new();
+        for (pool, block) in blocks {
+            if self.get(&pool).is_some_and(|b| b >= block) {
+                continue;
+            }
+            self.blocks.insert(pool.clone(), block);
+            changed.push((pool, block));
+        }
+        if changed.is_empty() {
+            return;
+        }
+
+        if let Err(e) = self.save(changed).await {
+            warn!("⚠️ Failed to save checkpoints: {:?}", e);
+        }
+    }
+
+    async fn save(&self, changed: Vec<(String, u64)>) -> Result<()> {
+        match (&self.store, &self.db) {
+            (CheckpointStore::Table, None) => eyre::bail!("CHECKPOINT_STORE needs a database sink"),
+            (CheckpointStore::Table, Some(CheckpointDb::Postgres(postgres))) => postgres.save_checkpoints(self.chain_id, &changed).await?,
+            (CheckpointStore::Table, Some(CheckpointDb::ClickHouse(client))) => {
+                let mut insert = client.insert::<CheckpointRow>(CHECKPOINT_TABLE).await?;
+                let now = chrono::Utc::now().timestamp_millis();
+                for (pool_address, block_number) in changed {
+                    insert.write(&CheckpointRow { chain_id: self.chain_id, pool_address, block_number, updated_at: now }).await?;
+                }
+                insert.end().await?;
+            }
+            (CheckpointStore::File(path), _) => {
+                let mut lines: Vec<String> = self.blocks.iter().map(|(pool, block)| format!("{} {}", pool, block)).collect();
+                lines.sort();
+                // Written aside and renamed, so a crash never leaves a truncated file
+                let tmp = path.with_extension("tmp");
+                fs::write(&tmp, lines.join("\n") + "\n")?;
+                fs::rename(&tmp, path)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn parse_checkpoint_file(contents: &str) -> Result<HashMap<String, u64>> {
+    let mut blocks = HashMap::new();
+    for line in contents.lines().map(str::trim).filter(|l| !l.is_empty()) {
+        let (pool, block) = line.split_once(' ').ok_or_else(|| eyre::eyre!("Invalid checkpoint line '{}'", line))?;
+        blocks.insert(pool.to_string(), block.trim().parse()?);
+    }
+    Ok(blocks)
+}
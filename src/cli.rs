@@ -0,0 +1,101 @@
+use clap::{Parser, Subcommand};
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use crate::backfill::{DEFAULT_CHUNK_SIZE, DEFAULT_PARALLELISM};
+use crate::stdout::StdoutMode;
+
+// Everything else is configured through the environment (.env)
+#[derive(Debug, Parser)]
+#[command(name = "uniswap-indexer", version, about = "Uniswap event indexer")]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+    /// Decode a captured JSONL log file instead of connecting to the RPC
+    #[arg(long, value_name = "FILE", conflicts_with = "capture")]
+    pub replay: Option<PathBuf>,
+    /// Print rows as JSON lines, logs go to stderr: `only` (the default) instead of writing
+    /// them to the sink, `also` next to it
+    #[arg(long, value_name = "MODE", num_args = 0..=1, require_equals = true, default_missing_value = "only")]
+    pub stdout: Option<StdoutMode>,
+    /// Tee every raw log and the metadata of its pool to a JSONL file, for --replay
+    #[arg(long, value_name = "FILE")]
+    pub capture: Option<PathBuf>,
+    /// Blocks a live record waits below the head before it is written, 0 writes
+    /// immediately (overrides CONFIRMATIONS, default 3)
+    #[arg(long, value_name = "N")]
+    pub confirmations: Option<u64>,
+    /// Create missing tables and columns at startup (same as CREATE_TABLES=true)
+    #[arg(long)]
+    pub migrate: bool,
+    /// Log the NATS subjects and payloads swaps would be published with, no server needed
+    /// (same as NATS_DRY_RUN=true)
+    #[arg(long)]
+    pub nats_dry_run: bool,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Follow the live stream (the default)
+    Run,
+    /// Ingest a block range with eth_getLogs, flush it and exit
+    Backfill {
+        #[arg(long)]
+        from_block: u64,
+        /// Block number or "latest", resolved once at start
+        #[arg(long, default_value = "latest")]
+        to_block: ToBlock,
+        /// Blocks per eth_getLogs call, halved when the node rejects a range
+        #[arg(long, default_value_t = DEFAULT_CHUNK_SIZE)]
+        chunk_size: u64,
+        /// Chunks fetched concurrently, still ingested in block order
+        #[arg(long, default_value_t = DEFAULT_PARALLELISM)]
+        parallelism: usize,
+    },
+    /// Compare Swap logs from eth_getLogs with uniswap_swaps rows, exit non-zero on a mismatch
+    Verify {
+        #[arg(long)]
+        from_block: u64,
+        /// Block number or "latest", resolved once at start
+        #[arg(long, default_value = "latest")]
+        to_block: ToBlock,
+        /// Blocks per eth_getLogs call and count query
+        #[arg(long, default_value_t = DEFAULT_CHUNK_SIZE)]
+        chunk_size: u64,
+        /// Re-ingest the swaps that have no row
+        #[arg(long)]
+        repair: bool,
+    },
+    /// Compare the ClickHouse tables with the schema this version writes, exit non-zero on drift
+    VerifySchema,
+    /// Copy tables with Int64 millisecond timestamps into DateTime64(3) ones, keeping each
+    /// original as <table>_int64. Stop the indexer first
+    ConvertTimestamps,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum ToBlock {
+    Number(u64),
+    Latest,
+}
+
+impl ToBlock {
+    // None for "latest"
+    pub fn number(self) -> Option<u64> {
+        match self {
+            ToBlock::Number(n) => Some(n),
+            ToBlock::Latest => None,
+        }
+    }
+}
+
+impl FromStr for ToBlock {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.eq_ignore_ascii_case("latest") {
+            return Ok(ToBlock::Latest);
+        }
+        s.parse().map(ToBlock::Number).map_err(|_| format!("expected a block number or \"latest\", got '{}'", s))
+    }
+}
@@ -0,0 +1,1117 @@
+use alloy::{
+    primitives::{Address, B256},
+    providers::Provider,
+};
+use clickhouse::Compression;
+use eyre::Result;
+use std::collections::{HashMap, HashSet};
+use std::env;
+use std::fmt;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
+use tracing::warn;
+
+use crate::backfill::{BackfillRange, DEFAULT_CHUNK_SIZE, DEFAULT_PARALLELISM};
+use crate::batch::{ASYNC_INSERT_BATCH_SIZE, DEFAULT_BATCH_MAX_AGE};
+use crate::blocks::BLOCK_CACHE_SIZE;
+use crate::candles::{CandleSettings, DEFAULT_CANDLE_GRACE};
+use crate::board::{ReferencePool, DEFAULT_REFERENCE_MAX_AGE};
+use crate::chainlink::{ChainlinkFeeds, DEFAULT_MAX_AGE, DEFAULT_POLL_INTERVAL};
+use crate::checkpoint::CheckpointStore;
+use crate::confirmations::DEFAULT_CONFIRMATIONS;
+use crate::ema::EmaHalfLives;
+use crate::failover::{FailoverMode, FailoverSettings, DEFAULT_RECHECK_INTERVAL, DEFAULT_UNHEALTHY_AFTER};
+use crate::file_sink::{FileFormat, FileRotation, FileSettings, DEFAULT_FILE_ROTATE_BYTES, DEFAULT_FILE_SINK_DIR};
+use crate::grpc::{GrpcSettings, DEFAULT_GRPC_BUFFER};
+use crate::health::{ReadinessSettings, DEFAULT_MAX_DISCONNECTED, DEFAULT_MAX_IDLE, DEFAULT_MAX_INSERT_FAILING};
+use crate::indexer::DEDUP_WINDOW;
+use crate::kafka::{parse_kafka_config, KafkaFormat, KafkaMode, KafkaSettings, DEFAULT_KAFKA_BUFFER, DEFAULT_KAFKA_TOPIC};
+use crate::latest_price::{RedisSettings, DEFAULT_REDIS_KEY_PREFIX, DEFAULT_REDIS_PRICE_TTL};
+use crate::migrations::TABLES;
+use crate::nats::{NatsSettings, DEFAULT_NATS_BUFFER, DEFAULT_NATS_SUBJECT_PREFIX};
+use crate::notify::{AlertThresholds, NotifySettings, TelegramSettings};
+use crate::sanity::{PriceSanity, DEFAULT_MEDIAN_WINDOW};
+use crate::pool::{Dex, PoolMeta, PoolRef, PoolSpec, Protocol, QuoteSide};
+use crate::pool_source::read_pools_file;
+use crate::postgres::PostgresSettings;
+use crate::rpc::http_provider;
+use crate::shutdown::DEFAULT_SHUTDOWN_TIMEOUT;
+use crate::sink::SinkKind;
+use crate::spill::{SpillDir, SpillPolicy, DEFAULT_SPILL_DIR, DEFAULT_SPILL_MAX_BYTES};
+use crate::storage::{AsyncInsert, InsertRetry, DEFAULT_INSERT_RETRIES, DEFAULT_INSERT_RETRY_DELAY};
+use crate::webhooks::{read_rules_file, Rule};
+use crate::ws_server::{WsServerSettings, DEFAULT_WS_BUFFER, DEFAULT_WS_MAX_CLIENTS, DEFAULT_WS_SEND_TIMEOUT};
+
+// Factory discovery settings
+#[derive(Debug)]
+pub struct PoolDiscovery {
+    pub factory: Address,
+    pub tokens: HashSet<Address>,
+}
+
+pub const UNISWAP_V3_FACTORY: &str = "0x1F98431c8aD98523631AE4a59f267346ea31F984";
+
+// V4 singletons that swaps are emitted from and pool keys are resolved against
+#[derive(Debug)]
+pub struct V4Contracts {
+    pub pool_manager: Address,
+    pub position_manager: Address,
+}
+
+// INDEX_POSITIONS: follow the position manager for positions in indexed pools
+#[derive(Debug)]
+pub struct PositionTracking {
+    pub manager: Address,
+    pub factory: Address,
+}
+
+pub const UNISWAP_V3_POSITION_MANAGER: &str = "0xC36442b4a4522E871399CD717aBDD847Ab11FE88";
+pub const UNISWAP_V3_POOL_INIT_CODE_HASH: B256 =
+    alloy::primitives::b256!("e34f199b19b2b4f47f68442619d555527d244f78a3297ea89325f843f87b8b54");
+
+// CLICKHOUSE_*: where rows, migrations, checkpoints and POOLS_TABLE live
+#[derive(Clone)]
+pub struct ClickhouseSettings {
+    // CLICKHOUSE_URL, comma-separated for several replicas
+    pub urls: Vec<String>,
+    pub user: String,
+    // CLICKHOUSE_PASSWORD, or the contents of CLICKHOUSE_PASSWORD_FILE
+    pub password: String,
+    pub database: String,
+    // CLICKHOUSE_CA_FILE: PEM bundle trusted for https instead of the webpki roots
+    pub ca_file: Option<PathBuf>,
+    // CLICKHOUSE_INSECURE_SKIP_VERIFY: accept any server certificate, self-signed dev setups only
+    pub insecure_skip_verify: bool,
+    // CLICKHOUSE_HEADERS: sent with every request, e.g. a proxy's auth header
+    pub headers: Vec<(String, String)>,
+    // CLICKHOUSE_FAILOVER / CLICKHOUSE_UNHEALTHY_AFTER / CLICKHOUSE_RECHECK_SECONDS
+    pub failover: FailoverSettings,
+    // CLICKHOUSE_COMPRESSION: lz4 (the default with the lz4 feature) or none
+    pub compression: Compression,
+}
+
+// The config is logged on errors, the password and header values aren't
+impl fmt::Debug for ClickhouseSettings {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ClickhouseSettings")
+            .field("urls", &self.urls)
+            .field("user", &self.user)
+            .field("password", &"<redacted>")
+            .field("database", &self.database)
+            .field("ca_file", &self.ca_file)
+            .field("insecure_skip_verify", &self.insecure_skip_verify)
+            .field("headers", &self.headers.iter().map(|(name, _)| format!("{}: <redacted>", name)).collect::<Vec<_>>())
+            .field("failover", &self.failover)
+            .field("compression", &self.compression)
+            .finish()
+    }
+}
+
+// TABLE_NAMES: destination tables by their default name, unlisted ones keep it
+#[derive(Debug, Clone, Default)]
+pub struct TableNames {
+    renamed: HashMap<&'static str, String>,
+}
+
+impl TableNames {
+    // `table` is one of migrations::TABLES
+    pub fn get<'a>(&'a self, table: &'a str) -> &'a str {
+        self.renamed.get(table).map_or(table, String::as_str)
+    }
+
+    pub fn is_renamed(&self, table: &str) -> bool {
+        self.renamed.contains_key(table)
+    }
+}
+
+// POOLS_TABLE: pool specs maintained in ClickHouse by another job
+#[derive(Debug, Clone)]
+pub struct PoolsTable {
+    pub table: String,
+    pub column: String,
+    // POOLS_TABLE_REFRESH_MINUTES, re-read the table this often
+    pub refresh: Option<Duration>,
+}
+
+// TOKEN_ALLOWLIST / TOKEN_DENYLIST entry: an address, or a symbol matched against the pool metadata
+#[derive(Debug, Clone, PartialEq)]
+pub enum TokenMatch {
+    Address(Address),
+    Symbol(String),
+}
+
+impl TokenMatch {
+    pub fn parse(entry: &str) -> Self {
+        let entry = entry.trim();
+        match Address::from_str(entry) {
+            Ok(addr) => TokenMatch::Address(addr),
+            Err(_) => TokenMatch::Symbol(entry.to_string()),
+        }
+    }
+
+    pub fn matches(&self, token: Address, symbol: &str) -> bool {
+        match self {
+            TokenMatch::Address(addr) => *addr == token,
+            TokenMatch::Symbol(s) => s.eq_ignore_ascii_case(symbol),
+        }
+    }
+}
+
+// Applied to discovered pools (factory events, firehose): both tokens must pass
+#[derive(Debug, Clone, Default)]
+pub struct TokenFilter {
+    // Empty allows every token not denied
+    pub allow: Vec<TokenMatch>,
+    pub deny: Vec<TokenMatch>,
+}
+
+impl TokenFilter {
+    pub fn allows(&self, meta: &PoolMeta) -> bool {
+        let passes = |token: Address, symbol: &str| {
+            (self.allow.is_empty() || self.allow.iter().any(|m| m.matches(token, symbol)))
+                && !self.deny.iter().any(|m| m.matches(token, symbol))
+        };
+        passes(meta.token0, &meta.token0_symbol) && passes(meta.token1, &meta.token1_symbol)
+    }
+}
+
+// QUOTE_TOKEN: the token prices are expressed in, ahead of the stablecoin list
+#[derive(Debug, Clone, Default)]
+pub struct QuoteTokens {
+    // Bare entries, tried in order on every pool
+    pub tokens: Vec<TokenMatch>,
+    // pool=token entries, these win over everything else
+    pub pools: HashMap<PoolRef, TokenMatch>,
+}
+
+impl QuoteTokens {
+    // None when neither token is named, the stablecoin list decides then
+    pub fn side(&self, pool: PoolRef, meta: &PoolMeta) -> Option<QuoteSide> {
+        let side = |m: &TokenMatch| {
+            if m.matches(meta.token0, &meta.token0_symbol) {
+                Some(QuoteSide::Token0)
+            } else if m.matches(meta.token1, &meta.token1_symbol) {
+                Some(QuoteSide::Token1)
+            } else {
+                None
+            }
+        };
+        if let Some(m) = self.pools.get(&pool) {
+            match side(m) {
+                Some(quote) => return Some(quote),
+                None => warn!("⚠️ QUOTE_TOKEN {:?} for {} is neither of its tokens, ignored", m, pool),
+            }
+        }
+        self.tokens.iter().find_map(side)
+    }
+}
+
+// Settings read once at startup
+#[derive(Debug)]
+pub struct IndexerConfig {
+    pub rpc_url: String,
+    pub rpc_http_url: String,
+    // RPC_RATE_LIMIT, requests/s over all HTTP calls
+    pub rpc_rate_limit: Option<u32>,
+    // POOLS_FILE, POOL_ADDRESSES, or the single POOL_ADDRESS; PAIRS pools are added at startup
+    pub pools: Vec<PoolSpec>,
+    pub pools_file: Option<PathBuf>,
+    pub pools_table: Option<PoolsTable>,
+    // PAIRS, resolved through the factory at startup
+    pub pairs: Vec<(Address, Address)>,
+    // Start idle instead of failing when POOLS_TABLE returns nothing
+    pub allow_empty_pools: bool,
+    pub discovery: Option<PoolDiscovery>,
+    pub token_filter: TokenFilter,
+    // MIN_LIQUIDITY, raw in-range liquidity a discovered pool needs to be indexed
+    pub min_liquidity: Option<u128>,
+    pub liquidity_recheck: Duration,
+    pub v4: V4Contracts,
+    pub positions: Option<PositionTracking>,
+    // STABLECOINS, or the chain's USDC/USDT/DAI once chain_id is set. Tokens treated as $1:
+    // prices are quoted in them and their leg is volume_usd
+    pub stablecoins: HashSet<Address>,
+    pub quote_tokens: QuoteTokens,
+    // INVERT_PRICE: pools quoted in the other token than the one picked above
+    pub invert_price: HashSet<PoolRef>,
+    // PRICE_DECIMAL: also write price_usd as Decimal(38, 18), price_usd stays a Float64
+    pub price_decimal: bool,
+    // CHAINLINK_FEEDS: USD prices for quote tokens that aren't stablecoins
+    pub chainlink: Option<ChainlinkFeeds>,
+    // REFERENCE_POOL: the other source, a WETH/USDC-style pool indexed alongside
+    pub reference: Option<ReferencePool>,
+    // WATCHLIST: only swaps touching these wallets are written, empty keeps all
+    pub watchlist: HashSet<Address>,
+    // FETCH_TX_FROM: one extra eth_getTransactionByHash per swap tx
+    pub fetch_tx_from: bool,
+    // CHAIN_ID, checked against the RPC at startup
+    pub expected_chain_id: Option<u64>,
+    // BACKFILL_FROM_BLOCK: history replayed before going live
+    pub backfill: Option<BackfillRange>,
+    // CHECKPOINT_STORE, None when off
+    pub checkpoints: Option<CheckpointStore>,
+    // Reported by the RPC, set by resolve_chain_id
+    pub chain_id: u64,
+    // FIREHOSE: every V3 Swap on the chain, pools resolved lazily
+    pub firehose: bool,
+    // Pools whose metadata is kept in the registry, and firehose pools held by the handler
+    pub pool_cache_size: usize,
+    // Block timestamps kept for the live stream and backfill
+    pub block_cache_size: usize,
+    // Blocks a live record waits below the head before it is written, 0 = immediately
+    pub confirmations: u64,
+    // Recent (tx_hash, log_index) keys remembered to drop duplicates, 0 disables it
+    pub dedup_window: usize,
+    // Price bounds and median check, failing swaps go to suspect_swaps
+    pub price_sanity: PriceSanity,
+    // TWAP_WINDOWS, pool_twaps window sizes; empty writes none
+    pub twap_windows: Vec<Duration>,
+    // PRICE_EMA_HALF_LIFE, price_ema is NULL for pools without one
+    pub ema_half_lives: EmaHalfLives,
+    // CANDLES_1M: uniswap_candles_1m from the swap stream
+    pub candles: CandleSettings,
+    pub clickhouse: ClickhouseSettings,
+    // SINK: ClickHouse, PostgreSQL (POSTGRES_URL) or files, the first one listed
+    pub sink: SinkKind,
+    // The rest of SINK, each written to on its own
+    pub secondary_sinks: Vec<SinkKind>,
+    pub tables: TableNames,
+    // CREATE_TABLES: create missing tables at startup instead of only warning
+    pub create_tables: bool,
+    pub channel_capacity: usize,
+    // Rows buffered before a ClickHouse insert
+    pub batch_size: usize,
+    // INSERT_WORKERS: batches inserted concurrently, 1 keeps a single writer
+    pub insert_workers: usize,
+    // SHUTDOWN_TIMEOUT_SECONDS: how long the writer gets to drain on Ctrl-C / SIGTERM
+    pub shutdown_timeout: Duration,
+    // INSERT_RETRIES and INSERT_RETRY_DELAY_MS
+    pub insert_retry: InsertRetry,
+    // SPILL_DIR, SPILL_MAX_MB and SPILL_FULL_POLICY
+    pub spill: SpillDir,
+    // BATCH_MAX_AGE_SECONDS: a partial batch is inserted once its oldest row is this old
+    pub batch_max_age: Duration,
+    // ASYNC_INSERT / ASYNC_INSERT_WAIT
+    pub async_insert: Option<AsyncInsert>,
+    // KAFKA_BROKERS: swaps are published to Kafka as well (or instead)
+    pub kafka: Option<KafkaSettings>,
+    // NATS_URL or NATS_DRY_RUN: swaps are published to NATS JetStream as well
+    pub nats: Option<NatsSettings>,
+    // REDIS_URL: the latest price per pool is kept in Redis
+    pub redis: Option<RedisSettings>,
+    // WEBHOOK_RULES_FILE: alerts POSTed when a rule matches
+    pub webhooks: Vec<Rule>,
+    // ALERT_MIN_USD with TELEGRAM_BOT_TOKEN / DISCORD_WEBHOOK_URL: large swaps to a chat
+    pub notify: Option<NotifySettings>,
+    // METRICS_ADDR: GET /metrics in the Prometheus text format
+    pub metrics_addr: Option<SocketAddr>,
+    // READY_MAX_*_SECONDS: when /readyz (on METRICS_ADDR) turns to 503
+    pub readiness: ReadinessSettings,
+    // GRPC_ADDR: live swaps streamed to SubscribeSwaps clients
+    pub grpc: Option<GrpcSettings>,
+    // WS_SERVER_ADDR: live swaps to WebSocket clients on /swaps
+    pub ws_server: Option<WsServerSettings>,
+}
+
+pub const UNISWAP_V4_POOL_MANAGER: &str = "0x000000000004444c5dc75cB358380D2e3dE08A90";
+pub const UNISWAP_V4_POSITION_MANAGER: &str = "0xbD216513d74C8cf14cf4747E6AaA6420FF64ee9e";
+
+impl IndexerConfig {
+    pub fn from_env() -> Self {
+        let rpc_url = env::var("RPC_URL").expect("RPC_URL must be set");
+        let rpc_http_url = env::var("RPC_HTTP_URL").expect("RPC_HTTP_URL (HTTP) must be set");
+        let pools_file = env::var("POOLS_FILE").ok().map(PathBuf::from);
+
+        let pools_table = pools_table_from_env();
+        let pairs = pairs_from_env();
+
+        // ASYNC_INSERT hands the batching to ClickHouse, the writer inserts whatever it has
+        let async_insert = async_insert_from_env();
+        if async_insert.is_some() && ["BATCH_SIZE", "BATCH_MAX_AGE_SECONDS"].iter().any(|v| env::var(v).is_ok_and(|v| !v.trim().is_empty())) {
+            panic!("ASYNC_INSERT and BATCH_SIZE/BATCH_MAX_AGE_SECONDS are mutually exclusive");
+        }
+
+        // ASYNC_INSERT and POOLS_TABLE are ClickHouse's
+        let mut sinks = sinks_from_env();
+        let sink = sinks.remove(0);
+        if !matches!(sink, SinkKind::ClickHouse) && (async_insert.is_some() || pools_table.is_some()) {
+            panic!("Only SINK=clickhouse supports ASYNC_INSERT and POOLS_TABLE");
+        }
+
+        // POOLS_TABLE is loaded at startup, then POOLS_FILE (can change at runtime), then the env vars
+        let pools = if pools_table.is_some() {
+            Vec::new()
+        } else if let Some(path) = &pools_file {
+            read_pools_file(path).expect("Invalid POOLS_FILE")
+        } else {
+            // The default pool only applies when PAIRS doesn't name any either
+            let pool_str = match env::var("POOL_ADDRESSES").or_else(|_| env::var("POOL_ADDRESS")) {
+                Ok(s) => s,
+                Err(_) if !pairs.is_empty() => String::new(),
+                Err(_) => "0x88e6a0c2ddd26feeb64f039a2c41296fcb3f5640".to_string(),
+            };
+            parse_pool_specs(&pool_str).expect("Invalid POOL_ADDRESSES")
+        };
+        if pools.is_empty() && pools_table.is_none() && pairs.is_empty() {
+            panic!("No pools configured");
+        }
+
+        Self {
+            rpc_url,
+            rpc_http_url,
+            rpc_rate_limit: u64_from_env("RPC_RATE_LIMIT").map(|rps| u32::try_from(rps).ok().filter(|r| *r > 0).expect("Invalid RPC_RATE_LIMIT")),
+            pools,
+            pools_file,
+            pools_table,
+            pairs,
+            allow_empty_pools: env::var("ALLOW_EMPTY_POOLS").map(|v| v == "true" || v == "1").unwrap_or(false),
+            discovery: discovery_from_env(),
+            token_filter: token_filter_from_env(),
+            min_liquidity: env::var("MIN_LIQUIDITY").ok().filter(|v| !v.trim().is_empty()).map(|v| v.trim().parse().expect("Invalid MIN_LIQUIDITY")),
+            liquidity_recheck: Duration::from_secs(usize_from_env("LIQUIDITY_RECHECK_MINUTES", 60) as u64 * 60),
+            v4: v4_contracts_from_env(),
+            positions: positions_from_env(),
+            stablecoins: stablecoins_from_env().unwrap_or_else(|| default_stablecoins(1)),
+            quote_tokens: quote_tokens_from_env(),
+            invert_price: parse_pool_refs(&env::var("INVERT_PRICE").unwrap_or_default()).expect("Invalid INVERT_PRICE"),
+            price_decimal: env::var("PRICE_DECIMAL").map(|v| v == "true" || v == "1").unwrap_or(false),
+            chainlink: chainlink_from_env(),
+            reference: reference_from_env(),
+            watchlist: watchlist_from_env(),
+            fetch_tx_from: env::var("FETCH_TX_FROM").map(|v| v != "false" && v != "0").unwrap_or(true),
+            expected_chain_id: env::var("CHAIN_ID").ok().filter(|v| !v.trim().is_empty()).map(|v| v.trim().parse().expect("Invalid CHAIN_ID")),
+            backfill: backfill_from_env(),
+            checkpoints: checkpoint_store_from_env(&sink),
+            chain_id: 0,
+            firehose: env::var("FIREHOSE").map(|v| v == "true" || v == "1").unwrap_or(false),
+            pool_cache_size: usize_from_env("POOL_CACHE_SIZE", 10_000),
+            block_cache_size: usize_from_env("BLOCK_CACHE_SIZE", BLOCK_CACHE_SIZE),
+            confirmations: u64_from_env("CONFIRMATIONS").unwrap_or(DEFAULT_CONFIRMATIONS),
+            dedup_window: usize_from_env("DEDUP_WINDOW", DEDUP_WINDOW),
+            price_sanity: price_sanity_from_env(),
+            ema_half_lives: parse_half_lives(&env::var("PRICE_EMA_HALF_LIFE").unwrap_or_default()).expect("Invalid PRICE_EMA_HALF_LIFE"),
+            candles: CandleSettings {
+                enabled: env::var("CANDLES_1M").map(|v| v == "true" || v == "1").unwrap_or(false),
+                grace: u64_from_env("CANDLE_GRACE_SECONDS").map(Duration::from_secs).unwrap_or(DEFAULT_CANDLE_GRACE),
+                fill_gaps: env::var("CANDLES_FILL_GAPS").map(|v| v == "true" || v == "1").unwrap_or(false),
+            },
+            twap_windows: parse_windows(&env::var("TWAP_WINDOWS").unwrap_or_default()).expect("Invalid TWAP_WINDOWS"),
+            clickhouse: clickhouse_from_env(),
+            sink,
+            secondary_sinks: sinks,
+            tables: parse_table_names(&env::var("TABLE_NAMES").unwrap_or_default()).expect("Invalid TABLE_NAMES"),
+            create_tables: env::var("CREATE_TABLES").map(|v| v == "true" || v == "1").unwrap_or(false),
+            channel_capacity: usize_from_env("CHANNEL_CAPACITY", 10_000),
+            batch_size: match async_insert {
+                Some(_) => ASYNC_INSERT_BATCH_SIZE,
+                None => usize_from_env("BATCH_SIZE", 10),
+            },
+            insert_workers: usize_from_env("INSERT_WORKERS", 1),
+            shutdown_timeout: u64_from_env("SHUTDOWN_TIMEOUT_SECONDS").map(Duration::from_secs).unwrap_or(DEFAULT_SHUTDOWN_TIMEOUT),
+            insert_retry: InsertRetry {
+                retries: u64_from_env("INSERT_RETRIES").map_or(DEFAULT_INSERT_RETRIES, |n| n as u32),
+                base_delay: u64_from_env("INSERT_RETRY_DELAY_MS").map(Duration::from_millis).unwrap_or(DEFAULT_INSERT_RETRY_DELAY),
+            },
+            spill: spill_dir_from_env(),
+            batch_max_age: match async_insert {
+                Some(_) => Duration::ZERO,
+                None => u64_from_env("BATCH_MAX_AGE_SECONDS").map(Duration::from_secs).unwrap_or(DEFAULT_BATCH_MAX_AGE),
+            },
+            async_insert,
+            kafka: kafka_from_env(),
+            nats: nats_from_env(),
+            redis: redis_from_env(),
+            webhooks: webhook_rules_from_env(),
+            notify: notify_from_env(),
+            metrics_addr: env::var("METRICS_ADDR").ok().filter(|a| !a.trim().is_empty()).map(|a| a.trim().parse().expect("Invalid METRICS_ADDR")),
+            readiness: readiness_from_env(),
+            grpc: grpc_from_env(),
+            ws_server: ws_server_from_env(),
+        }
+    }
+
+    // eth_chainId from the HTTP RPC, a different CHAIN_ID than configured aborts startup
+    pub async fn resolve_chain_id(&mut self) -> Result<u64> {
+        let provider = http_provider(&self.rpc_http_url)?;
+        let chain_id = provider.get_chain_id().await?;
+
+        if let Some(expected) = self.expected_chain_id && expected != chain_id {
+            eyre::bail!("CHAIN_ID is {} but the RPC at {} reports chain {}", expected, self.rpc_http_url, chain_id);
+        }
+
+        self.set_chain_id(chain_id);
+        Ok(chain_id)
+    }
+
+    // Also swaps in the chain's default stablecoins unless STABLECOINS lists its own
+    pub fn set_chain_id(&mut self, chain_id: u64) {
+        self.chain_id = chain_id;
+        if stablecoins_from_env().is_none() {
+            self.stablecoins = default_stablecoins(chain_id);
+        }
+    }
+}
+
+// Comma-separated specs, duplicates dropped
+pub fn parse_pool_specs(specs: &str) -> Result<Vec<PoolSpec>> {
+    let mut pools: Vec<PoolSpec> = Vec::new();
+    for spec in specs.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+        let spec = parse_pool_spec(spec)?;
+        if !pools.iter().any(|p| p.pool == spec.pool) {
+            pools.push(spec);
+        }
+    }
+    Ok(pools)
+}
+
+// "0x..." is a V3 pool, "v2:0x..." a V2 pair, "v4:0x<PoolId>" a V4 pool,
+// "pancake:0x..." a PancakeSwap V3 pool
+pub fn parse_pool_spec(spec: &str) -> Result<PoolSpec> {
+    let spec = spec.trim();
+    let (prefix, value) = spec.split_once(':').unwrap_or(("v3", spec));
+
+    let address = || -> Result<PoolRef> {
+        Ok(PoolRef::Address(Address::from_str(value).map_err(|e| eyre::eyre!("Invalid pool address '{}': {}", value, e))?))
+    };
+    let (protocol, dex, pool) = match prefix {
+        "v2" => (Protocol::V2, Dex::Uniswap, address()?),
+        "v3" => (Protocol::V3, Dex::Uniswap, address()?),
+        "pancake" => (Protocol::V3, Dex::Pancake, address()?),
+        "v4" => {
+            let id = B256::from_str(value).map_err(|e| eyre::eyre!("Invalid V4 PoolId '{}': {}", value, e))?;
+            (Protocol::V4, Dex::Uniswap, PoolRef::Id(id))
+        }
+        other => eyre::bail!("Unknown protocol '{}' in pool spec", other),
+    };
+
+    Ok(PoolSpec { protocol, dex, pool })
+}
+
+// Defaults match a local server with the stock user; CLICKHOUSE_PASSWORD_FILE wins over
+// CLICKHOUSE_PASSWORD, for secrets mounted as files
+pub fn clickhouse_from_env() -> ClickhouseSettings {
+    let var = |name: &str, default: &str| env::var(name).ok().filter(|v| !v.trim().is_empty()).unwrap_or_else(|| default.to_string());
+
+    let urls: Vec<String> = var("CLICKHOUSE_URL", "http://localhost:8123")
+        .split(',')
+        .map(|url| url.trim().trim_end_matches('/').to_string())
+        .filter(|url| !url.is_empty())
+        .collect();
+    for url in &urls {
+        if !url.starts_with("http://") && !url.starts_with("https://") {
+            panic!("Invalid CLICKHOUSE_URL '{}', expected http(s)://host:port", url);
+        }
+    }
+
+    let failover = FailoverSettings {
+        mode: match env::var("CLICKHOUSE_FAILOVER").unwrap_or_default().trim() {
+            "" | "ordered" => FailoverMode::Ordered,
+            "round_robin" => FailoverMode::RoundRobin,
+            other => panic!("Invalid CLICKHOUSE_FAILOVER '{}', expected ordered or round_robin", other),
+        },
+        unhealthy_after: env::var("CLICKHOUSE_UNHEALTHY_AFTER")
+            .map(|v| v.parse().expect("Invalid CLICKHOUSE_UNHEALTHY_AFTER"))
+            .unwrap_or(DEFAULT_UNHEALTHY_AFTER)
+            .max(1),
+        recheck: env::var("CLICKHOUSE_RECHECK_SECONDS")
+            .map(|v| Duration::from_secs(v.parse().expect("Invalid CLICKHOUSE_RECHECK_SECONDS")))
+            .unwrap_or(DEFAULT_RECHECK_INTERVAL),
+    };
+
+    let password = match env::var("CLICKHOUSE_PASSWORD_FILE").ok().filter(|p| !p.trim().is_empty()) {
+        Some(path) => std::fs::read_to_string(path.trim())
+            .unwrap_or_else(|e| panic!("Failed to read CLICKHOUSE_PASSWORD_FILE {}: {}", path, e))
+            .trim_end_matches(['\r', '\n'])
+            .to_string(),
+        None => env::var("CLICKHOUSE_PASSWORD").unwrap_or_default(),
+    };
+
+    ClickhouseSettings {
+        urls,
+        user: var("CLICKHOUSE_USER", "default").trim().to_string(),
+        password,
+        database: var("CLICKHOUSE_DATABASE", "crypto_db").trim().to_string(),
+        ca_file: env::var("CLICKHOUSE_CA_FILE").ok().filter(|p| !p.trim().is_empty()).map(|p| PathBuf::from(p.trim())),
+        insecure_skip_verify: env::var("CLICKHOUSE_INSECURE_SKIP_VERIFY").map(|v| v == "true" || v == "1").unwrap_or(false),
+        headers: parse_clickhouse_headers(&env::var("CLICKHOUSE_HEADERS").unwrap_or_default()).expect("Invalid CLICKHOUSE_HEADERS"),
+        failover,
+        compression: compression_from_env(),
+    }
+}
+
+// Table names are interpolated into SQL, so only plain (optionally database-qualified) identifiers
+fn valid_identifier(s: &str) -> bool {
+    !s.is_empty() && s.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.')
+}
+
+fn compression_from_env() -> Compression {
+    match env::var("CLICKHOUSE_COMPRESSION").unwrap_or_default().trim() {
+        "none" => Compression::None,
+        #[cfg(feature = "lz4")]
+        "" | "lz4" => Compression::Lz4,
+        #[cfg(not(feature = "lz4"))]
+        "" => Compression::None,
+        #[cfg(not(feature = "lz4"))]
+        "lz4" => panic!("CLICKHOUSE_COMPRESSION=lz4 needs a build with the lz4 feature"),
+        other => panic!("Invalid CLICKHOUSE_COMPRESSION '{}', expected lz4 or none", other),
+    }
+}
+
+// CLICKHOUSE_HEADERS=X-Api-Key=secret,X-Team=data. Values may contain '=' (base64 tokens),
+// only the first one splits
+pub fn parse_clickhouse_headers(list: &str) -> Result<Vec<(String, String)>> {
+    let mut headers: Vec<(String, String)> = Vec::new();
+    for entry in list.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+        let (name, value) = entry.split_once('=').ok_or_else(|| eyre::eyre!("Invalid header '{}', expected name=value", entry))?;
+        let (name, value) = (name.trim(), value.trim());
+        if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+            eyre::bail!("Invalid header name '{}'", name);
+        }
+        if value.chars().any(|c| c.is_control()) {
+            eyre::bail!("Invalid value for header {}", name);
+        }
+        if headers.iter().any(|(n, _)| n.eq_ignore_ascii_case(name)) {
+            eyre::bail!("Header {} is set twice", name);
+        }
+        headers.push((name.to_string(), value.to_string()));
+    }
+    Ok(headers)
+}
+
+// TABLE_NAMES=uniswap_swaps=swaps_arbitrum,pool_twaps=twaps_arbitrum
+pub fn parse_table_names(list: &str) -> Result<TableNames> {
+    let mut tables = TableNames::default();
+    for entry in list.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+        let (table, name) = entry.split_once('=').ok_or_else(|| eyre::eyre!("Invalid table name '{}', expected table=name", entry))?;
+        let (table, name) = (table.trim(), name.trim());
+        let table = TABLES.iter().find(|t| **t == table).ok_or_else(|| eyre::eyre!("Unknown table '{}'", table))?;
+        // In CLICKHOUSE_DATABASE like the rest, that's where migrate() looks for it
+        if !valid_identifier(name) || name.contains('.') {
+            eyre::bail!("Invalid table name '{}' for {}", name, table);
+        }
+        // Two record types in one table can't share a schema
+        if TABLES.iter().any(|t| t != table && tables.get(t) == name) {
+            eyre::bail!("Table name '{}' for {} is already used by another table", name, table);
+        }
+        tables.renamed.insert(table, name.to_string());
+    }
+    Ok(tables)
+}
+
+// ASYNC_INSERT=true, waiting for ClickHouse's acknowledgment unless ASYNC_INSERT_WAIT=false
+pub fn async_insert_from_env() -> Option<AsyncInsert> {
+    if !env::var("ASYNC_INSERT").map(|v| v == "true" || v == "1").unwrap_or(false) {
+        return None;
+    }
+    let wait = env::var("ASYNC_INSERT_WAIT").map(|v| v == "true" || v == "1").unwrap_or(true);
+    Some(AsyncInsert { wait })
+}
+
+pub fn spill_dir_from_env() -> SpillDir {
+    let path = env::var("SPILL_DIR").ok().filter(|p| !p.trim().is_empty()).unwrap_or_else(|| DEFAULT_SPILL_DIR.to_string());
+    let policy = match env::var("SPILL_FULL_POLICY").unwrap_or_default().trim() {
+        "" | "stop" => SpillPolicy::Stop,
+        "drop-oldest" => SpillPolicy::DropOldest,
+        other => panic!("Invalid SPILL_FULL_POLICY '{}', expected stop or drop-oldest", other),
+    };
+    SpillDir {
+        path: PathBuf::from(path.trim()),
+        max_bytes: u64_from_env("SPILL_MAX_MB").map_or(DEFAULT_SPILL_MAX_BYTES, |mb| mb * 1024 * 1024),
+        policy,
+    }
+}
+
+pub fn kafka_from_env() -> Option<KafkaSettings> {
+    let brokers = env::var("KAFKA_BROKERS").ok().filter(|b| !b.trim().is_empty())?;
+    let format = match env::var("KAFKA_FORMAT").unwrap_or_default().trim() {
+        "" | "json" => KafkaFormat::Json,
+        "schema" => KafkaFormat::Schema,
+        other => panic!("Invalid KAFKA_FORMAT '{}', expected json or schema", other),
+    };
+    let mode = match env::var("KAFKA_SINK").unwrap_or_default().trim() {
+        "" | "also" => KafkaMode::Also,
+        "instead" => KafkaMode::Instead,
+        other => panic!("Invalid KAFKA_SINK '{}', expected also or instead", other),
+    };
+    Some(KafkaSettings {
+        brokers: brokers.trim().to_string(),
+        topic: env::var("KAFKA_TOPIC").ok().filter(|t| !t.trim().is_empty()).map_or(DEFAULT_KAFKA_TOPIC.to_string(), |t| t.trim().to_string()),
+        format,
+        mode,
+        buffer: usize_from_env("KAFKA_BUFFER", DEFAULT_KAFKA_BUFFER),
+        config: parse_kafka_config(&env::var("KAFKA_CONFIG").unwrap_or_default()).expect("Invalid KAFKA_CONFIG"),
+    })
+}
+
+pub fn nats_from_env() -> Option<NatsSettings> {
+    let servers: Vec<String> =
+        env::var("NATS_URL").unwrap_or_default().split(',').map(str::trim).filter(|s| !s.is_empty()).map(String::from).collect();
+    let dry_run = env::var("NATS_DRY_RUN").map(|v| v == "true" || v == "1").unwrap_or(false);
+    if servers.is_empty() && !dry_run {
+        return None;
+    }
+    let subject_prefix = env::var("NATS_SUBJECT_PREFIX").ok().filter(|p| !p.trim().is_empty()).map_or(DEFAULT_NATS_SUBJECT_PREFIX.to_string(), |p| p.trim().to_string());
+    // Tokens of a subject to publish on: no wildcards, whitespace or empty tokens
+    if subject_prefix.split('.').any(|t| t.is_empty() || !t.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')) {
+        panic!("Invalid NATS_SUBJECT_PREFIX '{}'", subject_prefix);
+    }
+    Some(NatsSettings {
+        servers,
+        subject_prefix,
+        creds_file: env::var("NATS_CREDS_FILE").ok().filter(|p| !p.trim().is_empty()).map(|p| PathBuf::from(p.trim())),
+        buffer: usize_from_env("NATS_BUFFER", DEFAULT_NATS_BUFFER),
+        dry_run,
+    })
+}
+
+pub fn redis_from_env() -> Option<RedisSettings> {
+    let url = env::var("REDIS_URL").ok().filter(|u| !u.trim().is_empty())?;
+    Some(RedisSettings {
+        url: url.trim().to_string(),
+        key_prefix: env::var("REDIS_KEY_PREFIX").ok().filter(|p| !p.trim().is_empty()).map_or(DEFAULT_REDIS_KEY_PREFIX.to_string(), |p| p.trim().to_string()),
+        ttl: u64_from_env("REDIS_PRICE_TTL_SECONDS").map(Duration::from_secs).unwrap_or(DEFAULT_REDIS_PRICE_TTL),
+    })
+}
+
+pub fn webhook_rules_from_env() -> Vec<Rule> {
+    match env::var("WEBHOOK_RULES_FILE").ok().filter(|p| !p.trim().is_empty()) {
+        Some(path) => read_rules_file(Path::new(path.trim())).expect("Invalid WEBHOOK_RULES_FILE"),
+        None => Vec::new(),
+    }
+}
+
+pub fn grpc_from_env() -> Option<GrpcSettings> {
+    let addr = env::var("GRPC_ADDR").ok().filter(|a| !a.trim().is_empty())?;
+    let buffer = usize_from_env("GRPC_BUFFER", DEFAULT_GRPC_BUFFER);
+    assert!(buffer > 0, "Invalid GRPC_BUFFER");
+    Some(GrpcSettings { addr: addr.trim().parse().expect("Invalid GRPC_ADDR"), buffer })
+}
+
+// READY_MAX_IDLE_SECONDS=0 turns the idle check off
+pub fn readiness_from_env() -> ReadinessSettings {
+    let seconds = |name: &str, default: Duration| u64_from_env(name).map(Duration::from_secs).unwrap_or(default);
+    ReadinessSettings {
+        max_disconnected: seconds("READY_MAX_DISCONNECTED_SECONDS", DEFAULT_MAX_DISCONNECTED),
+        max_idle: Some(seconds("READY_MAX_IDLE_SECONDS", DEFAULT_MAX_IDLE)).filter(|d| !d.is_zero()),
+        max_insert_failing: seconds("READY_MAX_INSERT_FAILING_SECONDS", DEFAULT_MAX_INSERT_FAILING),
+    }
+}
+
+pub fn ws_server_from_env() -> Option<WsServerSettings> {
+    let addr = env::var("WS_SERVER_ADDR").ok().filter(|a| !a.trim().is_empty())?;
+    let settings = WsServerSettings {
+        addr: addr.trim().parse().expect("Invalid WS_SERVER_ADDR"),
+        max_clients: usize_from_env("WS_SERVER_MAX_CLIENTS", DEFAULT_WS_MAX_CLIENTS),
+        send_timeout: u64_from_env("WS_SERVER_SEND_TIMEOUT_SECONDS").map(Duration::from_secs).unwrap_or(DEFAULT_WS_SEND_TIMEOUT),
+        buffer: usize_from_env("WS_SERVER_BUFFER", DEFAULT_WS_BUFFER),
+    };
+    assert!(settings.max_clients > 0, "Invalid WS_SERVER_MAX_CLIENTS");
+    assert!(!settings.send_timeout.is_zero(), "Invalid WS_SERVER_SEND_TIMEOUT_SECONDS");
+    assert!(settings.buffer > 0, "Invalid WS_SERVER_BUFFER");
+    Some(settings)
+}
+
+// Off unless a Telegram chat or a Discord webhook is set
+pub fn notify_from_env() -> Option<NotifySettings> {
+    let var = |name: &str| env::var(name).ok().map(|v| v.trim().to_string()).filter(|v| !v.is_empty());
+    let telegram = var("TELEGRAM_BOT_TOKEN").map(|bot_token| TelegramSettings {
+        bot_token,
+        chat_id: var("TELEGRAM_CHAT_ID").expect("Invalid TELEGRAM_CHAT_ID"),
+    });
+    let discord = var("DISCORD_WEBHOOK_URL");
+    if let Some(url) = &discord
+        && !url.starts_with("https://")
+    {
+        panic!("Invalid DISCORD_WEBHOOK_URL");
+    }
+    if telegram.is_none() && discord.is_none() {
+        return None;
+    }
+    let thresholds = parse_alert_thresholds(&env::var("ALERT_MIN_USD").unwrap_or_default()).expect("Invalid ALERT_MIN_USD");
+    Some(NotifySettings { thresholds, telegram, discord })
+}
+
+// ALERT_MIN_USD=1000000,0x<pool>=5000000,v4:0x<PoolId>=100000: a bare value applies to every
+// other pool, DEFAULT_ALERT_MIN_USD without one
+pub fn parse_alert_thresholds(list: &str) -> Result<AlertThresholds> {
+    let usd = |value: &str| -> Result<f64> {
+        match value.trim().parse::<f64>() {
+            Ok(usd) if usd.is_finite() && usd > 0.0 => Ok(usd),
+            _ => Err(eyre::eyre!("Invalid USD threshold '{}'", value.trim())),
+        }
+    };
+    let mut thresholds = AlertThresholds::default();
+    for entry in list.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+        match entry.split_once('=') {
+            Some((pool, value)) => {
+                let pool = parse_pool_spec(pool)?.pool;
+                thresholds.pools.insert(pool.to_string(), usd(value)?);
+            }
+            None => thresholds.default = usd(entry)?,
+        }
+    }
+    Ok(thresholds)
+}
+
+pub fn pools_table_from_env() -> Option<PoolsTable> {
+    let table = env::var("POOLS_TABLE").ok().filter(|t| !t.trim().is_empty())?;
+    let column = env::var("POOLS_TABLE_COLUMN").unwrap_or_else(|_| "pool_address".to_string());
+
+    if !valid_identifier(table.trim()) || !valid_identifier(column.trim()) {
+        panic!("Invalid POOLS_TABLE/POOLS_TABLE_COLUMN");
+    }
+
+    let refresh = match usize_from_env("POOLS_TABLE_REFRESH_MINUTES", 0) {
+        0 => None,
+        minutes => Some(Duration::from_secs(minutes as u64 * 60)),
+    };
+
+    Some(PoolsTable { table: table.trim().to_string(), column: column.trim().to_string(), refresh })
+}
+
+fn u64_from_env(name: &str) -> Option<u64> {
+    let value = env::var(name).ok().filter(|v| !v.trim().is_empty())?;
+    Some(value.trim().parse().unwrap_or_else(|_| panic!("Invalid {}", name)))
+}
+
+// BACKFILL_FROM_BLOCK, BACKFILL_TO_BLOCK (default: head), BACKFILL_CHUNK_SIZE, BACKFILL_PARALLELISM
+pub fn backfill_from_env() -> Option<BackfillRange> {
+    let from = u64_from_env("BACKFILL_FROM_BLOCK")?;
+    Some(BackfillRange {
+        from,
+        to: u64_from_env("BACKFILL_TO_BLOCK"),
+        chunk_size: u64_from_env("BACKFILL_CHUNK_SIZE").unwrap_or(DEFAULT_CHUNK_SIZE),
+        parallelism: usize_from_env("BACKFILL_PARALLELISM", DEFAULT_PARALLELISM),
+    })
+}
+
+// SINK=clickhouse (default), postgres or file, or several of them comma-separated
+pub fn sinks_from_env() -> Vec<SinkKind> {
+    let list = env::var("SINK").unwrap_or_default();
+    let mut sinks: Vec<SinkKind> = Vec::new();
+    for name in list.split(',').map(str::trim).filter(|n| !n.is_empty()) {
+        let sink = match name {
+            "clickhouse" => SinkKind::ClickHouse,
+            "postgres" => {
+                let url = env::var("POSTGRES_URL").ok().filter(|u| !u.trim().is_empty()).expect("SINK=postgres needs POSTGRES_URL");
+                SinkKind::Postgres(PostgresSettings { url: url.trim().to_string() })
+            }
+            "file" => SinkKind::File(file_settings_from_env()),
+            other => panic!("Invalid SINK '{}', expected clickhouse, postgres or file", other),
+        };
+        if sinks.iter().any(|s| s.name() == sink.name()) {
+            panic!("SINK lists {} twice", name);
+        }
+        sinks.push(sink);
+    }
+    if sinks.is_empty() {
+        sinks.push(SinkKind::ClickHouse);
+    }
+    sinks
+}
+
+// SINK=file: FILE_SINK_DIR, FILE_FORMAT=csv (default) or parquet, FILE_ROTATE=size (default,
+// FILE_ROTATE_MB) or hour
+pub fn file_settings_from_env() -> FileSettings {
+    let format = match env::var("FILE_FORMAT").unwrap_or_default().trim() {
+        "" | "csv" => FileFormat::Csv,
+        "parquet" => FileFormat::Parquet,
+        other => panic!("Invalid FILE_FORMAT '{}', expected csv or parquet", other),
+    };
+    let rotation = match env::var("FILE_ROTATE").unwrap_or_default().trim() {
+        "" | "size" => FileRotation::Size(
+            u64_from_env("FILE_ROTATE_MB").filter(|mb| *mb > 0).map_or(DEFAULT_FILE_ROTATE_BYTES, |mb| mb * 1024 * 1024),
+        ),
+        "hour" => FileRotation::Hour,
+        other => panic!("Invalid FILE_ROTATE '{}', expected size or hour", other),
+    };
+    let dir = env::var("FILE_SINK_DIR").ok().filter(|d| !d.trim().is_empty()).unwrap_or_else(|| DEFAULT_FILE_SINK_DIR.to_string());
+    FileSettings { dir: PathBuf::from(dir.trim()), format, rotation }
+}
+
+// CHECKPOINT_STORE=clickhouse or postgres (the SINK database, the default), file (CHECKPOINT_FILE) or off.
+// SINK=file has no database, its default is file
+pub fn checkpoint_store_from_env(sink: &SinkKind) -> Option<CheckpointStore> {
+    match (env::var("CHECKPOINT_STORE").unwrap_or_default().trim(), sink) {
+        ("", SinkKind::File(_)) | ("file", _) => {
+            let path = env::var("CHECKPOINT_FILE").unwrap_or_else(|_| "checkpoints.txt".to_string());
+            Some(CheckpointStore::File(PathBuf::from(path)))
+        }
+        ("", _) | ("clickhouse", SinkKind::ClickHouse) | ("postgres", SinkKind::Postgres(_)) => Some(CheckpointStore::Table),
+        ("clickhouse" | "postgres", _) => panic!("CHECKPOINT_STORE names a database other than SINK"),
+        ("off" | "false", _) => None,
+        (other, _) => panic!("Invalid CHECKPOINT_STORE '{}'", other),
+    }
+}
+
+fn f64_from_env(name: &str) -> Option<f64> {
+    let value = env::var(name).ok().filter(|v| !v.trim().is_empty())?;
+    Some(value.trim().parse().ok().filter(|v: &f64| v.is_finite()).unwrap_or_else(|| panic!("Invalid {}", name)))
+}
+
+// PRICE_MIN / PRICE_MAX, PRICE_MAX_DEVIATION_PCT over the last PRICE_MEDIAN_WINDOW swaps
+pub fn price_sanity_from_env() -> PriceSanity {
+    let window = usize_from_env("PRICE_MEDIAN_WINDOW", DEFAULT_MEDIAN_WINDOW);
+    assert!(window > 0, "Invalid PRICE_MEDIAN_WINDOW");
+    PriceSanity {
+        min: f64_from_env("PRICE_MIN"),
+        max: f64_from_env("PRICE_MAX"),
+        max_deviation: f64_from_env("PRICE_MAX_DEVIATION_PCT").map(|pct| pct / 100.0),
+        window,
+    }
+}
+
+// "1m,5m,30m": window sizes, see parse_window
+pub fn parse_windows(list: &str) -> Result<Vec<Duration>> {
+    list.split(',').map(str::trim).filter(|w| !w.is_empty()).map(parse_window).collect()
+}
+
+// Seconds, or with an s/m/h suffix
+pub fn parse_window(window: &str) -> Result<Duration> {
+    let window = window.trim();
+    let (number, unit) = match window.char_indices().last() {
+        Some((i, 's')) => (&window[..i], 1),
+        Some((i, 'm')) => (&window[..i], 60),
+        Some((i, 'h')) => (&window[..i], 3600),
+        _ => (window, 1),
+    };
+    match number.parse::<u64>() {
+        Ok(n) if n > 0 => Ok(Duration::from_secs(n * unit)),
+        _ => Err(eyre::eyre!("Invalid window '{}'", window)),
+    }
+}
+
+// PRICE_EMA_HALF_LIFE=5m,0x<pool>=30s,v4:0x<PoolId>=1h: a bare window applies to every pool
+pub fn parse_half_lives(list: &str) -> Result<EmaHalfLives> {
+    let mut half_lives = EmaHalfLives::default();
+    for entry in list.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+        match entry.split_once('=') {
+            Some((pool, window)) => {
+                let pool = parse_pool_spec(pool)?.pool;
+                half_lives.pools.insert(pool.to_string(), parse_window(window)?);
+            }
+            None => half_lives.default = Some(parse_window(entry)?),
+        }
+    }
+    Ok(half_lives)
+}
+
+fn usize_from_env(name: &str, default: usize) -> usize {
+    match env::var(name) {
+        Ok(v) => v.trim().parse().unwrap_or_else(|_| panic!("Invalid {}", name)),
+        Err(_) => default,
+    }
+}
+
+pub fn v4_contracts_from_env() -> V4Contracts {
+    let pool_manager = env::var("V4_POOL_MANAGER").unwrap_or_else(|_| UNISWAP_V4_POOL_MANAGER.to_string());
+    let position_manager = env::var("V4_POSITION_MANAGER").unwrap_or_else(|_| UNISWAP_V4_POSITION_MANAGER.to_string());
+
+    V4Contracts {
+        pool_manager: Address::from_str(&pool_manager).expect("Invalid V4_POOL_MANAGER"),
+        position_manager: Address::from_str(&position_manager).expect("Invalid V4_POSITION_MANAGER"),
+    }
+}
+
+pub fn factory_from_env() -> Address {
+    let factory_str = env::var("FACTORY_ADDRESS").unwrap_or_else(|_| UNISWAP_V3_FACTORY.to_string());
+    Address::from_str(&factory_str).expect("Invalid factory address")
+}
+
+pub fn positions_from_env() -> Option<PositionTracking> {
+    let enabled = env::var("INDEX_POSITIONS").map(|v| v == "true" || v == "1").unwrap_or(false);
+    if !enabled {
+        return None;
+    }
+
+    let manager_str = env::var("POSITION_MANAGER").unwrap_or_else(|_| UNISWAP_V3_POSITION_MANAGER.to_string());
+    let manager = Address::from_str(&manager_str).expect("Invalid POSITION_MANAGER");
+
+    Some(PositionTracking { manager, factory: factory_from_env() })
+}
+
+// Symbols accepted in PAIRS
+pub const KNOWN_TOKENS: [(&str, &str); 5] = [
+    ("WETH", "0xC02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2"),
+    ("USDC", "0xA0b86991c6218b36c1d19D4a2e9Eb0cE3606eB48"),
+    ("USDT", "0xdAC17F958D2ee523a2206206994597C13D831ec7"),
+    ("DAI", "0x6B175474E89094C44Da98b954EedeAC495271d0F"),
+    ("WBTC", "0x2260FAC5E5542a773Aa44fBCfeDf7C193bc2C599"),
+];
+
+// A known symbol or an address
+pub fn parse_token(token: &str) -> Result<Address> {
+    let token = token.trim();
+    if let Some((_, addr)) = KNOWN_TOKENS.iter().find(|(symbol, _)| symbol.eq_ignore_ascii_case(token)) {
+        return Ok(Address::from_str(addr)?);
+    }
+    Address::from_str(token).map_err(|e| eyre::eyre!("Unknown token '{}': {}", token, e))
+}
+
+// PAIRS=WETH/USDC,0x.../0x...: every fee tier pool of each pair is indexed
+pub fn pairs_from_env() -> Vec<(Address, Address)> {
+    env::var("PAIRS")
+        .unwrap_or_default()
+        .split(',')
+        .map(str::trim)
+        .filter(|p| !p.is_empty())
+        .map(|p| {
+            let (a, b) = p.split_once('/').unwrap_or_else(|| panic!("Invalid pair '{}', expected A/B", p));
+            (parse_token(a).expect("Invalid token in PAIRS"), parse_token(b).expect("Invalid token in PAIRS"))
+        })
+        .collect()
+}
+
+// USDC, USDT and DAI (USDbC on Base) per chain id, bridged variants aside
+pub const DEFAULT_STABLECOINS: [(u64, &[&str]); 6] = [
+    (1, &[
+        "0xA0b86991c6218b36c1d19D4a2e9Eb0cE3606eB48",
+        "0xdAC17F958D2ee523a2206206994597C13D831ec7",
+        "0x6B175474E89094C44Da98b954EedeAC495271d0F",
+    ]),
+    (10, &[
+        "0x0b2C639c533813f4Aa9D7837CAf62653d097Ff85",
+        "0x94b008aA00579c1307B0EF2c499aD98a8ce58e58",
+        "0xDA10009cBd5D07dd0CeCc66161FC93D7c9000da1",
+    ]),
+    (56, &[
+        "0x8AC76a51cc950d9822D68b83fE1Ad97B32Cd580d",
+        "0x55d398326f99059fF775485246999027B3197955",
+    ]),
+    (137, &[
+        "0x3c499c542cEF5E3811e1192ce70d8cC03d5c3359",
+        "0xc2132D05D31c914a87C6611C10748AEb04B58e8F",
+        "0x8f3Cf7ad23Cd3CaDbD9735AFf958023239c6A063",
+    ]),
+    (8453, &[
+        "0x833589fCD6eDb6E08f4c7C32D4f71b54bdA02913",
+        "0xd9aAEc86B65D86f6A7B5B1b0c42FFA531710b6CA",
+        "0x50c5725949A6F0c72E6C4a641F24049A917DB0Cb",
+    ]),
+    (42161, &[
+        "0xaf88d065e77c8cC2239327C5EDb3A432268e5831",
+        "0xFd086bC7CD5C481DCC9C85ebE478A1C0b69FCbb9",
+        "0xDA10009cBd5D07dd0CeCc66161FC93D7c9000da1",
+    ]),
+];
+
+// Empty for chains without a default list
+pub fn default_stablecoins(chain_id: u64) -> HashSet<Address> {
+    DEFAULT_STABLECOINS
+        .iter()
+        .filter(|(id, _)| *id == chain_id)
+        .flat_map(|(_, tokens)| tokens.iter())
+        .map(|t| Address::from_str(t).expect("Invalid default stablecoin"))
+        .collect()
+}
+
+// STABLECOINS (comma-separated) replaces the chain's default list
+pub fn stablecoins_from_env() -> Option<HashSet<Address>> {
+    let list = env::var("STABLECOINS").ok().filter(|v| !v.trim().is_empty())?;
+    Some(
+        list.split(',')
+            .map(str::trim)
+            .filter(|t| !t.is_empty())
+            .map(|t| Address::from_str(t).expect("Invalid address in STABLECOINS"))
+            .collect(),
+    )
+}
+
+// QUOTE_TOKEN=USDC,v4:0x<PoolId>=WETH,0x<pool>=0x<token>: bare tokens apply to every pool
+// holding one, pool=token pins a single pool. Tokens are addresses or symbols
+pub fn parse_quote_tokens(list: &str) -> Result<QuoteTokens> {
+    let mut quote = QuoteTokens::default();
+    for entry in list.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+        match entry.split_once('=') {
+            Some((pool, token)) => {
+                let spec = parse_pool_spec(pool)?;
+                quote.pools.insert(spec.pool, TokenMatch::parse(token));
+            }
+            None => quote.tokens.push(TokenMatch::parse(entry)),
+        }
+    }
+    Ok(quote)
+}
+
+pub fn quote_tokens_from_env() -> QuoteTokens {
+    parse_quote_tokens(&env::var("QUOTE_TOKEN").unwrap_or_default()).expect("Invalid QUOTE_TOKEN")
+}
+
+// INVERT_PRICE=0x<pool>,v4:0x<PoolId>: pool specs, same syntax as POOL_ADDRESSES
+pub fn parse_pool_refs(list: &str) -> Result<HashSet<PoolRef>> {
+    list.split(',').map(str::trim).filter(|e| !e.is_empty()).map(|e| Ok(parse_pool_spec(e)?.pool)).collect()
+}
+
+// CHAINLINK_FEEDS=WETH=0x<aggregator>,0x<token>=0x<aggregator>: tokens are symbols or addresses
+pub fn parse_chainlink_feeds(list: &str) -> Result<HashMap<Address, Address>> {
+    let mut feeds = HashMap::new();
+    for entry in list.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+        let (token, feed) = entry.split_once('=').ok_or_else(|| eyre::eyre!("Invalid feed '{}', expected token=aggregator", entry))?;
+        let feed = Address::from_str(feed.trim()).map_err(|e| eyre::eyre!("Invalid aggregator '{}': {}", feed, e))?;
+        feeds.insert(parse_token(token)?, feed);
+    }
+    Ok(feeds)
+}
+
+pub fn chainlink_from_env() -> Option<ChainlinkFeeds> {
+    let feeds = parse_chainlink_feeds(&env::var("CHAINLINK_FEEDS").unwrap_or_default()).expect("Invalid CHAINLINK_FEEDS");
+    if feeds.is_empty() {
+        return None;
+    }
+    let poll = u64_from_env("CHAINLINK_POLL_SECONDS").filter(|s| *s > 0).map(Duration::from_secs).unwrap_or(DEFAULT_POLL_INTERVAL);
+    let max_age = u64_from_env("CHAINLINK_MAX_AGE_SECONDS").map(Duration::from_secs).unwrap_or(DEFAULT_MAX_AGE);
+    Some(ChainlinkFeeds { feeds, poll, max_age })
+}
+
+// REFERENCE_POOL=<pool spec>, REFERENCE_TOKEN defaults to WETH
+pub fn reference_from_env() -> Option<ReferencePool> {
+    let spec = env::var("REFERENCE_POOL").ok().filter(|v| !v.trim().is_empty())?;
+    let spec = parse_pool_spec(&spec).expect("Invalid REFERENCE_POOL");
+    let token = env::var("REFERENCE_TOKEN").ok().filter(|v| !v.trim().is_empty()).unwrap_or_else(|| "WETH".to_string());
+    let max_age = u64_from_env("REFERENCE_MAX_AGE_SECONDS").map(Duration::from_secs).unwrap_or(DEFAULT_REFERENCE_MAX_AGE);
+    Some(ReferencePool { spec, token: parse_token(&token).expect("Invalid REFERENCE_TOKEN"), max_age })
+}
+
+fn token_list_from_env(name: &str) -> Vec<TokenMatch> {
+    env::var(name)
+        .unwrap_or_default()
+        .split(',')
+        .filter(|t| !t.trim().is_empty())
+        .map(TokenMatch::parse)
+        .collect()
+}
+
+// TOKEN_ALLOWLIST / TOKEN_DENYLIST, comma-separated addresses or symbols
+pub fn token_filter_from_env() -> TokenFilter {
+    TokenFilter { allow: token_list_from_env("TOKEN_ALLOWLIST"), deny: token_list_from_env("TOKEN_DENYLIST") }
+}
+
+// WATCHLIST, comma-separated wallet addresses (any case)
+pub fn watchlist_from_env() -> HashSet<Address> {
+    env::var("WATCHLIST")
+        .unwrap_or_default()
+        .split(',')
+        .map(str::trim)
+        .filter(|a| !a.is_empty())
+        .map(|a| Address::from_str(a).expect("Invalid address in WATCHLIST"))
+        .collect()
+}
+
+// FACTORY_ADDRESS + WATCH_TOKENS: index new pools that contain one of the tokens
+pub fn discovery_from_env() -> Option<PoolDiscovery> {
+    let tokens: HashSet<Address> = env::var("WATCH_TOKENS")
+        .unwrap_or_default()
+        .split(',')
+        .map(str::trim)
+        .filter(|t| !t.is_empty())
+        .map(|t| Address::from_str(t).expect("Invalid address in WATCH_TOKENS"))
+        .collect();
+
+    if tokens.is_empty() {
+        return None;
+    }
+
+    Some(PoolDiscovery { factory: factory_from_env(), tokens })
+}
@@ -0,0 +1,108 @@
+use eyre::Result;
+use std::collections::BTreeMap;
+use tokio::sync::{mpsc, watch};
+use tracing::{info, warn};
+
+use crate::metrics;
+use crate::records::IndexedEvent;
+
+// Default for CONFIRMATIONS
+pub const DEFAULT_CONFIRMATIONS: u64 = 3;
+
+// Records waiting for their block to be deep enough, in block order
+struct Pending {
+    depth: u64,
+    head: u64,
+    blocks: BTreeMap<u64, Vec<IndexedEvent>>,
+}
+
+impl Pending {
+    fn confirmed(&self, block: u64) -> bool {
+        block.saturating_add(self.depth) <= self.head
+    }
+
+    // The records of `block` decoded from the reverted block `hash`. Those of the block
+    // that replaced it at the same height stay
+    fn evict(&mut self, block: u64, hash: &str) -> usize {
+        let Some(events) = self.blocks.get_mut(&block) else { return 0 };
+        let before = events.len();
+        events.retain(|event| event.block_hash() != Some(hash));
+        let evicted = before - events.len();
+        if events.is_empty() {
+            self.blocks.remove(&block);
+        }
+        evicted
+    }
+
+    // Everything now `depth` blocks below the head, oldest block first
+    fn release(&mut self) -> Vec<IndexedEvent> {
+        let mut ready = Vec::new();
+        while let Some(block) = self.blocks.keys().next().copied() && self.confirmed(block) {
+            ready.extend(self.blocks.remove(&block).unwrap_or_default());
+        }
+        ready
+    }
+}
+
+// Between the handler and the writer: a record goes on once its block is `depth`
+// blocks below the head, the highest of newHeads and the blocks of the records
+// themselves. Records of a reverted block are evicted. Whatever is still pending
+// when the senders are gone is forwarded, the process is exiting
+pub async fn run_confirmer(
+    mut rx: mpsc::Receiver<IndexedEvent>,
+    tx: mpsc::Sender<IndexedEvent>,
+    depth: u64,
+    heads: watch::Receiver<u64>,
+) -> Result<()> {
+    let mut pending = Pending { depth, head: *heads.borrow(), blocks: BTreeMap::new() };
+    // None once the newHeads side is gone, the records' own blocks keep the head moving
+    let mut heads = Some(heads);
+
+    loop {
+        let head_changed = async {
+            match heads.as_mut() {
+                Some(heads) => heads.changed().await.map(|_| *heads.borrow_and_update()),
+                None => std::future::pending().await,
+            }
+        };
+
+        tokio::select! {
+            event = rx.recv() => {
+                let Some(event) = event else { break };
+                let block = event.block_number();
+                pending.head = pending.head.max(block);
+
+                match event {
+                    IndexedEvent::Reverted { block_hash, .. } => {
+                        let evicted = pending.evict(block, &block_hash);
+                        if evicted > 0 {
+                            metrics::REORG_EVICTED_RECORDS.inc_by(evicted as u64);
+                            warn!("↩️ Evicted {} unconfirmed record(s) of reorged block {} ({})", evicted, block, block_hash);
+                        }
+                    }
+                    // Audit rows describe the reorg itself, nothing to wait for
+                    IndexedEvent::Reorged(_) => tx.send(event).await?,
+                    event if pending.confirmed(block) => tx.send(event).await?,
+                    event => pending.blocks.entry(block).or_default().push(event),
+                }
+            }
+            head = head_changed => match head {
+                Ok(head) => pending.head = pending.head.max(head),
+                Err(_) => heads = None,
+            },
+        }
+
+        for event in pending.release() {
+            tx.send(event).await?;
+        }
+    }
+
+    let left: Vec<IndexedEvent> = std::mem::take(&mut pending.blocks).into_values().flatten().collect();
+    if !left.is_empty() {
+        info!("⏳ Forwarding {} record(s) still short of {} confirmation(s)", left.len(), depth);
+    }
+    for event in left {
+        tx.send(event).await?;
+    }
+    Ok(())
+}
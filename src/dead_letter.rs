@@ -0,0 +1,119 @@
+use alloy::rpc::types::Log;
+use serde::Serialize;
+use serde_json::Value;
+use std::sync::OnceLock;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tracing::{error, info};
+
+use crate::metrics;
+use crate::records::{ErrorRecord, SCHEMA_VERSION};
+use crate::sink::Sink;
+
+// Error rows waiting for the dead-letter task, past that they are dropped (and counted)
+const CAPACITY: usize = 10_000;
+// Most rows per indexer_errors insert
+const MAX_ROWS: usize = 1000;
+// Pause after a failed insert, so a database outage doesn't turn into an insert loop
+const FAILURE_BACKOFF: Duration = Duration::from_secs(5);
+
+static SINK: OnceLock<mpsc::Sender<ErrorRecord>> = OnceLock::new();
+
+// indexer_errors.category, also the label of indexer_errors_total
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorCategory {
+    // A log of a subscribed contract whose data doesn't fit the event it claims to be
+    Decode,
+    // A row the sink refused (type overflow, bad value), the rest of its batch went in
+    Insert,
+}
+
+impl ErrorCategory {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ErrorCategory::Decode => "decode",
+            ErrorCategory::Insert => "insert",
+        }
+    }
+}
+
+pub fn decode_failure(log: &Log, error: String) -> ErrorRecord {
+    ErrorRecord {
+        chain_id: 0,
+        schema_version: SCHEMA_VERSION,
+        detected_at: chrono::Utc::now().timestamp_millis(),
+        category: ErrorCategory::Decode.as_str().to_string(),
+        error,
+        target_table: String::new(),
+        block_number: log.block_number.unwrap_or_default(),
+        block_hash: log.block_hash.map(|h| format!("{:?}", h)).unwrap_or_default(),
+        tx_hash: log.transaction_hash.map(|h| format!("{:?}", h)).unwrap_or_default(),
+        log_index: log.log_index.unwrap_or_default(),
+        address: format!("{:?}", log.address()),
+        topics: log.topics().iter().map(|t| format!("{:?}", t)).collect(),
+        data: log.data().data.to_string(),
+    }
+}
+
+// The row as JSON, with the log it came from where the record has one
+pub fn rejected_row<T: Serialize>(table: &str, row: &T, error: &eyre::Report) -> ErrorRecord {
+    let json = serde_json::to_string(row).unwrap_or_default();
+    // Numbers past u64 parse as floats here, only the identifying fields are read
+    let fields: Value = serde_json::from_str(&json).unwrap_or_default();
+    let text = |name: &str| fields.get(name).and_then(Value::as_str).unwrap_or_default().to_string();
+    let number = |name: &str| fields.get(name).and_then(Value::as_u64).unwrap_or_default();
+    ErrorRecord {
+        chain_id: number("chain_id"),
+        schema_version: SCHEMA_VERSION,
+        detected_at: chrono::Utc::now().timestamp_millis(),
+        category: ErrorCategory::Insert.as_str().to_string(),
+        error: format!("{:#}", error),
+        target_table: table.to_string(),
+        block_number: number("block_number"),
+        block_hash: text("block_hash"),
+        tx_hash: text("tx_hash"),
+        log_index: number("log_index"),
+        address: text("pool_address"),
+        topics: Vec::new(),
+        data: json,
+    }
+}
+
+// Best effort: counted always, dropped when no dead-letter task runs (--stdout) or it is backed up.
+// The failure itself is logged where it happened
+pub fn report(record: ErrorRecord) {
+    metrics::ERRORS.with_label_values(&[&record.category]).inc();
+    if let Some(sink) = SINK.get()
+        && sink.try_send(record).is_err()
+    {
+        metrics::ERRORS_DROPPED.inc();
+    }
+}
+
+// Starts the task writing reported errors to `table`, once per process. It has its own
+// inserts, a single attempt each: a failure drops those rows instead of holding up the batch
+pub fn spawn<S: Sink>(sink: S, table: String, chain_id: u64) {
+    let (tx, mut rx) = mpsc::channel::<ErrorRecord>(CAPACITY);
+    if SINK.set(tx).is_err() {
+        return;
+    }
+    info!("🪦 Logs that fail to decode and rows the sink refuses go to {}", table);
+    tokio::spawn(async move {
+        while let Some(first) = rx.recv().await {
+            let mut rows = vec![first];
+            while rows.len() < MAX_ROWS && let Ok(row) = rx.try_recv() {
+                rows.push(row);
+            }
+            for row in &mut rows {
+                if row.chain_id == 0 {
+                    row.chain_id = chain_id;
+                }
+            }
+            if let Err(e) = sink.write_rows(&table, &rows, None, None).await {
+                metrics::ERRORS_DROPPED.inc_by(rows.len() as u64);
+                error!("❌ Dropped {} error rows, the {} insert failed: {:?}", rows.len(), table, e);
+                tokio::time::sleep(FAILURE_BACKOFF).await;
+            }
+        }
+    });
+}
@@ -0,0 +1,696 @@
+use alloy::{
+    primitives::{Address, I256, U256},
+    rpc::types::Log,
+    sol_types::SolEvent,
+};
+use bigdecimal::{BigDecimal, ToPrimitive};
+use std::collections::HashMap;
+use tracing::{error, info, warn};
+
+use crate::abi::{
+    pancake_v3, position_manager, uniswap_v2, uniswap_v4, Burn, Collect, CollectProtocol, Flash, Initialize,
+    Mint, SetFeeProtocol, Swap,
+};
+use crate::board::BOARD;
+use crate::chainlink;
+use crate::config::PositionTracking;
+use crate::dead_letter;
+use crate::metrics;
+use crate::pool::{fetch_position_pool, Dex, PoolInfo, PoolRef, Protocol, QuoteSide, UsdSource};
+use crate::price::{
+    adjust_amount, adjust_signed_amount, calculate_pair_prices, calculate_pair_prices_v2, format_price_exact, to_decimal_38_18,
+    exec_price, price_from_tick, swap_fee, price_impact_bps, price_matches_tick, quoted_price, to_f64_rounded, volume_usd, PairPrices, PriceError,
+};
+use crate::records::*;
+
+// Position events are only kept for positions in pools we index, so their decimals are known.
+// `pool_info` looks a pool up in the configured and the firehose pools
+pub async fn decode_position_log<'a>(
+    log: &Log,
+    http_url: &str,
+    tracking: &PositionTracking,
+    pool_info: impl Fn(PoolRef) -> Option<&'a PoolInfo>,
+    position_pools: &mut HashMap<U256, Address>,
+) -> Option<IndexedEvent> {
+    let at = log_position(log, "Position event")?;
+    let token_id = U256::from_be_bytes(log.topics().get(1)?.0);
+
+    let pool = match position_pools.get(&token_id) {
+        Some(pool) => *pool,
+        None => match fetch_position_pool(http_url, tracking, token_id).await {
+            Ok(pool) => {
+                position_pools.insert(token_id, pool);
+                pool
+            }
+            Err(e) => {
+                warn!("⚠️ Failed to resolve pool of position {}: {:?}", token_id, e);
+                return None;
+            }
+        },
+    };
+
+    let info = pool_info(PoolRef::Address(pool))?;
+    let decimals = info.meta.decimals;
+
+    let (event_type, liquidity, recipient, amount0, amount1) = match log.topic0() {
+        Some(&position_manager::IncreaseLiquidity::SIGNATURE_HASH) => {
+            let data = decode_or_warn::<position_manager::IncreaseLiquidity>(log)?;
+            ("increase", Some(data.liquidity.to_string()), None, data.amount0, data.amount1)
+        }
+        Some(&position_manager::DecreaseLiquidity::SIGNATURE_HASH) => {
+            let data = decode_or_warn::<position_manager::DecreaseLiquidity>(log)?;
+            ("decrease", Some(data.liquidity.to_string()), None, data.amount0, data.amount1)
+        }
+        Some(&position_manager::Collect::SIGNATURE_HASH) => {
+            let data = decode_or_warn::<position_manager::Collect>(log)?;
+            ("collect", None, Some(data.recipient.to_string()), data.amount0, data.amount1)
+        }
+        _ => {
+            unknown_topic(log);
+            return None;
+        }
+    };
+
+    info!("🎫 Position {} {} in pool {:?}", token_id, event_type, pool);
+
+    Some(IndexedEvent::Position(PositionEventRecord {
+        chain_id: info.chain_id,
+        schema_version: SCHEMA_VERSION,
+        timestamp: block_millis(log, "position event"),
+        block_number: at.block_number,
+        log_index: at.log_index,
+        block_hash: log.block_hash.unwrap_or_default().to_string(),
+        tx_hash: log.transaction_hash.unwrap_or_default().to_string(),
+        pool_address: pool.to_string(),
+        token_id: token_id.to_string(),
+        event_type: event_type.to_string(),
+        liquidity,
+        recipient,
+        amount0_raw: amount0.to_string(),
+        amount1_raw: amount1.to_string(),
+        amount0: amount_f64(log, adjust_amount(amount0, decimals.token0)),
+        amount1: amount_f64(log, adjust_amount(amount1, decimals.token1)),
+    }))
+}
+
+pub fn decode_log(log: &Log, pool: PoolRef, info: &mut PoolInfo) -> Option<IndexedEvent> {
+    match info.protocol {
+        Protocol::V3 => decode_v3_log(log, pool, info),
+        Protocol::V2 => decode_v2_log(log, pool, info),
+        Protocol::V4 => decode_v4_log(log, pool, info),
+    }
+}
+
+pub fn decode_v4_log(log: &Log, pool: PoolRef, info: &PoolInfo) -> Option<IndexedEvent> {
+    if log.topic0() != Some(&uniswap_v4::Swap::SIGNATURE_HASH) {
+        unknown_topic(log);
+        return None;
+    }
+
+    let data = decode_or_warn::<uniswap_v4::Swap>(log)?;
+
+    swap_record(log, pool, info, DecodedSwap {
+        protocol: Protocol::V4,
+        dex: Dex::Uniswap,
+        sender: data.sender,
+        recipient: None, // V4 Swap has no recipient
+        // V4 deltas are from the swapper's side, flip them to the pool's side like V3
+        amount0: -I256::try_from(data.amount0).ok()?,
+        amount1: -I256::try_from(data.amount1).ok()?,
+        prices: price_or_warn(log, calculate_pair_prices(U256::from(data.sqrtPriceX96), info.meta.decimals.diff())),
+        sqrt_price_x96: Some(U256::from(data.sqrtPriceX96)),
+        tick: Some(data.tick.as_i32()),
+        liquidity: Some(data.liquidity),
+        // Per-swap fee, dynamic-fee pools change it between swaps
+        fee_tier: data.fee.to::<u32>(),
+        protocol_fees: None,
+    })
+}
+
+pub fn decode_v2_log(log: &Log, pool: PoolRef, info: &mut PoolInfo) -> Option<IndexedEvent> {
+    match log.topic0() {
+        Some(&uniswap_v2::Sync::SIGNATURE_HASH) => {
+            let data = decode_or_warn::<uniswap_v2::Sync>(log)?;
+            info.reserves = Some((U256::from(data.reserve0), U256::from(data.reserve1)));
+            None
+        }
+        Some(&uniswap_v2::Swap::SIGNATURE_HASH) => {
+            let data = decode_or_warn::<uniswap_v2::Swap>(log)?;
+
+            // The pair emits Sync right before Swap, so reserves are post-swap
+            let Some((reserve0, reserve1)) = info.reserves else {
+                warn!("⚠️ V2 Swap on {:?} before any Sync, skipping", pool);
+                return None;
+            };
+
+            swap_record(log, pool, info, DecodedSwap {
+                protocol: Protocol::V2,
+                dex: Dex::Uniswap,
+                sender: data.sender,
+                recipient: Some(data.to),
+                // Net flow into the pair, same sign convention as V3
+                amount0: I256::from_raw(data.amount0In) - I256::from_raw(data.amount0Out),
+                amount1: I256::from_raw(data.amount1In) - I256::from_raw(data.amount1Out),
+                prices: price_or_warn(log, calculate_pair_prices_v2(reserve0, reserve1, info.meta.decimals.diff())),
+                sqrt_price_x96: None,
+                tick: None,
+                liquidity: None, // V2 pairs have no active liquidity
+                fee_tier: info.meta.fee,
+                protocol_fees: None,
+            })
+        }
+        _ => {
+            unknown_topic(log);
+            None
+        }
+    }
+}
+
+// Protocol-independent view of a decoded swap, turned into a row by swap_record
+pub struct DecodedSwap {
+    pub protocol: Protocol,
+    pub dex: Dex,
+    pub sender: Address,
+    pub recipient: Option<Address>,
+    // Pool's side: positive flows into the pool, negative out of it
+    pub amount0: I256,
+    pub amount1: I256,
+    // None when the on-chain values give no price, the row keeps NULL prices
+    pub prices: Option<PairPrices>,
+    // Raw on-chain price state, V2 pairs have neither
+    pub sqrt_price_x96: Option<U256>,
+    pub tick: Option<i32>,
+    pub liquidity: Option<u128>,
+    pub fee_tier: u32,
+    // PancakeSwap V3 only
+    pub protocol_fees: Option<(u128, u128)>,
+}
+
+// Buy/sell of the base token, the one price_usd prices. Token1 unless the quote side is token1
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Buy,
+    Sell,
+    Unknown,
+}
+
+impl Direction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Direction::Buy => "buy",
+            Direction::Sell => "sell",
+            Direction::Unknown => "unknown",
+        }
+    }
+}
+
+// Amounts are from the pool's side: base leaving the pool is a buy
+pub fn swap_direction(amount0: I256, amount1: I256, quote: Option<QuoteSide>) -> Direction {
+    if amount0.is_zero() || amount1.is_zero() {
+        return Direction::Unknown;
+    }
+
+    let base = if quote == Some(QuoteSide::Token1) { amount0 } else { amount1 };
+    if base.is_negative() { Direction::Buy } else { Direction::Sell }
+}
+
+// A quote-token amount in USD: as is for stablecoins and QUOTE_TOKEN, times the Chainlink or
+// reference pool price at the row's time (ms) otherwise. None when that price is missing or stale
+fn to_usd(info: &PoolInfo, value: &BigDecimal, timestamp: i64) -> Option<BigDecimal> {
+    match info.quote_usd {
+        None => Some(value.clone()),
+        Some(UsdSource::Chainlink(token)) => chainlink::usd_price(token, (timestamp / 1000) as u64).map(|usd| value * usd),
+        Some(UsdSource::Reference(max_age)) => {
+            let usd = BigDecimal::try_from(BOARD.get(timestamp, max_age)?).ok()?;
+            Some(value * usd)
+        }
+    }
+}
+
+pub fn swap_record(log: &Log, pool: PoolRef, info: &PoolInfo, swap: DecodedSwap) -> Option<IndexedEvent> {
+    let LogPosition { block_number, transaction_index, log_index } = log_position(log, "Swap")?;
+
+    // Unresolved pools (firehose) have no decimals: keep the raw values, leave prices NULL
+    let resolved = info.meta.resolved;
+    let prices = swap.prices.as_ref().filter(|_| resolved);
+    let decimals = info.meta.decimals;
+    let amount0 = adjust_signed_amount(swap.amount0, decimals.token0);
+    let amount1 = adjust_signed_amount(swap.amount1, decimals.token1);
+
+    // V3/V4 carry a tick as well, a price that disagrees with it points at a decimals or orientation bug
+    let price_check_failed = match (prices, swap.tick) {
+        (Some(p), Some(tick)) if !price_matches_tick(p, tick, decimals.diff()) => {
+            warn!(
+                "⚠️ Price {} from sqrtPriceX96 disagrees with {} from tick {} in tx {:?}",
+                p.token1_in_token0, price_from_tick(tick, decimals.token0, decimals.token1), tick, log.transaction_hash
+            );
+            true
+        }
+        _ => false,
+    };
+
+    // The event's price is after the swap, the amounts are what the trader actually got
+    let exec = exec_price(&amount0, &amount1).filter(|_| resolved);
+    let impact = exec.as_ref().zip(prices).and_then(|(e, p)| price_impact_bps(e, &p.token0_in_token1));
+
+    let direction = swap_direction(swap.amount0, swap.amount1, info.quote);
+    if direction == Direction::Unknown {
+        warn!(
+            "⚠️ Swap in tx {:?} with a zero leg (amount0 {}, amount1 {}), direction unknown",
+            log.transaction_hash, swap.amount0, swap.amount1
+        );
+    }
+
+    let ingested_at = chrono::Utc::now();
+    let timestamp = block_time(log, "swap").unwrap_or(ingested_at);
+
+    // price_usd is in the quote token, pools without one only fill the raw orientation columns.
+    // price_exact is NULL along with it, the f64 is NULL rather than a fake 0.0 when it doesn't convert
+    let millis = timestamp.timestamp_millis();
+    let quoted = prices.and_then(|p| quoted_price(p, info.quote)).and_then(|p| to_usd(info, p, millis));
+    let volume = prices.and(volume_usd(&amount0, &amount1, info.quote)).and_then(|v| to_usd(info, &v, millis));
+    let price_exact = quoted.as_ref().map(format_price_exact);
+    let price_f64 = quoted.as_ref().and_then(to_f64_rounded).filter(|p| p.is_finite());
+    if let Some(exact) = &price_exact && price_f64.is_none() {
+        warn!("⚠️ Price {} in tx {:?} doesn't fit an f64, price_usd left empty", exact, log.transaction_hash);
+    }
+    let price_decimal = quoted.as_ref().filter(|_| info.price_decimal).and_then(|p| {
+        let decimal = to_decimal_38_18(p);
+        if decimal.is_none() {
+            warn!("⚠️ Price {} in tx {:?} doesn't fit Decimal(38, 18), price_usd_decimal left empty", format_price_exact(p), log.transaction_hash);
+        }
+        decimal
+    });
+
+    // The fee is in the input token: a quote-side fee is priced like volume_usd, a base-side one
+    // at the swap's price
+    let fee = swap_fee(&amount0, &amount1, swap.fee_tier).filter(|_| resolved);
+    let fee_usd = fee.as_ref().and_then(|(side, amount)| match info.quote? {
+        quote if quote == *side => to_usd(info, amount, millis),
+        _ => quoted.as_ref().map(|price| amount * price),
+    });
+
+    if info.reference && let Some(price) = price_f64 {
+        BOARD.update(price, millis);
+    }
+
+    info!("🔄 Swap detected: ${:.2} ({} {})", price_f64.unwrap_or_default(), swap.dex.as_str(), swap.protocol.as_str());
+
+    Some(IndexedEvent::Swap(Box::new(SwapRecord {
+        chain_id: info.chain_id,
+        schema_version: SCHEMA_VERSION,
+        timestamp,
+        ingested_at,
+        block_number,
+        block_hash: log.block_hash.unwrap_or_default().to_string(),
+        transaction_index,
+        log_index,
+        tx_hash: log.transaction_hash.unwrap_or_default().to_string(),
+        pool_address: pool.to_string(),
+        sender: swap.sender.to_string(),
+        recipient: swap.recipient.map(|r| r.to_string()).unwrap_or_default(),
+        amount0_raw: swap.amount0.to_string(),
+        amount1_raw: swap.amount1.to_string(),
+        amount0: amount0.to_f64().filter(|_| resolved),
+        amount1: amount1.to_f64().filter(|_| resolved),
+        price_usd: price_f64,
+        price_exact,
+        price_usd_decimal: price_decimal,
+        price_token0_in_token1: prices.and_then(|p| to_f64_rounded(&p.token0_in_token1)),
+        price_token1_in_token0: prices.and_then(|p| to_f64_rounded(&p.token1_in_token0)),
+        volume_usd: volume.as_ref().and_then(to_f64_rounded),
+        exec_price: exec.as_ref().and_then(to_f64_rounded),
+        price_impact_bps: impact,
+        fee_amount: fee.as_ref().and_then(|(_, amount)| to_f64_rounded(amount)),
+        fee_usd: fee_usd.as_ref().and_then(to_f64_rounded),
+        // Filled in by the writer, in chain order
+        price_move_bps: None,
+        price_ema: None,
+        direction: direction.as_str().to_string(),
+        price_inverted: info.inverted,
+        sqrt_price_x96: swap.sqrt_price_x96.map(|p| p.to_string()),
+        tick: swap.tick,
+        liquidity: swap.liquidity,
+        decimals_shift: decimals.diff(),
+        price_check_failed,
+        fee_tier: swap.fee_tier,
+        token0_symbol: info.meta.token0_symbol.clone(),
+        token1_symbol: info.meta.token1_symbol.clone(),
+        pair: info.meta.pair(info.quote),
+        // Filled in by TxLookup
+        gas_used: None,
+        gas_price_gwei: None,
+        tx_from: None,
+        protocol: swap.protocol.as_str().to_string(),
+        dex: swap.dex.as_str().to_string(),
+        protocol_fees_token0: swap.protocol_fees.map(|(f0, _)| f0.to_string()),
+        protocol_fees_token1: swap.protocol_fees.map(|(_, f1)| f1.to_string()),
+        // Stamped by the writer
+        insert_version: 0,
+    })))
+}
+
+pub fn decode_v3_log(log: &Log, pool: PoolRef, info: &PoolInfo) -> Option<IndexedEvent> {
+    let tx_hash = log.transaction_hash.unwrap_or_default();
+    let decimals = info.meta.decimals;
+    let decimal_diff = decimals.diff();
+
+    let event = match log.topic0() {
+        Some(&Swap::SIGNATURE_HASH) => {
+            let data = decode_or_warn::<Swap>(log)?;
+            if info.dex != Dex::Uniswap {
+                warn!("⚠️ Pool {} is configured as {} but emits Uniswap swaps", pool, info.dex.as_str());
+            }
+
+            return swap_record(log, pool, info, DecodedSwap {
+                protocol: Protocol::V3,
+                dex: Dex::Uniswap,
+                sender: data.sender,
+                recipient: Some(data.recipient),
+                amount0: data.amount0,
+                amount1: data.amount1,
+                prices: price_or_warn(log, calculate_pair_prices(U256::from(data.sqrtPriceX96), decimal_diff)),
+                sqrt_price_x96: Some(U256::from(data.sqrtPriceX96)),
+                tick: Some(data.tick.as_i32()),
+                liquidity: Some(data.liquidity),
+                fee_tier: info.meta.fee,
+                protocol_fees: None,
+            });
+        }
+        Some(&pancake_v3::Swap::SIGNATURE_HASH) => {
+            let data = decode_or_warn::<pancake_v3::Swap>(log)?;
+            if info.dex != Dex::Pancake {
+                warn!("⚠️ Pool {} is configured as {} but emits PancakeSwap swaps", pool, info.dex.as_str());
+            }
+
+            return swap_record(log, pool, info, DecodedSwap {
+                protocol: Protocol::V3,
+                dex: Dex::Pancake,
+                sender: data.sender,
+                recipient: Some(data.recipient),
+                amount0: data.amount0,
+                amount1: data.amount1,
+                prices: price_or_warn(log, calculate_pair_prices(U256::from(data.sqrtPriceX96), decimal_diff)),
+                sqrt_price_x96: Some(U256::from(data.sqrtPriceX96)),
+                tick: Some(data.tick.as_i32()),
+                liquidity: Some(data.liquidity),
+                fee_tier: info.meta.fee,
+                protocol_fees: Some((data.protocolFeesToken0, data.protocolFeesToken1)),
+            });
+        }
+        Some(&Mint::SIGNATURE_HASH) => {
+            let data = decode_or_warn::<Mint>(log)?;
+            let at = log_position(log, "Mint")?;
+
+            info!("🌱 Mint detected: liquidity {}", data.amount);
+
+            IndexedEvent::Mint(MintRecord {
+                chain_id: info.chain_id,
+                schema_version: SCHEMA_VERSION,
+                timestamp: block_millis(log, "mint"),
+                block_number: at.block_number,
+                log_index: at.log_index,
+                block_hash: log.block_hash.unwrap_or_default().to_string(),
+                tx_hash: tx_hash.to_string(),
+                pool_address: pool.to_string(),
+                owner: data.owner.to_string(),
+                tick_lower: data.tickLower.as_i32(),
+                tick_upper: data.tickUpper.as_i32(),
+                amount: data.amount.to_string(),
+                amount0: data.amount0.to_string(),
+                amount1: data.amount1.to_string(),
+            })
+        }
+        Some(&Burn::SIGNATURE_HASH) => {
+            let data = decode_or_warn::<Burn>(log)?;
+            let at = log_position(log, "Burn")?;
+
+            let amount0 = amount_f64(log, adjust_amount(data.amount0, decimals.token0));
+            let amount1 = amount_f64(log, adjust_amount(data.amount1, decimals.token1));
+
+            info!("🔥 Burn detected: {:.4} / {:.4}", amount0.unwrap_or_default(), amount1.unwrap_or_default());
+
+            IndexedEvent::Burn(BurnRecord {
+                chain_id: info.chain_id,
+                schema_version: SCHEMA_VERSION,
+                timestamp: block_millis(log, "burn"),
+                block_number: at.block_number,
+                log_index: at.log_index,
+                block_hash: log.block_hash.unwrap_or_default().to_string(),
+                tx_hash: tx_hash.to_string(),
+                pool_address: pool.to_string(),
+                owner: data.owner.to_string(),
+                tick_lower: data.tickLower.as_i32(),
+                tick_upper: data.tickUpper.as_i32(),
+                amount: data.amount.to_string(),
+                amount0_raw: data.amount0.to_string(),
+                amount1_raw: data.amount1.to_string(),
+                amount0,
+                amount1,
+            })
+        }
+        Some(&Collect::SIGNATURE_HASH) => {
+            let data = decode_or_warn::<Collect>(log)?;
+            let at = log_position(log, "Collect")?;
+
+            let amount0 = amount_f64(log, adjust_amount(U256::from(data.amount0), decimals.token0));
+            let amount1 = amount_f64(log, adjust_amount(U256::from(data.amount1), decimals.token1));
+
+            info!("💰 Collect detected: {:.4} / {:.4}", amount0.unwrap_or_default(), amount1.unwrap_or_default());
+
+            IndexedEvent::Collect(CollectRecord {
+                chain_id: info.chain_id,
+                schema_version: SCHEMA_VERSION,
+                timestamp: block_millis(log, "collect"),
+                block_number: at.block_number,
+                log_index: at.log_index,
+                block_hash: log.block_hash.unwrap_or_default().to_string(),
+                tx_hash: tx_hash.to_string(),
+                pool_address: pool.to_string(),
+                owner: data.owner.to_string(),
+                recipient: data.recipient.to_string(),
+                tick_lower: data.tickLower.as_i32(),
+                tick_upper: data.tickUpper.as_i32(),
+                amount0_raw: data.amount0.to_string(),
+                amount1_raw: data.amount1.to_string(),
+                amount0,
+                amount1,
+            })
+        }
+        Some(&Flash::SIGNATURE_HASH) => {
+            let data = decode_or_warn::<Flash>(log)?;
+            let at = log_position(log, "Flash")?;
+
+            let amount0 = amount_f64(log, adjust_amount(data.amount0, decimals.token0));
+            let amount1 = amount_f64(log, adjust_amount(data.amount1, decimals.token1));
+            let fee0 = amount_f64(log, adjust_amount(data.paid0, decimals.token0));
+            let fee1 = amount_f64(log, adjust_amount(data.paid1, decimals.token1));
+
+            info!(
+                "⚡ Flash detected: {:.4} / {:.4}, fee {:.4} / {:.4}",
+                amount0.unwrap_or_default(), amount1.unwrap_or_default(), fee0.unwrap_or_default(), fee1.unwrap_or_default()
+            );
+
+            IndexedEvent::Flash(FlashRecord {
+                chain_id: info.chain_id,
+                schema_version: SCHEMA_VERSION,
+                timestamp: block_millis(log, "flash"),
+                block_number: at.block_number,
+                log_index: at.log_index,
+                block_hash: log.block_hash.unwrap_or_default().to_string(),
+                tx_hash: tx_hash.to_string(),
+                pool_address: pool.to_string(),
+                sender: data.sender.to_string(),
+                recipient: data.recipient.to_string(),
+                amount0_raw: data.amount0.to_string(),
+                amount1_raw: data.amount1.to_string(),
+                paid0_raw: data.paid0.to_string(),
+                paid1_raw: data.paid1.to_string(),
+                amount0,
+                amount1,
+                fee0,
+                fee1,
+            })
+        }
+        Some(&Initialize::SIGNATURE_HASH) => {
+            let data = decode_or_warn::<Initialize>(log)?;
+            let at = log_position(log, "Initialize")?;
+
+            // Priced in USD at the block time, like a swap, so a backfilled Initialize gets the
+            // Chainlink round of its block
+            let timestamp = block_millis(log, "initialize");
+            let prices = price_or_warn(log, calculate_pair_prices(U256::from(data.sqrtPriceX96), decimal_diff));
+            let price_f64 = prices
+                .as_ref()
+                .and_then(|p| quoted_price(p, info.quote))
+                .and_then(|p| to_usd(info, p, timestamp))
+                .and_then(|p| to_f64_rounded(&p))
+                .filter(|p| p.is_finite());
+
+            info!("🐣 Pool initialized: ${:.2}", price_f64.unwrap_or_default());
+
+            IndexedEvent::Initialize(InitializeRecord {
+                chain_id: info.chain_id,
+                schema_version: SCHEMA_VERSION,
+                timestamp,
+                block_number: at.block_number,
+                log_index: at.log_index,
+                block_hash: log.block_hash.unwrap_or_default().to_string(),
+                tx_hash: tx_hash.to_string(),
+                pool_address: pool.to_string(),
+                sqrt_price_x96: data.sqrtPriceX96.to_string(),
+                tick: data.tick.as_i32(),
+                price_usd: price_f64,
+                decimals_shift: decimal_diff,
+            })
+        }
+        Some(&SetFeeProtocol::SIGNATURE_HASH) => {
+            let data = decode_or_warn::<SetFeeProtocol>(log)?;
+
+            info!(
+                "🏛️ Fee protocol changed: {}/{} -> {}/{}",
+                data.feeProtocol0Old, data.feeProtocol1Old, data.feeProtocol0New, data.feeProtocol1New
+            );
+
+            IndexedEvent::ProtocolFee(ProtocolFeeRecord {
+                fee_protocol0_old: Some(data.feeProtocol0Old),
+                fee_protocol1_old: Some(data.feeProtocol1Old),
+                fee_protocol0_new: Some(data.feeProtocol0New),
+                fee_protocol1_new: Some(data.feeProtocol1New),
+                ..protocol_fee_record(log, pool, info.chain_id, "set_fee_protocol")?
+            })
+        }
+        Some(&CollectProtocol::SIGNATURE_HASH) => {
+            let data = decode_or_warn::<CollectProtocol>(log)?;
+
+            let amount0 = amount_f64(log, adjust_amount(U256::from(data.amount0), decimals.token0));
+            let amount1 = amount_f64(log, adjust_amount(U256::from(data.amount1), decimals.token1));
+
+            info!("🏛️ Protocol fees collected: {:.4} / {:.4}", amount0.unwrap_or_default(), amount1.unwrap_or_default());
+
+            IndexedEvent::ProtocolFee(ProtocolFeeRecord {
+                sender: Some(data.sender.to_string()),
+                recipient: Some(data.recipient.to_string()),
+                amount0_raw: Some(data.amount0.to_string()),
+                amount1_raw: Some(data.amount1.to_string()),
+                amount0,
+                amount1,
+                ..protocol_fee_record(log, pool, info.chain_id, "collect_protocol")?
+            })
+        }
+        topic0 => {
+            metrics::UNKNOWN_TOPICS.inc();
+            error!("❌ Unexpected event layout from pool {}: topic0 {:?}, tx {:?}", pool, topic0, log.transaction_hash);
+            return None;
+        }
+    };
+
+    Some(event)
+}
+
+// Where a log sits in the chain
+pub struct LogPosition {
+    pub block_number: u64,
+    pub transaction_index: u64,
+    pub log_index: u64,
+}
+
+// Logs without position metadata are still pending, there is nothing to order, dedup or
+// checkpoint them by: no row, a warning
+pub fn log_position(log: &Log, what: &str) -> Option<LogPosition> {
+    let (Some(block_number), Some(transaction_index), Some(log_index)) = (log.block_number, log.transaction_index, log.log_index) else {
+        warn!(
+            "⚠️ {} in tx {:?} missing metadata (block {:?}, tx index {:?}, log index {:?}), skipping",
+            what, log.transaction_hash, log.block_number, log.transaction_index, log.log_index
+        );
+        return None;
+    };
+    Some(LogPosition { block_number, transaction_index, log_index })
+}
+
+// Live and backfilled logs both carry the block time here, filled in by BlockTimes. None,
+// with a warning, when neither the provider nor the header had it: the caller uses ingest time
+fn block_time(log: &Log, what: &str) -> Option<chrono::DateTime<chrono::Utc>> {
+    let time = log.block_timestamp.and_then(|ts| chrono::DateTime::from_timestamp(ts as i64, 0));
+    if time.is_none() {
+        warn!("⚠️ No block timestamp for {} in tx {:?}, using ingest time", what, log.transaction_hash);
+    }
+    time
+}
+
+// Block time of a non-swap row, Unix millis
+fn block_millis(log: &Log, what: &str) -> i64 {
+    block_time(log, what).unwrap_or_else(chrono::Utc::now).timestamp_millis()
+}
+
+// Decimal-adjusted amount of a non-swap row, NULL rather than 0.0 when it doesn't fit an f64
+fn amount_f64(log: &Log, amount: BigDecimal) -> Option<f64> {
+    let value = to_f64_rounded(&amount).filter(|a| a.is_finite());
+    if value.is_none() {
+        warn!("⚠️ Amount {} in tx {:?} doesn't fit an f64, left empty", amount, log.transaction_hash);
+    }
+    value
+}
+
+fn unknown_topic(log: &Log) {
+    metrics::UNKNOWN_TOPICS.inc();
+    warn!("⚠️ Unhandled topic0 {:?} from {:?}", log.topic0(), log.address());
+}
+
+// Block/tx context of a protocol fee row, event-specific columns left empty
+pub fn protocol_fee_record(log: &Log, pool: PoolRef, chain_id: u64, event_type: &str) -> Option<ProtocolFeeRecord> {
+    let at = log_position(log, event_type)?;
+    Some(ProtocolFeeRecord {
+        chain_id,
+        schema_version: SCHEMA_VERSION,
+        timestamp: block_millis(log, event_type),
+        block_number: at.block_number,
+        log_index: at.log_index,
+        block_hash: log.block_hash.unwrap_or_default().to_string(),
+        tx_hash: log.transaction_hash.unwrap_or_default().to_string(),
+        transaction_index: at.transaction_index,
+        pool_address: pool.to_string(),
+        event_type: event_type.to_string(),
+        fee_protocol0_old: None,
+        fee_protocol1_old: None,
+        fee_protocol0_new: None,
+        fee_protocol1_new: None,
+        sender: None,
+        recipient: None,
+        amount0_raw: None,
+        amount1_raw: None,
+        amount0: None,
+        amount1: None,
+    })
+}
+
+// A price that can't be computed leaves the price columns NULL, never a $0 row
+pub fn price_or_warn(log: &Log, prices: Result<PairPrices, PriceError>) -> Option<PairPrices> {
+    match prices {
+        Ok(prices) => Some(prices),
+        Err(e) => {
+            metrics::PRICE_ERRORS.inc();
+            warn!(
+                "⚠️ No price for {:?} in tx {:?} (block {:?}, log index {:?}): {}",
+                log.address(), log.transaction_hash, log.block_number, log.log_index, e
+            );
+            None
+        }
+    }
+}
+
+// Decode failures are an event layout we don't understand, never drop them quietly: logged,
+// counted and kept in indexer_errors
+pub fn decode_or_warn<E: SolEvent>(log: &Log) -> Option<E> {
+    match log.log_decode::<E>() {
+        Ok(decoded) => Some(decoded.inner.data),
+        Err(e) => {
+            error!(
+                "❌ Failed to decode {} from {:?} in tx {:?}: {:?} (topics {:?}, data {})",
+                E::SIGNATURE, log.address(), log.transaction_hash, e, log.topics(), log.data().data
+            );
+            dead_letter::report(dead_letter::decode_failure(log, format!("{}: {}", E::SIGNATURE, e)));
+            None
+        }
+    }
+}
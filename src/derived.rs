@@ -0,0 +1,37 @@
+use crate::candles::Candles;
+use crate::config::IndexerConfig;
+use crate::ema::PriceEma;
+use crate::price_move::PriceMoves;
+use crate::records::IndexedEvent;
+use crate::twap::Twaps;
+
+// Per-pool state the writer keeps over the ordered stream, after the confirmation
+// buffer and the batch sort: price moves, EMAs, TWAP windows and candles. The default
+// derives price moves only
+#[derive(Debug, Default)]
+pub struct Derived {
+    moves: PriceMoves,
+    ema: PriceEma,
+    twaps: Twaps,
+    candles: Candles,
+}
+
+impl Derived {
+    pub fn new(config: &IndexerConfig) -> Self {
+        Self {
+            moves: PriceMoves::new(),
+            ema: PriceEma::new(config.ema_half_lives.clone()),
+            twaps: Twaps::new(&config.twap_windows),
+            candles: Candles::new(config.candles.clone()),
+        }
+    }
+
+    // Fills the swaps' derived columns, then appends the rows the batch completes
+    pub fn apply(&mut self, batch: &mut Vec<IndexedEvent>) {
+        self.moves.apply(batch);
+        self.ema.apply(batch);
+        let mut rows = self.twaps.apply(batch);
+        rows.extend(self.candles.apply(batch));
+        batch.extend(rows);
+    }
+}
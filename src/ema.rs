@@ -0,0 +1,70 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use crate::records::IndexedEvent;
+
+// PRICE_EMA_HALF_LIFE: half-life of price_ema, for every pool and per pool_address
+#[derive(Debug, Clone, Default)]
+pub struct EmaHalfLives {
+    pub default: Option<Duration>,
+    pub pools: HashMap<String, Duration>,
+}
+
+impl EmaHalfLives {
+    fn get(&self, pool: &str) -> Option<Duration> {
+        self.pools.get(pool).copied().or(self.default)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct EmaState {
+    position: (u64, u64),
+    // Block time of the last swap, ms
+    at: i64,
+    value: f64,
+}
+
+// price_ema of every swap, applied by the writer in chain order. A pool's first swap
+// starts it at its own price; each later one moves it by 1 - 0.5^(dt / half_life), so
+// after a long idle gap the new price all but replaces it. A swap at or behind the
+// pool's last one (replayed after a reconnect) gets no value and leaves it alone
+#[derive(Debug, Default)]
+pub struct PriceEma {
+    half_lives: EmaHalfLives,
+    pools: HashMap<String, EmaState>,
+}
+
+impl PriceEma {
+    pub fn new(half_lives: EmaHalfLives) -> Self {
+        Self { half_lives, pools: HashMap::new() }
+    }
+
+    pub fn apply(&mut self, batch: &mut [IndexedEvent]) {
+        for event in batch {
+            let IndexedEvent::Swap(swap) = event else { continue };
+            let (Some(price), Some(half_life)) = (swap.price_usd, self.half_lives.get(&swap.pool_address)) else {
+                continue;
+            };
+            let position = (swap.block_number, swap.log_index);
+            let at = swap.timestamp.timestamp_millis();
+
+            let state = match self.pools.get(&swap.pool_address) {
+                Some(last) if last.position >= position => continue,
+                Some(last) => {
+                    let dt = (at - last.at).max(0);
+                    let value = ema_step(last.value, price, dt, half_life);
+                    EmaState { position, at: at.max(last.at), value }
+                }
+                None => EmaState { position, at, value: price },
+            };
+            swap.price_ema = Some(state.value);
+            self.pools.insert(swap.pool_address.clone(), state);
+        }
+    }
+}
+
+// The previous EMA moved towards `price` after `dt` ms
+pub fn ema_step(previous: f64, price: f64, dt: i64, half_life: Duration) -> f64 {
+    let alpha = 1.0 - 0.5f64.powf(dt as f64 / half_life.as_millis() as f64);
+    previous + alpha * (price - previous)
+}
@@ -0,0 +1,186 @@
+use clickhouse::{Client, RowOwned, RowWrite};
+use eyre::Result;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+use crate::metrics;
+use crate::storage::{write_rows, AsyncInsert};
+
+// Defaults for CLICKHOUSE_UNHEALTHY_AFTER / CLICKHOUSE_RECHECK_SECONDS
+pub const DEFAULT_UNHEALTHY_AFTER: u32 = 3;
+pub const DEFAULT_RECHECK_INTERVAL: Duration = Duration::from_secs(30);
+
+// CLICKHOUSE_FAILOVER: which endpoint an insert tries first
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FailoverMode {
+    // The first healthy one in CLICKHOUSE_URL order, later ones only take over
+    Ordered,
+    // Each insert starts one endpoint further along
+    RoundRobin,
+}
+
+#[derive(Debug, Clone)]
+pub struct FailoverSettings {
+    pub mode: FailoverMode,
+    // Consecutive failed inserts before an endpoint is skipped
+    pub unhealthy_after: u32,
+    // How long a skipped endpoint waits before an insert tries it again
+    pub recheck: Duration,
+}
+
+impl Default for FailoverSettings {
+    fn default() -> Self {
+        Self { mode: FailoverMode::Ordered, unhealthy_after: DEFAULT_UNHEALTHY_AFTER, recheck: DEFAULT_RECHECK_INTERVAL }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+struct EndpointHealth {
+    failures: u32,
+    // Set while unhealthy, moved on by each failed recheck
+    down_since: Option<Instant>,
+}
+
+// Health of the CLICKHOUSE_URL endpoints, by index
+#[derive(Debug)]
+pub struct Health {
+    settings: FailoverSettings,
+    endpoints: Vec<EndpointHealth>,
+    next: usize,
+}
+
+impl Health {
+    pub fn new(count: usize, settings: &FailoverSettings) -> Self {
+        Self { settings: settings.clone(), endpoints: vec![EndpointHealth::default(); count], next: 0 }
+    }
+
+    // Endpoints one insert attempt goes through, in order: the healthy ones and the unhealthy
+    // ones due for a recheck. When none is left every endpoint is tried anyway
+    pub fn candidates(&mut self, now: Instant) -> Vec<usize> {
+        let count = self.endpoints.len();
+        let start = match self.settings.mode {
+            FailoverMode::Ordered => 0,
+            FailoverMode::RoundRobin => {
+                let start = self.next;
+                self.next = (start + 1) % count;
+                start
+            }
+        };
+        let order: Vec<usize> = (0..count).map(|i| (start + i) % count).collect();
+        let usable: Vec<usize> = order
+            .iter()
+            .copied()
+            .filter(|&i| self.endpoints[i].down_since.is_none_or(|since| now.duration_since(since) >= self.settings.recheck))
+            .collect();
+        if usable.is_empty() { order } else { usable }
+    }
+
+    // Some(false) when this failure took the endpoint down, Some(true) when it came back
+    pub fn record(&mut self, index: usize, ok: bool, now: Instant) -> Option<bool> {
+        let endpoint = &mut self.endpoints[index];
+        if ok {
+            endpoint.failures = 0;
+            return endpoint.down_since.take().map(|_| true);
+        }
+        endpoint.failures += 1;
+        match endpoint.down_since {
+            Some(_) => {
+                endpoint.down_since = Some(now);
+                None
+            }
+            None if endpoint.failures >= self.settings.unhealthy_after => {
+                endpoint.down_since = Some(now);
+                Some(false)
+            }
+            None => None,
+        }
+    }
+
+    // Unhealthy right away, for an endpoint that failed its startup ping
+    pub fn mark_down(&mut self, index: usize, now: Instant) {
+        self.endpoints[index] = EndpointHealth { failures: self.settings.unhealthy_after, down_since: Some(now) };
+    }
+
+    pub fn is_healthy(&self, index: usize) -> bool {
+        self.endpoints[index].down_since.is_none()
+    }
+}
+
+// One client per CLICKHOUSE_URL endpoint. Inserts fail over between them; migrations,
+// checkpoints and reads use client(), the first healthy one when it is asked for
+#[derive(Clone)]
+pub struct Endpoints {
+    clients: Arc<Vec<(String, Client)>>,
+    health: Arc<Mutex<Health>>,
+}
+
+impl Endpoints {
+    pub fn new(clients: Vec<(String, Client)>, settings: &FailoverSettings) -> Self {
+        assert!(!clients.is_empty(), "No ClickHouse endpoints");
+        for (url, _) in &clients {
+            metrics::CLICKHOUSE_ENDPOINT_HEALTHY.with_label_values(&[url]).set(1);
+        }
+        let health = Health::new(clients.len(), settings);
+        Self { clients: Arc::new(clients), health: Arc::new(Mutex::new(health)) }
+    }
+
+    pub fn client(&self) -> Client {
+        let health = self.health.lock().unwrap();
+        let index = (0..self.clients.len()).find(|&i| health.is_healthy(i)).unwrap_or(0);
+        self.clients[index].1.clone()
+    }
+
+    pub fn mark_down(&self, index: usize) {
+        self.health.lock().unwrap().mark_down(index, Instant::now());
+        metrics::CLICKHOUSE_ENDPOINT_HEALTHY.with_label_values(&[&self.clients[index].0]).set(0);
+    }
+
+    // Counts an operation on an endpoint towards its health, logged when that changes
+    pub fn record(&self, index: usize, ok: bool) {
+        let change = self.health.lock().unwrap().record(index, ok, Instant::now());
+        let url = &self.clients[index].0;
+        match change {
+            Some(true) => {
+                info!("💚 ClickHouse at {} is back", url);
+                metrics::CLICKHOUSE_ENDPOINT_HEALTHY.with_label_values(&[url]).set(1);
+            }
+            Some(false) => {
+                warn!("💔 ClickHouse at {} marked unhealthy, skipped by inserts for the next while", url);
+                metrics::CLICKHOUSE_ENDPOINT_HEALTHY.with_label_values(&[url]).set(0);
+            }
+            None => {}
+        }
+    }
+
+    // One insert attempt: each candidate endpoint in turn until one takes the rows. Only when
+    // all of them failed does the attempt fail, with the last error
+    pub async fn write_rows<T: RowOwned + RowWrite>(
+        &self,
+        table: &str,
+        rows: &[T],
+        dedup_token: Option<&str>,
+        async_insert: Option<AsyncInsert>,
+    ) -> Result<()> {
+        let candidates = self.health.lock().unwrap().candidates(Instant::now());
+        let mut last = None;
+        for (n, &index) in candidates.iter().enumerate() {
+            let (url, client) = &self.clients[index];
+            match write_rows(client, table, rows, dedup_token, async_insert).await {
+                Ok(()) => {
+                    self.record(index, true);
+                    return Ok(());
+                }
+                Err(e) => {
+                    self.record(index, false);
+                    if let Some(&next) = candidates.get(n + 1) {
+                        metrics::CLICKHOUSE_FAILOVERS.inc();
+                        warn!("🔀 Insert into {} failed on {}, failing over to {}", table, url, self.clients[next].0);
+                    }
+                    last = Some(e);
+                }
+            }
+        }
+        Err(last.expect("At least one candidate endpoint"))
+    }
+}
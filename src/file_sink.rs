@@ -0,0 +1,460 @@
+use eyre::{Result, WrapErr};
+use serde::Serialize;
+use serde_json::value::RawValue;
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::BufWriter;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use tracing::{info, warn};
+
+use crate::config::TableNames;
+use crate::metrics;
+use crate::migrations::{TableSchema, SCHEMAS};
+
+// Defaults for FILE_SINK_DIR / FILE_ROTATE_MB
+pub const DEFAULT_FILE_SINK_DIR: &str = "export";
+pub const DEFAULT_FILE_ROTATE_BYTES: u64 = 256 * 1024 * 1024;
+
+// FILE_FORMAT
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileFormat {
+    // A header line, then one line per row
+    Csv,
+    // One row group per batch, snappy-compressed. Needs the parquet cargo feature
+    Parquet,
+}
+
+impl FileFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            FileFormat::Csv => "csv",
+            FileFormat::Parquet => "parquet",
+        }
+    }
+}
+
+// FILE_ROTATE: when a table's file is closed and the next one started
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileRotation {
+    // Once it holds FILE_ROTATE_MB
+    Size(u64),
+    // With the first batch of a new UTC hour
+    Hour,
+}
+
+// SINK=file
+#[derive(Debug, Clone)]
+pub struct FileSettings {
+    // FILE_SINK_DIR, one directory per table under it
+    pub dir: PathBuf,
+    pub format: FileFormat,
+    pub rotation: FileRotation,
+}
+
+// A value that doesn't fit its column (a NULL in a plain one, a string in a number), only
+// its row is refused
+#[derive(Debug)]
+pub struct ValueError(pub String);
+
+impl fmt::Display for ValueError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for ValueError {}
+
+// A row's fields by column, the raw JSON of each ("null" for NULL)
+pub type Fields = HashMap<String, Box<RawValue>>;
+
+pub fn fields<T: Serialize>(row: &T) -> Result<Fields> {
+    let json = serde_json::to_string(row).wrap_err("Write error")?;
+    Ok(serde_json::from_str(&json)?)
+}
+
+// The text of a value as it goes into a CSV cell (and a Parquet string column), None for NULL.
+// DateTime64 columns are written the way ClickHouse reads them back, Decimals with their
+// fraction; 128-bit integers are their digits
+pub fn cell(ty: &str, raw: &RawValue) -> Result<Option<String>> {
+    let raw = raw.get();
+    if raw == "null" {
+        return Ok(None);
+    }
+    let ty = ty.strip_prefix("Nullable(").and_then(|t| t.strip_suffix(')')).unwrap_or(ty);
+    if ty.starts_with("DateTime64") {
+        let millis: i64 = raw.parse().map_err(|_| ValueError(format!("Invalid timestamp {}", raw)))?;
+        let time = chrono::DateTime::from_timestamp_millis(millis).ok_or_else(|| ValueError(format!("Invalid timestamp {}", raw)))?;
+        return Ok(Some(time.format("%Y-%m-%d %H:%M:%S%.3f").to_string()));
+    }
+    if let Some(scale) = ty.strip_prefix("Decimal(").and_then(|t| t.strip_suffix(')')).and_then(|t| t.split(',').nth(1)) {
+        let scale = scale.trim().parse().map_err(|_| ValueError(format!("Invalid type {}", ty)))?;
+        return Ok(Some(decimal_string(raw, scale).ok_or_else(|| ValueError(format!("Invalid decimal {}", raw)))?));
+    }
+    if raw.starts_with('"') {
+        return Ok(Some(serde_json::from_str(raw)?));
+    }
+    Ok(Some(raw.to_string()))
+}
+
+// Raw units to a decimal string, "-1500" at scale 3 -> "-1.500"
+pub fn decimal_string(units: &str, scale: usize) -> Option<String> {
+    let (sign, digits) = match units.strip_prefix('-') {
+        Some(digits) => ("-", digits),
+        None => ("", units),
+    };
+    if digits.is_empty() || !digits.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    if scale == 0 {
+        return Some(format!("{}{}", sign, digits));
+    }
+    let digits = format!("{:0>width$}", digits, width = scale + 1);
+    let (integer, fraction) = digits.split_at(digits.len() - scale);
+    Some(format!("{}{}.{}", sign, integer, fraction))
+}
+
+// The Parquet schema of a table, as a message type: integers keep their width and sign,
+// DateTime64 is a UTC millis timestamp, 128-bit integers, Decimals and Arrays are strings
+pub fn parquet_schema(schema: &TableSchema) -> String {
+    let fields: Vec<String> = schema
+        .columns
+        .iter()
+        .map(|(column, ty)| {
+            let (inner, repetition) = match ty.strip_prefix("Nullable(").and_then(|t| t.strip_suffix(')')) {
+                Some(inner) => (inner, "OPTIONAL"),
+                None => (*ty, "REQUIRED"),
+            };
+            let (physical, annotation) = parquet_type(inner);
+            format!("  {} {} {}{};", repetition, physical, column, annotation)
+        })
+        .collect();
+    format!("message {} {{\n{}\n}}", schema.table, fields.join("\n"))
+}
+
+// Physical type and logical annotation
+fn parquet_type(ty: &str) -> (&'static str, &'static str) {
+    let ty = ty.strip_prefix("LowCardinality(").and_then(|t| t.strip_suffix(')')).unwrap_or(ty);
+    match ty {
+        "Bool" => ("BOOLEAN", ""),
+        "UInt8" => ("INT32", " (INTEGER(8,false))"),
+        "Int8" => ("INT32", " (INTEGER(8,true))"),
+        "UInt16" => ("INT32", " (INTEGER(16,false))"),
+        "Int16" => ("INT32", " (INTEGER(16,true))"),
+        "UInt32" => ("INT32", " (INTEGER(32,false))"),
+        "Int32" => ("INT32", " (INTEGER(32,true))"),
+        "UInt64" => ("INT64", " (INTEGER(64,false))"),
+        "Int64" => ("INT64", " (INTEGER(64,true))"),
+        "Float32" => ("FLOAT", ""),
+        "Float64" => ("DOUBLE", ""),
+        t if t.starts_with("DateTime64") => ("INT64", " (TIMESTAMP(MILLIS,true))"),
+        _ => ("BYTE_ARRAY", " (STRING)"),
+    }
+}
+
+// Files written by this process in the same second still get their own names
+static SEQUENCE: AtomicU64 = AtomicU64::new(0);
+
+struct OpenFile {
+    // Written as <name>.tmp, renamed to <name> once closed
+    tmp: PathBuf,
+    path: PathBuf,
+    // UTC hour it was opened in, for FileRotation::Hour
+    hour: i64,
+    writer: Writer,
+}
+
+enum Writer {
+    Csv(csv::Writer<BufWriter<File>>),
+    #[cfg(feature = "parquet")]
+    Parquet(parquet_writer::ParquetFile),
+}
+
+// SINK=file: each table's rows go to <FILE_SINK_DIR>/<table>/<table>-<time>-<seq>.<csv|parquet>.
+// A file only gets that name once it is complete (rotated, or closed at shutdown); until then
+// it is a .tmp downstream jobs don't match. Every batch is flushed to its file before the
+// insert counts as done, so checkpoints never pass rows that aren't on disk
+#[derive(Clone)]
+pub struct FileSink {
+    settings: FileSettings,
+    tables: TableNames,
+    open: Arc<Mutex<HashMap<String, OpenFile>>>,
+}
+
+impl FileSink {
+    pub fn new(settings: &FileSettings, tables: &TableNames) -> Result<Self> {
+        #[cfg(not(feature = "parquet"))]
+        if settings.format == FileFormat::Parquet {
+            eyre::bail!("FILE_FORMAT=parquet needs a build with the parquet feature (cargo build --features parquet)");
+        }
+        fs::create_dir_all(&settings.dir).wrap_err_with(|| format!("Failed to create {}", settings.dir.display()))?;
+        info!("📁 Writing {:?} files to {} (rotation {:?})", settings.format, settings.dir.display(), settings.rotation);
+        Ok(Self { settings: settings.clone(), tables: tables.clone(), open: Arc::new(Mutex::new(HashMap::new())) })
+    }
+
+    // The schema of a table by its TABLE_NAMES name
+    fn schema(&self, name: &str) -> Option<&'static TableSchema> {
+        SCHEMAS.iter().find(|s| self.tables.get(s.table) == name)
+    }
+
+    // .tmp files a crash left behind: CSV ones hold every flushed batch, Parquet ones have
+    // no footer and can't be read
+    pub fn check_leftovers(&self) {
+        for schema in &SCHEMAS {
+            let dir = self.settings.dir.join(self.tables.get(schema.table));
+            let Ok(entries) = fs::read_dir(&dir) else { continue };
+            let leftovers = entries.filter_map(|e| e.ok()).filter(|e| e.path().extension().is_some_and(|ext| ext == "tmp")).count();
+            if leftovers > 0 {
+                warn!("⚠️ {} has {} unfinished .tmp file(s) from an earlier run", dir.display(), leftovers);
+            }
+        }
+    }
+
+    pub fn write_rows<T: Serialize>(&self, table: &str, rows: &[T]) -> Result<()> {
+        if rows.is_empty() {
+            return Ok(());
+        }
+        let schema = self.schema(table).ok_or_else(|| eyre::eyre!("No schema for table {}", table))?;
+        let rows: Vec<Fields> = rows.iter().map(fields).collect::<Result<_>>()?;
+
+        let mut open = self.open.lock().unwrap();
+        let now = chrono::Utc::now();
+        let hour = now.timestamp() / 3600;
+        let rotate = open.get(table).is_some_and(|file| match self.settings.rotation {
+            FileRotation::Hour => file.hour != hour,
+            FileRotation::Size(max) => file.size() >= max,
+        });
+        if rotate && let Some(file) = open.remove(table) {
+            file.close()?;
+        }
+        let file = match open.entry(table.to_string()) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => entry.insert(self.create(schema, table, now, hour)?),
+        };
+        let before = file.size();
+        file.write(schema, &rows).wrap_err_with(|| format!("Write to {} failed", file.tmp.display()))?;
+        let bytes = file.size().saturating_sub(before);
+
+        metrics::ROWS_INSERTED.inc_by(rows.len() as u64);
+        metrics::BYTES_INSERTED.inc_by(bytes);
+        info!("💾 Saved {} rows ({} bytes) to {}", rows.len(), bytes, file.tmp.display());
+        Ok(())
+    }
+
+    fn create(&self, schema: &TableSchema, table: &str, now: chrono::DateTime<chrono::Utc>, hour: i64) -> Result<OpenFile> {
+        let dir = self.settings.dir.join(table);
+        fs::create_dir_all(&dir).wrap_err_with(|| format!("Failed to create {}", dir.display()))?;
+        let name = format!(
+            "{}-{}-{:06}.{}",
+            table,
+            now.format("%Y%m%dT%H%M%S"),
+            SEQUENCE.fetch_add(1, Ordering::Relaxed) % 1_000_000,
+            self.settings.format.extension()
+        );
+        let path = dir.join(&name);
+        let tmp = dir.join(format!("{}.tmp", name));
+        let out = File::create(&tmp).wrap_err_with(|| format!("Failed to create {}", tmp.display()))?;
+        let writer = match self.settings.format {
+            FileFormat::Csv => {
+                let mut writer = csv::Writer::from_writer(BufWriter::new(out));
+                writer.write_record(schema.columns.iter().map(|(column, _)| column))?;
+                Writer::Csv(writer)
+            }
+            #[cfg(feature = "parquet")]
+            FileFormat::Parquet => Writer::Parquet(parquet_writer::ParquetFile::create(out, schema)?),
+            #[cfg(not(feature = "parquet"))]
+            FileFormat::Parquet => unreachable!("Refused by FileSink::new"),
+        };
+        Ok(OpenFile { tmp, path, hour, writer })
+    }
+
+    // Closes and renames every open file, at shutdown once the writer is done
+    pub fn close(&self) -> Result<()> {
+        let files: Vec<OpenFile> = self.open.lock().unwrap().drain().map(|(_, file)| file).collect();
+        let mut first = None;
+        for file in files {
+            if let Err(e) = file.close() {
+                first.get_or_insert(e);
+            }
+        }
+        first.map_or(Ok(()), Err)
+    }
+}
+
+impl OpenFile {
+    fn size(&self) -> u64 {
+        match &self.writer {
+            Writer::Csv(writer) => writer.get_ref().get_ref().metadata().map_or(0, |m| m.len()),
+            #[cfg(feature = "parquet")]
+            Writer::Parquet(file) => file.size(),
+        }
+    }
+
+    // Converted in full before anything is written, a refused value leaves the file as it was
+    fn write(&mut self, schema: &TableSchema, rows: &[Fields]) -> Result<()> {
+        match &mut self.writer {
+            Writer::Csv(writer) => {
+                let mut records = Vec::with_capacity(rows.len());
+                for row in rows {
+                    let record: Vec<String> = schema
+                        .columns
+                        .iter()
+                        .map(|(column, ty)| match row.get(*column) {
+                            Some(raw) => Ok(cell(ty, raw)?.unwrap_or_default()),
+                            None => Err(ValueError(format!("Row has no {}", column)).into()),
+                        })
+                        .collect::<Result<_>>()?;
+                    records.push(record);
+                }
+                for record in records {
+                    writer.write_record(&record)?;
+                }
+                writer.flush()?;
+            }
+            #[cfg(feature = "parquet")]
+            Writer::Parquet(file) => file.write(schema, rows)?,
+        }
+        Ok(())
+    }
+
+    fn close(self) -> Result<()> {
+        match self.writer {
+            Writer::Csv(writer) => {
+                let out = writer.into_inner().map_err(|e| eyre::eyre!("{}", e.error()))?;
+                out.into_inner().map_err(|e| e.into_error())?.sync_all()?;
+            }
+            #[cfg(feature = "parquet")]
+            Writer::Parquet(file) => file.close()?,
+        }
+        fs::rename(&self.tmp, &self.path).wrap_err_with(|| format!("Failed to rename {}", self.tmp.display()))?;
+        info!("📁 Closed {}", self.path.display());
+        Ok(())
+    }
+}
+
+// Nothing about a file write is a row's fault except a value that doesn't fit its column
+pub fn is_row_error(e: &eyre::Report) -> bool {
+    e.chain().any(|cause| cause.downcast_ref::<ValueError>().is_some())
+}
+
+#[cfg(feature = "parquet")]
+mod parquet_writer {
+    use eyre::Result;
+    use parquet::basic::Compression;
+    use parquet::data_type::{BoolType, ByteArray, ByteArrayType, DataType, DoubleType, FloatType, Int32Type, Int64Type};
+    use parquet::file::properties::WriterProperties;
+    use parquet::file::writer::{SerializedColumnWriter, SerializedFileWriter};
+    use parquet::schema::parser::parse_message_type;
+    use std::fs::File;
+    use std::sync::Arc;
+
+    use super::{cell, parquet_schema, Fields, ValueError};
+    use crate::migrations::TableSchema;
+
+    pub struct ParquetFile {
+        writer: SerializedFileWriter<File>,
+    }
+
+    // A column's values, NULLs as None
+    enum Values {
+        Bool(Vec<Option<bool>>),
+        Int32(Vec<Option<i32>>),
+        Int64(Vec<Option<i64>>),
+        Float(Vec<Option<f32>>),
+        Double(Vec<Option<f64>>),
+        Text(Vec<Option<ByteArray>>),
+    }
+
+    impl ParquetFile {
+        pub fn create(out: File, schema: &TableSchema) -> Result<Self> {
+            let message = parse_message_type(&parquet_schema(schema))?;
+            let properties = WriterProperties::builder().set_compression(Compression::SNAPPY).build();
+            Ok(Self { writer: SerializedFileWriter::new(out, Arc::new(message), Arc::new(properties))? })
+        }
+
+        // Bytes so far, the row groups written; the footer comes on close
+        pub fn size(&self) -> u64 {
+            self.writer.bytes_written() as u64
+        }
+
+        pub fn write(&mut self, schema: &TableSchema, rows: &[Fields]) -> Result<()> {
+            let columns: Vec<(Values, bool)> =
+                schema.columns.iter().map(|(column, ty)| Ok((values(column, ty, rows)?, ty.starts_with("Nullable(")))).collect::<Result<_>>()?;
+
+            let mut group = self.writer.next_row_group()?;
+            for (values, nullable) in columns {
+                let column = group.next_column()?.ok_or_else(|| eyre::eyre!("Parquet schema is missing a column"))?;
+                match values {
+                    Values::Bool(v) => write_column::<BoolType>(column, v, nullable)?,
+                    Values::Int32(v) => write_column::<Int32Type>(column, v, nullable)?,
+                    Values::Int64(v) => write_column::<Int64Type>(column, v, nullable)?,
+                    Values::Float(v) => write_column::<FloatType>(column, v, nullable)?,
+                    Values::Double(v) => write_column::<DoubleType>(column, v, nullable)?,
+                    Values::Text(v) => write_column::<ByteArrayType>(column, v, nullable)?,
+                }
+            }
+            group.close()?;
+            self.writer.flush()?;
+            Ok(())
+        }
+
+        pub fn close(self) -> Result<()> {
+            let out = self.writer.into_inner()?;
+            out.sync_all()?;
+            Ok(())
+        }
+    }
+
+    fn write_column<T: DataType>(mut column: SerializedColumnWriter<'_>, values: Vec<Option<T::T>>, nullable: bool) -> Result<()> {
+        let levels: Vec<i16> = values.iter().map(|v| v.is_some() as i16).collect();
+        let present: Vec<T::T> = values.into_iter().flatten().collect();
+        column.typed::<T>().write_batch(&present, nullable.then_some(levels.as_slice()), None)?;
+        column.close()?;
+        Ok(())
+    }
+
+    fn values(column: &str, ty: &str, rows: &[Fields]) -> Result<Values> {
+        let nullable = ty.starts_with("Nullable(");
+        let inner = ty.strip_prefix("Nullable(").and_then(|t| t.strip_suffix(')')).unwrap_or(ty);
+        let inner = inner.strip_prefix("LowCardinality(").and_then(|t| t.strip_suffix(')')).unwrap_or(inner);
+        let raw = |row: &Fields| -> Result<Option<String>> {
+            let value = row.get(column).map(|raw| raw.get()).filter(|raw| *raw != "null").map(str::to_string);
+            if value.is_none() && !nullable {
+                return Err(ValueError(format!("NULL in {} ({})", column, ty)).into());
+            }
+            Ok(value)
+        };
+        fn number<N: std::str::FromStr>(column: &str, raw: Option<String>) -> Result<Option<N>> {
+            raw.map(|raw| raw.parse().map_err(|_| ValueError(format!("Invalid {} {}", column, raw)).into())).transpose()
+        }
+
+        Ok(match inner {
+            "Bool" => Values::Bool(rows.iter().map(|r| number(column, raw(r)?)).collect::<Result<_>>()?),
+            "UInt8" | "Int8" | "UInt16" | "Int16" | "Int32" => Values::Int32(rows.iter().map(|r| number(column, raw(r)?)).collect::<Result<_>>()?),
+            // Unsigned 32 and 64-bit values are stored in the signed type, the schema says unsigned
+            "UInt32" => Values::Int32(rows.iter().map(|r| Ok(number::<u32>(column, raw(r)?)?.map(|v| v as i32))).collect::<Result<_>>()?),
+            "UInt64" => Values::Int64(rows.iter().map(|r| Ok(number::<u64>(column, raw(r)?)?.map(|v| v as i64))).collect::<Result<_>>()?),
+            "Int64" => Values::Int64(rows.iter().map(|r| number(column, raw(r)?)).collect::<Result<_>>()?),
+            t if t.starts_with("DateTime64") => Values::Int64(rows.iter().map(|r| number(column, raw(r)?)).collect::<Result<_>>()?),
+            "Float32" => Values::Float(rows.iter().map(|r| number(column, raw(r)?)).collect::<Result<_>>()?),
+            "Float64" => Values::Double(rows.iter().map(|r| number(column, raw(r)?)).collect::<Result<_>>()?),
+            _ => Values::Text(
+                rows.iter()
+                    .map(|r| {
+                        let value = match r.get(column) {
+                            Some(raw) => cell(ty, raw)?,
+                            None => None,
+                        };
+                        if value.is_none() && !nullable {
+                            return Err(ValueError(format!("NULL in {} ({})", column, ty)).into());
+                        }
+                        Ok(value.map(|v| ByteArray::from(v.into_bytes())))
+                    })
+                    .collect::<Result<_>>()?,
+            ),
+        })
+    }
+}
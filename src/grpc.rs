@@ -0,0 +1,171 @@
+use std::net::SocketAddr;
+
+use crate::records::SwapRecord;
+
+// GRPC_BUFFER without a value
+pub const DEFAULT_GRPC_BUFFER: usize = 1_024;
+
+// GRPC_ADDR and the rest, needs the grpc cargo feature
+#[derive(Debug, Clone)]
+pub struct GrpcSettings {
+    pub addr: SocketAddr,
+    // Swaps a subscriber can fall behind before it loses the oldest
+    pub buffer: usize,
+}
+
+// Generated from proto/indexer.proto
+#[cfg(feature = "grpc")]
+pub mod proto {
+    include!("grpc/uniswap_indexer.v1.rs");
+}
+
+#[cfg(feature = "grpc")]
+pub use server::{matches, start, swap_event, swap_stream};
+
+// Called for each swap next to latest_price::report. Never waits: a subscriber that fell behind
+// loses its oldest swaps, not the pipeline its time
+#[cfg(feature = "grpc")]
+pub fn report(swap: &SwapRecord) {
+    server::report(swap)
+}
+
+#[cfg(not(feature = "grpc"))]
+pub fn report(_swap: &SwapRecord) {}
+
+#[cfg(not(feature = "grpc"))]
+pub async fn start(_settings: &GrpcSettings) -> eyre::Result<SocketAddr> {
+    eyre::bail!("GRPC_ADDR needs a build with the grpc feature (cargo build --features grpc)")
+}
+
+#[cfg(feature = "grpc")]
+mod server {
+    use eyre::Result;
+    use futures_util::stream::{BoxStream, Stream, StreamExt};
+    use std::net::SocketAddr;
+    use std::sync::{Arc, OnceLock};
+    use tokio::sync::broadcast::{self, error::RecvError};
+    use tonic::transport::server::TcpIncoming;
+    use tonic::{Request, Response, Status};
+    use tracing::{error, info};
+
+    use super::proto::swap_stream_server::{SwapStream, SwapStreamServer};
+    use super::proto::{SwapEvent, SwapFilter};
+    use super::GrpcSettings;
+    use crate::metrics;
+    use crate::records::SwapRecord;
+
+    static TX: OnceLock<broadcast::Sender<Arc<SwapEvent>>> = OnceLock::new();
+
+    pub fn swap_event(swap: &SwapRecord) -> SwapEvent {
+        SwapEvent {
+            chain_id: swap.chain_id,
+            block_number: swap.block_number,
+            log_index: swap.log_index,
+            tx_hash: swap.tx_hash.clone(),
+            pool_address: swap.pool_address.clone(),
+            timestamp: swap.timestamp.timestamp_millis(),
+            pair: swap.pair.clone(),
+            token0_symbol: swap.token0_symbol.clone(),
+            token1_symbol: swap.token1_symbol.clone(),
+            amount0_raw: swap.amount0_raw.clone(),
+            amount1_raw: swap.amount1_raw.clone(),
+            amount0: swap.amount0,
+            amount1: swap.amount1,
+            price_usd: swap.price_usd,
+            price_exact: swap.price_exact.clone(),
+            volume_usd: swap.volume_usd,
+            direction: swap.direction.clone(),
+            protocol: swap.protocol.clone(),
+            dex: swap.dex.clone(),
+            dropped: 0,
+        }
+    }
+
+    // Pools compare case-insensitively, a min_usd needs a priced swap
+    pub fn matches(filter: &SwapFilter, event: &SwapEvent) -> bool {
+        (filter.pools.is_empty() || filter.pools.iter().any(|pool| pool.trim().eq_ignore_ascii_case(&event.pool_address)))
+            && (filter.min_usd <= 0.0 || event.volume_usd.is_some_and(|usd| usd >= filter.min_usd))
+    }
+
+    pub fn report(swap: &SwapRecord) {
+        let Some(tx) = TX.get() else { return };
+        if tx.receiver_count() > 0 {
+            // Only fails without subscribers
+            let _ = tx.send(Arc::new(swap_event(swap)));
+        }
+    }
+
+    // Counted in indexer_grpc_subscribers while its stream is alive
+    struct Subscriber;
+
+    impl Subscriber {
+        fn new() -> Self {
+            metrics::GRPC_SUBSCRIBERS.inc();
+            Subscriber
+        }
+    }
+
+    impl Drop for Subscriber {
+        fn drop(&mut self) {
+            metrics::GRPC_SUBSCRIBERS.dec();
+        }
+    }
+
+    // The swaps of one subscriber. Its receiver holds the last `buffer` swaps it hasn't read; when
+    // it falls further behind the oldest are dropped, counted, and reported in the next event
+    pub fn swap_stream(rx: broadcast::Receiver<Arc<SwapEvent>>, filter: SwapFilter) -> impl Stream<Item = SwapEvent> + Send + 'static {
+        futures_util::stream::unfold((rx, filter, 0, Subscriber::new()), |(mut rx, filter, mut dropped, subscriber)| async move {
+            loop {
+                match rx.recv().await {
+                    Ok(event) if matches(&filter, &event) => {
+                        let event = SwapEvent { dropped: std::mem::take(&mut dropped), ..(*event).clone() };
+                        return Some((event, (rx, filter, dropped, subscriber)));
+                    }
+                    Ok(_) => {}
+                    Err(RecvError::Lagged(n)) => {
+                        metrics::GRPC_DROPPED.inc_by(n);
+                        dropped += n;
+                    }
+                    Err(RecvError::Closed) => return None,
+                }
+            }
+        })
+    }
+
+    struct Service {
+        tx: broadcast::Sender<Arc<SwapEvent>>,
+    }
+
+    #[tonic::async_trait]
+    impl SwapStream for Service {
+        type SubscribeSwapsStream = BoxStream<'static, Result<SwapEvent, Status>>;
+
+        async fn subscribe_swaps(&self, request: Request<SwapFilter>) -> Result<Response<Self::SubscribeSwapsStream>, Status> {
+            let filter = request.into_inner();
+            if !filter.min_usd.is_finite() {
+                return Err(Status::invalid_argument("min_usd must be a number"));
+            }
+            info!("📡 gRPC subscriber for {} pool(s) from ${}", if filter.pools.is_empty() { "all".to_string() } else { filter.pools.len().to_string() }, filter.min_usd);
+            Ok(Response::new(swap_stream(self.tx.subscribe(), filter).map(Ok).boxed()))
+        }
+    }
+
+    // Binds GRPC_ADDR (port 0 picks one) and serves SubscribeSwaps in the background, once per
+    // process. Returns the bound address
+    pub async fn start(settings: &GrpcSettings) -> Result<SocketAddr> {
+        let incoming = TcpIncoming::bind(settings.addr).map_err(|e| eyre::eyre!("Failed to bind GRPC_ADDR {}: {}", settings.addr, e))?;
+        let addr = incoming.local_addr()?;
+        let (tx, _) = broadcast::channel(settings.buffer.max(1));
+        if TX.set(tx.clone()).is_err() {
+            eyre::bail!("The gRPC server is already running");
+        }
+        info!("📡 gRPC SubscribeSwaps on {}", addr);
+        tokio::spawn(async move {
+            let server = tonic::transport::Server::builder().add_service(SwapStreamServer::new(Service { tx }));
+            if let Err(e) = server.serve_with_incoming(incoming).await {
+                error!("❌ gRPC server stopped: {}", e);
+            }
+        });
+        Ok(addr)
+    }
+}
@@ -0,0 +1,369 @@
+// This file is @generated by prost-build.
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct SwapFilter {
+    /// Pool addresses (or V4 PoolIds), any case; empty for every pool
+    #[prost(string, repeated, tag = "1")]
+    pub pools: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+    /// Only swaps with a volume_usd at least this, 0 for every swap (unpriced ones too)
+    #[prost(double, tag = "2")]
+    pub min_usd: f64,
+}
+/// The uniswap_swaps columns a consumer usually wants
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct SwapEvent {
+    #[prost(uint64, tag = "1")]
+    pub chain_id: u64,
+    #[prost(uint64, tag = "2")]
+    pub block_number: u64,
+    #[prost(uint64, tag = "3")]
+    pub log_index: u64,
+    #[prost(string, tag = "4")]
+    pub tx_hash: ::prost::alloc::string::String,
+    #[prost(string, tag = "5")]
+    pub pool_address: ::prost::alloc::string::String,
+    /// Block time, Unix millis
+    #[prost(int64, tag = "6")]
+    pub timestamp: i64,
+    #[prost(string, tag = "7")]
+    pub pair: ::prost::alloc::string::String,
+    #[prost(string, tag = "8")]
+    pub token0_symbol: ::prost::alloc::string::String,
+    #[prost(string, tag = "9")]
+    pub token1_symbol: ::prost::alloc::string::String,
+    /// Signed raw amounts from the pool's side, positive = into the pool
+    #[prost(string, tag = "10")]
+    pub amount0_raw: ::prost::alloc::string::String,
+    #[prost(string, tag = "11")]
+    pub amount1_raw: ::prost::alloc::string::String,
+    #[prost(double, optional, tag = "12")]
+    pub amount0: ::core::option::Option<f64>,
+    #[prost(double, optional, tag = "13")]
+    pub amount1: ::core::option::Option<f64>,
+    #[prost(double, optional, tag = "14")]
+    pub price_usd: ::core::option::Option<f64>,
+    /// Unset without a price_usd
+    #[prost(string, optional, tag = "15")]
+    pub price_exact: ::core::option::Option<::prost::alloc::string::String>,
+    #[prost(double, optional, tag = "16")]
+    pub volume_usd: ::core::option::Option<f64>,
+    #[prost(string, tag = "17")]
+    pub direction: ::prost::alloc::string::String,
+    #[prost(string, tag = "18")]
+    pub protocol: ::prost::alloc::string::String,
+    #[prost(string, tag = "19")]
+    pub dex: ::prost::alloc::string::String,
+    /// Swaps this subscriber missed right before this one because it fell behind GRPC_BUFFER,
+    /// counted before its filter
+    #[prost(uint64, tag = "20")]
+    pub dropped: u64,
+}
+/// Generated client implementations.
+pub mod swap_stream_client {
+    #![allow(
+        unused_variables,
+        dead_code,
+        missing_docs,
+        clippy::wildcard_imports,
+        clippy::let_unit_value,
+    )]
+    use tonic::codegen::*;
+    use tonic::codegen::http::Uri;
+    #[derive(Debug, Clone)]
+    pub struct SwapStreamClient<T> {
+        inner: tonic::client::Grpc<T>,
+    }
+    impl SwapStreamClient<tonic::transport::Channel> {
+        /// Attempt to create a new client by connecting to a given endpoint.
+        pub async fn connect<D>(dst: D) -> Result<Self, tonic::transport::Error>
+        where
+            D: TryInto<tonic::transport::Endpoint>,
+            D::Error: Into<StdError>,
+        {
+            let conn = tonic::transport::Endpoint::new(dst)?.connect().await?;
+            Ok(Self::new(conn))
+        }
+    }
+    impl<T> SwapStreamClient<T>
+    where
+        T: tonic::client::GrpcService<tonic::body::Body>,
+        T::Error: Into<StdError>,
+        T::ResponseBody: Body<Data = Bytes> + std::marker::Send + 'static,
+        <T::ResponseBody as Body>::Error: Into<StdError> + std::marker::Send,
+    {
+        pub fn new(inner: T) -> Self {
+            let inner = tonic::client::Grpc::new(inner);
+            Self { inner }
+        }
+        pub fn with_origin(inner: T, origin: Uri) -> Self {
+            let inner = tonic::client::Grpc::with_origin(inner, origin);
+            Self { inner }
+        }
+        pub fn with_interceptor<F>(
+            inner: T,
+            interceptor: F,
+        ) -> SwapStreamClient<InterceptedService<T, F>>
+        where
+            F: tonic::service::Interceptor,
+            T::ResponseBody: Default,
+            T: tonic::codegen::Service<
+                http::Request<tonic::body::Body>,
+                Response = http::Response<
+                    <T as tonic::client::GrpcService<tonic::body::Body>>::ResponseBody,
+                >,
+            >,
+            <T as tonic::codegen::Service<
+                http::Request<tonic::body::Body>,
+            >>::Error: Into<StdError> + std::marker::Send + std::marker::Sync,
+        {
+            SwapStreamClient::new(InterceptedService::new(inner, interceptor))
+        }
+        /// Compress requests with the given encoding.
+        ///
+        /// This requires the server to support it otherwise it might respond with an
+        /// error.
+        #[must_use]
+        pub fn send_compressed(mut self, encoding: CompressionEncoding) -> Self {
+            self.inner = self.inner.send_compressed(encoding);
+            self
+        }
+        /// Enable decompressing responses.
+        #[must_use]
+        pub fn accept_compressed(mut self, encoding: CompressionEncoding) -> Self {
+            self.inner = self.inner.accept_compressed(encoding);
+            self
+        }
+        /// Limits the maximum size of a decoded message.
+        ///
+        /// Default: `4MB`
+        #[must_use]
+        pub fn max_decoding_message_size(mut self, limit: usize) -> Self {
+            self.inner = self.inner.max_decoding_message_size(limit);
+            self
+        }
+        /// Limits the maximum size of an encoded message.
+        ///
+        /// Default: `usize::MAX`
+        #[must_use]
+        pub fn max_encoding_message_size(mut self, limit: usize) -> Self {
+            self.inner = self.inner.max_encoding_message_size(limit);
+            self
+        }
+        /// Swaps as they are decoded, before CONFIRMATIONS, until the client hangs up
+        pub async fn subscribe_swaps(
+            &mut self,
+            request: impl tonic::IntoRequest<super::SwapFilter>,
+        ) -> std::result::Result<
+            tonic::Response<tonic::codec::Streaming<super::SwapEvent>>,
+            tonic::Status,
+        > {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::unknown(
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic_prost::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/uniswap_indexer.v1.SwapStream/SubscribeSwaps",
+            );
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(
+                    GrpcMethod::new("uniswap_indexer.v1.SwapStream", "SubscribeSwaps"),
+                );
+            self.inner.server_streaming(req, path, codec).await
+        }
+    }
+}
+/// Generated server implementations.
+pub mod swap_stream_server {
+    #![allow(
+        unused_variables,
+        dead_code,
+        missing_docs,
+        clippy::wildcard_imports,
+        clippy::let_unit_value,
+    )]
+    use tonic::codegen::*;
+    /// Generated trait containing gRPC methods that should be implemented for use with SwapStreamServer.
+    #[async_trait]
+    pub trait SwapStream: std::marker::Send + std::marker::Sync + 'static {
+        /// Server streaming response type for the SubscribeSwaps method.
+        type SubscribeSwapsStream: tonic::codegen::tokio_stream::Stream<
+                Item = std::result::Result<super::SwapEvent, tonic::Status>,
+            >
+            + std::marker::Send
+            + 'static;
+        /// Swaps as they are decoded, before CONFIRMATIONS, until the client hangs up
+        async fn subscribe_swaps(
+            &self,
+            request: tonic::Request<super::SwapFilter>,
+        ) -> std::result::Result<
+            tonic::Response<Self::SubscribeSwapsStream>,
+            tonic::Status,
+        >;
+    }
+    #[derive(Debug)]
+    pub struct SwapStreamServer<T> {
+        inner: Arc<T>,
+        accept_compression_encodings: EnabledCompressionEncodings,
+        send_compression_encodings: EnabledCompressionEncodings,
+        max_decoding_message_size: Option<usize>,
+        max_encoding_message_size: Option<usize>,
+    }
+    impl<T> SwapStreamServer<T> {
+        pub fn new(inner: T) -> Self {
+            Self::from_arc(Arc::new(inner))
+        }
+        pub fn from_arc(inner: Arc<T>) -> Self {
+            Self {
+                inner,
+                accept_compression_encodings: Default::default(),
+                send_compression_encodings: Default::default(),
+                max_decoding_message_size: None,
+                max_encoding_message_size: None,
+            }
+        }
+        pub fn with_interceptor<F>(
+            inner: T,
+            interceptor: F,
+        ) -> InterceptedService<Self, F>
+        where
+            F: tonic::service::Interceptor,
+        {
+            InterceptedService::new(Self::new(inner), interceptor)
+        }
+        /// Enable decompressing requests with the given encoding.
+        #[must_use]
+        pub fn accept_compressed(mut self, encoding: CompressionEncoding) -> Self {
+            self.accept_compression_encodings.enable(encoding);
+            self
+        }
+        /// Compress responses with the given encoding, if the client supports it.
+        #[must_use]
+        pub fn send_compressed(mut self, encoding: CompressionEncoding) -> Self {
+            self.send_compression_encodings.enable(encoding);
+            self
+        }
+        /// Limits the maximum size of a decoded message.
+        ///
+        /// Default: `4MB`
+        #[must_use]
+        pub fn max_decoding_message_size(mut self, limit: usize) -> Self {
+            self.max_decoding_message_size = Some(limit);
+            self
+        }
+        /// Limits the maximum size of an encoded message.
+        ///
+        /// Default: `usize::MAX`
+        #[must_use]
+        pub fn max_encoding_message_size(mut self, limit: usize) -> Self {
+            self.max_encoding_message_size = Some(limit);
+            self
+        }
+    }
+    impl<T, B> tonic::codegen::Service<http::Request<B>> for SwapStreamServer<T>
+    where
+        T: SwapStream,
+        B: Body + std::marker::Send + 'static,
+        B::Error: Into<StdError> + std::marker::Send + 'static,
+    {
+        type Response = http::Response<tonic::body::Body>;
+        type Error = std::convert::Infallible;
+        type Future = BoxFuture<Self::Response, Self::Error>;
+        fn poll_ready(
+            &mut self,
+            _cx: &mut Context<'_>,
+        ) -> Poll<std::result::Result<(), Self::Error>> {
+            Poll::Ready(Ok(()))
+        }
+        fn call(&mut self, req: http::Request<B>) -> Self::Future {
+            match req.uri().path() {
+                "/uniswap_indexer.v1.SwapStream/SubscribeSwaps" => {
+                    #[allow(non_camel_case_types)]
+                    struct SubscribeSwapsSvc<T: SwapStream>(pub Arc<T>);
+                    impl<
+                        T: SwapStream,
+                    > tonic::server::ServerStreamingService<super::SwapFilter>
+                    for SubscribeSwapsSvc<T> {
+                        type Response = super::SwapEvent;
+                        type ResponseStream = T::SubscribeSwapsStream;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::ResponseStream>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::SwapFilter>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as SwapStream>::subscribe_swaps(&inner, request).await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let method = SubscribeSwapsSvc(inner);
+                        let codec = tonic_prost::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.server_streaming(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                _ => {
+                    Box::pin(async move {
+                        let mut response = http::Response::new(
+                            tonic::body::Body::default(),
+                        );
+                        let headers = response.headers_mut();
+                        headers
+                            .insert(
+                                tonic::Status::GRPC_STATUS,
+                                (tonic::Code::Unimplemented as i32).into(),
+                            );
+                        headers
+                            .insert(
+                                http::header::CONTENT_TYPE,
+                                tonic::metadata::GRPC_CONTENT_TYPE,
+                            );
+                        Ok(response)
+                    })
+                }
+            }
+        }
+    }
+    impl<T> Clone for SwapStreamServer<T> {
+        fn clone(&self) -> Self {
+            let inner = self.inner.clone();
+            Self {
+                inner,
+                accept_compression_encodings: self.accept_compression_encodings,
+                send_compression_encodings: self.send_compression_encodings,
+                max_decoding_message_size: self.max_decoding_message_size,
+                max_encoding_message_size: self.max_encoding_message_size,
+            }
+        }
+    }
+    /// Generated gRPC service name
+    pub const SERVICE_NAME: &str = "uniswap_indexer.v1.SwapStream";
+    impl<T> tonic::server::NamedService for SwapStreamServer<T> {
+        const NAME: &'static str = SERVICE_NAME;
+    }
+}
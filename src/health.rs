@@ -0,0 +1,105 @@
+use serde::Serialize;
+use std::sync::LazyLock;
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::time::Duration;
+
+// Defaults for READY_MAX_DISCONNECTED_SECONDS / READY_MAX_IDLE_SECONDS / READY_MAX_INSERT_FAILING_SECONDS
+pub const DEFAULT_MAX_DISCONNECTED: Duration = Duration::from_secs(60);
+pub const DEFAULT_MAX_IDLE: Duration = Duration::from_secs(600);
+pub const DEFAULT_MAX_INSERT_FAILING: Duration = Duration::from_secs(60);
+
+// How long each condition is tolerated before /readyz answers 503
+#[derive(Debug, Clone)]
+pub struct ReadinessSettings {
+    pub max_disconnected: Duration,
+    // No log or new head for that long. None: quiet pools are never stale
+    pub max_idle: Option<Duration>,
+    pub max_insert_failing: Duration,
+}
+
+impl Default for ReadinessSettings {
+    fn default() -> Self {
+        Self { max_disconnected: DEFAULT_MAX_DISCONNECTED, max_idle: Some(DEFAULT_MAX_IDLE), max_insert_failing: DEFAULT_MAX_INSERT_FAILING }
+    }
+}
+
+// Unix seconds, 0 when the condition doesn't hold
+#[derive(Debug)]
+pub struct Health {
+    disconnected_since: AtomicI64,
+    last_event: AtomicI64,
+    inserts_failing_since: AtomicI64,
+}
+
+// Shared by run_indexer, the writer and the HTTP server. Created on first use, which is the
+// server start: the stream is down and the clock for the first event runs from there
+pub static HEALTH: LazyLock<Health> = LazyLock::new(|| Health::new(now()));
+
+pub fn now() -> i64 {
+    chrono::Utc::now().timestamp()
+}
+
+// The /readyz body
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Readiness {
+    pub ready: bool,
+    pub reasons: Vec<String>,
+}
+
+impl Health {
+    pub fn new(now: i64) -> Self {
+        Self { disconnected_since: AtomicI64::new(now), last_event: AtomicI64::new(now), inserts_failing_since: AtomicI64::new(0) }
+    }
+
+    // The subscriptions are in; this also counts as an event so the idle clock restarts
+    pub fn connected(&self, now: i64) {
+        self.disconnected_since.store(0, Ordering::Relaxed);
+        self.event(now);
+    }
+
+    // Keeps the first time it went down until it is back
+    pub fn disconnected(&self, now: i64) {
+        let _ = self.disconnected_since.compare_exchange(0, now, Ordering::Relaxed, Ordering::Relaxed);
+    }
+
+    // A log or a new head from the stream
+    pub fn event(&self, now: i64) {
+        self.last_event.fetch_max(now, Ordering::Relaxed);
+    }
+
+    pub fn insert_succeeded(&self) {
+        self.inserts_failing_since.store(0, Ordering::Relaxed);
+    }
+
+    pub fn insert_failed(&self, now: i64) {
+        let _ = self.inserts_failing_since.compare_exchange(0, now, Ordering::Relaxed, Ordering::Relaxed);
+    }
+
+    // Without the stream (backfill and the other one-shot commands) only inserts count
+    pub fn readiness(&self, settings: &ReadinessSettings, stream: bool, now: i64) -> Readiness {
+        let mut reasons = Vec::new();
+        let since = |at: &AtomicI64| match at.load(Ordering::Relaxed) {
+            0 => None,
+            at => Some((now - at).max(0) as u64),
+        };
+        if stream {
+            if let Some(down) = since(&self.disconnected_since)
+                && down > settings.max_disconnected.as_secs()
+            {
+                reasons.push(format!("WebSocket subscription down for {}s", down));
+            }
+            if let Some(max_idle) = settings.max_idle
+                && let Some(idle) = since(&self.last_event)
+                && idle > max_idle.as_secs()
+            {
+                reasons.push(format!("No logs or blocks for {}s", idle));
+            }
+        }
+        if let Some(failing) = since(&self.inserts_failing_since)
+            && failing > settings.max_insert_failing.as_secs()
+        {
+            reasons.push(format!("Inserts failing for {}s", failing));
+        }
+        Readiness { ready: reasons.is_empty(), reasons }
+    }
+}
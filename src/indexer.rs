@@ -0,0 +1,648 @@
+use alloy::{
+    primitives::{Address, B256, U256},
+    providers::{Provider, ProviderBuilder, WsConnect},
+    rpc::types::{Filter, Log},
+    sol_types::SolEvent,
+};
+use eyre::Result;
+use futures_util::future::BoxFuture;
+use futures_util::stream::{self, BoxStream, StreamExt};
+use lru::LruCache;
+use std::collections::{hash_map::Entry, HashMap};
+use std::num::NonZeroUsize;
+use std::sync::Arc;
+use tokio::sync::{mpsc, watch};
+use tracing::{debug, error, info, warn};
+
+use crate::abi::{
+    pancake_v3, position_manager, uniswap_v2, uniswap_v4, Burn, Collect, CollectProtocol, Flash, Initialize,
+    Mint, PoolCreated, SetFeeProtocol, Swap,
+};
+use crate::backfill::{backfill, BackfillRange};
+use crate::blocks::BlockTimes;
+use crate::config::IndexerConfig;
+use crate::decode::{decode_log, decode_or_warn, decode_position_log, log_position};
+use crate::grpc;
+use crate::health;
+use crate::latest_price;
+use crate::liquidity::LiquidityGate;
+use crate::metrics;
+use crate::notify;
+use crate::pool::{Dex, PoolInfo, PoolMeta, PoolRef, PoolSpec, Protocol};
+use crate::pool_source::PoolSet;
+use crate::records::{IndexedEvent, PoolRecord, ReorgedSwapRecord, SwapRecord, SCHEMA_VERSION};
+use crate::registry::PoolRegistry;
+use crate::replay::Capture;
+use crate::sanity::screen;
+use crate::tx_lookup::TxLookup;
+use crate::watermark::Watermarks;
+use crate::watchlist;
+use crate::webhooks;
+use crate::ws_server;
+
+// Default for DEDUP_WINDOW, raise it for firehose volume
+pub const DEDUP_WINDOW: usize = 50_000;
+// Records between the handler and the writer while swaps wait on their receipts
+pub const ENRICH_WINDOW: usize = 256;
+
+// One filter for V2/V3 pool contracts, one for V4 PoolIds on the PoolManager,
+// and one for the position manager when enabled
+pub fn pool_filters(pools: &HashMap<PoolRef, PoolInfo>, config: &IndexerConfig) -> Vec<Filter> {
+    let mut addresses = Vec::new();
+    let mut ids = Vec::new();
+    for pool in pools.keys() {
+        match pool {
+            PoolRef::Address(addr) => addresses.push(*addr),
+            PoolRef::Id(id) => ids.push(*id),
+        }
+    }
+
+    let mut filters = Vec::new();
+
+    // Firehose: every V3 Swap on the chain, next to the configured pools' own filter so they
+    // keep their other events. Their V3 swaps come through both, the handler drops the copy
+    if config.firehose {
+        filters.push(Filter::new().event_signature(Swap::SIGNATURE_HASH));
+    }
+    if !addresses.is_empty() {
+        let filter = Filter::new()
+            .address(addresses)
+            .event_signature(vec![
+                Swap::SIGNATURE_HASH,
+                pancake_v3::Swap::SIGNATURE_HASH,
+                Mint::SIGNATURE_HASH,
+                Burn::SIGNATURE_HASH,
+                Collect::SIGNATURE_HASH,
+                Flash::SIGNATURE_HASH,
+                Initialize::SIGNATURE_HASH,
+                SetFeeProtocol::SIGNATURE_HASH,
+                CollectProtocol::SIGNATURE_HASH,
+                uniswap_v2::Swap::SIGNATURE_HASH,
+                uniswap_v2::Sync::SIGNATURE_HASH,
+            ]);
+        filters.push(filter);
+    }
+
+    if !ids.is_empty() {
+        let filter = Filter::new()
+            .address(config.v4.pool_manager)
+            .event_signature(uniswap_v4::Swap::SIGNATURE_HASH)
+            .topic1(ids);
+        filters.push(filter);
+    }
+
+    if let Some(positions) = &config.positions {
+        let filter = Filter::new()
+            .address(positions.manager)
+            .event_signature(vec![
+                position_manager::IncreaseLiquidity::SIGNATURE_HASH,
+                position_manager::DecreaseLiquidity::SIGNATURE_HASH,
+                position_manager::Collect::SIGNATURE_HASH,
+            ]);
+        filters.push(filter);
+    }
+
+    filters
+}
+
+pub async fn subscribe_pools<P: Provider>(
+    provider: &P,
+    pools: &HashMap<PoolRef, PoolInfo>,
+    config: &IndexerConfig,
+) -> Result<BoxStream<'static, Log>> {
+    let mut streams = Vec::new();
+    for filter in pool_filters(pools, config) {
+        streams.push(provider.subscribe_logs(&filter).await?.into_stream().boxed());
+    }
+
+    // Nothing to subscribe to: stay idle instead of ending the stream
+    if streams.is_empty() {
+        return Ok(stream::pending().boxed());
+    }
+
+    Ok(stream::select_all(streams).boxed())
+}
+
+// Pool a V2/V3/V4 log belongs to, V4 logs carry the PoolId in topic1
+pub fn log_pool(log: &Log, config: &IndexerConfig) -> Option<PoolRef> {
+    if log.address() == config.v4.pool_manager {
+        return log.topics().get(1).map(|id| PoolRef::Id(*id));
+    }
+    Some(PoolRef::Address(log.address()))
+}
+
+// Every Swap layout uniswap_swaps holds rows of
+pub fn is_swap(log: &Log) -> bool {
+    matches!(
+        log.topic0(),
+        Some(
+            &Swap::SIGNATURE_HASH
+                | &pancake_v3::Swap::SIGNATURE_HASH
+                | &uniswap_v2::Swap::SIGNATURE_HASH
+                | &uniswap_v4::Swap::SIGNATURE_HASH
+        )
+    )
+}
+
+// Protocol of a pool we only know from one of its logs
+fn spec_for_log(log: &Log, pool: PoolRef) -> PoolSpec {
+    let (protocol, dex) = match (pool, log.topic0()) {
+        (PoolRef::Id(_), _) => (Protocol::V4, Dex::Uniswap),
+        (_, Some(&uniswap_v2::Swap::SIGNATURE_HASH | &uniswap_v2::Sync::SIGNATURE_HASH)) => (Protocol::V2, Dex::Uniswap),
+        (_, Some(&pancake_v3::Swap::SIGNATURE_HASH)) => (Protocol::V3, Dex::Pancake),
+        _ => (Protocol::V3, Dex::Uniswap),
+    };
+    PoolSpec { protocol, dex, pool }
+}
+
+// Token allow/deny lists, checked once token0()/token1() are known
+fn passes_token_filter(config: &IndexerConfig, pool: PoolRef, meta: &PoolMeta) -> bool {
+    if config.token_filter.allows(meta) {
+        return true;
+    }
+    metrics::TOKEN_FILTER_REJECTED.inc();
+    debug!("🚫 Skipping {} ({:?}/{:?}): token filter", pool, meta.token0, meta.token1);
+    false
+}
+
+// A record on its way to the writer: ready, or a swap still waiting on its lookups.
+// None for a swap the watchlist left out
+pub type Pending = BoxFuture<'static, Option<IndexedEvent>>;
+
+// Receipt/tx lookups happen here, off the log loop. The watchlist is applied
+// after enrichment so tx_from can match
+async fn enrich_swap(
+    tx_lookup: Option<TxLookup>,
+    config: Arc<IndexerConfig>,
+    mut record: Box<SwapRecord>,
+    tx_hash: B256,
+) -> Option<IndexedEvent> {
+    if let Some(tx_lookup) = &tx_lookup {
+        tx_lookup.enrich(&mut record, tx_hash).await;
+    }
+    if !config.watchlist.is_empty() && !watchlist::matches(&config.watchlist, &record) {
+        return None;
+    }
+    Some(IndexedEvent::Swap(record))
+}
+
+// Between the handler and the writer: up to ENRICH_WINDOW records are in here, swaps looking up
+// their receipts concurrently, and they leave in the order they were handled. The writer's
+// chain-order state (price moves, EMAs, candles, confirmations, checkpoints) relies on it. A
+// full window stops the handler, so the writer's backpressure still reaches the stream
+pub async fn run_ordered(mut rx: mpsc::Receiver<Pending>, tx: mpsc::Sender<IndexedEvent>) {
+    let mut ready = stream::poll_fn(move |cx| rx.poll_recv(cx)).buffered(ENRICH_WINDOW);
+    while let Some(event) = ready.next().await {
+        let Some(event) = event else { continue };
+        if let IndexedEvent::Swap(record) = &event {
+            latest_price::report(record);
+            grpc::report(record);
+            ws_server::report(record);
+            webhooks::report_swap(record);
+            notify::report(record);
+        }
+        if let Err(e) = tx.send(event).await {
+            error!("❌ Channel closed, receiver died: {:?}", e);
+            return;
+        }
+    }
+}
+
+// Everything a pool log goes through, shared by the live stream and backfill
+pub struct LogHandler {
+    pub config: Arc<IndexerConfig>,
+    pub registry: Arc<PoolRegistry>,
+    pub gate: Arc<LiquidityGate>,
+    pub block_times: Arc<BlockTimes>,
+    // Into run_ordered, which forwards to the writer
+    out: mpsc::Sender<Pending>,
+    // --capture: raw logs and pool metadata teed to a file
+    pub capture: Option<Arc<Capture>>,
+    // newHeads for the confirmation buffer, None when CONFIRMATIONS=0
+    pub heads: Option<watch::Sender<u64>>,
+    // None when replaying, swap rows keep NULL gas and tx_from
+    tx_lookup: Option<TxLookup>,
+    position_pools: HashMap<U256, Address>,
+    // (tx_hash, log_index) of recently handled logs: reconnect overlaps, gap fills and
+    // double deliveries are dropped here, best effort. It keeps the derived columns and the
+    // MergeTree tables clean; uniswap_swaps dedups on its own. None when DEDUP_WINDOW=0
+    seen: Option<LruCache<(B256, u64), ()>>,
+    watermarks: Watermarks,
+    // Pools the firehose resolved, apart from the configured ones. Bounded by POOL_CACHE_SIZE:
+    // an evicted pool is resolved again through the registry on its next log
+    firehose_pools: LruCache<PoolRef, PoolInfo>,
+    // Firehose pools the token filter rejected, not resolved again while they are in here.
+    // Bounded by POOL_CACHE_SIZE, a pool that drops out is only checked once more
+    filtered: LruCache<PoolRef, ()>,
+}
+
+impl LogHandler {
+    pub fn new(
+        config: &Arc<IndexerConfig>,
+        registry: &Arc<PoolRegistry>,
+        gate: &Arc<LiquidityGate>,
+        block_times: &Arc<BlockTimes>,
+        tx: mpsc::Sender<IndexedEvent>,
+    ) -> Result<Self> {
+        let tx_lookup = TxLookup::new(&config.rpc_http_url, config.fetch_tx_from)?;
+        Ok(Self { tx_lookup: Some(tx_lookup), ..Self::offline(config, registry, gate, block_times, tx) })
+    }
+
+    // No receipt or transaction lookups, for replays. Spawns the ordering stage in front of `tx`
+    pub fn offline(
+        config: &Arc<IndexerConfig>,
+        registry: &Arc<PoolRegistry>,
+        gate: &Arc<LiquidityGate>,
+        block_times: &Arc<BlockTimes>,
+        tx: mpsc::Sender<IndexedEvent>,
+    ) -> Self {
+        let (out, rx) = mpsc::channel(ENRICH_WINDOW);
+        tokio::spawn(run_ordered(rx, tx));
+        let pool_cache = NonZeroUsize::new(config.pool_cache_size).unwrap_or(NonZeroUsize::MIN);
+        Self {
+            config: config.clone(),
+            registry: registry.clone(),
+            gate: gate.clone(),
+            block_times: block_times.clone(),
+            out,
+            capture: None,
+            heads: None,
+            tx_lookup: None,
+            position_pools: HashMap::new(),
+            seen: NonZeroUsize::new(config.dedup_window).map(LruCache::new),
+            watermarks: Watermarks::new(),
+            firehose_pools: LruCache::new(pool_cache),
+            filtered: LruCache::new(pool_cache),
+        }
+    }
+
+    // Behind every record handled before it, false once the writer is gone
+    pub async fn send(&self, event: IndexedEvent) -> bool {
+        self.forward(Box::pin(std::future::ready(Some(event)))).await
+    }
+
+    async fn forward(&self, pending: Pending) -> bool {
+        if self.out.send(pending).await.is_err() {
+            error!("❌ Channel closed, receiver died");
+            return false;
+        }
+        true
+    }
+
+    // A new subscription may replay what was already handled, hold each pool to its watermark
+    pub fn resubscribed(&mut self) {
+        self.watermarks.resubscribed();
+    }
+
+    // false for a log handled recently, or replayed by a new subscription
+    fn first_sight(&mut self, log: &Log) -> bool {
+        let (Some(tx_hash), Some(log_index)) = (log.transaction_hash, log.log_index) else { return true };
+        if let Some(seen) = &mut self.seen && seen.put((tx_hash, log_index), ()).is_some() {
+            metrics::DUPLICATES_SUPPRESSED.inc();
+            debug!("♻️ Dropping duplicate log {} of tx {:?}", log_index, tx_hash);
+            return false;
+        }
+
+        let (Some(pool), Some(block)) = (log_pool(log, &self.config), log.block_number) else { return true };
+        if !self.watermarks.admit(pool, block, log_index) {
+            metrics::DUPLICATES_SUPPRESSED.inc();
+            debug!("♻️ Dropping replayed log {} at block {} of {}", log_index, block, pool);
+            return false;
+        }
+        true
+    }
+
+    // A log a reorg reverted: nothing is decoded, reverted swaps get an audit row.
+    // Checked before the dedup, which would take it for a copy of the original
+    async fn reorged(&mut self, log: &Log) -> bool {
+        metrics::REORGED_LOGS.inc();
+        // The tx may be re-included with the same log index, that copy isn't a duplicate
+        if let (Some(seen), Some(tx_hash), Some(log_index)) = (&mut self.seen, log.transaction_hash, log.log_index) {
+            seen.pop(&(tx_hash, log_index));
+        }
+        warn!("↩️ Reorg removed log {:?} of tx {:?} in block {:?}", log.log_index, log.transaction_hash, log.block_number);
+        let reverted = log.block_number.zip(log.block_hash).map(|(block_number, hash)| IndexedEvent::Reverted { block_number, block_hash: hash.to_string() });
+        if let Some(reverted) = reverted && !self.send(reverted).await {
+            return false;
+        }
+        if !is_swap(log) {
+            return true;
+        }
+        let Some(pool) = log_pool(log, &self.config) else { return true };
+        let Some(at) = log_position(log, "Reorged swap") else { return true };
+
+        let record = ReorgedSwapRecord {
+            chain_id: self.config.chain_id,
+            schema_version: SCHEMA_VERSION,
+            detected_at: chrono::Utc::now().timestamp_millis(),
+            block_number: at.block_number,
+            block_hash: log.block_hash.unwrap_or_default().to_string(),
+            tx_hash: log.transaction_hash.unwrap_or_default().to_string(),
+            log_index: at.log_index,
+            pool_address: pool.to_string(),
+        };
+        self.send(IndexedEvent::Reorged(record)).await
+    }
+
+    // A firehose pool seen for the first time, or again after it dropped out of firehose_pools.
+    // Unresolved pools still get raw rows, None for the ones the token filter or the
+    // liquidity gate keep out
+    async fn resolve_firehose(&mut self, log: &Log, pool: PoolRef) -> Option<PoolInfo> {
+        if self.filtered.get(&pool).is_some() {
+            return None;
+        }
+        let spec = spec_for_log(log, pool);
+        let meta = self.registry.get_or_fetch(spec).await.unwrap_or_else(|| Arc::new(PoolMeta::unresolved()));
+        if !passes_token_filter(&self.config, pool, &meta) {
+            self.filtered.put(pool, ());
+            return None;
+        }
+        // The swap carries the pool's liquidity, no extra call needed
+        let observed = log.log_decode::<Swap>().ok().map(|l| l.inner.data.liquidity);
+        if !self.gate.check(spec, observed).await {
+            // Added by the periodic re-check once it grew
+            self.gate.quarantine(spec);
+            return None;
+        }
+        Some(PoolInfo::new(spec, meta, &self.config))
+    }
+
+    // Firehose pools currently held, at most POOL_CACHE_SIZE
+    pub fn firehose_pools(&self) -> usize {
+        self.firehose_pools.len()
+    }
+
+    // Decode one log and forward it, false once the writer is gone
+    pub async fn handle(&mut self, pools: &mut HashMap<PoolRef, PoolInfo>, mut log: Log) -> bool {
+        if log.removed {
+            return self.reorged(&log).await;
+        }
+        if !self.first_sight(&log) {
+            return true;
+        }
+        if let Some(block) = log.block_number {
+            metrics::observe_block(block);
+        }
+        if let Some(capture) = &self.capture {
+            capture.log(&log);
+        }
+
+        // Decoders read the block time from the log, fill it in when the provider didn't
+        log.block_timestamp = self.block_times.resolve(&log).await;
+
+        if let Some(tracking) = &self.config.positions && log.address() == tracking.manager {
+            let firehose_pools = &self.firehose_pools;
+            let pool_info = |pool| pools.get(&pool).or_else(|| firehose_pools.peek(&pool));
+            let event = decode_position_log(&log, &self.config.rpc_http_url, tracking, pool_info, &mut self.position_pools).await;
+            return match event {
+                Some(event) => self.send(event).await,
+                None => true,
+            };
+        }
+
+        let Some(pool) = log_pool(&log, &self.config) else { return true };
+        let info = match pools.entry(pool) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            // Firehose: an unknown pool is resolved on its first log and indexed like a configured
+            // one from then on, with its own sanity history and V2 reserves
+            Entry::Vacant(_) if self.config.firehose => {
+                if !self.firehose_pools.contains(&pool) {
+                    let Some(info) = self.resolve_firehose(&log, pool).await else { return true };
+                    self.firehose_pools.put(pool, info);
+                }
+                let Some(info) = self.firehose_pools.get_mut(&pool) else { return true };
+                info
+            }
+            Entry::Vacant(entry) => {
+                // First sight of this pool: fetch its metadata once, quarantined pools are skipped
+                let spec = spec_for_log(&log, pool);
+                let Some(meta) = self.registry.get_or_fetch(spec).await else { return true };
+                entry.insert(PoolInfo::new(spec, meta, &self.config))
+            }
+        };
+        if let Some(capture) = &self.capture {
+            capture.pool(PoolSpec { protocol: info.protocol, dex: info.dex, pool }, &info.meta);
+        }
+
+        let Some(event) = decode_log(&log, pool, info) else { return true };
+        let event = match event {
+            IndexedEvent::Swap(record) => screen(&self.config.price_sanity, &mut info.recent_prices, record),
+            event => event,
+        };
+
+        // Swaps wait for their receipt in run_ordered, so a slow RPC only stalls the stream once
+        // the window is full; the records behind them wait their turn
+        if let IndexedEvent::Swap(record) = event {
+            metrics::SWAPS_DECODED.with_label_values(&[&record.pool_address]).inc();
+            let tx_hash = log.transaction_hash.unwrap_or_default();
+            return self.forward(Box::pin(enrich_swap(self.tx_lookup.clone(), self.config.clone(), record, tx_hash))).await;
+        }
+        self.send(event).await
+    }
+}
+
+// Run after every subscription, the first and each resubscribe: the old stream is dropped with
+// whatever it still held, so the blocks from the last one seen up to the head are fetched,
+// that block included since the stream may have died halfway through it. Without a last block
+// (the first connect of a fresh run) the head is where later gaps start. Logs delivered twice
+// are dropped by the handler while they are within DEDUP_WINDOW; past it, swaps still collapse
+// in uniswap_swaps (ReplacingMergeTree on tx_hash, log_index)
+async fn fill_gap<P: Provider>(
+    provider: &P,
+    handler: &mut LogHandler,
+    pools: &mut HashMap<PoolRef, PoolInfo>,
+    last_block: &mut Option<u64>,
+) -> Result<()> {
+    let head = provider.get_block_number().await?;
+    match *last_block {
+        Some(last) if head >= last => {
+            let config = handler.config.clone();
+            let filled = backfill(provider, handler, pools, &config, BackfillRange::new(last, Some(head)), None).await?;
+            metrics::GAP_FILLED_LOGS.inc_by(filled.logs);
+            info!("🩹 Gap-filled {} logs in blocks {}..={}", filled.logs, last, head);
+            *last_block = Some(head);
+        }
+        Some(_) => {}
+        None => *last_block = Some(head),
+    }
+    // After the gap fill, its logs are part of the watermarks the stream is held to
+    handler.resubscribed();
+    Ok(())
+}
+
+// The handler outlives reconnects, its caches stay warm
+pub async fn run_indexer(
+    handler: &mut LogHandler,
+    pools: &mut HashMap<PoolRef, PoolInfo>,
+    mut pool_set: Option<&mut PoolSet>,
+    last_block: &mut Option<u64>,
+) -> Result<()> {
+    let config = &handler.config.clone();
+    let (registry, gate) = (handler.registry.clone(), handler.gate.clone());
+
+    let ws = WsConnect::new(&config.rpc_url);
+    let provider = ProviderBuilder::new().connect_ws(ws).await?;
+
+    info!("✅ Connected! Waiting for Swaps...\n");
+
+    // Subscribed first, then the blocks missed while disconnected are fetched
+    let mut stream = subscribe_pools(&provider, pools, config).await?;
+    health::HEALTH.connected(health::now());
+    fill_gap(&provider, handler, pools, last_block).await?;
+
+    let mut factory_stream = match &config.discovery {
+        Some(d) => {
+            let filter = Filter::new()
+                .address(d.factory)
+                .event_signature(PoolCreated::SIGNATURE_HASH);
+            Some(provider.subscribe_logs(&filter).await?.into_stream())
+        }
+        None => None,
+    };
+
+    // Quiet pools alone would leave the confirmation buffer waiting for the head
+    let mut head_stream = match &handler.heads {
+        Some(_) => Some(provider.subscribe_blocks().await?.into_stream()),
+        None => None,
+    };
+
+    let mut liquidity_recheck = gate.recheck_interval().map(tokio::time::interval);
+
+    loop {
+        let recheck_next = async {
+            match liquidity_recheck.as_mut() {
+                Some(interval) => interval.tick().await,
+                None => std::future::pending().await,
+            }
+        };
+        let factory_next = async {
+            match factory_stream.as_mut() {
+                Some(s) => s.next().await,
+                None => std::future::pending().await,
+            }
+        };
+        let pool_set_next = async {
+            match pool_set.as_deref_mut() {
+                Some(set) => set.changed().await,
+                None => std::future::pending().await,
+            }
+        };
+        let head_next = async {
+            match head_stream.as_mut() {
+                Some(s) => s.next().await,
+                None => std::future::pending().await,
+            }
+        };
+
+        tokio::select! {
+            log = stream.next() => {
+                let Some(log) = log else { break };
+                health::HEALTH.event(health::now());
+                if let Some(block) = log.block_number {
+                    *last_block = Some(last_block.map_or(block, |last| last.max(block)));
+                }
+                if !handler.handle(pools, log).await {
+                    break;
+                }
+            }
+            header = head_next => {
+                let Some(header) = header else { break };
+                health::HEALTH.event(health::now());
+                if let Some(heads) = &handler.heads {
+                    heads.send_replace(header.number);
+                }
+            }
+            log = factory_next => {
+                let Some(log) = log else { break };
+                let Some(data) = decode_or_warn::<PoolCreated>(&log) else { continue };
+                let Some(discovery) = &config.discovery else { continue };
+
+                if !discovery.tokens.contains(&data.token0) && !discovery.tokens.contains(&data.token1) {
+                    continue;
+                }
+                if pools.contains_key(&PoolRef::Address(data.pool)) {
+                    continue;
+                }
+
+                info!("🏭 New pool {:?} ({:?}/{:?}, fee {})", data.pool, data.token0, data.token1, data.fee);
+
+                let spec = PoolSpec { protocol: Protocol::V3, dex: Dex::Uniswap, pool: PoolRef::Address(data.pool) };
+                let Some(meta) = registry.get_or_fetch(spec).await else { continue };
+                if !passes_token_filter(config, spec.pool, &meta) {
+                    continue;
+                }
+                let decimals = meta.decimals;
+
+                let Some(at) = log_position(&log, "PoolCreated") else { continue };
+                let record = PoolRecord {
+                    chain_id: config.chain_id,
+                    schema_version: SCHEMA_VERSION,
+                    timestamp: chrono::Utc::now().timestamp_millis(),
+                    block_number: at.block_number,
+                    log_index: at.log_index,
+                    block_hash: log.block_hash.unwrap_or_default().to_string(),
+                    tx_hash: log.transaction_hash.unwrap_or_default().to_string(),
+                    factory_address: discovery.factory.to_string(),
+                    pool_address: data.pool.to_string(),
+                    token0: data.token0.to_string(),
+                    token1: data.token1.to_string(),
+                    fee: data.fee.to::<u32>(),
+                    tick_spacing: data.tickSpacing.as_i32(),
+                    decimals0: decimals.token0,
+                    decimals1: decimals.token1,
+                };
+                if !handler.send(IndexedEvent::Pool(record)).await {
+                    break;
+                }
+
+                // Fresh pools usually start empty, dust ones wait for the periodic re-check
+                if !gate.check(spec, None).await {
+                    gate.quarantine(spec);
+                    continue;
+                }
+                pools.insert(spec.pool, PoolInfo::new(spec, meta, config));
+
+                // Resubscribe with the new pool in the address set
+                stream = subscribe_pools(&provider, pools, config).await?;
+                fill_gap(&provider, handler, pools, last_block).await?;
+                info!("🎯 Now indexing {} pools", pools.len());
+            }
+            _ = recheck_next => {
+                let grown = gate.recheck_quarantined().await;
+                if grown.is_empty() {
+                    continue;
+                }
+                for spec in grown {
+                    let Some(meta) = registry.get_or_fetch(spec).await else { continue };
+                    pools.insert(spec.pool, PoolInfo::new(spec, meta, config));
+                }
+                stream = subscribe_pools(&provider, pools, config).await?;
+                fill_gap(&provider, handler, pools, last_block).await?;
+                info!("🎯 Now indexing {} pools", pools.len());
+            }
+            Some((added, removed)) = pool_set_next => {
+                // Rows already in the channel are unaffected, only the subscription changes
+                info!("🔁 Pool set changed: +{} -{}", added.len(), removed.len());
+                for pool in &removed {
+                    pools.remove(pool);
+                    metrics::POOLS_REMOVED.inc();
+                    info!("➖ Stopped indexing {}", pool);
+                }
+
+                // Metadata first, so the pool's first swap is already priced
+                for spec in added {
+                    let Some(meta) = registry.get_or_fetch(spec).await else { continue };
+                    pools.insert(spec.pool, PoolInfo::new(spec, meta, config));
+                    metrics::POOLS_ADDED.inc();
+                    info!("➕ Now indexing {}", spec.pool);
+                }
+
+                stream = subscribe_pools(&provider, pools, config).await?;
+                fill_gap(&provider, handler, pools, last_block).await?;
+                info!("🎯 Now indexing {} pools", pools.len());
+            }
+        }
+    }
+
+    Ok(())
+}
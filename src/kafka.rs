@@ -0,0 +1,246 @@
+use eyre::Result;
+use serde_json::{json, Value};
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tracing::warn;
+
+use crate::metrics;
+use crate::migrations;
+use crate::records::SwapRecord;
+
+// Defaults for KAFKA_TOPIC / KAFKA_BUFFER
+pub const DEFAULT_KAFKA_TOPIC: &str = "uniswap_swaps";
+pub const DEFAULT_KAFKA_BUFFER: usize = 10_000;
+
+// KAFKA_FORMAT: how a swap is written into a message
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KafkaFormat {
+    // The row as in the --stdout and spill JSON
+    Json,
+    // Kafka Connect's {"schema", "payload"} envelope (JsonConverter with schemas.enable=true),
+    // the schema built from the uniswap_swaps columns
+    Schema,
+}
+
+// KAFKA_SINK: where swaps go once Kafka is on
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KafkaMode {
+    // Kafka and uniswap_swaps
+    Also,
+    // Kafka only, the other tables still go to ClickHouse
+    Instead,
+}
+
+// KAFKA_BROKERS and the rest, needs the kafka cargo feature
+#[derive(Debug, Clone)]
+pub struct KafkaSettings {
+    pub brokers: String,
+    pub topic: String,
+    pub format: KafkaFormat,
+    pub mode: KafkaMode,
+    // KAFKA_BUFFER: messages waiting for the producer task, past that they are dropped
+    pub buffer: usize,
+    // KAFKA_CONFIG, librdkafka properties on top of ours (acks, compression.type, sasl.*, ...)
+    pub config: Vec<(String, String)>,
+}
+
+// The writer's end: batches hand their swaps over without waiting on the producer, so a broker
+// outage fills the buffer and drops messages (indexer_kafka_dropped_total) instead of backing up
+// the ClickHouse inserts and the log handler
+#[derive(Debug, Clone)]
+pub struct KafkaSink {
+    tx: mpsc::Sender<(String, String)>,
+    format: KafkaFormat,
+    schema: Value,
+    pub mode: KafkaMode,
+}
+
+impl KafkaSink {
+    // Messages are (key, payload) pairs, keyed by pool address
+    pub fn new(tx: mpsc::Sender<(String, String)>, format: KafkaFormat, mode: KafkaMode) -> Self {
+        let columns = migrations::schema("uniswap_swaps").expect("uniswap_swaps schema").columns;
+        Self { tx, format, schema: connect_schema("uniswap_swaps", columns), mode }
+    }
+
+    pub fn publish(&self, swaps: &[SwapRecord]) {
+        let mut dropped = 0;
+        for swap in swaps {
+            let message = (swap.pool_address.clone(), self.encode(swap));
+            if self.tx.try_send(message).is_err() {
+                dropped += 1;
+            }
+        }
+        if dropped > 0 {
+            metrics::KAFKA_DROPPED.inc_by(dropped);
+            warn!("⚠️ Kafka buffer full, dropped {} of {} swaps", dropped, swaps.len());
+        }
+    }
+
+    // Serialized straight to text like spill_line, a serde_json::Value can't hold the u128 columns
+    pub fn encode(&self, swap: &SwapRecord) -> String {
+        let row = serde_json::to_string(swap).expect("Records serialize to JSON");
+        match self.format {
+            KafkaFormat::Json => row,
+            KafkaFormat::Schema => format!("{{\"schema\":{},\"payload\":{}}}", self.schema, row),
+        }
+    }
+}
+
+// Connect schema of a table's rows. DateTime64 columns are millis (the Timestamp logical type),
+// 128-bit and Decimal columns Connect Decimals read from the JSON number, at scale 0 since
+// price_usd_decimal is written in raw units
+pub fn connect_schema(name: &str, columns: &[(&str, &str)]) -> Value {
+    let fields: Vec<Value> = columns
+        .iter()
+        .map(|(column, ty)| {
+            let mut field = connect_type(ty);
+            field["field"] = json!(column);
+            field
+        })
+        .collect();
+    json!({ "type": "struct", "name": name, "optional": false, "fields": fields })
+}
+
+fn connect_type(ty: &str) -> Value {
+    if let Some(inner) = ty.strip_prefix("Nullable(").and_then(|t| t.strip_suffix(')')) {
+        let mut field = connect_type(inner);
+        field["optional"] = json!(true);
+        return field;
+    }
+    if let Some(inner) = ty.strip_prefix("LowCardinality(").and_then(|t| t.strip_suffix(')')) {
+        return connect_type(inner);
+    }
+    if let Some(inner) = ty.strip_prefix("Array(").and_then(|t| t.strip_suffix(')')) {
+        return json!({ "type": "array", "items": connect_type(inner), "optional": false });
+    }
+    let plain = |name: &str| json!({ "type": name, "optional": false });
+    match ty {
+        "Bool" => plain("boolean"),
+        "Int8" => plain("int8"),
+        "UInt8" | "Int16" => plain("int16"),
+        "UInt16" | "Int32" => plain("int32"),
+        "UInt32" | "Int64" | "UInt64" => plain("int64"),
+        "Float32" => plain("float"),
+        "Float64" => plain("double"),
+        t if t.starts_with("DateTime64") => {
+            json!({ "type": "int64", "name": "org.apache.kafka.connect.data.Timestamp", "version": 1, "optional": false })
+        }
+        t if t.ends_with("128") || t.ends_with("256") || t.starts_with("Decimal") => json!({
+            "type": "bytes",
+            "name": "org.apache.kafka.connect.data.Decimal",
+            "version": 1,
+            "parameters": { "scale": "0" },
+            "optional": false
+        }),
+        _ => plain("string"),
+    }
+}
+
+// KAFKA_CONFIG=acks=all,compression.type=zstd
+pub fn parse_kafka_config(list: &str) -> Result<Vec<(String, String)>> {
+    let mut config = Vec::new();
+    for entry in list.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+        let (name, value) = entry.split_once('=').ok_or_else(|| eyre::eyre!("Invalid entry '{}', expected name=value", entry))?;
+        let (name, value) = (name.trim(), value.trim());
+        if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '.' || c == '_') {
+            eyre::bail!("Invalid property name '{}'", name);
+        }
+        config.push((name.to_string(), value.to_string()));
+    }
+    Ok(config)
+}
+
+// Starts the producer task. It ends once every KafkaSink is gone (the writer is done) and each
+// message it took is delivered or given up on, so awaiting it is the shutdown flush
+#[cfg(feature = "kafka")]
+pub fn start(settings: &KafkaSettings) -> Result<(KafkaSink, JoinHandle<()>)> {
+    let producer = producer::create(settings)?;
+    let (tx, rx) = mpsc::channel(settings.buffer.max(1));
+    tracing::info!("📮 Publishing swaps to Kafka topic {} at {} ({:?}, {:?})", settings.topic, settings.brokers, settings.format, settings.mode);
+    let task = tokio::spawn(producer::run(producer, settings.topic.clone(), rx));
+    Ok((KafkaSink::new(tx, settings.format, settings.mode), task))
+}
+
+#[cfg(not(feature = "kafka"))]
+pub fn start(_settings: &KafkaSettings) -> Result<(KafkaSink, JoinHandle<()>)> {
+    eyre::bail!("KAFKA_BROKERS needs a build with the kafka feature (cargo build --features kafka)")
+}
+
+#[cfg(feature = "kafka")]
+mod producer {
+    use eyre::{Result, WrapErr};
+    use futures_util::stream::{FuturesUnordered, StreamExt};
+    use rdkafka::config::ClientConfig;
+    use rdkafka::error::KafkaError;
+    use rdkafka::producer::{FutureProducer, FutureRecord};
+    use rdkafka::types::RDKafkaErrorCode;
+    use std::time::Duration;
+    use tokio::sync::mpsc;
+    use tracing::{error, info};
+
+    use super::KafkaSettings;
+    use crate::metrics;
+
+    // How long a send waits before retrying when librdkafka's own queue is full
+    const QUEUE_FULL_BACKOFF: Duration = Duration::from_millis(100);
+
+    pub fn create(settings: &KafkaSettings) -> Result<FutureProducer> {
+        let mut config = ClientConfig::new();
+        config
+            .set("bootstrap.servers", &settings.brokers)
+            // Retries keep their order within a partition
+            .set("enable.idempotence", "true")
+            // The Java client's key hashing, so the pool -> partition mapping matches its producers
+            .set("partitioner", "murmur2_random")
+            .set("message.timeout.ms", "300000");
+        for (name, value) in &settings.config {
+            config.set(name, value);
+        }
+        config.create().wrap_err_with(|| format!("Failed to create a Kafka producer for {}", settings.brokers))
+    }
+
+    // Messages go to librdkafka in the order the writer handed them over, a full queue holds up
+    // this task only. Delivery reports are counted as they come in
+    pub async fn run(producer: FutureProducer, topic: String, mut rx: mpsc::Receiver<(String, String)>) {
+        let mut deliveries = FuturesUnordered::new();
+        let mut open = true;
+        while open || !deliveries.is_empty() {
+            tokio::select! {
+                message = rx.recv(), if open => match message {
+                    Some((key, payload)) => loop {
+                        let record = FutureRecord::to(&topic).key(&key).payload(&payload);
+                        match producer.send_result(record) {
+                            Ok(delivery) => {
+                                deliveries.push(delivery);
+                                break;
+                            }
+                            Err((KafkaError::MessageProduction(RDKafkaErrorCode::QueueFull), _)) => {
+                                metrics::KAFKA_QUEUE_FULL.inc();
+                                tokio::time::sleep(QUEUE_FULL_BACKOFF).await;
+                            }
+                            Err((e, _)) => {
+                                metrics::KAFKA_FAILED.inc();
+                                error!("❌ Kafka refused a swap of pool {}: {}", key, e);
+                                break;
+                            }
+                        }
+                    },
+                    None => {
+                        open = false;
+                        if !deliveries.is_empty() {
+                            info!("📮 Waiting for {} Kafka deliveries", deliveries.len());
+                        }
+                    }
+                },
+                Some(report) = deliveries.next(), if !deliveries.is_empty() => match report {
+                    Ok(Ok(_)) => metrics::KAFKA_DELIVERED.inc(),
+                    Ok(Err((e, _))) => {
+                        metrics::KAFKA_FAILED.inc();
+                        error!("❌ Kafka delivery to {} failed: {}", topic, e);
+                    }
+                    Err(_) => metrics::KAFKA_FAILED.inc(),
+                },
+            }
+        }
+    }
+}
@@ -0,0 +1,175 @@
+use eyre::Result;
+use serde::Serialize;
+use std::sync::OnceLock;
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+use crate::metrics;
+use crate::records::SwapRecord;
+
+// Defaults for REDIS_KEY_PREFIX / REDIS_PRICE_TTL_SECONDS
+pub const DEFAULT_REDIS_KEY_PREFIX: &str = "price";
+pub const DEFAULT_REDIS_PRICE_TTL: Duration = Duration::from_secs(60);
+
+// Prices waiting for the Redis task, past that they are dropped (and counted as failed)
+#[cfg(feature = "redis")]
+const CAPACITY: usize = 10_000;
+
+// REDIS_URL and the rest, needs the redis cargo feature
+#[derive(Debug, Clone)]
+pub struct RedisSettings {
+    pub url: String,
+    pub key_prefix: String,
+    pub ttl: Duration,
+}
+
+// The value of price:<chain>:<pool>, also what its channel carries
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct LatestPrice {
+    pub price: Option<f64>,
+    pub price_exact: Option<String>,
+    pub block: u64,
+    pub log_index: u64,
+    // Block time, Unix millis
+    pub timestamp: i64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PriceUpdate {
+    // Key and channel, <prefix>:<chain_id>:<pool_address>
+    pub key: String,
+    pub price: LatestPrice,
+}
+
+struct Sink {
+    tx: mpsc::Sender<PriceUpdate>,
+    prefix: String,
+}
+
+static SINK: OnceLock<Sink> = OnceLock::new();
+
+pub fn price_update(prefix: &str, swap: &SwapRecord) -> PriceUpdate {
+    PriceUpdate {
+        key: format!("{}:{}:{}", prefix, swap.chain_id, swap.pool_address),
+        price: LatestPrice {
+            price: swap.price_usd,
+            price_exact: swap.price_exact.clone(),
+            block: swap.block_number,
+            log_index: swap.log_index,
+            timestamp: swap.timestamp.timestamp_millis(),
+        },
+    }
+}
+
+// Called for each swap as it is decoded, before it is batched (or confirmed). Never waits:
+// without a Redis task nothing happens, with a backed up one the price is dropped
+pub fn report(swap: &SwapRecord) {
+    let Some(sink) = SINK.get() else { return };
+    if sink.tx.try_send(price_update(&sink.prefix, swap)).is_err() {
+        metrics::REDIS_FAILED.inc();
+    }
+}
+
+// Starts the task writing reported prices to Redis, once per process. It connects (and
+// reconnects) in the background, ingestion never waits on it
+#[cfg(feature = "redis")]
+pub fn start(settings: &RedisSettings) -> Result<()> {
+    let client = redis::Client::open(settings.url.as_str()).map_err(|e| eyre::eyre!("Invalid REDIS_URL: {}", e))?;
+    let (tx, rx) = mpsc::channel(CAPACITY);
+    if SINK.set(Sink { tx, prefix: settings.key_prefix.clone() }).is_err() {
+        return Ok(());
+    }
+    tracing::info!("🧲 Latest prices go to Redis as {}:<chain_id>:<pool> (TTL {:?})", settings.key_prefix, settings.ttl);
+    tokio::spawn(writer::run(client, settings.ttl, rx));
+    Ok(())
+}
+
+#[cfg(not(feature = "redis"))]
+pub fn start(_settings: &RedisSettings) -> Result<()> {
+    eyre::bail!("REDIS_URL needs a build with the redis feature (cargo build --features redis)")
+}
+
+#[cfg(feature = "redis")]
+mod writer {
+    use redis::aio::ConnectionManager;
+    use std::collections::HashMap;
+    use std::time::Duration;
+    use tokio::sync::mpsc;
+    use tracing::{info, warn};
+
+    use super::{LatestPrice, PriceUpdate};
+    use crate::metrics;
+
+    const CONNECT_RETRY_DELAY: Duration = Duration::from_secs(5);
+
+    async fn connect(client: &redis::Client) -> ConnectionManager {
+        loop {
+            match ConnectionManager::new(client.clone()).await {
+                Ok(connection) => {
+                    info!("🧲 Redis connected");
+                    return connection;
+                }
+                Err(e) => {
+                    warn!("⚠️ Redis unreachable: {}, retrying in {:?}", e, CONNECT_RETRY_DELAY);
+                    tokio::time::sleep(CONNECT_RETRY_DELAY).await;
+                }
+            }
+        }
+    }
+
+    // What queued up while the last write ran is written in one pipeline, the newest price per
+    // pool only. A price older than the last one written for its pool (receipt lookups finish
+    // out of order) is skipped
+    pub async fn run(client: redis::Client, ttl: Duration, mut rx: mpsc::Receiver<PriceUpdate>) {
+        let mut connection = None;
+        let mut written: HashMap<String, (u64, u64)> = HashMap::new();
+        let mut failing = false;
+        while let Some(first) = rx.recv().await {
+            let mut latest: HashMap<String, LatestPrice> = HashMap::new();
+            let mut next = Some(first);
+            while let Some(PriceUpdate { key, price }) = next {
+                let position = (price.block, price.log_index);
+                if written.get(&key).is_none_or(|&last| position >= last)
+                    && latest.get(&key).is_none_or(|p| position >= (p.block, p.log_index))
+                {
+                    latest.insert(key, price);
+                }
+                next = rx.try_recv().ok();
+            }
+            if latest.is_empty() {
+                continue;
+            }
+
+            let connection = match &mut connection {
+                Some(connection) => connection,
+                None => connection.insert(connect(&client).await),
+            };
+            let mut pipe = redis::pipe();
+            for (key, price) in &latest {
+                let blob = serde_json::to_string(price).expect("Prices serialize to JSON");
+                pipe.cmd("SET").arg(key).arg(&blob).arg("EX").arg(ttl.as_secs().max(1)).ignore();
+                pipe.cmd("PUBLISH").arg(key).arg(&blob).ignore();
+            }
+            match pipe.query_async::<()>(connection).await {
+                Ok(()) => {
+                    metrics::REDIS_PUBLISHED.inc_by(latest.len() as u64);
+                    if failing {
+                        info!("🧲 Redis writes are back");
+                        failing = false;
+                    }
+                    for (key, price) in latest {
+                        written.insert(key, (price.block, price.log_index));
+                    }
+                }
+                // The connection manager reconnects on its own, meanwhile prices are counted, not logged
+                Err(e) => {
+                    metrics::REDIS_FAILED.inc_by(latest.len() as u64);
+                    if !failing {
+                        warn!("⚠️ Redis write failed: {}, counting failures in indexer_redis_failed_total until it recovers", e);
+                        failing = true;
+                    }
+                }
+            }
+        }
+    }
+}
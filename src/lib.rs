@@ -0,0 +1,54 @@
+//! Uniswap event indexer: decodes pool logs and ships them to ClickHouse.
+
+pub mod abi;
+pub mod backfill;
+pub mod batch;
+pub mod blocks;
+pub mod board;
+pub mod candles;
+pub mod chainlink;
+pub mod checkpoint;
+pub mod cli;
+pub mod config;
+pub mod confirmations;
+pub mod dead_letter;
+pub mod decode;
+pub mod derived;
+pub mod ema;
+pub mod failover;
+pub mod file_sink;
+pub mod grpc;
+pub mod health;
+pub mod indexer;
+pub mod kafka;
+pub mod latest_price;
+pub mod liquidity;
+pub mod metrics;
+pub mod metrics_server;
+pub mod migrations;
+pub mod nats;
+pub mod notify;
+pub mod pool;
+pub mod pool_source;
+pub mod postgres;
+pub mod price;
+pub mod price_move;
+pub mod records;
+pub mod registry;
+pub mod replay;
+pub mod rpc;
+pub mod sanity;
+pub mod shutdown;
+pub mod sink;
+pub mod spill;
+pub mod stdout;
+pub mod storage;
+pub mod transport;
+pub mod tx_lookup;
+pub mod twap;
+pub mod verify;
+pub mod watchlist;
+pub mod watermark;
+pub mod webhooks;
+pub mod workers;
+pub mod ws_server;
@@ -0,0 +1,100 @@
+use alloy::providers::DynProvider;
+use eyre::Result;
+use lru::LruCache;
+use std::collections::HashMap;
+use std::num::NonZeroUsize;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+use crate::abi::IUniswapV3Pool;
+use crate::config::IndexerConfig;
+use crate::pool::{PoolRef, PoolSpec, Protocol};
+use crate::rpc::http_provider;
+
+#[derive(Debug, Clone, Copy)]
+struct Decision {
+    passed: bool,
+    checked_at: Instant,
+}
+
+// MIN_LIQUIDITY: discovered pools below the threshold aren't indexed. Passing pools
+// are decided once, rejected ones are re-checked every LIQUIDITY_RECHECK_MINUTES
+pub struct LiquidityGate {
+    min: Option<u128>,
+    recheck: Duration,
+    provider: DynProvider,
+    decisions: Mutex<LruCache<PoolRef, Decision>>,
+    // Factory pools waiting to grow, they have no swaps to trigger a re-check
+    quarantined: Mutex<HashMap<PoolRef, PoolSpec>>,
+}
+
+impl LiquidityGate {
+    pub fn new(config: &IndexerConfig) -> Result<Self> {
+        let size = NonZeroUsize::new(config.pool_cache_size).unwrap_or(NonZeroUsize::MIN);
+        Ok(Self {
+            min: config.min_liquidity,
+            recheck: config.liquidity_recheck,
+            provider: http_provider(&config.rpc_http_url)?,
+            decisions: Mutex::new(LruCache::new(size)),
+            quarantined: Mutex::new(HashMap::new()),
+        })
+    }
+
+    pub fn recheck_interval(&self) -> Option<Duration> {
+        self.min.map(|_| self.recheck)
+    }
+
+    // `observed` is the liquidity from a Swap log, otherwise liquidity() is read.
+    // Pools without a liquidity() getter and failed reads are let through
+    pub async fn check(&self, spec: PoolSpec, observed: Option<u128>) -> bool {
+        let Some(min) = self.min else { return true };
+
+        if let Some(d) = self.decisions.lock().unwrap().get(&spec.pool)
+            && (d.passed || d.checked_at.elapsed() < self.recheck)
+        {
+            return d.passed;
+        }
+
+        let liquidity = match (observed, spec.pool) {
+            (Some(liquidity), _) => liquidity,
+            (None, PoolRef::Address(addr)) if spec.protocol == Protocol::V3 => {
+                match IUniswapV3Pool::new(addr, &self.provider).liquidity().call().await {
+                    Ok(liquidity) => liquidity,
+                    Err(e) => {
+                        warn!("⚠️ liquidity() failed for {}, indexing it anyway: {:?}", spec.pool, e);
+                        return true;
+                    }
+                }
+            }
+            _ => return true,
+        };
+
+        let passed = liquidity >= min;
+        if passed {
+            info!("💧 {}: liquidity {} >= MIN_LIQUIDITY {}, indexing", spec.pool, liquidity, min);
+        } else {
+            info!("💧 {}: liquidity {} < MIN_LIQUIDITY {}, skipping, re-check in {:?}", spec.pool, liquidity, min, self.recheck);
+        }
+        self.decisions.lock().unwrap().put(spec.pool, Decision { passed, checked_at: Instant::now() });
+        passed
+    }
+
+    pub fn quarantine(&self, spec: PoolSpec) {
+        self.quarantined.lock().unwrap().insert(spec.pool, spec);
+    }
+
+    // Quarantined pools that now pass, they leave the quarantine
+    pub async fn recheck_quarantined(&self) -> Vec<PoolSpec> {
+        let specs: Vec<PoolSpec> = self.quarantined.lock().unwrap().values().copied().collect();
+
+        let mut passed = Vec::new();
+        for spec in specs {
+            if self.check(spec, None).await {
+                self.quarantined.lock().unwrap().remove(&spec.pool);
+                passed.push(spec);
+            }
+        }
+        passed
+    }
+}
@@ -1,224 +1,402 @@
-use alloy::{
-    primitives::{Address, U256}, 
-    providers::{Provider, ProviderBuilder, WsConnect}, 
-    rpc::types::Filter, 
-    sol, 
-    sol_types::SolEvent
-};
-use bigdecimal::{BigDecimal, ToPrimitive};
-use num_traits::{One, Zero};
 use eyre::Result;
-use futures_util::StreamExt;
-use tokio::sync::mpsc;
-use std::env;
-use std::str::FromStr;
+use futures_util::future::join_all;
+use std::collections::HashMap;
+use std::sync::Arc;
 use std::time::Duration;
+use tokio::sync::{mpsc, watch};
 use tracing::{error, warn, info};
-use clickhouse::{Client, Row};
-use serde::Serialize;
-
-sol! {
-    event Swap(
-        address indexed sender,
-        address indexed recipient,
-        int256 amount0,
-        int256 amount1,
-        uint160 sqrtPriceX96,
-        uint128 liquidity,
-        int24 tick,
-    );
-
-    // Interface: get token from Pull
-    #[sol(rpc)]
-    interface IUniswapV3Pool {
-        function token0() external view returns (address);
-        function token1() external view returns (address);
-    }
-
-    // Interface: get decimals
-    #[sol(rpc)]
-    interface IERC20 {
-        function decimals() external view returns (uint8);
-    }
-}
-
-#[derive(Debug, Serialize, Row)]
-struct SwapRecord {
-    timestamp: i64,
-    tx_hash: String,
-    pool_address: String,
-    sender: String,
-    recipient: String,
-    price_usd: f64,
-    liquidity: String,
-    decimals_shift: i32
-}
-
-const Q96_STR: &str = "79228162514264337593543950336";
-
-fn calculate_price(sqrt_price_x96: U256, decimal_diff: i32) -> BigDecimal {
-    let price_bd = BigDecimal::from_str(&sqrt_price_x96.to_string()).unwrap_or_default();
-    let q96_bd = BigDecimal::from_str(Q96_STR).unwrap();
-
-    let sqrt_price = &price_bd / &q96_bd;
-    let price_raw = &sqrt_price * &sqrt_price;
+use clap::Parser;
+use uniswap_indexer::{
+    backfill::{backfill, BackfillRange},
+    blocks::BlockTimes,
+    chainlink,
+    checkpoint::Checkpoints,
+    cli::{Cli, Command},
+    config::{factory_from_env, IndexerConfig},
+    confirmations::run_confirmer,
+    derived::Derived,
+    grpc,
+    health,
+    indexer::{run_indexer, LogHandler},
+    kafka,
+    latest_price,
+    liquidity::LiquidityGate,
+    metrics,
+    metrics_server,
+    migrations::{convert_timestamps, verify_schema},
+    nats::{self, NatsSettings},
+    notify,
+    pool::{fetch_pair_pools, Dex, PoolInfo, PoolRef, PoolSpec, Protocol},
+    pool_source::{watch_pools_file, watch_pools_table, PoolSet},
+    registry::PoolRegistry,
+    replay::{replay, Capture},
+    rpc::{self, http_provider},
+    shutdown,
+    sink::{self, Database, Secondary, SinkKind},
+    stdout::StdoutMode,
+    records::{IndexedEvent, PoolRecord, SCHEMA_VERSION},
+    storage::{connect_clickhouse, load_tracked_pools, WriterSettings},
+    verify::{self, verify},
+    watchlist::{self, WATCHLIST_REPORT_INTERVAL},
+    webhooks,
+    ws_server,
+};
 
-    // Shift correction
-    let shift_val = 10u128.pow(decimal_diff.abs() as u32);
-    let shift = BigDecimal::from(shift_val);
+#[tokio::main]
+async fn main() -> Result<()> {
+    let cli = Cli::parse();
 
-    let adjusted_price = if decimal_diff > 0 {
-        price_raw  * shift
-    } else {
-        price_raw / shift
-    };
+    // Rows own stdout with --stdout, logs move to stderr
+    let to_stdout = cli.stdout.is_some();
+    tracing_subscriber::fmt()
+        .with_max_level(tracing::Level::INFO)
+        .with_writer(move || -> Box<dyn std::io::Write> {
+            if to_stdout { Box::new(std::io::stderr()) } else { Box::new(std::io::stdout()) }
+        })
+        .init();
+    dotenv::dotenv().ok();
 
-    if adjusted_price.is_zero() {
-        return BigDecimal::zero();
+    let mut config = IndexerConfig::from_env();
+    if let Some(depth) = cli.confirmations {
+        config.confirmations = depth;
+    }
+    if cli.nats_dry_run {
+        config.nats.get_or_insert_with(NatsSettings::default).dry_run = true;
+    }
+    if let Some(path) = cli.replay.clone() {
+        // Offline: no liquidity checks, and position events need an eth_call per position
+        config.set_chain_id(config.expected_chain_id.unwrap_or(1));
+        config.min_liquidity = None;
+        config.positions = None;
+        info!("⛓️ Replaying as chain {} (CHAIN_ID)", config.chain_id);
+        return replay(Arc::new(config), &path, cli.stdout).await;
+    }
+    if let Some(rps) = config.rpc_rate_limit {
+        info!("🚦 HTTP RPC limited to {} requests/s", rps);
+        rpc::set_rate_limit(rps);
     }
 
-    let one = BigDecimal::one();
-    one / adjusted_price
-}
-
-// func: get decimals
-async fn fetch_pool_decimals(http_url: &str, pool_addr: Address) -> Result<i32> {
-    let provider = ProviderBuilder::new().connect_http(http_url.parse()?);
-
-    let pool_contract = IUniswapV3Pool::new(pool_addr, provider.clone());
-
-    let t0_raw = pool_contract.token0().call().await?.0;
-    let t1_raw = pool_contract.token1().call().await?.0;
-
-    let t0_addr = Address::from(t0_raw);
-    let t1_addr = Address::from(t1_raw);
-
-    info!("🔍 Token0: {:?}, Token1: {:?}", t0_addr, t1_addr);
-
-    let t0_contract = IERC20::new(t0_addr, provider.clone());
-    let t1_contract = IERC20::new(t1_addr, provider.clone());
-
-    let d0 = t0_contract.decimals().call().await?;
-    let d1 = t1_contract.decimals().call().await?;
+    // `verify-schema` and `convert-timestamps` subcommands: only need ClickHouse
+    if let Some(Command::VerifySchema | Command::ConvertTimestamps) = cli.command {
+        if !matches!(config.sink, SinkKind::ClickHouse) {
+            eyre::bail!("verify-schema and convert-timestamps need SINK=clickhouse");
+        }
+        let clickhouse = connect_clickhouse(&config.clickhouse).await?;
+        return match cli.command {
+            Some(Command::ConvertTimestamps) => convert_timestamps(&clickhouse, &config.tables).await,
+            _ => verify_schema(&clickhouse, &config.tables).await,
+        };
+    }
 
-    info!("📊 Decimals: T0={}, T1={}", d0, d1);
+    // Live runs hold records until they are CONFIRMATIONS deep, one-shot commands write history right away
+    let live = matches!(cli.command, None | Some(Command::Run));
 
-    let diff = (d0 as i32) - (d1 as i32);
-    Ok(diff)
-}
+    // Up before the first RPC call, so a run whose node is down still reports
+    if let Some(addr) = config.metrics_addr {
+        metrics_server::start(addr, config.readiness.clone(), live).await?;
+    }
 
-// ClickHouse
-fn get_clickhouse_client() -> Client {
-    Client::default()
-        .with_url("http://localhost:8123")
-        .with_user("default")
-        .with_password("password123")
-        .with_database("crypto_db")
-}
+    let chain_id = config.resolve_chain_id().await?;
+    // --stdout=only writes nowhere a checkpoint could refer to
+    let database = match cli.stdout {
+        Some(StdoutMode::Only) => {
+            if config.pools_table.is_some() {
+                eyre::bail!("POOLS_TABLE needs SINK=clickhouse, not --stdout=only");
+            }
+            config.checkpoints = None;
+            config.secondary_sinks.clear();
+            Database::Stdout
+        }
+        _ => Database::connect(&config.sink, &config).await?,
+    };
+    let mut secondaries = Vec::new();
+    for kind in &config.secondary_sinks {
+        secondaries.push(Secondary::connect(kind, &config).await?);
+    }
+    // POOLS_TABLE and verify read ClickHouse, the other sinks refuse POOLS_TABLE
+    let clickhouse = database.clickhouse();
+
+    if let Some(source) = &config.pools_table && let Some(clickhouse) = &clickhouse {
+        config.pools = load_tracked_pools(clickhouse, source).await?;
+        info!("🗄️ Loaded {} pool(s) from {}", config.pools.len(), source.table);
+        if config.pools.is_empty() && !config.allow_empty_pools {
+            eyre::bail!("{} has no pools, set ALLOW_EMPTY_POOLS=true to start idle", source.table);
+        }
+    }
 
-#[tokio::main]
-async fn main() -> Result<()> {
-    tracing_subscriber::fmt()
-        .with_max_level(tracing::Level::INFO)
-        .init();
-    dotenv::dotenv().ok();
+    // PAIRS: every fee tier the factory has a pool for
+    let factory = factory_from_env();
+    let mut pair_pools = Vec::new();
+    for (token_a, token_b) in config.pairs.clone() {
+        let found = fetch_pair_pools(&config.rpc_http_url, factory, token_a, token_b).await?;
+        if found.is_empty() {
+            warn!("⚠️ No pools for pair {:?}/{:?}", token_a, token_b);
+        }
+        for (pool, fee) in found {
+            info!("🔗 Pair pool {:?} ({:?}/{:?}, fee tier {})", pool, token_a, token_b, fee);
+            let spec = PoolSpec { protocol: Protocol::V3, dex: Dex::Uniswap, pool: PoolRef::Address(pool) };
+            if !config.pools.iter().any(|p| p.pool == spec.pool) {
+                config.pools.push(spec);
+            }
+            pair_pools.push(spec.pool);
+        }
+    }
+    if let Some(reference) = &config.reference {
+        info!("💵 Reference pool {} prices {:?}", reference.spec.pool, reference.token);
+        if !config.pools.iter().any(|p| p.pool == reference.spec.pool) {
+            config.pools.push(reference.spec);
+        }
+    }
+    let config = Arc::new(config);
 
-    let rpc_url = env::var("RPC_URL").expect("RPC_URL must be set");
-    let rpc_http_url = env::var("RPC_HTTP_URL").expect("RPC_HTTP_URL (HTTP) must be set");
-    let pool_str = env::var("POOL_ADDRESS").unwrap_or_else(|_| "0x88e6a0c2ddd26feeb64f039a2c41296fcb3f5640".to_string());
-    let pool_address = Address::from_str(&pool_str).expect("Invalid pool address");
-    
     info!("🦄 Uniswap Indexer v0.2 Started");
-    info!("🎯 Pool: {:?}", pool_address);
+    info!("⛓️ Chain id: {}", chain_id);
+    for spec in &config.pools {
+        info!("🎯 Pool: {} ({} {})", spec.pool, spec.dex.as_str(), spec.protocol.as_str());
+    }
+    if let Some(d) = &config.discovery {
+        info!("🏭 Watching factory {:?} for pools with {} token(s)", d.factory, d.tokens.len());
+    }
+    if let Some(p) = &config.positions {
+        info!("🎫 Tracking positions from {:?}", p.manager);
+    }
+    if !config.watchlist.is_empty() {
+        info!("👀 Only keeping swaps of {} watched address(es)", config.watchlist.len());
+        tokio::spawn(watchlist::report(WATCHLIST_REPORT_INTERVAL));
+    }
+    if let Some(feeds) = &config.chainlink {
+        chainlink::spawn(&config.rpc_http_url, feeds);
+    }
 
-    info!("⏳ Fetching token decimals...");
-    let decimal_diff = fetch_pool_decimals(&rpc_http_url, pool_address).await?;
-    info!("✅ Decimal Shift Calculated: {}", decimal_diff);
+    info!("⏳ Fetching metadata for {} pool(s)...", config.pools.len());
+    let registry = Arc::new(PoolRegistry::new(&config));
+    let gate = Arc::new(LiquidityGate::new(&config)?);
+    let metas = join_all(config.pools.iter().map(|spec| registry.get_or_fetch(*spec))).await;
 
-    let (tx, mut rx) = mpsc::channel::<SwapRecord>(10000);
+    // Active pools, grows when the factory creates a matching pool; failed ones stay quarantined
+    let mut pools = HashMap::new();
+    for (spec, meta) in config.pools.iter().zip(metas) {
+        if let Some(meta) = meta {
+            pools.insert(spec.pool, PoolInfo::new(*spec, meta, &config));
+        }
+    }
+    if config.firehose {
+        info!("🌊 Firehose mode: indexing every V3 Swap on the chain, and every event of {} configured pools", pools.len());
+    } else if pools.is_empty() && config.allow_empty_pools {
+        warn!("⚠️ No pools to index, starting idle");
+    } else if pools.is_empty() {
+        eyre::bail!("No pool metadata could be fetched, nothing to index");
+    }
 
-    tokio::spawn(async move {
-        let client = get_clickhouse_client();
-        let mut batch = Vec::with_capacity(100); // buffer for batch to send to DB
+    // Fails startup if the tables are newer than this binary
+    database.migrate(&config.tables, config.create_tables || cli.migrate).await?;
+    for secondary in &secondaries {
+        secondary.database.migrate(&config.tables, config.create_tables || cli.migrate).await?;
+    }
 
-        while let Some(record) = rx.recv().await {
-            batch.push(record);
+    let (tx, rx) = mpsc::channel::<IndexedEvent>(config.channel_capacity);
+    metrics::track_channel(&tx);
 
-            if batch.len() >= 10 {
+    let checkpoints = match config.checkpoints.clone() {
+        Some(store) => Some(Checkpoints::load(store, database.checkpoint_db(), config.chain_id).await?),
+        None => None,
+    };
 
-        match client.insert::<SwapRecord>("uniswap_swaps").await {
-            Ok(mut insert) => {
-                for r in &batch {
-                    if let Err(e) = insert.write(r).await {
-                        error!("❌ Write error: {:?}", e);
-                    }
-                }
+    // Pools with a checkpoint resume from the block after it
+    let resume: HashMap<PoolRef, u64> = match &checkpoints {
+        Some(checkpoints) => pools.keys().filter_map(|pool| Some((*pool, checkpoints.get(&pool.to_string())?))).collect(),
+        None => HashMap::new(),
+    };
 
-                match insert.end().await {
-                    Ok(_) => info!("💾 Saved {} swaps to ClickHouse", batch.len()),
-                    Err(e) => error!("❌ ClickHouse End Error: {:?}", e),
-                }
-            }
-            Err(e) => error!("❌ Failed to create inserter: {:?}", e),
-        }
-        batch.clear();
+    database.spawn_dead_letter(config.tables.get("indexer_errors").to_string(), config.chain_id);
+    // The Kafka and NATS publishers outlive the writer until their last messages are acknowledged,
+    // the writer task only finishes after them so every shutdown path below flushes all of it
+    let mut settings = WriterSettings { stdout: cli.stdout == Some(StdoutMode::Also), ..WriterSettings::new(&config) };
+    let mut publishers = Vec::new();
+    if let Some(kafka) = &config.kafka {
+        let (sink, producer) = kafka::start(kafka)?;
+        settings.kafka = Some(sink);
+        publishers.push(producer);
     }
-        }
+    if let Some(nats) = &config.nats {
+        let (sink, publisher) = nats::start(nats).await?;
+        settings.nats = Some(sink);
+        publishers.push(publisher);
+    }
+    if let Some(redis) = &config.redis {
+        latest_price::start(redis)?;
+    }
+    if !config.webhooks.is_empty() {
+        webhooks::start(config.webhooks.clone())?;
+    }
+    let derived = Derived::new(&config);
+    let capacity = config.channel_capacity;
+    let writer = tokio::spawn(async move {
+        let result = sink::run_writers(database, secondaries, rx, settings, derived, checkpoints, capacity).await;
+        join_all(publishers).await;
+        result
     });
 
-    loop {
-        info!("Connecting to WebSocket...");
-        match run_indexer(&rpc_url, pool_address, decimal_diff, tx.clone()).await {
-            Ok(_) => warn!("⚠️ Connection closed. Reconnecting..."),
-            Err(e) => error!("❌ WS Error: {:?}. Reconnecting...", e),
-        }
-        tokio::time::sleep(Duration::from_secs(5)).await;
+    // Backfills would alert on history
+    if live && let Some(notify) = &config.notify {
+        notify::start(notify)?;
     }
-}
+    if live && let Some(settings) = &config.grpc {
+        grpc::start(settings).await?;
+    }
+    if live && let Some(settings) = &config.ws_server {
+        ws_server::start(settings).await?;
+    }
+    let mut heads = None;
+    let tx = if live && config.confirmations > 0 {
+        info!("⏳ Writing records {} block(s) behind the head", config.confirmations);
+        let (heads_tx, heads_rx) = watch::channel(0);
+        let (pending_tx, pending_rx) = mpsc::channel::<IndexedEvent>(config.channel_capacity);
+        tokio::spawn(run_confirmer(pending_rx, tx, config.confirmations, heads_rx));
+        heads = Some(heads_tx);
+        pending_tx
+    } else {
+        tx
+    };
 
-async fn run_indexer(rpc_url: &str, target: Address, decimal_diff: i32, tx: mpsc::Sender<SwapRecord>) -> Result<()> {
-    
-    let ws = WsConnect::new(rpc_url);
-    let provider = ProviderBuilder::new().connect_ws(ws).await?;
+    // Pair pools go to the pools metadata table like discovered ones
+    for pool in &pair_pools {
+        let (PoolRef::Address(address), Some(info)) = (pool, pools.get(pool)) else { continue };
+        let record = PoolRecord {
+            chain_id: config.chain_id,
+            schema_version: SCHEMA_VERSION,
+            timestamp: chrono::Utc::now().timestamp_millis(),
+            block_number: 0,
+            log_index: 0,
+            block_hash: String::new(),
+            tx_hash: String::new(),
+            factory_address: factory.to_string(),
+            pool_address: address.to_string(),
+            token0: info.meta.token0.to_string(),
+            token1: info.meta.token1.to_string(),
+            fee: info.meta.fee,
+            tick_spacing: info.meta.tick_spacing,
+            decimals0: info.meta.decimals.token0,
+            decimals1: info.meta.decimals.token1,
+        };
+        tx.send(IndexedEvent::Pool(record)).await?;
+    }
 
-    info!("✅ Connected! Waiting for Swaps...\n");
+    // `backfill` subcommand: the CLI range replaces BACKFILL_FROM_BLOCK and the process exits after it
+    let one_shot = match cli.command {
+        Some(Command::Backfill { from_block, to_block, chunk_size, parallelism }) => {
+            Some(BackfillRange { from: from_block, to: to_block.number(), chunk_size, parallelism })
+        }
+        Some(Command::Run | Command::Verify { .. } | Command::VerifySchema | Command::ConvertTimestamps) | None => None,
+    };
 
-    let filter = Filter::new()
-        .address(target)
-        .event_signature(Swap::SIGNATURE_HASH);
+    // History first, then live. Without an explicit range, checkpointed pools catch up
+    // from where they stopped; blocks mined meanwhile are gap-filled on connect
+    let provider = http_provider(&config.rpc_http_url)?;
+    let block_times = Arc::new(BlockTimes::new(&config.rpc_http_url, config.block_cache_size)?);
+    let mut handler = LogHandler::new(&config, &registry, &gate, &block_times, tx.clone())?;
+    handler.heads = heads;
+    if let Some(path) = &cli.capture {
+        info!("📼 Capturing raw logs to {}", path.display());
+        handler.capture = Some(Arc::new(Capture::create(path)?));
+    }
 
-    let sub = provider.subscribe_logs(&filter).await?;
-    let mut stream = sub.into_stream();
+    // `verify` subcommand: no catch-up and no live stream, only the repaired swaps are written
+    if let Some(Command::Verify { from_block, to_block, chunk_size, repair }) = cli.command {
+        let Some(clickhouse) = &clickhouse else { eyre::bail!("verify needs SINK=clickhouse") };
+        let range = BackfillRange { chunk_size, ..BackfillRange::new(from_block, to_block.number()) };
+        let mut report = verify(&provider, clickhouse, &pools, &config, range).await?;
+        if repair && !report.missing.is_empty() {
+            verify::repair(&mut handler, &mut pools, std::mem::take(&mut report.missing)).await?;
+        }
+        drop(handler);
+        drop(tx);
+        writer.await??;
 
-    while let Some(log) = stream.next().await {
-        if let Ok(decoded) = log.log_decode::<Swap>() {
-            let data = decoded.inner.data;
-            let tx_hash = log.transaction_hash.unwrap_or_default();
+        if report.mismatches.is_empty() || (repair && report.repairable()) {
+            return Ok(());
+        }
+        eyre::bail!("{} block(s) disagree with the chain", report.mismatches.len());
+    }
 
-            let price_bd = calculate_price(U256::from(data.sqrtPriceX96), decimal_diff);
+    // Ctrl-C / SIGTERM: stop reading logs, then drain the channel into the sink before exiting
+    let shutdown = shutdown::signal();
+    tokio::pin!(shutdown);
+
+    let mut last_block = None;
+    let catch_up = async {
+        if let Some(range) = one_shot.or(config.backfill) {
+            last_block = Some(backfill(&provider, &mut handler, &mut pools, &config, range, None).await?.last_block);
+        } else if let Some(from) = resume.values().min() {
+            info!("📍 Resuming {} pool(s) from their checkpoints", resume.len());
+            let range = BackfillRange::new(from + 1, None);
+            last_block = Some(backfill(&provider, &mut handler, &mut pools, &config, range, Some(&resume)).await?.last_block);
+        }
+        eyre::Ok(())
+    };
+    let interrupted = tokio::select! {
+        result = catch_up => {
+            result?;
+            false
+        }
+        _ = &mut shutdown => true,
+    };
 
-            let price_f64 = price_bd.to_f64().unwrap_or(0.0);
-            let now = chrono::Utc::now();
+    if interrupted {
+        drop(handler);
+        drop(tx);
+        shutdown::drain(writer, config.shutdown_timeout).await?;
+        if one_shot.is_some() {
+            eyre::bail!("Backfill interrupted, rows up to the interruption were written");
+        }
+        return Ok(());
+    }
 
-            let record = SwapRecord {
-                timestamp: now.timestamp_millis(),
-                tx_hash: tx_hash.to_string(),
-                pool_address: target.to_string(),
-                sender: data.sender.to_string(),
-                recipient: data.recipient.to_string(),
-                price_usd: price_f64,
-                liquidity: data.liquidity.to_string(),
-                decimals_shift: decimal_diff
-            };
+    if one_shot.is_some() {
+        // The ordering stage still holds a sender: the writer ends once its swaps are through and the partial batch is flushed
+        drop(handler);
+        drop(tx);
+        writer.await??;
+        info!("✅ Backfill ingested and flushed, {} rows inserted", metrics::ROWS_INSERTED.get());
+        return Ok(());
+    }
 
-            if let Err(e) = tx.send(record).await {
-                error!("❌ Channel closed, receiver died: {:?}", e);
-                break;
-            }
+    // POOLS_TABLE refresh or POOLS_FILE: pool set changes are applied without restarting, the writer keeps running
+    let (set_tx, set_rx) = watch::channel(config.pools.clone());
+    let mut pool_set = None;
+    if let Some(source) = config.pools_table.clone() && let Some(refresh) = source.refresh && let Some(clickhouse) = clickhouse {
+        info!("🗄️ Re-reading {} every {:?}", source.table, refresh);
+        tokio::spawn(watch_pools_table(clickhouse, source, refresh, set_tx));
+        pool_set = Some(PoolSet::new(set_rx, config.pools.clone()));
+    } else if let Some(path) = config.pools_file.clone() {
+        info!("📄 Watching {} for pool changes", path.display());
+        tokio::spawn(watch_pools_file(path, set_tx));
+        pool_set = Some(PoolSet::new(set_rx, config.pools.clone()));
+    }
 
-            info!("🔄 Swap detected: ${:.2}", price_f64);   
+    loop {
+        info!("Connecting to WebSocket...");
+        tokio::select! {
+            result = run_indexer(&mut handler, &mut pools, pool_set.as_mut(), &mut last_block) => match result {
+                Ok(_) => warn!("⚠️ Connection closed. Reconnecting..."),
+                Err(e) => {
+                    metrics::RPC_ERRORS.with_label_values(&["ws"]).inc();
+                    error!("❌ WS Error: {:?}. Reconnecting...", e);
+                }
+            },
+            _ = &mut shutdown => break,
+        }
+        health::HEALTH.disconnected(health::now());
+        metrics::RECONNECTS.inc();
+        webhooks::report_reconnect();
+        tokio::select! {
+            _ = tokio::time::sleep(Duration::from_secs(5)) => {}
+            _ = &mut shutdown => break,
         }
     }
 
-    Ok(())
-}
\ No newline at end of file
+    // Swaps still waiting on receipts go through the ordering stage first, it holds a sender until then
+    drop(handler);
+    drop(tx);
+    shutdown::drain(writer, config.shutdown_timeout).await
+}
@@ -0,0 +1,280 @@
+use prometheus::{Encoder, Histogram, HistogramOpts, IntCounter, IntCounterVec, IntGauge, IntGaugeVec, Opts, Registry, TextEncoder};
+use std::sync::{LazyLock, OnceLock};
+use tokio::sync::mpsc;
+
+use crate::records::IndexedEvent;
+
+// All indexer metrics are registered here
+pub static REGISTRY: LazyLock<Registry> = LazyLock::new(Registry::new);
+
+fn register<T: prometheus::core::Collector + Clone + 'static>(metric: T) -> T {
+    REGISTRY.register(Box::new(metric.clone())).expect("duplicate metric");
+    metric
+}
+
+// Swaps decoded (and past the price sanity check) per pool, before the watchlist
+pub static SWAPS_DECODED: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register(IntCounterVec::new(Opts::new("indexer_swaps_decoded_total", "Swaps decoded by pool"), &["pool"]).unwrap())
+});
+
+// Highest block a log was handled for, and when (Unix seconds) it last moved: alert on
+// time() - indexer_last_block_timestamp_seconds. Quiet pools hold it too
+pub static LAST_BLOCK: LazyLock<IntGauge> = LazyLock::new(|| {
+    register(IntGauge::new("indexer_last_block", "Highest block with a handled log").unwrap())
+});
+
+pub static LAST_BLOCK_TIME: LazyLock<IntGauge> = LazyLock::new(|| {
+    register(IntGauge::new("indexer_last_block_timestamp_seconds", "When indexer_last_block last moved, Unix seconds").unwrap())
+});
+
+// Stream reconnects of the live run
+pub static RECONNECTS: LazyLock<IntCounter> = LazyLock::new(|| {
+    register(IntCounter::new("indexer_reconnects_total", "WebSocket stream reconnects").unwrap())
+});
+
+// Failed RPC calls by kind: ws (the stream), get_logs, block, transaction, receipt
+pub static RPC_ERRORS: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register(IntCounterVec::new(Opts::new("indexer_rpc_errors_total", "Failed RPC calls by kind"), &["kind"]).unwrap())
+});
+
+// Records waiting in the channel to the writer, read at scrape time
+pub static CHANNEL_DEPTH: LazyLock<IntGauge> = LazyLock::new(|| {
+    register(IntGauge::new("indexer_channel_depth", "Records queued for the writer").unwrap())
+});
+
+// One flush_batch: every table's insert, retries included
+pub static FLUSH_SECONDS: LazyLock<Histogram> = LazyLock::new(|| {
+    let buckets = vec![0.01, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0];
+    register(Histogram::with_opts(HistogramOpts::new("indexer_flush_duration_seconds", "Time to flush a batch").buckets(buckets)).unwrap())
+});
+
+// Failed insert attempts by table, the retried ones and those that gave up
+pub static INSERT_FAILURES: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register(IntCounterVec::new(Opts::new("indexer_insert_failures_total", "Failed inserts by table"), &["table"]).unwrap())
+});
+
+// Rows for indexer_errors by category (decode, insert), and those that never got there
+pub static ERRORS: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register(IntCounterVec::new(Opts::new("indexer_errors_total", "Logs that failed to decode and rows ClickHouse refused"), &["category"]).unwrap())
+});
+
+pub static ERRORS_DROPPED: LazyLock<IntCounter> = LazyLock::new(|| {
+    register(IntCounter::new("indexer_errors_dropped_total", "Error rows not written to indexer_errors").unwrap())
+});
+
+// KAFKA_BROKERS: swaps the broker acknowledged, gave up on, or the full buffer dropped
+pub static KAFKA_DELIVERED: LazyLock<IntCounter> = LazyLock::new(|| {
+    register(IntCounter::new("indexer_kafka_delivered_total", "Swaps delivered to Kafka").unwrap())
+});
+
+pub static KAFKA_FAILED: LazyLock<IntCounter> = LazyLock::new(|| {
+    register(IntCounter::new("indexer_kafka_failed_total", "Swaps Kafka refused or didn't acknowledge in time").unwrap())
+});
+
+pub static KAFKA_DROPPED: LazyLock<IntCounter> = LazyLock::new(|| {
+    register(IntCounter::new("indexer_kafka_dropped_total", "Swaps dropped because the Kafka buffer was full").unwrap())
+});
+
+// Sends retried because librdkafka's queue was full
+pub static KAFKA_QUEUE_FULL: LazyLock<IntCounter> = LazyLock::new(|| {
+    register(IntCounter::new("indexer_kafka_queue_full_total", "Kafka sends retried on a full producer queue").unwrap())
+});
+
+// NATS_URL: swaps JetStream acknowledged, and publishes retried for want of an ack
+pub static NATS_PUBLISHED: LazyLock<IntCounter> = LazyLock::new(|| {
+    register(IntCounter::new("indexer_nats_published_total", "Swaps acknowledged by NATS JetStream").unwrap())
+});
+
+pub static NATS_RETRIES: LazyLock<IntCounter> = LazyLock::new(|| {
+    register(IntCounter::new("indexer_nats_publish_retries_total", "NATS publishes retried").unwrap())
+});
+
+// REDIS_URL: latest prices written, and those dropped or refused on the way
+pub static REDIS_PUBLISHED: LazyLock<IntCounter> = LazyLock::new(|| {
+    register(IntCounter::new("indexer_redis_published_total", "Latest prices written to Redis").unwrap())
+});
+
+pub static REDIS_FAILED: LazyLock<IntCounter> = LazyLock::new(|| {
+    register(IntCounter::new("indexer_redis_failed_total", "Latest prices not written to Redis").unwrap())
+});
+
+// GRPC_ADDR: open SubscribeSwaps streams, and swaps they missed by falling behind GRPC_BUFFER
+pub static GRPC_SUBSCRIBERS: LazyLock<IntGauge> = LazyLock::new(|| {
+    register(IntGauge::new("indexer_grpc_subscribers", "Open gRPC SubscribeSwaps streams").unwrap())
+});
+
+pub static GRPC_DROPPED: LazyLock<IntCounter> = LazyLock::new(|| {
+    register(IntCounter::new("indexer_grpc_dropped_total", "Swaps dropped for gRPC subscribers that fell behind").unwrap())
+});
+
+// WS_SERVER_ADDR: connected /swaps clients, and swaps they missed by falling behind WS_SERVER_BUFFER
+pub static WS_CLIENTS: LazyLock<IntGauge> = LazyLock::new(|| {
+    register(IntGauge::new("indexer_ws_clients", "Connected WebSocket /swaps clients").unwrap())
+});
+
+pub static WS_DROPPED: LazyLock<IntCounter> = LazyLock::new(|| {
+    register(IntCounter::new("indexer_ws_dropped_total", "Swaps dropped for WebSocket clients that fell behind").unwrap())
+});
+
+// WEBHOOK_RULES_FILE: deliveries per rule by outcome (delivered, retried, failed, suppressed)
+pub static WEBHOOKS: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register(IntCounterVec::new(Opts::new("indexer_webhooks_total", "Webhook alerts by rule and outcome"), &["rule", "outcome"]).unwrap())
+});
+
+// Large swap alerts per transport (telegram, discord) by outcome (sent, failed)
+pub static NOTIFICATIONS: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register(IntCounterVec::new(Opts::new("indexer_notifications_total", "Large swap alerts by transport and outcome"), &["transport", "outcome"]).unwrap())
+});
+
+// Logs from subscribed contracts whose topic0 no decoder handles
+pub static UNKNOWN_TOPICS: LazyLock<IntCounter> = LazyLock::new(|| {
+    register(IntCounter::new("indexer_unknown_topics_total", "Logs with an unhandled topic0").unwrap())
+});
+
+// Pool set changes from POOLS_FILE / POOLS_TABLE reloads
+pub static POOLS_ADDED: LazyLock<IntCounter> = LazyLock::new(|| {
+    register(IntCounter::new("indexer_pools_added_total", "Pools added by a pool set reload").unwrap())
+});
+
+pub static POOLS_REMOVED: LazyLock<IntCounter> = LazyLock::new(|| {
+    register(IntCounter::new("indexer_pools_removed_total", "Pools removed by a pool set reload").unwrap())
+});
+
+// Discovered pools (or firehose swaps of unknown pools) dropped by TOKEN_ALLOWLIST / TOKEN_DENYLIST
+pub static TOKEN_FILTER_REJECTED: LazyLock<IntCounter> = LazyLock::new(|| {
+    register(IntCounter::new("indexer_token_filter_rejected_total", "Pools rejected by the token allow/deny lists").unwrap())
+});
+
+// WATCHLIST outcomes per swap
+pub static WATCHLIST_MATCHED: LazyLock<IntCounter> = LazyLock::new(|| {
+    register(IntCounter::new("indexer_watchlist_matched_total", "Swaps touching a watchlist address").unwrap())
+});
+
+pub static WATCHLIST_DROPPED: LazyLock<IntCounter> = LazyLock::new(|| {
+    register(IntCounter::new("indexer_watchlist_dropped_total", "Swaps dropped by the watchlist").unwrap())
+});
+
+// Logs recovered by get_logs for blocks missed during a reconnect
+pub static GAP_FILLED_LOGS: LazyLock<IntCounter> = LazyLock::new(|| {
+    register(IntCounter::new("indexer_gap_filled_logs_total", "Logs fetched to fill reconnect gaps").unwrap())
+});
+
+// Rows whose insert.end() succeeded, all tables
+pub static ROWS_INSERTED: LazyLock<IntCounter> = LazyLock::new(|| {
+    register(IntCounter::new("indexer_rows_inserted_total", "Rows inserted into ClickHouse").unwrap())
+});
+
+// Inserts retried after a ClickHouse error, one per attempt
+pub static INSERT_RETRIES: LazyLock<IntCounter> = LazyLock::new(|| {
+    register(IntCounter::new("indexer_insert_retries_total", "ClickHouse inserts retried").unwrap())
+});
+
+// Rows of inserts that ran out of retries: written to SPILL_DIR, or lost when that failed
+// too, the directory was full or SPILL_FULL_POLICY=drop-oldest deleted them
+pub static ROWS_SPILLED: LazyLock<IntCounter> = LazyLock::new(|| {
+    register(IntCounter::new("indexer_rows_spilled_total", "Rows written to SPILL_DIR after the insert retries ran out").unwrap())
+});
+
+pub static ROWS_LOST: LazyLock<IntCounter> = LazyLock::new(|| {
+    register(IntCounter::new("indexer_rows_lost_total", "Rows neither inserted nor kept in SPILL_DIR").unwrap())
+});
+
+pub static SPILL_FILES_REPLAYED: LazyLock<IntCounter> = LazyLock::new(|| {
+    register(IntCounter::new("indexer_spill_files_replayed_total", "Spilled batches inserted and deleted").unwrap())
+});
+
+// INSERT_WORKERS > 1: rows each worker inserted and batches it handled, to tune the count
+pub static WORKER_ROWS: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register(IntCounterVec::new(Opts::new("indexer_worker_rows_inserted_total", "Rows inserted per insert worker"), &["worker"]).unwrap())
+});
+
+pub static WORKER_BATCHES: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register(IntCounterVec::new(Opts::new("indexer_worker_batches_total", "Batches handled per insert worker"), &["worker"]).unwrap())
+});
+
+// CLICKHOUSE_URL lists several endpoints: inserts moved on to the next one, and which are up
+pub static CLICKHOUSE_FAILOVERS: LazyLock<IntCounter> = LazyLock::new(|| {
+    register(IntCounter::new("indexer_clickhouse_failovers_total", "Inserts retried on the next ClickHouse endpoint").unwrap())
+});
+
+pub static CLICKHOUSE_ENDPOINT_HEALTHY: LazyLock<IntGaugeVec> = LazyLock::new(|| {
+    register(IntGaugeVec::new(Opts::new("indexer_clickhouse_endpoint_healthy", "1 while inserts use the endpoint, 0 while it is skipped"), &["endpoint"]).unwrap())
+});
+
+// Batches inserted because their oldest row reached BATCH_MAX_AGE_SECONDS, not BATCH_SIZE
+pub static TIMED_FLUSHES: LazyLock<IntCounter> = LazyLock::new(|| {
+    register(IntCounter::new("indexer_timed_flushes_total", "Partial batches flushed by the batch age timer").unwrap())
+});
+
+// Uncompressed RowBinary bytes of those rows
+pub static BYTES_INSERTED: LazyLock<IntCounter> = LazyLock::new(|| {
+    register(IntCounter::new("indexer_bytes_inserted_total", "Bytes inserted into ClickHouse, uncompressed").unwrap())
+});
+
+// Bytes written to ClickHouse connections, compressed and TLS-wrapped as they leave
+pub static CLICKHOUSE_BYTES_SENT: LazyLock<IntCounter> = LazyLock::new(|| {
+    register(IntCounter::new("indexer_clickhouse_bytes_sent_total", "Bytes sent to ClickHouse on the wire").unwrap())
+});
+
+// Logs delivered with removed: true, swaps among them go to reorged_swaps
+pub static REORGED_LOGS: LazyLock<IntCounter> = LazyLock::new(|| {
+    register(IntCounter::new("indexer_reorged_logs_total", "Logs reverted by a reorg").unwrap())
+});
+
+// Buffered records dropped because their block was reorged before it was confirmed
+pub static REORG_EVICTED_RECORDS: LazyLock<IntCounter> = LazyLock::new(|| {
+    register(IntCounter::new("indexer_reorg_evicted_records_total", "Unconfirmed records of reorged blocks").unwrap())
+});
+
+// Logs dropped by the handler's (tx_hash, log_index) window
+pub static DUPLICATES_SUPPRESSED: LazyLock<IntCounter> = LazyLock::new(|| {
+    register(IntCounter::new("indexer_duplicates_suppressed_total", "Logs seen again within DEDUP_WINDOW").unwrap())
+});
+
+// Events whose on-chain values give no price (zero or out-of-range sqrtPriceX96, empty reserves)
+pub static PRICE_ERRORS: LazyLock<IntCounter> = LazyLock::new(|| {
+    register(IntCounter::new("indexer_price_errors_total", "Events written without a price").unwrap())
+});
+
+// Chainlink reads that failed or answered nothing usable
+pub static FEED_ERRORS: LazyLock<IntCounter> = LazyLock::new(|| {
+    register(IntCounter::new("indexer_feed_errors_total", "Failed Chainlink feed reads").unwrap())
+});
+
+// Swaps whose price failed the sanity check, written to suspect_swaps instead
+pub static SUSPECT_SWAPS: LazyLock<IntCounter> = LazyLock::new(|| {
+    register(IntCounter::new("indexer_suspect_swaps_total", "Swaps quarantined by the price sanity check").unwrap())
+});
+
+// BlockTimes lookups that needed no header fetch, for sizing BLOCK_CACHE_SIZE
+pub static BLOCK_CACHE_HITS: LazyLock<IntCounter> = LazyLock::new(|| {
+    register(IntCounter::new("indexer_block_cache_hits_total", "Block timestamps served from the cache").unwrap())
+});
+
+pub static BLOCK_CACHE_MISSES: LazyLock<IntCounter> = LazyLock::new(|| {
+    register(IntCounter::new("indexer_block_cache_misses_total", "Block timestamps not in the cache").unwrap())
+});
+
+static CHANNEL: OnceLock<mpsc::WeakSender<IndexedEvent>> = OnceLock::new();
+
+// The writer channel behind indexer_channel_depth, without keeping it open
+pub fn track_channel(tx: &mpsc::Sender<IndexedEvent>) {
+    let _ = CHANNEL.set(tx.downgrade());
+}
+
+pub fn observe_block(block: u64) {
+    if block as i64 > LAST_BLOCK.get() {
+        LAST_BLOCK.set(block as i64);
+        LAST_BLOCK_TIME.set(chrono::Utc::now().timestamp());
+    }
+}
+
+// Every metric in the text exposition format
+pub fn encode() -> String {
+    if let Some(tx) = CHANNEL.get().and_then(|tx| tx.upgrade()) {
+        CHANNEL_DEPTH.set((tx.max_capacity() - tx.capacity()) as i64);
+    }
+    let mut buffer = Vec::new();
+    TextEncoder::new().encode(&REGISTRY.gather(), &mut buffer).expect("Metrics encode as text");
+    String::from_utf8(buffer).expect("Metrics are UTF-8")
+}
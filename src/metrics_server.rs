@@ -0,0 +1,76 @@
+use eyre::Result;
+use http_body_util::Full;
+use hyper::body::{Bytes, Incoming};
+use hyper::header::CONTENT_TYPE;
+use hyper::server::conn::http1;
+use hyper::service::service_fn;
+use hyper::{Method, Request, Response, StatusCode};
+use hyper_util::rt::TokioIo;
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::sync::{Arc, LazyLock};
+use std::time::Duration;
+use tokio::net::TcpListener;
+use tracing::{error, info};
+
+use crate::health::{self, ReadinessSettings, HEALTH};
+use crate::metrics;
+
+// What /readyz checks: the thresholds, and whether this run has a stream to be down
+#[derive(Debug, Clone)]
+struct Probe {
+    settings: ReadinessSettings,
+    stream: bool,
+}
+
+async fn respond(request: Request<Incoming>, probe: Arc<Probe>) -> Result<Response<Full<Bytes>>, Infallible> {
+    let response = match (request.method(), request.uri().path()) {
+        (&Method::GET, "/metrics") => Response::builder()
+            .header(CONTENT_TYPE, "text/plain; version=0.0.4")
+            .body(Full::new(Bytes::from(metrics::encode()))),
+        // Answering at all is the liveness check
+        (&Method::GET, "/healthz") => Response::builder().body(Full::new(Bytes::from_static(b"ok\n"))),
+        (&Method::GET, "/readyz") => {
+            let readiness = HEALTH.readiness(&probe.settings, probe.stream, health::now());
+            let status = if readiness.ready { StatusCode::OK } else { StatusCode::SERVICE_UNAVAILABLE };
+            Response::builder()
+                .status(status)
+                .header(CONTENT_TYPE, "application/json")
+                .body(Full::new(Bytes::from(serde_json::to_vec(&readiness).expect("Readiness serializes"))))
+        }
+        _ => Response::builder()
+            .status(StatusCode::NOT_FOUND)
+            .body(Full::new(Bytes::from_static(b"Served: /metrics, /healthz, /readyz\n"))),
+    };
+    Ok(response.expect("Static responses build"))
+}
+
+// Binds METRICS_ADDR (port 0 picks one) and serves GET /metrics, /healthz and /readyz in the
+// background, up before the first RPC call so a run that can't reach its node still reports.
+// `stream` is false for one-shot commands, whose readiness only depends on inserts. Returns
+// the bound address
+pub async fn start(addr: SocketAddr, readiness: ReadinessSettings, stream: bool) -> Result<SocketAddr> {
+    let listener = TcpListener::bind(addr).await.map_err(|e| eyre::eyre!("Failed to bind METRICS_ADDR {}: {}", addr, e))?;
+    let addr = listener.local_addr()?;
+    // Starts the clocks: the stream counts as down from here
+    LazyLock::force(&HEALTH);
+    info!("📈 Metrics on http://{}/metrics, probes on /healthz and /readyz", addr);
+    let probe = Arc::new(Probe { settings: readiness, stream });
+    tokio::spawn(async move {
+        loop {
+            match listener.accept().await {
+                Ok((stream, _)) => {
+                    let probe = probe.clone();
+                    let service = service_fn(move |request| respond(request, probe.clone()));
+                    // A scraper that hangs up mid-response is its own problem
+                    tokio::spawn(http1::Builder::new().serve_connection(TokioIo::new(stream), service));
+                }
+                Err(e) => {
+                    error!("❌ Metrics accept failed: {}", e);
+                    tokio::time::sleep(Duration::from_secs(1)).await;
+                }
+            }
+        }
+    });
+    Ok(addr)
+}
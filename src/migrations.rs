@@ -0,0 +1,841 @@
+use clickhouse::Client;
+use eyre::{Result, WrapErr};
+use tracing::{error, info, warn};
+
+use crate::config::TableNames;
+use crate::records::SCHEMA_VERSION;
+
+// A column an existing table may be missing, or have with an older type
+pub struct Migration {
+    pub version: u16,
+    pub table: &'static str,
+    pub column: &'static str,
+    pub ty: &'static str,
+}
+
+pub const TABLES: [&str; 14] = [
+    "uniswap_swaps",
+    "uniswap_mints",
+    "uniswap_burns",
+    "uniswap_collects",
+    "uniswap_flashes",
+    "pool_initializations",
+    "pools",
+    "positions_events",
+    "protocol_fees",
+    "reorged_swaps",
+    "suspect_swaps",
+    "pool_twaps",
+    "uniswap_candles_1m",
+    "indexer_errors",
+];
+
+// A table as this version creates it: every column with its current type, in insert order
+#[derive(Debug)]
+pub struct TableSchema {
+    pub table: &'static str,
+    pub columns: &'static [(&'static str, &'static str)],
+    pub engine: &'static str,
+    pub partition_by: Option<&'static str>,
+    pub order_by: &'static str,
+}
+
+impl TableSchema {
+    // `name` is the TABLE_NAMES name of the table
+    pub fn create_statement(&self, name: &str) -> String {
+        let columns: Vec<String> = self.columns.iter().map(|(column, ty)| format!("{} {}", column, ty)).collect();
+        let partition = self.partition_by.map(|p| format!(" PARTITION BY {}", p)).unwrap_or_default();
+        format!(
+            "CREATE TABLE IF NOT EXISTS {} ({}) ENGINE = {}{} ORDER BY {}",
+            name,
+            columns.join(", "),
+            self.engine,
+            partition,
+            self.order_by
+        )
+    }
+}
+
+// The tables at SCHEMA_VERSION, what CREATE_TABLES creates and verify-schema expects. Event
+// tables are partitioned by month and sorted in chain order per pool; the small pools and
+// pool_initializations tables aren't partitioned
+const UNISWAP_SWAPS_COLUMNS: &[(&str, &str)] = &[
+    ("chain_id", "UInt64"),
+    ("schema_version", "UInt16"),
+    ("timestamp", "DateTime64(3)"),
+    ("ingested_at", "DateTime64(3)"),
+    ("block_number", "UInt64"),
+    ("block_hash", "String"),
+    ("transaction_index", "UInt64"),
+    ("log_index", "UInt64"),
+    ("tx_hash", "String"),
+    ("pool_address", "String"),
+    ("sender", "String"),
+    ("recipient", "String"),
+    ("amount0_raw", "String"),
+    ("amount1_raw", "String"),
+    ("amount0", "Nullable(Float64)"),
+    ("amount1", "Nullable(Float64)"),
+    ("price_usd", "Nullable(Float64)"),
+    ("price_exact", "Nullable(String)"),
+    ("price_usd_decimal", "Nullable(Decimal(38, 18))"),
+    ("price_token0_in_token1", "Nullable(Float64)"),
+    ("price_token1_in_token0", "Nullable(Float64)"),
+    ("volume_usd", "Nullable(Float64)"),
+    ("exec_price", "Nullable(Float64)"),
+    ("price_impact_bps", "Nullable(Float64)"),
+    ("fee_amount", "Nullable(Float64)"),
+    ("fee_usd", "Nullable(Float64)"),
+    ("price_move_bps", "Nullable(Float64)"),
+    ("price_ema", "Nullable(Float64)"),
+    ("direction", "LowCardinality(String)"),
+    ("price_inverted", "Bool"),
+    ("sqrt_price_x96", "Nullable(String)"),
+    ("tick", "Nullable(Int32)"),
+    ("liquidity", "Nullable(UInt128)"),
+    ("decimals_shift", "Int32"),
+    ("price_check_failed", "Bool"),
+    ("fee_tier", "UInt32"),
+    ("token0_symbol", "LowCardinality(String)"),
+    ("token1_symbol", "LowCardinality(String)"),
+    ("pair", "LowCardinality(String)"),
+    ("gas_used", "Nullable(UInt64)"),
+    ("gas_price_gwei", "Nullable(Float64)"),
+    ("tx_from", "Nullable(String)"),
+    ("protocol", "LowCardinality(String)"),
+    ("dex", "LowCardinality(String)"),
+    ("protocol_fees_token0", "Nullable(String)"),
+    ("protocol_fees_token1", "Nullable(String)"),
+    ("insert_version", "UInt64"),
+];
+
+const UNISWAP_MINTS_COLUMNS: &[(&str, &str)] = &[
+    ("chain_id", "UInt64"),
+    ("schema_version", "UInt16"),
+    ("timestamp", "DateTime64(3)"),
+    ("block_number", "UInt64"),
+    ("log_index", "UInt64"),
+    ("block_hash", "String"),
+    ("tx_hash", "String"),
+    ("pool_address", "String"),
+    ("owner", "String"),
+    ("tick_lower", "Int32"),
+    ("tick_upper", "Int32"),
+    ("amount", "String"),
+    ("amount0", "String"),
+    ("amount1", "String"),
+];
+
+const UNISWAP_BURNS_COLUMNS: &[(&str, &str)] = &[
+    ("chain_id", "UInt64"),
+    ("schema_version", "UInt16"),
+    ("timestamp", "DateTime64(3)"),
+    ("block_number", "UInt64"),
+    ("log_index", "UInt64"),
+    ("block_hash", "String"),
+    ("tx_hash", "String"),
+    ("pool_address", "String"),
+    ("owner", "String"),
+    ("tick_lower", "Int32"),
+    ("tick_upper", "Int32"),
+    ("amount", "String"),
+    ("amount0_raw", "String"),
+    ("amount1_raw", "String"),
+    ("amount0", "Nullable(Float64)"),
+    ("amount1", "Nullable(Float64)"),
+];
+
+const UNISWAP_COLLECTS_COLUMNS: &[(&str, &str)] = &[
+    ("chain_id", "UInt64"),
+    ("schema_version", "UInt16"),
+    ("timestamp", "DateTime64(3)"),
+    ("block_number", "UInt64"),
+    ("log_index", "UInt64"),
+    ("block_hash", "String"),
+    ("tx_hash", "String"),
+    ("pool_address", "String"),
+    ("owner", "String"),
+    ("recipient", "String"),
+    ("tick_lower", "Int32"),
+    ("tick_upper", "Int32"),
+    ("amount0_raw", "String"),
+    ("amount1_raw", "String"),
+    ("amount0", "Nullable(Float64)"),
+    ("amount1", "Nullable(Float64)"),
+];
+
+const UNISWAP_FLASHES_COLUMNS: &[(&str, &str)] = &[
+    ("chain_id", "UInt64"),
+    ("schema_version", "UInt16"),
+    ("timestamp", "DateTime64(3)"),
+    ("block_number", "UInt64"),
+    ("log_index", "UInt64"),
+    ("block_hash", "String"),
+    ("tx_hash", "String"),
+    ("pool_address", "String"),
+    ("sender", "String"),
+    ("recipient", "String"),
+    ("amount0_raw", "String"),
+    ("amount1_raw", "String"),
+    ("paid0_raw", "String"),
+    ("paid1_raw", "String"),
+    ("amount0", "Nullable(Float64)"),
+    ("amount1", "Nullable(Float64)"),
+    ("fee0", "Nullable(Float64)"),
+    ("fee1", "Nullable(Float64)"),
+];
+
+const POOL_INITIALIZATIONS_COLUMNS: &[(&str, &str)] = &[
+    ("chain_id", "UInt64"),
+    ("schema_version", "UInt16"),
+    ("timestamp", "DateTime64(3)"),
+    ("block_number", "UInt64"),
+    ("log_index", "UInt64"),
+    ("block_hash", "String"),
+    ("tx_hash", "String"),
+    ("pool_address", "String"),
+    ("sqrt_price_x96", "String"),
+    ("tick", "Int32"),
+    ("price_usd", "Nullable(Float64)"),
+    ("decimals_shift", "Int32"),
+];
+
+const POOLS_COLUMNS: &[(&str, &str)] = &[
+    ("chain_id", "UInt64"),
+    ("schema_version", "UInt16"),
+    ("timestamp", "DateTime64(3)"),
+    ("block_number", "UInt64"),
+    ("log_index", "UInt64"),
+    ("block_hash", "String"),
+    ("tx_hash", "String"),
+    ("factory_address", "String"),
+    ("pool_address", "String"),
+    ("token0", "String"),
+    ("token1", "String"),
+    ("fee", "UInt32"),
+    ("tick_spacing", "Int32"),
+    ("decimals0", "UInt8"),
+    ("decimals1", "UInt8"),
+];
+
+const POSITIONS_EVENTS_COLUMNS: &[(&str, &str)] = &[
+    ("chain_id", "UInt64"),
+    ("schema_version", "UInt16"),
+    ("timestamp", "DateTime64(3)"),
+    ("block_number", "UInt64"),
+    ("log_index", "UInt64"),
+    ("block_hash", "String"),
+    ("tx_hash", "String"),
+    ("pool_address", "String"),
+    ("token_id", "String"),
+    ("event_type", "LowCardinality(String)"),
+    ("liquidity", "Nullable(String)"),
+    ("recipient", "Nullable(String)"),
+    ("amount0_raw", "String"),
+    ("amount1_raw", "String"),
+    ("amount0", "Nullable(Float64)"),
+    ("amount1", "Nullable(Float64)"),
+];
+
+const PROTOCOL_FEES_COLUMNS: &[(&str, &str)] = &[
+    ("chain_id", "UInt64"),
+    ("schema_version", "UInt16"),
+    ("timestamp", "DateTime64(3)"),
+    ("block_number", "UInt64"),
+    ("log_index", "UInt64"),
+    ("block_hash", "String"),
+    ("tx_hash", "String"),
+    ("transaction_index", "UInt64"),
+    ("pool_address", "String"),
+    ("event_type", "LowCardinality(String)"),
+    ("fee_protocol0_old", "Nullable(UInt8)"),
+    ("fee_protocol1_old", "Nullable(UInt8)"),
+    ("fee_protocol0_new", "Nullable(UInt8)"),
+    ("fee_protocol1_new", "Nullable(UInt8)"),
+    ("sender", "Nullable(String)"),
+    ("recipient", "Nullable(String)"),
+    ("amount0_raw", "Nullable(String)"),
+    ("amount1_raw", "Nullable(String)"),
+    ("amount0", "Nullable(Float64)"),
+    ("amount1", "Nullable(Float64)"),
+];
+
+const REORGED_SWAPS_COLUMNS: &[(&str, &str)] = &[
+    ("chain_id", "UInt64"),
+    ("schema_version", "UInt16"),
+    ("detected_at", "DateTime64(3)"),
+    ("block_number", "UInt64"),
+    ("block_hash", "String"),
+    ("tx_hash", "String"),
+    ("log_index", "UInt64"),
+    ("pool_address", "String"),
+];
+
+const SUSPECT_SWAPS_COLUMNS: &[(&str, &str)] = &[
+    ("chain_id", "UInt64"),
+    ("schema_version", "UInt16"),
+    ("timestamp", "DateTime64(3)"),
+    ("block_number", "UInt64"),
+    ("block_hash", "String"),
+    ("tx_hash", "String"),
+    ("log_index", "UInt64"),
+    ("pool_address", "String"),
+    ("price_usd", "Float64"),
+    ("price_exact", "String"),
+    ("sqrt_price_x96", "Nullable(String)"),
+    ("tick", "Nullable(Int32)"),
+    ("amount0_raw", "String"),
+    ("amount1_raw", "String"),
+    ("decimals_shift", "Int32"),
+    ("reason", "String"),
+];
+
+const INDEXER_ERRORS_COLUMNS: &[(&str, &str)] = &[
+    ("chain_id", "UInt64"),
+    ("schema_version", "UInt16"),
+    ("detected_at", "DateTime64(3)"),
+    ("category", "LowCardinality(String)"),
+    ("error", "String"),
+    ("target_table", "String"),
+    ("block_number", "UInt64"),
+    ("block_hash", "String"),
+    ("tx_hash", "String"),
+    ("log_index", "UInt64"),
+    ("address", "String"),
+    ("topics", "Array(String)"),
+    ("data", "String"),
+];
+
+const POOL_TWAPS_COLUMNS: &[(&str, &str)] = &[
+    ("chain_id", "UInt64"),
+    ("schema_version", "UInt16"),
+    ("pool_address", "String"),
+    ("window_seconds", "UInt32"),
+    ("window_start", "DateTime64(3)"),
+    ("window_end", "DateTime64(3)"),
+    ("twap_price", "Float64"),
+    ("n_swaps", "UInt64"),
+];
+
+const UNISWAP_CANDLES_1M_COLUMNS: &[(&str, &str)] = &[
+    ("chain_id", "UInt64"),
+    ("schema_version", "UInt16"),
+    ("pool_address", "String"),
+    ("minute", "DateTime64(3)"),
+    ("open", "Float64"),
+    ("high", "Float64"),
+    ("low", "Float64"),
+    ("close", "Float64"),
+    ("volume0", "Float64"),
+    ("volume1", "Float64"),
+    ("volume_usd", "Nullable(Float64)"),
+    ("swaps", "UInt64"),
+];
+
+pub const SCHEMAS: [TableSchema; 14] = [
+    TableSchema {
+        table: "uniswap_swaps",
+        columns: UNISWAP_SWAPS_COLUMNS,
+        // One row per swap once merged, see batch::stamp_insert_version
+        engine: "ReplacingMergeTree(insert_version)",
+        partition_by: Some("toYYYYMM(timestamp)"),
+        order_by: "(chain_id, pool_address, tx_hash, log_index)",
+    },
+    TableSchema {
+        table: "uniswap_mints",
+        columns: UNISWAP_MINTS_COLUMNS,
+        engine: "MergeTree()",
+        partition_by: Some("toYYYYMM(timestamp)"),
+        order_by: "(chain_id, pool_address, block_number, log_index)",
+    },
+    TableSchema {
+        table: "uniswap_burns",
+        columns: UNISWAP_BURNS_COLUMNS,
+        engine: "MergeTree()",
+        partition_by: Some("toYYYYMM(timestamp)"),
+        order_by: "(chain_id, pool_address, block_number, log_index)",
+    },
+    TableSchema {
+        table: "uniswap_collects",
+        columns: UNISWAP_COLLECTS_COLUMNS,
+        engine: "MergeTree()",
+        partition_by: Some("toYYYYMM(timestamp)"),
+        order_by: "(chain_id, pool_address, block_number, log_index)",
+    },
+    TableSchema {
+        table: "uniswap_flashes",
+        columns: UNISWAP_FLASHES_COLUMNS,
+        engine: "MergeTree()",
+        partition_by: Some("toYYYYMM(timestamp)"),
+        order_by: "(chain_id, pool_address, block_number, log_index)",
+    },
+    TableSchema {
+        table: "pool_initializations",
+        columns: POOL_INITIALIZATIONS_COLUMNS,
+        engine: "MergeTree()",
+        partition_by: None,
+        order_by: "(chain_id, pool_address)",
+    },
+    TableSchema {
+        table: "pools",
+        columns: POOLS_COLUMNS,
+        engine: "MergeTree()",
+        partition_by: None,
+        order_by: "(chain_id, pool_address)",
+    },
+    TableSchema {
+        table: "positions_events",
+        columns: POSITIONS_EVENTS_COLUMNS,
+        engine: "MergeTree()",
+        partition_by: Some("toYYYYMM(timestamp)"),
+        order_by: "(chain_id, pool_address, block_number, log_index)",
+    },
+    TableSchema {
+        table: "protocol_fees",
+        columns: PROTOCOL_FEES_COLUMNS,
+        engine: "MergeTree()",
+        partition_by: Some("toYYYYMM(timestamp)"),
+        order_by: "(chain_id, pool_address, block_number, log_index)",
+    },
+    TableSchema {
+        table: "reorged_swaps",
+        columns: REORGED_SWAPS_COLUMNS,
+        engine: "MergeTree()",
+        partition_by: Some("toYYYYMM(detected_at)"),
+        order_by: "(chain_id, block_number, tx_hash, log_index)",
+    },
+    TableSchema {
+        table: "suspect_swaps",
+        columns: SUSPECT_SWAPS_COLUMNS,
+        engine: "MergeTree()",
+        partition_by: Some("toYYYYMM(timestamp)"),
+        order_by: "(chain_id, pool_address, block_number, log_index)",
+    },
+    TableSchema {
+        table: "pool_twaps",
+        columns: POOL_TWAPS_COLUMNS,
+        engine: "ReplacingMergeTree()",
+        partition_by: Some("toYYYYMM(window_start)"),
+        order_by: "(chain_id, pool_address, window_seconds, window_start)",
+    },
+    TableSchema {
+        table: "uniswap_candles_1m",
+        columns: UNISWAP_CANDLES_1M_COLUMNS,
+        engine: "ReplacingMergeTree()",
+        partition_by: Some("toYYYYMM(minute)"),
+        order_by: "(chain_id, pool_address, minute)",
+    },
+    TableSchema {
+        table: "indexer_errors",
+        columns: INDEXER_ERRORS_COLUMNS,
+        engine: "MergeTree()",
+        partition_by: Some("toYYYYMM(detected_at)"),
+        order_by: "(chain_id, category, detected_at)",
+    },
+];
+
+pub fn schema(table: &str) -> Option<&'static TableSchema> {
+    SCHEMAS.iter().find(|s| s.table == table)
+}
+
+// Swap columns added since the original (timestamp, tx_hash, pool_address, sender,
+// price_usd, liquidity, decimals_shift) table
+const SWAP_COLUMNS_V1: [(&str, &str); 27] = [
+    ("ingested_at", "DateTime64(3)"),
+    ("block_number", "UInt64"),
+    ("transaction_index", "UInt64"),
+    ("log_index", "UInt64"),
+    ("recipient", "String"),
+    ("amount0_raw", "String"),
+    ("amount1_raw", "String"),
+    ("amount0", "Float64"),
+    ("amount1", "Float64"),
+    ("price_token0_in_token1", "Float64"),
+    ("price_token1_in_token0", "Float64"),
+    ("volume_usd", "Nullable(Float64)"),
+    ("direction", "LowCardinality(String)"),
+    ("sqrt_price_x96", "Nullable(String)"),
+    ("tick", "Nullable(Int32)"),
+    ("fee_tier", "UInt32"),
+    ("token0_symbol", "LowCardinality(String)"),
+    ("token1_symbol", "LowCardinality(String)"),
+    ("pair", "LowCardinality(String)"),
+    ("gas_used", "Nullable(UInt64)"),
+    ("gas_price_gwei", "Nullable(Float64)"),
+    ("tx_from", "Nullable(String)"),
+    ("protocol", "LowCardinality(String)"),
+    ("dex", "LowCardinality(String)"),
+    ("protocol_fees_token0", "Nullable(String)"),
+    ("protocol_fees_token1", "Nullable(String)"),
+    ("schema_version", "UInt16"),
+];
+
+pub fn migrations() -> Vec<Migration> {
+    let mut all = Vec::new();
+
+    // v1: every table gets chain_id and schema_version, swaps catch up on their columns
+    for table in TABLES {
+        all.push(Migration { version: 1, table, column: "chain_id", ty: "UInt64" });
+        if table != "uniswap_swaps" {
+            all.push(Migration { version: 1, table, column: "schema_version", ty: "UInt16" });
+        }
+    }
+    for (column, ty) in SWAP_COLUMNS_V1 {
+        all.push(Migration { version: 1, table: "uniswap_swaps", column, ty });
+    }
+
+    // v2: exact price string, price_usd NULL instead of 0.0 on conversion failure
+    all.push(Migration { version: 2, table: "uniswap_swaps", column: "price_exact", ty: "String" });
+    all.push(Migration { version: 2, table: "uniswap_swaps", column: "price_usd", ty: "Nullable(Float64)" });
+
+    // v3: firehose rows from unresolved pools have no adjusted amounts or prices
+    for column in ["amount0", "amount1", "price_token0_in_token1", "price_token1_in_token0"] {
+        all.push(Migration { version: 3, table: "uniswap_swaps", column, ty: "Nullable(Float64)" });
+    }
+
+    // v4: block hash on swaps, so reorged rows can be told apart
+    all.push(Migration { version: 4, table: "uniswap_swaps", column: "block_hash", ty: "String" });
+
+    // v5: mints and burns get their block, for checkpoints and confirmation depth
+    for table in ["uniswap_mints", "uniswap_burns"] {
+        all.push(Migration { version: 5, table, column: "block_number", ty: "UInt64" });
+    }
+
+    // v6: initializations without a price get NULL instead of 0.0
+    all.push(Migration { version: 6, table: "pool_initializations", column: "price_usd", ty: "Nullable(Float64)" });
+
+    // v7: swaps whose price disagrees with their tick are flagged
+    all.push(Migration { version: 7, table: "uniswap_swaps", column: "price_check_failed", ty: "Bool" });
+
+    // v8: every event carries its log index, batches are written in chain order
+    for table in TABLES.into_iter().filter(|t| !["uniswap_swaps", "reorged_swaps", "suspect_swaps", "pool_twaps", "uniswap_candles_1m"].contains(t)) {
+        all.push(Migration { version: 8, table, column: "log_index", ty: "UInt64" });
+    }
+
+    // v9: realized price of the swap and its distance from the pool price
+    for column in ["exec_price", "price_impact_bps"] {
+        all.push(Migration { version: 9, table: "uniswap_swaps", column, ty: "Nullable(Float64)" });
+    }
+
+    // v10: how far each swap moved its pool's price
+    all.push(Migration { version: 10, table: "uniswap_swaps", column: "price_move_bps", ty: "Nullable(Float64)" });
+
+    // v11: smoothed price
+    all.push(Migration { version: 11, table: "uniswap_swaps", column: "price_ema", ty: "Nullable(Float64)" });
+
+    // v12: LP fee of the swap in its input token and in USD
+    all.push(Migration { version: 12, table: "uniswap_swaps", column: "fee_amount", ty: "Nullable(Float64)" });
+    all.push(Migration { version: 12, table: "uniswap_swaps", column: "fee_usd", ty: "Nullable(Float64)" });
+
+    // v13: INVERT_PRICE pools are flagged, their prices are quoted the other way round
+    all.push(Migration { version: 13, table: "uniswap_swaps", column: "price_inverted", ty: "Bool" });
+
+    // v14: PRICE_DECIMAL, price_usd without the f64 rounding
+    all.push(Migration { version: 14, table: "uniswap_swaps", column: "price_usd_decimal", ty: "Nullable(Decimal(38, 18))" });
+
+    // v15: liquidity as a number, NULL instead of '' for V2 (see CONVERSIONS)
+    all.push(Migration { version: 15, table: "uniswap_swaps", column: "liquidity", ty: "Nullable(UInt128)" });
+
+    // v16: version for ReplacingMergeTree dedup, rows written before it are 0 and lose to any copy
+    all.push(Migration { version: 16, table: "uniswap_swaps", column: "insert_version", ty: "UInt64" });
+
+    // v17: every event row gets its block hash, the confirmation buffer evicts by (block, hash)
+    for table in ["uniswap_mints", "uniswap_burns", "uniswap_flashes", "pool_initializations", "pools", "positions_events"] {
+        all.push(Migration { version: 17, table, column: "block_hash", ty: "String" });
+    }
+
+    // v18: an amount that doesn't fit an f64 is NULL instead of 0.0
+    for table in ["uniswap_burns", "uniswap_collects", "uniswap_flashes", "positions_events"] {
+        for column in ["amount0", "amount1"] {
+            all.push(Migration { version: 18, table, column, ty: "Nullable(Float64)" });
+        }
+    }
+    for column in ["fee0", "fee1"] {
+        all.push(Migration { version: 18, table: "uniswap_flashes", column, ty: "Nullable(Float64)" });
+    }
+
+    // v19: price_exact NULL without a quote price instead of '' or token0 per token1 (see CONVERSIONS)
+    all.push(Migration { version: 19, table: "uniswap_swaps", column: "price_exact", ty: "Nullable(String)" });
+
+    all
+}
+
+// The last migration of each column of `table`, so a type a later version replaced isn't
+// applied on the way
+pub fn latest_migrations<'a>(migrations: &'a [Migration], table: &str) -> Vec<&'a Migration> {
+    let mut latest: Vec<&Migration> = Vec::new();
+    for m in migrations.iter().filter(|m| m.table == table && m.version <= SCHEMA_VERSION) {
+        match latest.iter_mut().find(|l| l.column == m.column) {
+            Some(l) if l.version <= m.version => *l = m,
+            Some(_) => {}
+            None => latest.push(m),
+        }
+    }
+    latest
+}
+
+// Statements run before a MODIFY COLUMN the cast alone can't do, {table} is the TABLE_NAMES name.
+// liquidity: '' (V2 rows) doesn't parse as a UInt128, it becomes NULL first. price_exact: rows
+// without a price_usd held '' or the token0-per-token1 fallback, neither a USD price
+pub const CONVERSIONS: [(&str, &str, &[&str]); 2] = [
+    (
+        "uniswap_swaps",
+        "liquidity",
+        &[
+            "ALTER TABLE {table} MODIFY COLUMN liquidity Nullable(String)",
+            "ALTER TABLE {table} UPDATE liquidity = NULL WHERE liquidity = '' SETTINGS mutations_sync = 2",
+        ],
+    ),
+    (
+        "uniswap_swaps",
+        "price_exact",
+        &[
+            "ALTER TABLE {table} MODIFY COLUMN price_exact Nullable(String)",
+            "ALTER TABLE {table} UPDATE price_exact = NULL WHERE price_usd IS NULL SETTINGS mutations_sync = 2",
+        ],
+    ),
+];
+
+// Brings the tables up to SCHEMA_VERSION, refuses to run against a newer schema. A missing
+// table is created with CREATE_TABLES (or --migrate); otherwise it's only a warning, unless
+// TABLE_NAMES renamed it: that's most likely a typo
+pub async fn migrate(client: &Client, tables: &TableNames, create: bool) -> Result<()> {
+    client
+        .query("CREATE TABLE IF NOT EXISTS schema_migrations (version UInt16, applied_at DateTime64(3)) ENGINE = MergeTree() ORDER BY version")
+        .execute()
+        .await
+        .wrap_err("Failed to create schema_migrations")?;
+
+    let live = live_version(client).await?;
+    if live > SCHEMA_VERSION {
+        eyre::bail!(
+            "ClickHouse schema is at version {} but this binary only understands up to {}, upgrade the indexer",
+            live,
+            SCHEMA_VERSION
+        );
+    }
+
+    let migrations = migrations();
+    for schema in &SCHEMAS {
+        let table = tables.get(schema.table);
+        let columns = live_columns(client, table).await?;
+
+        if columns.is_empty() && create {
+            info!("🧱 Creating table {} (v{})", table, SCHEMA_VERSION);
+            client
+                .query(&schema.create_statement(table))
+                .execute()
+                .await
+                .wrap_err_with(|| format!("Failed to create {}", table))?;
+            continue;
+        }
+        if columns.is_empty() && tables.is_renamed(schema.table) {
+            eyre::bail!("Table {} (TABLE_NAMES, for {}) does not exist, create it with CREATE_TABLES=true", table, schema.table);
+        }
+        if columns.is_empty() {
+            warn!("⚠️ Table {} does not exist, set CREATE_TABLES=true or create it from the README schema", table);
+            continue;
+        }
+
+        let millis = millis_columns(schema, &columns);
+        if !millis.is_empty() {
+            warn!("⚠️ {} stores {} as Int64 millis, run convert-timestamps to make them DateTime64(3)", table, millis.join(", "));
+        }
+
+        // Missing columns are added; a type is only changed where a migration changed it
+        let latest = latest_migrations(&migrations, schema.table);
+        for (column, ty) in schema.columns {
+            let m = latest.iter().find(|m| m.column == *column);
+            let action = match columns.iter().find(|(name, _)| name == column) {
+                None => "ADD COLUMN IF NOT EXISTS",
+                Some((_, live)) if live != ty && m.is_some() => "MODIFY COLUMN",
+                Some(_) => continue,
+            };
+
+            let version = m.map_or(String::new(), |m| format!(" (v{})", m.version));
+            info!("🧱 Migrating {}: {} {} {}{}", table, action, column, ty, version);
+            let conversion = CONVERSIONS.iter().find(|(t, c, _)| *t == schema.table && c == column);
+            if action == "MODIFY COLUMN" && let Some((_, _, statements)) = conversion {
+                for statement in statements.iter() {
+                    client
+                        .query(&statement.replace("{table}", table))
+                        .execute()
+                        .await
+                        .wrap_err_with(|| format!("Failed to prepare {}.{} for {}", table, column, ty))?;
+                }
+            }
+            client
+                .query(&format!("ALTER TABLE {} {} {} {}", table, action, column, ty))
+                .execute()
+                .await
+                .wrap_err_with(|| format!("Failed to migrate {}.{}", table, column))?;
+        }
+    }
+
+    if live < SCHEMA_VERSION {
+        client
+            .query("INSERT INTO schema_migrations VALUES (?, now64(3))")
+            .bind(SCHEMA_VERSION)
+            .execute()
+            .await?;
+        info!("✅ Schema migrated from v{} to v{}", live, SCHEMA_VERSION);
+    }
+
+    Ok(())
+}
+
+async fn live_version(client: &Client) -> Result<u16> {
+    Ok(client.query("SELECT max(version) FROM schema_migrations").fetch_one().await?)
+}
+
+// (name, type) of a table's columns, empty when it doesn't exist
+async fn live_columns(client: &Client, table: &str) -> Result<Vec<(String, String)>> {
+    client
+        .query("SELECT name, type FROM system.columns WHERE database = currentDatabase() AND table = ? ORDER BY position")
+        .bind(table)
+        .fetch_all()
+        .await
+        .wrap_err_with(|| format!("Failed to read the columns of {}", table))
+}
+
+// How a live table differs from its TableSchema
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Drift {
+    MissingTable,
+    MissingColumn { column: String, expected: String },
+    WrongType { column: String, expected: String, live: String },
+    // Not written by the indexer; harmless as long as it has a default
+    ExtraColumn { column: String, live: String },
+}
+
+impl Drift {
+    // Missing tables and columns or a wrong type break inserts, an extra column doesn't
+    pub fn is_breaking(&self) -> bool {
+        !matches!(self, Drift::ExtraColumn { .. })
+    }
+}
+
+pub fn schema_drift(schema: &TableSchema, live: &[(String, String)]) -> Vec<Drift> {
+    if live.is_empty() {
+        return vec![Drift::MissingTable];
+    }
+
+    let mut drift = Vec::new();
+    for (column, expected) in schema.columns {
+        match live.iter().find(|(name, _)| name == column) {
+            None => drift.push(Drift::MissingColumn { column: column.to_string(), expected: expected.to_string() }),
+            Some((_, ty)) if ty != expected => {
+                drift.push(Drift::WrongType { column: column.to_string(), expected: expected.to_string(), live: ty.clone() })
+            }
+            Some(_) => {}
+        }
+    }
+    for (name, ty) in live {
+        if !schema.columns.iter().any(|(column, _)| column == name) {
+            drift.push(Drift::ExtraColumn { column: name.clone(), live: ty.clone() });
+        }
+    }
+    drift
+}
+
+// `verify-schema`: every table against SCHEMAS, fails on drift that would break inserts
+pub async fn verify_schema(client: &Client, tables: &TableNames) -> Result<()> {
+    let live = live_version(client).await.unwrap_or(0);
+    match live.cmp(&SCHEMA_VERSION) {
+        std::cmp::Ordering::Equal => info!("✅ schema_migrations is at v{}", live),
+        std::cmp::Ordering::Less => warn!("⚠️ schema_migrations is at v{}, this binary migrates to v{}", live, SCHEMA_VERSION),
+        std::cmp::Ordering::Greater => warn!("⚠️ schema_migrations is at v{}, newer than this binary (v{})", live, SCHEMA_VERSION),
+    }
+
+    let mut breaking = 0;
+    for schema in &SCHEMAS {
+        let table = tables.get(schema.table);
+        let drift = schema_drift(schema, &live_columns(client, table).await?);
+        if drift.is_empty() {
+            info!("✅ {}: {} columns as expected", table, schema.columns.len());
+        }
+        for d in &drift {
+            match d {
+                Drift::MissingTable => error!("❌ {}: table does not exist", table),
+                Drift::MissingColumn { column, expected } => error!("❌ {}: missing column {} {}", table, column, expected),
+                Drift::WrongType { column, expected, live } => {
+                    error!("❌ {}: column {} is {}, expected {}", table, column, live, expected)
+                }
+                Drift::ExtraColumn { column, live } => warn!("⚠️ {}: extra column {} {}", table, column, live),
+            }
+        }
+        breaking += drift.iter().filter(|d| d.is_breaking()).count();
+    }
+
+    if breaking > 0 {
+        eyre::bail!("{} schema difference(s) would break inserts, --migrate adds missing tables and columns", breaking);
+    }
+    Ok(())
+}
+
+// Columns an older schema stored as Int64 Unix millis that are DateTime64(3) now
+fn millis_columns(schema: &TableSchema, live: &[(String, String)]) -> Vec<&'static str> {
+    schema
+        .columns
+        .iter()
+        .filter(|(column, ty)| *ty == "DateTime64(3)" && live.iter().any(|(name, live)| name == column && live == "Int64"))
+        .map(|(column, _)| *column)
+        .collect()
+}
+
+// INSERT ... SELECT copying `from` into `to`, a table created from `schema`, with the Int64
+// millis columns converted. Columns `from` doesn't have get their defaults. None when there
+// is nothing to convert
+pub fn timestamp_conversion(schema: &TableSchema, live: &[(String, String)], from: &str, to: &str) -> Option<String> {
+    let millis = millis_columns(schema, live);
+    if millis.is_empty() {
+        return None;
+    }
+
+    let copied: Vec<&str> = schema.columns.iter().map(|(column, _)| *column).filter(|c| live.iter().any(|(name, _)| name == c)).collect();
+    let values: Vec<String> = copied
+        .iter()
+        .map(|c| match millis.contains(c) {
+            true => format!("fromUnixTimestamp64Milli({})", c),
+            false => c.to_string(),
+        })
+        .collect();
+    Some(format!("INSERT INTO {} ({}) SELECT {} FROM {}", to, copied.join(", "), values.join(", "), from))
+}
+
+// `convert-timestamps`: tables written before timestamps were DateTime64(3) are copied into
+// new ones and swapped in, the original stays as <table>_int64. Run it with the indexer stopped
+pub async fn convert_timestamps(client: &Client, tables: &TableNames) -> Result<()> {
+    let mut converted = 0;
+    for schema in &SCHEMAS {
+        let table = tables.get(schema.table);
+        let (copy, backup) = (format!("{}_converted", table), format!("{}_int64", table));
+        let live = live_columns(client, table).await?;
+        let Some(insert) = timestamp_conversion(schema, &live, table, &copy) else { continue };
+
+        info!("🕰️ Converting {} to DateTime64(3) timestamps...", table);
+        // Left over by an interrupted run
+        client.query(&format!("DROP TABLE IF EXISTS {}", copy)).execute().await?;
+        client.query(&schema.create_statement(&copy)).execute().await.wrap_err_with(|| format!("Failed to create {}", copy))?;
+        client.query(&insert).execute().await.wrap_err_with(|| format!("Failed to copy {} into {}", table, copy))?;
+
+        let rows: u64 = client.query(&format!("SELECT count() FROM {}", table)).fetch_one().await?;
+        let copied: u64 = client.query(&format!("SELECT count() FROM {}", copy)).fetch_one().await?;
+        if rows != copied {
+            eyre::bail!("{} has {} rows but {} were copied into {}, nothing renamed", table, rows, copied, copy);
+        }
+
+        client
+            .query(&format!("RENAME TABLE {} TO {}, {} TO {}", table, backup, copy, table))
+            .execute()
+            .await
+            .wrap_err_with(|| format!("Failed to swap {} in for {}", copy, table))?;
+        info!("✅ {}: {} rows converted, the original is kept as {}, drop it once checked", table, rows, backup);
+        converted += 1;
+    }
+
+    if converted == 0 {
+        info!("✅ No Int64 timestamps left to convert");
+    }
+    Ok(())
+}
@@ -0,0 +1,185 @@
+use eyre::Result;
+use std::path::PathBuf;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tracing::info;
+
+use crate::records::SwapRecord;
+
+// Defaults for NATS_SUBJECT_PREFIX / NATS_BUFFER
+pub const DEFAULT_NATS_SUBJECT_PREFIX: &str = "swaps";
+pub const DEFAULT_NATS_BUFFER: usize = 10_000;
+
+// NATS_URL and the rest. Publishing needs the nats cargo feature, a dry run doesn't
+#[derive(Debug, Clone)]
+pub struct NatsSettings {
+    // Comma-separated in NATS_URL, the client moves between them on reconnects
+    pub servers: Vec<String>,
+    pub subject_prefix: String,
+    // NATS_CREDS_FILE: a .creds file (JWT and nkey seed)
+    pub creds_file: Option<PathBuf>,
+    // NATS_BUFFER: messages waiting for the publisher task, past that the writer waits
+    pub buffer: usize,
+    // NATS_DRY_RUN / --nats-dry-run: log subjects and payloads instead of publishing
+    pub dry_run: bool,
+}
+
+impl Default for NatsSettings {
+    fn default() -> Self {
+        Self {
+            servers: Vec::new(),
+            subject_prefix: DEFAULT_NATS_SUBJECT_PREFIX.to_string(),
+            creds_file: None,
+            buffer: DEFAULT_NATS_BUFFER,
+            dry_run: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NatsMessage {
+    pub subject: String,
+    // Nats-Msg-Id, JetStream drops a retried publish it already has (within the stream's
+    // duplicate window)
+    pub id: String,
+    pub payload: String,
+}
+
+// <prefix>.<chain_id>.<pool_address>, one subject per pool so consumers can filter on it
+pub fn nats_message(prefix: &str, swap: &SwapRecord) -> NatsMessage {
+    NatsMessage {
+        subject: format!("{}.{}.{}", prefix, swap.chain_id, swap.pool_address),
+        id: format!("{}:{}:{}", swap.chain_id, swap.tx_hash, swap.log_index),
+        payload: serde_json::to_string(swap).expect("Records serialize to JSON"),
+    }
+}
+
+// The writer's end. Unlike KAFKA_BROKERS this one pushes back: while the server is unreachable
+// the buffer fills, the batch waits for room and the channel backs up like it does for ClickHouse
+#[derive(Debug, Clone)]
+pub struct NatsSink {
+    tx: mpsc::Sender<NatsMessage>,
+    prefix: String,
+}
+
+impl NatsSink {
+    pub fn new(tx: mpsc::Sender<NatsMessage>, prefix: &str) -> Self {
+        Self { tx, prefix: prefix.to_string() }
+    }
+
+    pub async fn publish(&self, swaps: &[SwapRecord]) {
+        for swap in swaps {
+            if self.tx.send(nats_message(&self.prefix, swap)).await.is_err() {
+                return;
+            }
+        }
+    }
+}
+
+// Starts the publisher task (or the dry run). It ends once every NatsSink is gone and each
+// message it took is acknowledged, so awaiting it is the shutdown flush
+pub async fn start(settings: &NatsSettings) -> Result<(NatsSink, JoinHandle<()>)> {
+    let (tx, rx) = mpsc::channel(settings.buffer.max(1));
+    let sink = NatsSink::new(tx, &settings.subject_prefix);
+    if settings.dry_run {
+        info!("🛰️ NATS dry run: logging the {}.<chain_id>.<pool> messages instead of publishing", settings.subject_prefix);
+        return Ok((sink, tokio::spawn(dry_run(rx))));
+    }
+    #[cfg(feature = "nats")]
+    {
+        let jetstream = publisher::connect(settings).await?;
+        Ok((sink, tokio::spawn(publisher::run(jetstream, rx))))
+    }
+    #[cfg(not(feature = "nats"))]
+    {
+        eyre::bail!("NATS_URL needs a build with the nats feature (cargo build --features nats), NATS_DRY_RUN works without")
+    }
+}
+
+async fn dry_run(mut rx: mpsc::Receiver<NatsMessage>) {
+    while let Some(message) = rx.recv().await {
+        info!("🛰️ {} [{}] {}", message.subject, message.id, message.payload);
+    }
+}
+
+#[cfg(feature = "nats")]
+mod publisher {
+    use async_nats::jetstream::{self, context::Publish};
+    use async_nats::{ConnectOptions, Event};
+    use eyre::{Result, WrapErr};
+    use futures_util::stream::{FuturesUnordered, StreamExt};
+    use std::time::Duration;
+    use tokio::sync::mpsc;
+    use tracing::{info, warn};
+
+    use super::{NatsMessage, NatsSettings};
+    use crate::metrics;
+
+    // Publishes waiting for their ack at once
+    const MAX_IN_FLIGHT: usize = 256;
+    // A publish that got no ack is retried, doubling up to the cap
+    const RETRY_DELAY: Duration = Duration::from_millis(500);
+    const MAX_RETRY_DELAY: Duration = Duration::from_secs(30);
+
+    // The client reconnects by itself, forever; a server that is down at startup is retried too
+    pub async fn connect(settings: &NatsSettings) -> Result<jetstream::Context> {
+        let mut options = ConnectOptions::new()
+            .name("uniswap-indexer")
+            .retry_on_initial_connect()
+            .max_reconnects(None)
+            .event_callback(|event| async move {
+                match event {
+                    Event::Connected => info!("🛰️ NATS connected"),
+                    Event::Disconnected => warn!("⚠️ NATS disconnected, reconnecting"),
+                    other => warn!("⚠️ NATS: {}", other),
+                }
+            });
+        if let Some(path) = &settings.creds_file {
+            options = options.credentials_file(path).await.wrap_err_with(|| format!("Failed to read {}", path.display()))?;
+        }
+        let client = options.connect(&settings.servers).await.wrap_err_with(|| format!("Failed to connect to NATS at {:?}", settings.servers))?;
+        info!("🛰️ Publishing swaps to NATS JetStream at {}", settings.servers.join(","));
+        Ok(jetstream::new(client))
+    }
+
+    // Up to MAX_IN_FLIGHT publishes wait for their ack; a full window stops the channel reads,
+    // which is where the writer's backpressure comes from. A retried message can land after
+    // later ones of its pool, consumers order by (block_number, log_index)
+    pub async fn run(jetstream: jetstream::Context, mut rx: mpsc::Receiver<NatsMessage>) {
+        let mut in_flight = FuturesUnordered::new();
+        let mut open = true;
+        while open || !in_flight.is_empty() {
+            tokio::select! {
+                message = rx.recv(), if open && in_flight.len() < MAX_IN_FLIGHT => match message {
+                    Some(message) => in_flight.push(publish(&jetstream, message)),
+                    None => {
+                        open = false;
+                        if !in_flight.is_empty() {
+                            info!("🛰️ Waiting for {} NATS acks", in_flight.len());
+                        }
+                    }
+                },
+                Some(()) = in_flight.next(), if !in_flight.is_empty() => metrics::NATS_PUBLISHED.inc(),
+            }
+        }
+    }
+
+    // Until JetStream acknowledges it, same message id each time
+    async fn publish(jetstream: &jetstream::Context, message: NatsMessage) {
+        let mut delay = RETRY_DELAY;
+        loop {
+            let publish = Publish::build().payload(message.payload.clone().into()).message_id(&message.id);
+            let error = match jetstream.send_publish(message.subject.clone(), publish).await {
+                Ok(ack) => match ack.await {
+                    Ok(_) => return,
+                    Err(e) => e,
+                },
+                Err(e) => e,
+            };
+            metrics::NATS_RETRIES.inc();
+            warn!("⚠️ NATS publish to {} failed: {}, retrying in {:?}", message.subject, error, delay);
+            tokio::time::sleep(delay).await;
+            delay = (delay * 2).min(MAX_RETRY_DELAY);
+        }
+    }
+}
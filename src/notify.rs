@@ -0,0 +1,248 @@
+use alloy::transports::http::reqwest::Client;
+use eyre::Result;
+use lru::LruCache;
+use std::collections::HashMap;
+use std::future::Future;
+use std::num::NonZeroUsize;
+use std::sync::{Mutex, OnceLock};
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tracing::{info, warn};
+
+use crate::metrics;
+use crate::records::SwapRecord;
+
+// ALERT_MIN_USD without a bare value
+pub const DEFAULT_ALERT_MIN_USD: f64 = 1_000_000.0;
+
+// Messages waiting per transport, past that they are dropped (and counted as failed)
+const CAPACITY: usize = 100;
+// Transactions remembered for deduplication
+const SEEN_TXS: usize = 1_000;
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+// ALERT_MIN_USD=1000000,0x<pool>=5000000: a bare value applies to every other pool
+#[derive(Debug, Clone, PartialEq)]
+pub struct AlertThresholds {
+    pub default: f64,
+    pub pools: HashMap<String, f64>,
+}
+
+impl Default for AlertThresholds {
+    fn default() -> Self {
+        Self { default: DEFAULT_ALERT_MIN_USD, pools: HashMap::new() }
+    }
+}
+
+impl AlertThresholds {
+    pub fn get(&self, pool: &str) -> f64 {
+        self.pools.get(pool).copied().unwrap_or(self.default)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct TelegramSettings {
+    pub bot_token: String,
+    pub chat_id: String,
+}
+
+// Large swap alerts: TELEGRAM_BOT_TOKEN + TELEGRAM_CHAT_ID and/or DISCORD_WEBHOOK_URL
+#[derive(Debug, Clone)]
+pub struct NotifySettings {
+    pub thresholds: AlertThresholds,
+    pub telegram: Option<TelegramSettings>,
+    pub discord: Option<String>,
+}
+
+// Where alert messages go
+pub trait Notifier: Send + Sync + 'static {
+    fn name(&self) -> &'static str;
+
+    fn send(&self, text: &str) -> impl Future<Output = Result<()>> + Send;
+}
+
+pub struct Telegram {
+    client: Client,
+    // Has the bot token in it, never logged
+    url: String,
+    chat_id: String,
+}
+
+impl Telegram {
+    pub fn new(client: Client, settings: &TelegramSettings) -> Self {
+        Self { client, url: format!("https://api.telegram.org/bot{}/sendMessage", settings.bot_token), chat_id: settings.chat_id.clone() }
+    }
+}
+
+impl Notifier for Telegram {
+    fn name(&self) -> &'static str {
+        "telegram"
+    }
+
+    fn send(&self, text: &str) -> impl Future<Output = Result<()>> + Send {
+        let body = serde_json::json!({ "chat_id": self.chat_id, "text": text, "disable_web_page_preview": true });
+        post(&self.client, &self.url, body)
+    }
+}
+
+pub struct Discord {
+    client: Client,
+    // The webhook URL is its own secret too
+    url: String,
+}
+
+impl Discord {
+    pub fn new(client: Client, url: &str) -> Self {
+        Self { client, url: url.to_string() }
+    }
+}
+
+impl Notifier for Discord {
+    fn name(&self) -> &'static str {
+        "discord"
+    }
+
+    fn send(&self, text: &str) -> impl Future<Output = Result<()>> + Send {
+        post(&self.client, &self.url, serde_json::json!({ "content": text }))
+    }
+}
+
+// Errors leave the URL out
+async fn post(client: &Client, url: &str, body: serde_json::Value) -> Result<()> {
+    let response = client.post(url).json(&body).send().await.map_err(|e| eyre::eyre!("{}", e.without_url()))?;
+    let status = response.status();
+    if !status.is_success() {
+        let text = response.text().await.unwrap_or_default();
+        eyre::bail!("HTTP {}: {}", status, text.chars().take(200).collect::<String>());
+    }
+    Ok(())
+}
+
+// Picks the swaps worth an alert: at or over their pool's threshold, one per transaction
+pub struct LargeSwaps {
+    thresholds: AlertThresholds,
+    seen: LruCache<String, ()>,
+}
+
+impl LargeSwaps {
+    pub fn new(thresholds: AlertThresholds) -> Self {
+        Self { thresholds, seen: LruCache::new(NonZeroUsize::new(SEEN_TXS).unwrap()) }
+    }
+
+    // The message for a swap that gets an alert. Later legs of an alerted transaction (a
+    // routed trade through several pools) get none
+    pub fn check(&mut self, swap: &SwapRecord) -> Option<String> {
+        let usd = swap.volume_usd.filter(|usd| *usd >= self.thresholds.get(&swap.pool_address))?;
+        if self.seen.put(swap.tx_hash.clone(), ()).is_some() {
+            return None;
+        }
+        Some(message(swap, usd))
+    }
+}
+
+// "🐳 $2.3M WETH→USDC on 0x88e6… at $3,412, tx 0xabcd…", sold token first
+pub fn message(swap: &SwapRecord, usd: f64) -> String {
+    // amount0 is from the pool's side: positive means token0 was sold into it
+    let (sold, bought) = match swap.amount0_raw.starts_with('-') {
+        false => (&swap.token0_symbol, &swap.token1_symbol),
+        true => (&swap.token1_symbol, &swap.token0_symbol),
+    };
+    let price = swap.price_usd.map(|p| format!(" at ${}", price_text(p))).unwrap_or_default();
+    format!("🐳 ${} {}→{} on {}{}, tx {}", compact_usd(usd), sold, bought, short(&swap.pool_address), price, short(&swap.tx_hash))
+}
+
+// 2.3M, 850.0K, 1.2B
+pub fn compact_usd(usd: f64) -> String {
+    match usd.abs() {
+        v if v >= 1e9 => format!("{:.1}B", usd / 1e9),
+        v if v >= 1e6 => format!("{:.1}M", usd / 1e6),
+        v if v >= 1e3 => format!("{:.1}K", usd / 1e3),
+        _ => format!("{:.0}", usd),
+    }
+}
+
+// 3,412 from 100 up, 2 decimals from 1, 4 significant digits below
+pub fn price_text(price: f64) -> String {
+    if price >= 100.0 {
+        let digits = format!("{:.0}", price);
+        let mut text = String::new();
+        for (i, c) in digits.chars().enumerate() {
+            if i > 0 && (digits.len() - i) % 3 == 0 {
+                text.push(',');
+            }
+            text.push(c);
+        }
+        text
+    } else if price >= 1.0 {
+        format!("{:.2}", price)
+    } else if price > 0.0 {
+        format!("{:.*}", (3 - price.log10().floor() as i32).max(0) as usize, price)
+    } else {
+        price.to_string()
+    }
+}
+
+fn short(hex: &str) -> String {
+    match hex.get(..6) {
+        Some(start) if hex.len() > 6 => format!("{}…", start),
+        _ => hex.to_string(),
+    }
+}
+
+struct Hook {
+    swaps: Mutex<LargeSwaps>,
+    transports: Vec<(&'static str, mpsc::Sender<String>)>,
+}
+
+static HOOK: OnceLock<Hook> = OnceLock::new();
+
+// Called for each swap next to webhooks::report_swap. Never waits: a backed up transport drops the message
+pub fn report(swap: &SwapRecord) {
+    let Some(hook) = HOOK.get() else { return };
+    let Some(text) = hook.swaps.lock().unwrap().check(swap) else { return };
+    for (name, tx) in &hook.transports {
+        if tx.try_send(text.clone()).is_err() {
+            metrics::NOTIFICATIONS.with_label_values(&[name, "failed"]).inc();
+            warn!("⚠️ {} alert queue is full, dropping the alert for tx {}", name, swap.tx_hash);
+        }
+    }
+}
+
+// Starts one sending task per transport, once per process
+pub fn start(settings: &NotifySettings) -> Result<()> {
+    let client = Client::builder().timeout(REQUEST_TIMEOUT).build()?;
+    let (telegram_tx, telegram_rx) = mpsc::channel(CAPACITY);
+    let (discord_tx, discord_rx) = mpsc::channel(CAPACITY);
+    let mut transports = Vec::new();
+    if settings.telegram.is_some() {
+        transports.push(("telegram", telegram_tx));
+    }
+    if settings.discord.is_some() {
+        transports.push(("discord", discord_tx));
+    }
+    let names: Vec<&str> = transports.iter().map(|(name, _)| *name).collect();
+    info!("🐳 Swaps from ${} go to {}", compact_usd(settings.thresholds.default), names.join(" and "));
+    if HOOK.set(Hook { swaps: Mutex::new(LargeSwaps::new(settings.thresholds.clone())), transports }).is_err() {
+        return Ok(());
+    }
+    if let Some(telegram) = &settings.telegram {
+        tokio::spawn(run(Telegram::new(client.clone(), telegram), telegram_rx));
+    }
+    if let Some(discord) = &settings.discord {
+        tokio::spawn(run(Discord::new(client, discord), discord_rx));
+    }
+    Ok(())
+}
+
+// One message at a time, in order. A failed one is logged and dropped
+pub async fn run<N: Notifier>(notifier: N, mut rx: mpsc::Receiver<String>) {
+    while let Some(text) = rx.recv().await {
+        match notifier.send(&text).await {
+            Ok(()) => metrics::NOTIFICATIONS.with_label_values(&[notifier.name(), "sent"]).inc(),
+            Err(e) => {
+                metrics::NOTIFICATIONS.with_label_values(&[notifier.name(), "failed"]).inc();
+                warn!("⚠️ {} alert failed: {}", notifier.name(), e);
+            }
+        }
+    }
+}
@@ -0,0 +1,410 @@
+use alloy::{
+    primitives::{keccak256, Address, B256, FixedBytes, U256},
+    providers::Provider,
+    rpc::types::TransactionRequest,
+    sol_types::{SolCall, SolValue},
+};
+use eyre::Result;
+use std::collections::VecDeque;
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{info, warn};
+
+use crate::abi::{position_manager, uniswap_v4, IUniswapV3Factory, IERC20, IUniswapV3Pool};
+use crate::rpc::http_provider;
+use crate::config::{IndexerConfig, PositionTracking, UNISWAP_V3_POOL_INIT_CODE_HASH};
+
+// Token decimals of the pool
+#[derive(Debug, Clone, Copy)]
+pub struct PoolDecimals {
+    pub token0: u8,
+    pub token1: u8,
+}
+
+impl PoolDecimals {
+    // Shift used by calculate_price
+    pub fn diff(&self) -> i32 {
+        (self.token0 as i32) - (self.token1 as i32)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Protocol {
+    V2,
+    V3,
+    V4,
+}
+
+impl Protocol {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Protocol::V2 => "v2",
+            Protocol::V3 => "v3",
+            Protocol::V4 => "v4",
+        }
+    }
+}
+
+// Which deployment's event layout a pool emits
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Dex {
+    Uniswap,
+    Pancake,
+}
+
+impl Dex {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Dex::Uniswap => "uniswap",
+            Dex::Pancake => "pancake",
+        }
+    }
+}
+
+// How a pool is identified on-chain: its own contract, or a PoolId inside the V4 PoolManager
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PoolRef {
+    Address(Address),
+    Id(B256),
+}
+
+impl fmt::Display for PoolRef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PoolRef::Address(addr) => write!(f, "{}", addr),
+            PoolRef::Id(id) => write!(f, "{}", id),
+        }
+    }
+}
+
+// Per-pool state kept by the indexer
+#[derive(Debug, Clone)]
+pub struct PoolInfo {
+    pub protocol: Protocol,
+    pub dex: Dex,
+    pub meta: Arc<PoolMeta>,
+    // Side price_usd is quoted in: QUOTE_TOKEN, else a stablecoin, else a token with a
+    // Chainlink feed. None leaves price_usd NULL
+    pub quote: Option<QuoteSide>,
+    // INVERT_PRICE: quote flipped from the automatic pick, price_usd and volume_usd are then
+    // in the quote token itself rather than dollars
+    pub inverted: bool,
+    // PRICE_DECIMAL: price_usd_decimal is written next to price_usd
+    pub price_decimal: bool,
+    // Where a non-stable quote token's USD price comes from, scales price_usd and volume_usd
+    pub quote_usd: Option<UsdSource>,
+    // REFERENCE_POOL: its price_usd goes to the PriceBoard
+    pub reference: bool,
+    // Copied onto every row
+    pub chain_id: u64,
+    // V2 only: reserves from the latest Sync, used to price the next Swap
+    pub reserves: Option<(U256, U256)>,
+    // Latest swap prices, for the sanity check's median
+    pub recent_prices: VecDeque<f64>,
+}
+
+impl PoolInfo {
+    pub fn new(spec: PoolSpec, meta: Arc<PoolMeta>, config: &IndexerConfig) -> Self {
+        let has_feed = |token: Address| config.chainlink.as_ref().is_some_and(|c| c.feeds.contains_key(&token));
+        let reference = config.reference.as_ref();
+        let usd_source = |token: Address| {
+            if config.stablecoins.contains(&token) {
+                None
+            } else if has_feed(token) {
+                Some(UsdSource::Chainlink(token))
+            } else {
+                reference.filter(|r| r.token == token && r.spec.pool != spec.pool).map(|r| UsdSource::Reference(r.max_age))
+            }
+        };
+        let quote = if !meta.resolved {
+            None
+        } else if let Some(quote) = config.quote_tokens.side(spec.pool, &meta) {
+            Some(quote)
+        } else if config.stablecoins.contains(&meta.token0) {
+            Some(QuoteSide::Token0)
+        } else if config.stablecoins.contains(&meta.token1) {
+            Some(QuoteSide::Token1)
+        } else if has_feed(meta.token0) {
+            Some(QuoteSide::Token0)
+        } else if has_feed(meta.token1) {
+            Some(QuoteSide::Token1)
+        } else if usd_source(meta.token0).is_some() {
+            Some(QuoteSide::Token0)
+        } else if usd_source(meta.token1).is_some() {
+            Some(QuoteSide::Token1)
+        } else {
+            None
+        };
+        // Without a quote side the base is token1, inverting that makes it token0
+        let inverted = meta.resolved && config.invert_price.contains(&spec.pool);
+        let quote = if inverted { Some(quote.map_or(QuoteSide::Token1, QuoteSide::other)) } else { quote };
+        let quote_usd = quote
+            .filter(|_| !inverted)
+            .and_then(|side| usd_source(if side == QuoteSide::Token0 { meta.token0 } else { meta.token1 }));
+
+        // Its price_usd has to be in dollars already, or the board would feed on itself
+        let is_reference = reference.is_some_and(|r| r.spec.pool == spec.pool);
+        if is_reference && (quote.is_none() || quote_usd.is_some() || inverted) {
+            warn!("⚠️ REFERENCE_POOL {} has no stablecoin side, not used as the reference", spec.pool);
+        }
+        Self {
+            protocol: spec.protocol,
+            dex: spec.dex,
+            meta,
+            quote,
+            inverted,
+            price_decimal: config.price_decimal,
+            quote_usd,
+            reference: is_reference && quote.is_some() && quote_usd.is_none() && !inverted,
+            chain_id: config.chain_id,
+            reserves: None,
+            recent_prices: VecDeque::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QuoteSide {
+    Token0,
+    Token1,
+}
+
+impl QuoteSide {
+    pub fn other(self) -> Self {
+        match self {
+            QuoteSide::Token0 => QuoteSide::Token1,
+            QuoteSide::Token1 => QuoteSide::Token0,
+        }
+    }
+}
+
+// USD price of a non-stable quote token: its Chainlink feed, or the reference pool's
+// price on the PriceBoard with the age it is good for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UsdSource {
+    Chainlink(Address),
+    Reference(Duration),
+}
+
+// Static pool metadata, fetched once per pool
+#[derive(Debug, Clone)]
+pub struct PoolMeta {
+    pub token0: Address,
+    pub token1: Address,
+    pub decimals: PoolDecimals,
+    // Hundredths of a bip, e.g. 500 = 0.05%
+    pub fee: u32,
+    pub tick_spacing: i32,
+    pub token0_symbol: String,
+    pub token1_symbol: String,
+    // False for firehose pools whose metadata couldn't be fetched
+    pub resolved: bool,
+}
+
+impl PoolMeta {
+    // Placeholder for a pool we still index raw, without decimals or symbols
+    pub fn unresolved() -> Self {
+        Self {
+            token0: Address::ZERO,
+            token1: Address::ZERO,
+            decimals: PoolDecimals { token0: 0, token1: 0 },
+            fee: 0,
+            tick_spacing: 0,
+            token0_symbol: String::new(),
+            token1_symbol: String::new(),
+            resolved: false,
+        }
+    }
+
+    // "WETH/USDC 0.05%": base/quote like price_usd. Without a quote side token1 comes first,
+    // the orientation of price_token1_in_token0
+    pub fn pair(&self, quote: Option<QuoteSide>) -> String {
+        if !self.resolved {
+            return String::new();
+        }
+        let (base, quote) = match quote {
+            Some(QuoteSide::Token1) => (&self.token0_symbol, &self.token1_symbol),
+            _ => (&self.token1_symbol, &self.token0_symbol),
+        };
+        format!("{}/{} {}%", base, quote, self.fee as f64 / 10_000.0)
+    }
+}
+
+// V2 pairs have no fee() getter, the fee is fixed
+pub const UNISWAP_V2_FEE: u32 = 3000;
+
+// Pool as configured in POOL_ADDRESS
+#[derive(Debug, Clone, Copy)]
+pub struct PoolSpec {
+    pub protocol: Protocol,
+    pub dex: Dex,
+    pub pool: PoolRef,
+}
+
+// Same syntax parse_pool_spec reads
+impl fmt::Display for PoolSpec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let prefix = match (self.protocol, self.dex) {
+            (Protocol::V2, _) => "v2",
+            (Protocol::V3, Dex::Pancake) => "pancake",
+            (Protocol::V3, Dex::Uniswap) => "v3",
+            (Protocol::V4, _) => "v4",
+        };
+        write!(f, "{}:{}", prefix, self.pool)
+    }
+}
+
+// func: get tokens, decimals and fee tier
+pub async fn fetch_pool_meta(http_url: &str, pool_addr: Address, protocol: Protocol) -> Result<PoolMeta> {
+    let provider = http_provider(http_url)?;
+
+    // V2 pairs share the token0()/token1() getters
+    let pool_contract = IUniswapV3Pool::new(pool_addr, provider.clone());
+
+    let t0_raw = pool_contract.token0().call().await?.0;
+    let t1_raw = pool_contract.token1().call().await?.0;
+
+    let t0_addr = Address::from(t0_raw);
+    let t1_addr = Address::from(t1_raw);
+
+    info!("🔍 Token0: {:?}, Token1: {:?}", t0_addr, t1_addr);
+
+    let t0_contract = IERC20::new(t0_addr, provider.clone());
+    let t1_contract = IERC20::new(t1_addr, provider.clone());
+
+    let d0 = t0_contract.decimals().call().await?;
+    let d1 = t1_contract.decimals().call().await?;
+
+    info!("📊 Decimals: T0={}, T1={}", d0, d1);
+
+    let (s0, n0) = fetch_token_labels(&provider, t0_addr).await;
+    let (s1, n1) = fetch_token_labels(&provider, t1_addr).await;
+
+    info!("🏷️ Tokens: {} ({}), {} ({})", s0, n0, s1, n1);
+
+    let (fee, tick_spacing) = match protocol {
+        Protocol::V2 => (UNISWAP_V2_FEE, 0),
+        _ => {
+            let fee = pool_contract.fee().call().await?;
+            let tick_spacing = pool_contract.tickSpacing().call().await?;
+            (fee.to::<u32>(), tick_spacing.as_i32())
+        }
+    };
+
+    info!("💸 Fee tier: {} (tick spacing {})", fee, tick_spacing);
+
+    Ok(PoolMeta {
+        token0: t0_addr,
+        token1: t1_addr,
+        decimals: PoolDecimals { token0: d0, token1: d1 },
+        fee,
+        tick_spacing,
+        token0_symbol: s0,
+        token1_symbol: s1,
+        resolved: true,
+    })
+}
+
+// V4: decimals come from the pool key's currencies, address(0) is native ETH
+pub async fn fetch_v4_pool_meta(http_url: &str, position_manager: Address, pool_id: B256) -> Result<PoolMeta> {
+    let provider = http_provider(http_url)?;
+
+    let manager = uniswap_v4::IPositionManager::new(position_manager, provider.clone());
+    let key = manager.poolKeys(FixedBytes::<25>::from_slice(&pool_id[..25])).call().await?;
+
+    if key.currency0.is_zero() && key.currency1.is_zero() {
+        eyre::bail!("PoolId {} is unknown to the position manager", pool_id);
+    }
+
+    info!("🔍 Currency0: {:?}, Currency1: {:?}", key.currency0, key.currency1);
+
+    let mut decimals = [18u8; 2];
+    for (d, currency) in decimals.iter_mut().zip([key.currency0, key.currency1]) {
+        if !currency.is_zero() {
+            *d = IERC20::new(currency, provider.clone()).decimals().call().await?;
+        }
+    }
+
+    info!("📊 Decimals: C0={}, C1={}", decimals[0], decimals[1]);
+
+    let mut symbols = [String::from("ETH"), String::from("ETH")];
+    for (sym, currency) in symbols.iter_mut().zip([key.currency0, key.currency1]) {
+        if !currency.is_zero() {
+            *sym = fetch_token_labels(&provider, currency).await.0;
+        }
+    }
+    let [s0, s1] = symbols;
+
+    info!("🏷️ Currencies: {}, {}", s0, s1);
+
+    // Dynamic-fee pools report a flag here, their swaps carry the actual fee
+    Ok(PoolMeta {
+        token0: key.currency0,
+        token1: key.currency1,
+        decimals: PoolDecimals { token0: decimals[0], token1: decimals[1] },
+        fee: key.fee.to::<u32>(),
+        tick_spacing: key.tickSpacing.as_i32(),
+        token0_symbol: s0,
+        token1_symbol: s1,
+        resolved: true,
+    })
+}
+
+// (symbol, name) of a token, a short address when metadata is missing
+pub async fn fetch_token_labels<P: Provider>(provider: &P, token: Address) -> (String, String) {
+    let symbol = fetch_token_text(provider, token, IERC20::symbolCall {}.abi_encode()).await;
+    let name = fetch_token_text(provider, token, IERC20::nameCall {}.abi_encode()).await;
+
+    let fallback = short_address(token);
+    (symbol.unwrap_or_else(|| fallback.clone()), name.unwrap_or(fallback))
+}
+
+// Raw call so string and bytes32 (MKR-style) returns both decode; reverts give None
+async fn fetch_token_text<P: Provider>(provider: &P, token: Address, calldata: Vec<u8>) -> Option<String> {
+    let tx = TransactionRequest::default().to(token).input(calldata.into());
+    let out = provider.call(tx).await.ok()?;
+
+    let text = if out.len() == 32 {
+        String::from_utf8_lossy(&out).trim_end_matches('\0').to_string()
+    } else {
+        String::abi_decode(&out).ok()?
+    };
+
+    let text = text.trim().to_string();
+    (!text.is_empty()).then_some(text)
+}
+
+// 0x1234…abcd
+pub fn short_address(addr: Address) -> String {
+    let hex = addr.to_string();
+    format!("{}…{}", &hex[..6], &hex[hex.len() - 4..])
+}
+
+pub const V3_FEE_TIERS: [u32; 4] = [100, 500, 3000, 10000];
+
+// Existing V3 pools of a pair, one per fee tier that has been deployed
+pub async fn fetch_pair_pools(http_url: &str, factory: Address, token_a: Address, token_b: Address) -> Result<Vec<(Address, u32)>> {
+    let provider = http_provider(http_url)?;
+    let factory = IUniswapV3Factory::new(factory, provider);
+
+    let mut pools = Vec::new();
+    for fee in V3_FEE_TIERS {
+        let pool = factory.getPool(token_a, token_b, fee.try_into()?).call().await?;
+        if !pool.is_zero() {
+            pools.push((pool, fee));
+        }
+    }
+    Ok(pools)
+}
+
+// tokenId -> pool address: positions() gives the pool key, the address is its CREATE2 address
+pub async fn fetch_position_pool(http_url: &str, tracking: &PositionTracking, token_id: U256) -> Result<Address> {
+    let provider = http_provider(http_url)?;
+
+    let manager = position_manager::INonfungiblePositionManager::new(tracking.manager, provider);
+    let position = manager.positions(token_id).call().await?;
+
+    let salt = keccak256((position.token0, position.token1, U256::from(position.fee)).abi_encode());
+    Ok(tracking.factory.create2(salt, UNISWAP_V3_POOL_INIT_CODE_HASH))
+}
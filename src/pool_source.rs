@@ -0,0 +1,118 @@
+use clickhouse::Client;
+use eyre::{Result, WrapErr};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use tokio::sync::watch;
+use tracing::{info, warn};
+
+use crate::config::{parse_pool_specs, PoolsTable};
+use crate::pool::{PoolRef, PoolSpec};
+use crate::storage::load_tracked_pools;
+
+// Runtime sources of the pool set: a watched file or a periodically re-read ClickHouse table
+
+pub const POOLS_FILE_POLL: Duration = Duration::from_secs(5);
+
+// POOLS_FILE: one spec per line (or comma-separated), same syntax as POOL_ADDRESSES; '#' starts a comment
+pub fn read_pools_file(path: &Path) -> Result<Vec<PoolSpec>> {
+    let text = std::fs::read_to_string(path).wrap_err_with(|| format!("Failed to read {}", path.display()))?;
+    let specs: Vec<&str> = text.lines().map(|line| line.split('#').next().unwrap_or_default()).collect();
+    parse_pool_specs(&specs.join(","))
+}
+
+// Polls the file's mtime and publishes the new pool set; unreadable or invalid
+// contents keep the previous set
+pub async fn watch_pools_file(path: PathBuf, tx: watch::Sender<Vec<PoolSpec>>) {
+    let modified = |path: &Path| -> Option<SystemTime> { std::fs::metadata(path).ok()?.modified().ok() };
+    let mut last = modified(&path);
+
+    loop {
+        tokio::time::sleep(POOLS_FILE_POLL).await;
+
+        let current = modified(&path);
+        if current == last {
+            continue;
+        }
+        last = current;
+
+        match read_pools_file(&path) {
+            Ok(specs) => {
+                info!("📄 {} changed: {} pool(s)", path.display(), specs.len());
+                if tx.send(specs).is_err() {
+                    return;
+                }
+            }
+            Err(e) => warn!("⚠️ Ignoring invalid pools file: {:?}", e),
+        }
+    }
+}
+
+// Re-reads POOLS_TABLE every `refresh`. A pool is only dropped once two reads
+// in a row miss it, so one transient empty read doesn't unsubscribe everything
+pub async fn watch_pools_table(client: Client, source: PoolsTable, refresh: Duration, tx: watch::Sender<Vec<PoolSpec>>) {
+    let mut previous_read = tx.borrow().clone();
+
+    loop {
+        tokio::time::sleep(refresh).await;
+
+        let read = match load_tracked_pools(&client, &source).await {
+            Ok(read) => read,
+            Err(e) => {
+                warn!("⚠️ Failed to reload pools from {}: {:?}", source.table, e);
+                continue;
+            }
+        };
+        if read.is_empty() {
+            warn!("⚠️ {} returned no pools, keeping the current set until the next read", source.table);
+        }
+
+        let published = tx.borrow().clone();
+        let in_read = |specs: &[PoolSpec], pool: PoolRef| specs.iter().any(|s| s.pool == pool);
+
+        // New pools right away, missing ones only when the previous read missed them too
+        let mut next = read.clone();
+        for spec in &published {
+            if !in_read(&read, spec.pool) && in_read(&previous_read, spec.pool) {
+                next.push(*spec);
+            }
+        }
+        previous_read = read;
+
+        let changed = next.len() != published.len() || next.iter().any(|s| !in_read(&published, s.pool));
+        if changed {
+            info!("🗄️ {} changed: {} pool(s)", source.table, next.len());
+            if tx.send(next).is_err() {
+                return;
+            }
+        }
+    }
+}
+
+// Indexer side: the last applied pool set and the diffs to new ones
+pub struct PoolSet {
+    rx: watch::Receiver<Vec<PoolSpec>>,
+    pub current: Vec<PoolSpec>,
+}
+
+impl PoolSet {
+    pub fn new(rx: watch::Receiver<Vec<PoolSpec>>, current: Vec<PoolSpec>) -> Self {
+        Self { rx, current }
+    }
+
+    // Waits for the next file change, returns (added, removed)
+    pub async fn changed(&mut self) -> Option<(Vec<PoolSpec>, Vec<PoolRef>)> {
+        self.rx.changed().await.ok()?;
+        let next = self.rx.borrow_and_update().clone();
+
+        let added = next.iter().filter(|s| !self.current.iter().any(|c| c.pool == s.pool)).copied().collect();
+        let removed = self
+            .current
+            .iter()
+            .filter(|c| !next.iter().any(|s| s.pool == c.pool))
+            .map(|c| c.pool)
+            .collect();
+
+        self.current = next;
+        Some((added, removed))
+    }
+}
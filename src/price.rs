@@ -0,0 +1,308 @@
+use alloy::primitives::{I256, U256, U512};
+use bigdecimal::{BigDecimal, RoundingMode};
+use num_bigint::{BigInt, Sign};
+use num_traits::{One, ToPrimitive, Zero};
+use std::fmt;
+use std::str::FromStr;
+use std::sync::LazyLock;
+
+use crate::pool::QuoteSide;
+
+pub const Q96_STR: &str = "79228162514264337593543950336";
+
+// TickMath bounds, a sqrtPriceX96 outside them can't come from a V3 or V4 pool
+pub const MIN_SQRT_RATIO: U256 = U256::from_limbs([4295128739, 0, 0, 0]);
+pub const MAX_SQRT_RATIO: U256 = U256::from_limbs([0x5d951d5263988d26, 0xefd1fc6a50648849, 0xfffd8963, 0]);
+
+// Why an event carries no price, with the raw values it was computed from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PriceError {
+    ZeroSqrtPrice,
+    SqrtPriceOutOfRange(U256),
+    // reserve0 = 0 can't be divided by, reserve1 = 0 can't be inverted
+    ZeroReserve { reserve0: U256, reserve1: U256 },
+}
+
+impl fmt::Display for PriceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PriceError::ZeroSqrtPrice => write!(f, "sqrtPriceX96 is zero"),
+            PriceError::SqrtPriceOutOfRange(p) => write!(f, "sqrtPriceX96 {} is outside the tick range", p),
+            PriceError::ZeroReserve { reserve0, reserve1 } => write!(f, "empty reserves ({}, {})", reserve0, reserve1),
+        }
+    }
+}
+
+impl std::error::Error for PriceError {}
+
+pub fn calculate_price(sqrt_price_x96: U256, decimal_diff: i32) -> Result<BigDecimal, PriceError> {
+    Ok(calculate_pair_prices(sqrt_price_x96, decimal_diff)?.token1_in_token0)
+}
+
+// Both orientations of the same sqrtPriceX96
+pub fn calculate_pair_prices(sqrt_price_x96: U256, decimal_diff: i32) -> Result<PairPrices, PriceError> {
+    if sqrt_price_x96.is_zero() {
+        return Err(PriceError::ZeroSqrtPrice);
+    }
+    if !(MIN_SQRT_RATIO..=MAX_SQRT_RATIO).contains(&sqrt_price_x96) {
+        return Err(PriceError::SqrtPriceOutOfRange(sqrt_price_x96));
+    }
+    Ok(sqrt_pair_prices_integer(sqrt_price_x96, decimal_diff)
+        .unwrap_or_else(|| sqrt_pair_prices_bigdecimal(sqrt_price_x96, decimal_diff)))
+}
+
+// Fractional digits of the integer path's results
+pub const INTEGER_PATH_SCALE: i64 = 60;
+
+// Digits either result needs for the integer path to be used, below that it's too coarse
+// to agree with the BigDecimal path once rounded to price_exact or an f64
+const INTEGER_PATH_MIN_DIGITS: usize = 30;
+
+// Both prices scaled by 10^INTEGER_PATH_SCALE with U512 math, no allocation until the
+// results become BigDecimals. None when an intermediate overflows or the result would
+// lose precision, the caller falls back
+pub fn sqrt_pair_prices_integer(sqrt_price_x96: U256, decimal_diff: i32) -> Option<PairPrices> {
+    let sqrt = U512::from_limbs_slice(sqrt_price_x96.as_limbs());
+    let squared = sqrt * sqrt;
+
+    // token1 per token0 = sqrt^2 * 10^diff / 2^192, token0 per token1 = 2^192 / (sqrt^2 * 10^diff)
+    let up = decimal_diff as i64 + INTEGER_PATH_SCALE;
+    let down = INTEGER_PATH_SCALE - decimal_diff as i64;
+    let token0_in_token1 = match up {
+        0.. => squared.checked_mul(pow10(up)?)? >> 192usize,
+        _ => (squared >> 192usize) / pow10(-up)?,
+    };
+    let q192 = U512::ONE << 192usize;
+    let token1_in_token0 = match down {
+        0.. => q192.checked_mul(pow10(down)?)? / squared,
+        _ => q192 / squared.checked_mul(pow10(-down)?)?,
+    };
+
+    let min = POW10[INTEGER_PATH_MIN_DIGITS - 1];
+    if token0_in_token1 < min || token1_in_token0 < min {
+        return None;
+    }
+    Some(PairPrices {
+        token0_in_token1: BigDecimal::new(u512_to_bigint(token0_in_token1), INTEGER_PATH_SCALE),
+        token1_in_token0: BigDecimal::new(u512_to_bigint(token1_in_token0), INTEGER_PATH_SCALE),
+    })
+}
+
+// The general path, 100 significant digits at any decimal difference
+pub fn sqrt_pair_prices_bigdecimal(sqrt_price_x96: U256, decimal_diff: i32) -> PairPrices {
+    pair_prices(raw_price(sqrt_price_x96), decimal_diff)
+}
+
+// Every power of ten a U512 holds
+static POW10: LazyLock<Vec<U512>> =
+    LazyLock::new(|| std::iter::successors(Some(U512::ONE), |p| p.checked_mul(U512::from(10u64))).collect());
+
+fn pow10(exp: i64) -> Option<U512> {
+    POW10.get(usize::try_from(exp).ok()?).copied()
+}
+
+// Through a stack buffer, the BigInt is the only allocation
+fn u512_to_bigint(value: U512) -> BigInt {
+    let mut digits = [0u32; 16];
+    for (i, limb) in value.as_limbs().iter().enumerate() {
+        digits[2 * i] = *limb as u32;
+        digits[2 * i + 1] = (*limb >> 32) as u32;
+    }
+    BigInt::from_slice(Sign::Plus, &digits)
+}
+
+// (sqrtPriceX96 / 2^96)^2 = raw token1/token0
+fn raw_price(sqrt_price_x96: U256) -> BigDecimal {
+    let sqrt_price = u256_to_decimal(sqrt_price_x96) / &*Q96;
+    &sqrt_price * &sqrt_price
+}
+
+static Q96: LazyLock<BigDecimal> = LazyLock::new(|| BigDecimal::from_str(Q96_STR).expect("Q96_STR is a decimal"));
+
+// Every difference two u8 decimals can have
+const MAX_SHIFT: i32 = u8::MAX as i32;
+
+// 10^diff for diff in -MAX_SHIFT..=MAX_SHIFT, a pool's diff never changes
+static SHIFTS: LazyLock<Vec<BigDecimal>> =
+    LazyLock::new(|| (-MAX_SHIFT..=MAX_SHIFT).map(|diff| BigDecimal::new(BigInt::one(), -(diff as i64))).collect());
+
+// V2: the raw price is simply reserve1 / reserve0
+pub fn calculate_price_v2(reserve0: U256, reserve1: U256, decimal_diff: i32) -> Result<BigDecimal, PriceError> {
+    Ok(calculate_pair_prices_v2(reserve0, reserve1, decimal_diff)?.token1_in_token0)
+}
+
+pub fn calculate_pair_prices_v2(reserve0: U256, reserve1: U256, decimal_diff: i32) -> Result<PairPrices, PriceError> {
+    if reserve0.is_zero() || reserve1.is_zero() {
+        return Err(PriceError::ZeroReserve { reserve0, reserve1 });
+    }
+
+    Ok(pair_prices(u256_to_decimal(reserve1) / u256_to_decimal(reserve0), decimal_diff))
+}
+
+// Decimal-adjusted prices in both directions
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PairPrices {
+    // token1 per token0
+    pub token0_in_token1: BigDecimal,
+    // token0 per token1, what calculate_price returns
+    pub token1_in_token0: BigDecimal,
+}
+
+pub fn pair_prices(price_raw: BigDecimal, decimal_diff: i32) -> PairPrices {
+    let token0_in_token1 = shift_price(price_raw, decimal_diff);
+    let token1_in_token0 = invert(&token0_in_token1);
+    PairPrices { token0_in_token1, token1_in_token0 }
+}
+
+// Raw token1/token0 price -> decimal-adjusted token0 per token1
+pub fn adjust_price(price_raw: BigDecimal, decimal_diff: i32) -> BigDecimal {
+    invert(&shift_price(price_raw, decimal_diff))
+}
+
+// Raw token1/token0 price -> decimal-adjusted token1 per token0
+fn shift_price(price_raw: BigDecimal, decimal_diff: i32) -> BigDecimal {
+    if decimal_diff == 0 {
+        return price_raw;
+    }
+    // 10^decimal_diff as digits plus a scale, exact for any diff
+    match (-MAX_SHIFT..=MAX_SHIFT).contains(&decimal_diff).then(|| &SHIFTS[(decimal_diff + MAX_SHIFT) as usize]) {
+        Some(shift) => price_raw * shift,
+        None => price_raw * BigDecimal::new(BigInt::one(), -(decimal_diff as i64)),
+    }
+}
+
+// Straight from the bytes, there's no parse step that could fail
+fn u256_to_bigint(value: U256) -> BigInt {
+    BigInt::from_bytes_be(Sign::Plus, &value.to_be_bytes::<32>())
+}
+
+fn u256_to_decimal(value: U256) -> BigDecimal {
+    BigDecimal::from(u256_to_bigint(value))
+}
+
+// log10 of the price step between two ticks, 1.0001
+fn tick_step_log10() -> f64 {
+    1.0001_f64.log10()
+}
+
+// Token0 per token1 at a tick, the orientation of calculate_price: 1 / (1.0001^tick * 10^(d0 - d1)).
+// BigDecimal keeps 100 significant digits over the whole ±887272 range
+pub fn price_from_tick(tick: i32, decimals0: u8, decimals1: u8) -> BigDecimal {
+    let step = BigDecimal::new(BigInt::from(10_001), 4);
+    let shift = decimals1 as i64 - decimals0 as i64;
+    step.powi(-(tick as i64)) * BigDecimal::new(BigInt::one(), -shift)
+}
+
+// The tick is floor(log_1.0001(raw price)), so both prices agree within one tick.
+// Compared in log10, neither side has to fit an f64
+pub fn price_matches_tick(prices: &PairPrices, tick: i32, decimal_diff: i32) -> bool {
+    let Some(actual) = log10(&prices.token0_in_token1) else { return false };
+    let expected = tick as f64 * tick_step_log10() + decimal_diff as f64;
+    (actual - expected).abs() <= tick_step_log10() + 1e-9
+}
+
+// None for zero or negative values
+fn log10(value: &BigDecimal) -> Option<f64> {
+    let (digits, scale) = value.as_bigint_and_exponent();
+    if digits.sign() != Sign::Plus {
+        return None;
+    }
+    // Keep the top 53 bits, the rest only moves the exponent
+    let dropped = digits.bits().saturating_sub(53);
+    let mantissa = (digits >> dropped).to_f64()?;
+    Some(mantissa.log10() + dropped as f64 * 2f64.log10() - scale as f64)
+}
+
+fn invert(price: &BigDecimal) -> BigDecimal {
+    if price.is_zero() {
+        return BigDecimal::zero();
+    }
+
+    BigDecimal::one() / price
+}
+
+// f64 of a price or volume through its 17 significant digits, enough to pick the nearest
+// f64. BigDecimal::to_f64 depends on how many digits a value carries, so the integer and
+// BigDecimal paths could differ in the last bit without this
+pub fn to_f64_rounded(value: &BigDecimal) -> Option<f64> {
+    value.with_prec(17).to_f64()
+}
+
+// Fractional digits kept in price_exact
+pub const PRICE_EXACT_SCALE: i64 = 18;
+
+// Fixed-scale plain decimal string, never scientific notation
+pub fn format_price_exact(price: &BigDecimal) -> String {
+    price.with_scale_round(PRICE_EXACT_SCALE, RoundingMode::HalfEven).to_plain_string()
+}
+
+// Largest Decimal(38, 18) magnitude in its raw units: 20 integer and 18 fractional digits
+pub const PRICE_DECIMAL_MAX: i128 = 10i128.pow(38) - 1;
+
+// Raw Decimal(38, 18) value (price × 10^18), rounded like price_exact so both read the same.
+// None when the price needs more than 20 integer digits
+pub fn to_decimal_38_18(price: &BigDecimal) -> Option<i128> {
+    let (raw, scale) = price.with_scale_round(PRICE_EXACT_SCALE, RoundingMode::HalfEven).into_bigint_and_exponent();
+    debug_assert_eq!(scale, PRICE_EXACT_SCALE);
+    raw.to_i128().filter(|raw| raw.abs() <= PRICE_DECIMAL_MAX)
+}
+
+// Raw token amount -> human-readable amount
+pub fn adjust_amount(raw: U256, decimals: u8) -> BigDecimal {
+    BigDecimal::new(u256_to_bigint(raw), decimals as i64)
+}
+
+// Same for signed swap deltas, the sign is kept
+pub fn adjust_signed_amount(raw: I256, decimals: u8) -> BigDecimal {
+    let (sign, abs) = raw.into_sign_and_abs();
+    let amount = u256_to_bigint(abs);
+    BigDecimal::new(if sign.is_negative() { -amount } else { amount }, decimals as i64)
+}
+
+// The non-quote token priced in the quote token, what price_usd holds. None without a quote side
+pub fn quoted_price(prices: &PairPrices, quote: Option<QuoteSide>) -> Option<&BigDecimal> {
+    match quote? {
+        QuoteSide::Token0 => Some(&prices.token1_in_token0),
+        QuoteSide::Token1 => Some(&prices.token0_in_token1),
+    }
+}
+
+// Realized token1 per token0 of a swap, from its decimal-adjusted amounts. None with a zero leg
+pub fn exec_price(amount0: &BigDecimal, amount1: &BigDecimal) -> Option<BigDecimal> {
+    if amount0.is_zero() || amount1.is_zero() {
+        return None;
+    }
+    Some(amount1.abs() / amount0.abs())
+}
+
+// (exec - pool) / pool in basis points, pool being token1 per token0 as well
+pub fn price_impact_bps(exec: &BigDecimal, pool: &BigDecimal) -> Option<f64> {
+    if pool.is_zero() {
+        return None;
+    }
+    to_f64_rounded(&((exec - pool) * BigDecimal::from(10_000) / pool))
+}
+
+// LP fee of a swap in its input token, the leg paid into the pool: input × fee / 1e6 with the
+// fee in hundredths of a bip. None with a zero leg or no input side
+pub fn swap_fee(amount0: &BigDecimal, amount1: &BigDecimal, fee_tier: u32) -> Option<(QuoteSide, BigDecimal)> {
+    if amount0.is_zero() || amount1.is_zero() {
+        return None;
+    }
+    let (side, input) = if amount0 > &BigDecimal::zero() {
+        (QuoteSide::Token0, amount0)
+    } else if amount1 > &BigDecimal::zero() {
+        (QuoteSide::Token1, amount1)
+    } else {
+        return None;
+    };
+    Some((side, input * BigDecimal::from(fee_tier) / BigDecimal::from(1_000_000)))
+}
+
+// Dollar notional of a swap: its stablecoin leg. None for pools without a quote side
+pub fn volume_usd(amount0: &BigDecimal, amount1: &BigDecimal, quote: Option<QuoteSide>) -> Option<BigDecimal> {
+    Some(match quote? {
+        QuoteSide::Token0 => amount0.abs(),
+        QuoteSide::Token1 => amount1.abs(),
+    })
+}
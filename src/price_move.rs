@@ -0,0 +1,44 @@
+use std::collections::HashMap;
+
+use crate::records::IndexedEvent;
+
+// Last sqrtPriceX96 seen per pool, by pool_address. Applied by the writer (see Derived),
+// so swaps are met in chain order
+#[derive(Debug, Default)]
+pub struct PriceMoves {
+    last: HashMap<String, (u64, u64, f64)>,
+}
+
+impl PriceMoves {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Fills price_move_bps of the batch's swaps. A pool's first swap only seeds it, and a swap
+    // older than the last one (a backfill behind the live stream) neither gets a move nor moves it
+    pub fn apply(&mut self, batch: &mut [IndexedEvent]) {
+        for event in batch {
+            let IndexedEvent::Swap(swap) = event else { continue };
+            let Some(sqrt) = swap.sqrt_price_x96.as_deref().and_then(|s| s.parse::<f64>().ok()).filter(|s| *s > 0.0) else {
+                continue;
+            };
+            let position = (swap.block_number, swap.log_index);
+
+            match self.last.get_mut(&swap.pool_address) {
+                Some(last) if (last.0, last.1) >= position => {}
+                Some(last) => {
+                    swap.price_move_bps = Some(price_move_bps(last.2, sqrt));
+                    *last = (position.0, position.1, sqrt);
+                }
+                None => {
+                    self.last.insert(swap.pool_address.clone(), (position.0, position.1, sqrt));
+                }
+            }
+        }
+    }
+}
+
+// The price is the square of sqrtPriceX96, the Q96 scale and decimals cancel out
+pub fn price_move_bps(previous_sqrt: f64, sqrt: f64) -> f64 {
+    ((sqrt / previous_sqrt).powi(2) - 1.0) * 10_000.0
+}
@@ -0,0 +1,472 @@
+use chrono::{DateTime, Utc};
+use clickhouse::Row;
+use serde::{Deserialize, Serialize};
+
+// Stamped into every row, bump it (and add migrations) when a record changes shape
+pub const SCHEMA_VERSION: u16 = 19;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, Row)]
+pub struct SwapRecord {
+    pub chain_id: u64,
+    pub schema_version: u16,
+    // Block time, ingested_at is when the indexer wrote the row. DateTime64(3) columns; spilled
+    // and --stdout rows carry them as Unix millis
+    #[serde(with = "clickhouse::serde::chrono::datetime64::millis")]
+    pub timestamp: DateTime<Utc>,
+    #[serde(with = "clickhouse::serde::chrono::datetime64::millis")]
+    pub ingested_at: DateTime<Utc>,
+    pub block_number: u64,
+    // Tells a reorged-out row from the same swap re-included at the same height
+    pub block_hash: String,
+    pub transaction_index: u64,
+    // (tx_hash, log_index) identifies a swap
+    pub log_index: u64,
+    pub tx_hash: String,
+    // Pool contract address, or the PoolId for V4 pools
+    pub pool_address: String,
+    pub sender: String,
+    pub recipient: String,
+    // Signed, from the pool's side: positive = token flowed into the pool
+    pub amount0_raw: String,
+    pub amount1_raw: String,
+    // Adjusted values are NULL for pools whose metadata couldn't be resolved
+    pub amount0: Option<f64>,
+    pub amount1: Option<f64>,
+    // The base token in the pool's quote token (times its Chainlink or reference pool price),
+    // NULL without a quote side, with a stale feed, or when it doesn't convert to f64. price_exact
+    // is the full value, NULL without a quote side or with a stale feed
+    pub price_usd: Option<f64>,
+    pub price_exact: Option<String>,
+    // PRICE_DECIMAL: price_usd as Decimal(38, 18), raw units (× 10^18) on the wire and in JSON.
+    // NULL when off, without a price_usd or past 20 integer digits
+    pub price_usd_decimal: Option<i128>,
+    // Decimal-adjusted, token1 per token0 and token0 per token1
+    pub price_token0_in_token1: Option<f64>,
+    pub price_token1_in_token0: Option<f64>,
+    pub volume_usd: Option<f64>,
+    // What the trader got, |amount1| / |amount0| decimal-adjusted; NULL with a zero leg
+    pub exec_price: Option<f64>,
+    // exec_price against price_token0_in_token1 (the post-swap pool price), in bps
+    pub price_impact_bps: Option<f64>,
+    // LP fee, input amount × fee_tier / 1e6 in the input token, and in USD when priced
+    pub fee_amount: Option<f64>,
+    pub fee_usd: Option<f64>,
+    // Pool price change since the pool's previous swap, filled in by the writer
+    pub price_move_bps: Option<f64>,
+    // price_usd smoothed over PRICE_EMA_HALF_LIFE, filled in by the writer
+    pub price_ema: Option<f64>,
+    pub direction: String,
+    // INVERT_PRICE was set for the pool: price_usd, volume_usd, fee_usd and direction use the
+    // other quote token, in its own units
+    pub price_inverted: bool,
+    // On-chain values the price was derived from, NULL for V2
+    pub sqrt_price_x96: Option<String>,
+    pub tick: Option<i32>,
+    // uint128, UInt128 in ClickHouse
+    pub liquidity: Option<u128>,
+    pub decimals_shift: i32,
+    // The sqrtPriceX96 price is more than a tick away from 1.0001^tick
+    pub price_check_failed: bool,
+    pub fee_tier: u32,
+    pub token0_symbol: String,
+    pub token1_symbol: String,
+    pub pair: String,
+    // From the tx receipt, NULL when it couldn't be fetched
+    pub gas_used: Option<u64>,
+    pub gas_price_gwei: Option<f64>,
+    // Transaction sender, NULL when FETCH_TX_FROM is off
+    pub tx_from: Option<String>,
+    pub protocol: String,
+    pub dex: String,
+    // PancakeSwap V3 only
+    pub protocol_fees_token0: Option<String>,
+    pub protocol_fees_token1: Option<String>,
+    // ReplacingMergeTree version, stamped by the writer per batch (batch::stamp_insert_version)
+    pub insert_version: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Row)]
+pub struct MintRecord {
+    pub chain_id: u64,
+    pub schema_version: u16,
+    pub timestamp: i64,
+    pub block_number: u64,
+    pub log_index: u64,
+    pub block_hash: String,
+    pub tx_hash: String,
+    pub pool_address: String,
+    pub owner: String,
+    pub tick_lower: i32,
+    pub tick_upper: i32,
+    pub amount: String,
+    pub amount0: String,
+    pub amount1: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Row)]
+pub struct BurnRecord {
+    pub chain_id: u64,
+    pub schema_version: u16,
+    pub timestamp: i64,
+    pub block_number: u64,
+    pub log_index: u64,
+    pub block_hash: String,
+    pub tx_hash: String,
+    pub pool_address: String,
+    pub owner: String,
+    pub tick_lower: i32,
+    pub tick_upper: i32,
+    pub amount: String,
+    pub amount0_raw: String,
+    pub amount1_raw: String,
+    pub amount0: Option<f64>,
+    pub amount1: Option<f64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Row)]
+pub struct CollectRecord {
+    pub chain_id: u64,
+    pub schema_version: u16,
+    pub timestamp: i64,
+    pub block_number: u64,
+    pub log_index: u64,
+    pub block_hash: String,
+    pub tx_hash: String,
+    pub pool_address: String,
+    pub owner: String,
+    pub recipient: String,
+    pub tick_lower: i32,
+    pub tick_upper: i32,
+    pub amount0_raw: String,
+    pub amount1_raw: String,
+    pub amount0: Option<f64>,
+    pub amount1: Option<f64>,
+}
+
+// The pool emits paid = balanceAfter - balanceBefore, i.e. paid is already
+// the fee on top of the borrowed amount, so fee0/fee1 are the adjusted paid values
+#[derive(Debug, Clone, Serialize, Deserialize, Row)]
+pub struct FlashRecord {
+    pub chain_id: u64,
+    pub schema_version: u16,
+    pub timestamp: i64,
+    pub block_number: u64,
+    pub log_index: u64,
+    pub block_hash: String,
+    pub tx_hash: String,
+    pub pool_address: String,
+    pub sender: String,
+    pub recipient: String,
+    pub amount0_raw: String,
+    pub amount1_raw: String,
+    pub paid0_raw: String,
+    pub paid1_raw: String,
+    pub amount0: Option<f64>,
+    pub amount1: Option<f64>,
+    pub fee0: Option<f64>,
+    pub fee1: Option<f64>,
+}
+
+// Price anchor emitted once when the pool is created
+#[derive(Debug, Clone, Serialize, Deserialize, Row)]
+pub struct InitializeRecord {
+    pub chain_id: u64,
+    pub schema_version: u16,
+    pub timestamp: i64,
+    pub block_number: u64,
+    pub log_index: u64,
+    pub block_hash: String,
+    pub tx_hash: String,
+    pub pool_address: String,
+    pub sqrt_price_x96: String,
+    pub tick: i32,
+    // NULL when sqrtPriceX96 gives no price
+    pub price_usd: Option<f64>,
+    pub decimals_shift: i32,
+}
+
+// SetFeeProtocol and CollectProtocol share one table, unused columns are NULL
+#[derive(Debug, Clone, Serialize, Deserialize, Row)]
+pub struct ProtocolFeeRecord {
+    pub chain_id: u64,
+    pub schema_version: u16,
+    pub timestamp: i64,
+    pub block_number: u64,
+    pub log_index: u64,
+    pub block_hash: String,
+    pub tx_hash: String,
+    pub transaction_index: u64,
+    pub pool_address: String,
+    pub event_type: String,
+    pub fee_protocol0_old: Option<u8>,
+    pub fee_protocol1_old: Option<u8>,
+    pub fee_protocol0_new: Option<u8>,
+    pub fee_protocol1_new: Option<u8>,
+    pub sender: Option<String>,
+    pub recipient: Option<String>,
+    pub amount0_raw: Option<String>,
+    pub amount1_raw: Option<String>,
+    pub amount0: Option<f64>,
+    pub amount1: Option<f64>,
+}
+
+// Row for the pools metadata table, written when discovery picks up a new pool
+#[derive(Debug, Clone, Serialize, Deserialize, Row)]
+pub struct PoolRecord {
+    pub chain_id: u64,
+    pub schema_version: u16,
+    pub timestamp: i64,
+    pub block_number: u64,
+    pub log_index: u64,
+    pub block_hash: String,
+    pub tx_hash: String,
+    pub factory_address: String,
+    pub pool_address: String,
+    pub token0: String,
+    pub token1: String,
+    pub fee: u32,
+    pub tick_spacing: i32,
+    pub decimals0: u8,
+    pub decimals1: u8,
+}
+
+// Position manager event linked to one of the indexed pools
+#[derive(Debug, Clone, Serialize, Deserialize, Row)]
+pub struct PositionEventRecord {
+    pub chain_id: u64,
+    pub schema_version: u16,
+    pub timestamp: i64,
+    pub block_number: u64,
+    pub log_index: u64,
+    pub block_hash: String,
+    pub tx_hash: String,
+    pub pool_address: String,
+    pub token_id: String,
+    pub event_type: String,
+    // Not present on Collect
+    pub liquidity: Option<String>,
+    // Only present on Collect
+    pub recipient: Option<String>,
+    pub amount0_raw: String,
+    pub amount1_raw: String,
+    pub amount0: Option<f64>,
+    pub amount1: Option<f64>,
+}
+
+// A swap log a reorg reverted. Its row, if it was written, stays in uniswap_swaps;
+// anti-join on (chain_id, block_hash, tx_hash, log_index) to leave it out
+#[derive(Debug, Clone, Serialize, Deserialize, Row)]
+pub struct ReorgedSwapRecord {
+    pub chain_id: u64,
+    pub schema_version: u16,
+    // When the removed log arrived
+    pub detected_at: i64,
+    pub block_number: u64,
+    pub block_hash: String,
+    pub tx_hash: String,
+    pub log_index: u64,
+    pub pool_address: String,
+}
+
+// A swap whose price failed the sanity check, kept with the values it was derived from
+#[derive(Debug, Clone, Serialize, Deserialize, Row)]
+pub struct SuspectSwapRecord {
+    pub chain_id: u64,
+    pub schema_version: u16,
+    pub timestamp: i64,
+    pub block_number: u64,
+    pub block_hash: String,
+    pub tx_hash: String,
+    pub log_index: u64,
+    pub pool_address: String,
+    pub price_usd: f64,
+    pub price_exact: String,
+    // NULL for V2
+    pub sqrt_price_x96: Option<String>,
+    pub tick: Option<i32>,
+    pub amount0_raw: String,
+    pub amount1_raw: String,
+    pub decimals_shift: i32,
+    // Which check it failed
+    pub reason: String,
+}
+
+// indexer_errors row: a log that didn't decode, or a row ClickHouse refused. Written by the
+// dead-letter task, not the batch
+#[derive(Debug, Clone, Serialize, Deserialize, Row)]
+pub struct ErrorRecord {
+    pub chain_id: u64,
+    pub schema_version: u16,
+    pub detected_at: i64,
+    // decode or insert
+    pub category: String,
+    pub error: String,
+    // Table a rejected row was meant for, empty for decode failures
+    pub target_table: String,
+    pub block_number: u64,
+    pub block_hash: String,
+    pub tx_hash: String,
+    pub log_index: u64,
+    // Emitting contract of the log, pool of a rejected row
+    pub address: String,
+    pub topics: Vec<String>,
+    // Log data as 0x hex, the rejected row as JSON
+    pub data: String,
+}
+
+// pool_twaps row: the time-weighted price_usd of a pool over one window, ms timestamps
+#[derive(Debug, Clone, Serialize, Deserialize, Row)]
+pub struct PoolTwapRecord {
+    pub chain_id: u64,
+    pub schema_version: u16,
+    pub pool_address: String,
+    pub window_seconds: u32,
+    pub window_start: i64,
+    pub window_end: i64,
+    pub twap_price: f64,
+    // 0 for a window whose price was carried forward
+    pub n_swaps: u64,
+}
+
+// uniswap_candles_1m row: OHLC of price_usd over one minute, volumes decimal-adjusted
+#[derive(Debug, Clone, Serialize, Deserialize, Row)]
+pub struct CandleRecord {
+    pub chain_id: u64,
+    pub schema_version: u16,
+    pub pool_address: String,
+    // Start of the minute, ms
+    pub minute: i64,
+    pub open: f64,
+    pub high: f64,
+    pub low: f64,
+    pub close: f64,
+    pub volume0: f64,
+    pub volume1: f64,
+    pub volume_usd: Option<f64>,
+    // 0 for a carried-forward minute
+    pub swaps: u64,
+}
+
+// Everything the indexer sends to the writer, cloned per sink when SINK lists several
+#[derive(Debug, Clone)]
+pub enum IndexedEvent {
+    // Boxed, swap rows are much larger than the other events
+    Swap(Box<SwapRecord>),
+    Mint(MintRecord),
+    Burn(BurnRecord),
+    Collect(CollectRecord),
+    Flash(FlashRecord),
+    Initialize(InitializeRecord),
+    Pool(PoolRecord),
+    Position(PositionEventRecord),
+    ProtocolFee(ProtocolFeeRecord),
+    Reorged(ReorgedSwapRecord),
+    Suspect(SuspectSwapRecord),
+    // Derived by the writer from the ordered swaps, not from a log
+    Twap(PoolTwapRecord),
+    Candle(CandleRecord),
+    // A reorg removed a log of this block: the confirmation buffer evicts what it
+    // holds of the block with that hash, the writer ignores it
+    Reverted { block_number: u64, block_hash: String },
+}
+
+impl IndexedEvent {
+    // ClickHouse table the event is written to, None for signals
+    pub fn table(&self) -> Option<&'static str> {
+        let table = match self {
+            IndexedEvent::Swap(_) => "uniswap_swaps",
+            IndexedEvent::Mint(_) => "uniswap_mints",
+            IndexedEvent::Burn(_) => "uniswap_burns",
+            IndexedEvent::Collect(_) => "uniswap_collects",
+            IndexedEvent::Flash(_) => "uniswap_flashes",
+            IndexedEvent::Initialize(_) => "pool_initializations",
+            IndexedEvent::Pool(_) => "pools",
+            IndexedEvent::Position(_) => "positions_events",
+            IndexedEvent::ProtocolFee(_) => "protocol_fees",
+            IndexedEvent::Reorged(_) => "reorged_swaps",
+            IndexedEvent::Suspect(_) => "suspect_swaps",
+            IndexedEvent::Twap(_) => "pool_twaps",
+            IndexedEvent::Candle(_) => "uniswap_candles_1m",
+            IndexedEvent::Reverted { .. } => return None,
+        };
+        Some(table)
+    }
+
+    // Block of the log the event was decoded from. Pair pools recorded at startup have 0
+    pub fn block_number(&self) -> u64 {
+        match self {
+            IndexedEvent::Swap(r) => r.block_number,
+            IndexedEvent::Mint(r) => r.block_number,
+            IndexedEvent::Burn(r) => r.block_number,
+            IndexedEvent::Collect(r) => r.block_number,
+            IndexedEvent::Flash(r) => r.block_number,
+            IndexedEvent::Initialize(r) => r.block_number,
+            IndexedEvent::Pool(r) => r.block_number,
+            IndexedEvent::Position(r) => r.block_number,
+            IndexedEvent::ProtocolFee(r) => r.block_number,
+            IndexedEvent::Reorged(r) => r.block_number,
+            IndexedEvent::Suspect(r) => r.block_number,
+            IndexedEvent::Twap(_) | IndexedEvent::Candle(_) => 0,
+            IndexedEvent::Reverted { block_number, .. } => *block_number,
+        }
+    }
+
+    // Chain order of the log the event was decoded from, the block time breaks ties
+    pub fn sort_key(&self) -> (u64, u64, i64) {
+        match self {
+            IndexedEvent::Swap(r) => (r.block_number, r.log_index, r.timestamp.timestamp_millis()),
+            IndexedEvent::Mint(r) => (r.block_number, r.log_index, r.timestamp),
+            IndexedEvent::Burn(r) => (r.block_number, r.log_index, r.timestamp),
+            IndexedEvent::Collect(r) => (r.block_number, r.log_index, r.timestamp),
+            IndexedEvent::Flash(r) => (r.block_number, r.log_index, r.timestamp),
+            IndexedEvent::Initialize(r) => (r.block_number, r.log_index, r.timestamp),
+            IndexedEvent::Pool(r) => (r.block_number, r.log_index, r.timestamp),
+            IndexedEvent::Position(r) => (r.block_number, r.log_index, r.timestamp),
+            IndexedEvent::ProtocolFee(r) => (r.block_number, r.log_index, r.timestamp),
+            IndexedEvent::Reorged(r) => (r.block_number, r.log_index, r.detected_at),
+            IndexedEvent::Suspect(r) => (r.block_number, r.log_index, r.timestamp),
+            IndexedEvent::Twap(r) => (0, 0, r.window_end),
+            IndexedEvent::Candle(r) => (0, 0, r.minute),
+            IndexedEvent::Reverted { block_number, .. } => (*block_number, 0, 0),
+        }
+    }
+
+    // Hash of the block the event was decoded from, None for rows no log produced
+    pub fn block_hash(&self) -> Option<&str> {
+        match self {
+            IndexedEvent::Swap(r) => Some(&r.block_hash),
+            IndexedEvent::Mint(r) => Some(&r.block_hash),
+            IndexedEvent::Burn(r) => Some(&r.block_hash),
+            IndexedEvent::Collect(r) => Some(&r.block_hash),
+            IndexedEvent::Flash(r) => Some(&r.block_hash),
+            IndexedEvent::Initialize(r) => Some(&r.block_hash),
+            IndexedEvent::Pool(r) => Some(&r.block_hash),
+            IndexedEvent::Position(r) => Some(&r.block_hash),
+            IndexedEvent::ProtocolFee(r) => Some(&r.block_hash),
+            IndexedEvent::Reorged(r) => Some(&r.block_hash),
+            IndexedEvent::Suspect(r) => Some(&r.block_hash),
+            IndexedEvent::Reverted { block_hash, .. } => Some(block_hash),
+            IndexedEvent::Twap(_) | IndexedEvent::Candle(_) => None,
+        }
+    }
+
+    // (pool_address, block_number) for events that advance the pool's checkpoint
+    pub fn checkpoint(&self) -> Option<(&str, u64)> {
+        match self {
+            IndexedEvent::Swap(r) => Some((&r.pool_address, r.block_number)),
+            IndexedEvent::Mint(r) => Some((&r.pool_address, r.block_number)),
+            IndexedEvent::Burn(r) => Some((&r.pool_address, r.block_number)),
+            IndexedEvent::Collect(r) => Some((&r.pool_address, r.block_number)),
+            IndexedEvent::Flash(r) => Some((&r.pool_address, r.block_number)),
+            IndexedEvent::Initialize(r) => Some((&r.pool_address, r.block_number)),
+            IndexedEvent::Position(r) => Some((&r.pool_address, r.block_number)),
+            IndexedEvent::ProtocolFee(r) => Some((&r.pool_address, r.block_number)),
+            IndexedEvent::Suspect(r) => Some((&r.pool_address, r.block_number)),
+            IndexedEvent::Pool(_)
+            | IndexedEvent::Reorged(_)
+            | IndexedEvent::Twap(_)
+            | IndexedEvent::Candle(_)
+            | IndexedEvent::Reverted { .. } => None,
+        }
+    }
+}
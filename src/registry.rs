@@ -0,0 +1,77 @@
+use alloy::primitives::Address;
+use lru::LruCache;
+use std::num::NonZeroUsize;
+use std::sync::{Arc, Mutex};
+use tokio::sync::{OnceCell, Semaphore};
+use tracing::{info, warn};
+
+use crate::config::IndexerConfig;
+use crate::pool::{fetch_pool_meta, fetch_v4_pool_meta, PoolMeta, PoolRef, PoolSpec};
+
+// None once a fetch failed: the pool is quarantined and its logs skipped
+type MetaCell = Arc<OnceCell<Option<Arc<PoolMeta>>>>;
+
+// Pools resolving at once, each fetch is a handful of eth_calls
+pub const META_LOOKUP_CONCURRENCY: usize = 8;
+
+// Static metadata of recently seen pools, shared between tasks. Bounded, since
+// firehose mode sees every pool on the chain
+pub struct PoolRegistry {
+    // None when replaying: only seeded pools are known
+    http_url: Option<String>,
+    position_manager: Address,
+    permits: Semaphore,
+    cells: Mutex<LruCache<PoolRef, MetaCell>>,
+}
+
+impl PoolRegistry {
+    pub fn new(config: &IndexerConfig) -> Self {
+        Self { http_url: Some(config.rpc_http_url.clone()), ..Self::offline(config) }
+    }
+
+    // Never calls the RPC, pools that weren't seeded are quarantined
+    pub fn offline(config: &IndexerConfig) -> Self {
+        Self {
+            http_url: None,
+            position_manager: config.v4.position_manager,
+            permits: Semaphore::new(META_LOOKUP_CONCURRENCY),
+            cells: Mutex::new(LruCache::new(NonZeroUsize::new(config.pool_cache_size).unwrap_or(NonZeroUsize::MIN))),
+        }
+    }
+
+    // Metadata known up front, e.g. from a capture file
+    pub fn seed(&self, pool: PoolRef, meta: PoolMeta) {
+        let cell = Arc::new(OnceCell::new_with(Some(Some(Arc::new(meta)))));
+        self.cells.lock().unwrap().put(pool, cell);
+    }
+
+    // Concurrent callers for the same pool share one fetch
+    pub async fn get_or_fetch(&self, spec: PoolSpec) -> Option<Arc<PoolMeta>> {
+        let cell = self.cells.lock().unwrap().get_or_insert(spec.pool, Default::default).clone();
+
+        cell.get_or_init(|| async {
+            let Some(http_url) = &self.http_url else {
+                warn!("⚠️ No metadata for {}, quarantining it", spec.pool);
+                return None;
+            };
+            let _permit = self.permits.acquire().await.ok()?;
+            let result = match spec.pool {
+                PoolRef::Address(addr) => fetch_pool_meta(http_url, addr, spec.protocol).await,
+                PoolRef::Id(id) => fetch_v4_pool_meta(http_url, self.position_manager, id).await,
+            };
+
+            match result {
+                Ok(meta) => {
+                    info!("📇 {} {}: decimal shift {}", spec.pool, meta.pair(None), meta.decimals.diff());
+                    Some(Arc::new(meta))
+                }
+                Err(e) => {
+                    warn!("⚠️ Metadata fetch failed for {}, quarantining it: {:?}", spec.pool, e);
+                    None
+                }
+            }
+        })
+        .await
+        .clone()
+    }
+}
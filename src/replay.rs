@@ -0,0 +1,195 @@
+use alloy::{
+    primitives::{Address, Bytes, B256},
+    rpc::types::Log,
+};
+use eyre::{Result, WrapErr};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::{LineWriter, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use tokio::sync::mpsc;
+use tracing::{info, warn};
+
+use crate::blocks::BlockTimes;
+use crate::config::{parse_pool_spec, IndexerConfig};
+use crate::derived::Derived;
+use crate::indexer::LogHandler;
+use crate::liquidity::LiquidityGate;
+use crate::pool::{PoolDecimals, PoolMeta, PoolRef, PoolSpec};
+use crate::records::IndexedEvent;
+use crate::registry::PoolRegistry;
+use crate::sink::{run_writers, Database, Secondary};
+use crate::stdout::StdoutMode;
+use crate::storage::WriterSettings;
+
+// Metadata line, so a replay needs no eth_calls. `pool` uses the POOL_ADDRESSES syntax
+#[derive(Debug, Serialize, Deserialize)]
+struct PoolLine {
+    pool: String,
+    token0: Address,
+    token1: Address,
+    decimals0: u8,
+    decimals1: u8,
+    fee: u32,
+    tick_spacing: i32,
+    token0_symbol: String,
+    token1_symbol: String,
+}
+
+// Hand-written fixtures: plain numbers, no RPC envelope
+#[derive(Debug, Deserialize)]
+struct MinimalLog {
+    address: Address,
+    topics: Vec<B256>,
+    data: Bytes,
+    block_number: u64,
+    tx_hash: B256,
+    #[serde(default)]
+    transaction_index: u64,
+    #[serde(default)]
+    log_index: u64,
+    block_timestamp: Option<u64>,
+}
+
+impl From<MinimalLog> for Log {
+    fn from(log: MinimalLog) -> Self {
+        Log {
+            inner: alloy::primitives::Log::new_unchecked(log.address, log.topics, log.data),
+            block_number: Some(log.block_number),
+            block_timestamp: log.block_timestamp,
+            transaction_hash: Some(log.tx_hash),
+            transaction_index: Some(log.transaction_index),
+            log_index: Some(log.log_index),
+            ..Default::default()
+        }
+    }
+}
+
+// One line of a capture file. The minimal shape is tried before alloy's, which
+// would otherwise accept it and drop its snake_case fields
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum CaptureLine {
+    Pool(PoolLine),
+    Minimal(MinimalLog),
+    Log(Box<Log>),
+}
+
+// --capture: every log the handler sees, plus each pool's metadata once
+pub struct Capture {
+    file: Mutex<LineWriter<File>>,
+    pools: Mutex<HashSet<PoolRef>>,
+}
+
+impl Capture {
+    pub fn create(path: &Path) -> Result<Self> {
+        let file = File::create(path).wrap_err_with(|| format!("Failed to create capture file {}", path.display()))?;
+        Ok(Self { file: Mutex::new(LineWriter::new(file)), pools: Mutex::new(HashSet::new()) })
+    }
+
+    pub fn log(&self, log: &Log) {
+        self.write_line(log);
+    }
+
+    // Unresolved pools are left out, a replay treats them the same way
+    pub fn pool(&self, spec: PoolSpec, meta: &PoolMeta) {
+        if !meta.resolved || !self.pools.lock().unwrap().insert(spec.pool) {
+            return;
+        }
+        self.write_line(&PoolLine {
+            pool: spec.to_string(),
+            token0: meta.token0,
+            token1: meta.token1,
+            decimals0: meta.decimals.token0,
+            decimals1: meta.decimals.token1,
+            fee: meta.fee,
+            tick_spacing: meta.tick_spacing,
+            token0_symbol: meta.token0_symbol.clone(),
+            token1_symbol: meta.token1_symbol.clone(),
+        });
+    }
+
+    // A failed write costs a fixture line, not the live stream
+    fn write_line<T: Serialize>(&self, value: &T) {
+        let result = serde_json::to_string(value)
+            .map_err(eyre::Report::from)
+            .and_then(|line| Ok(writeln!(self.file.lock().unwrap(), "{}", line)?));
+        if let Err(e) = result {
+            warn!("⚠️ Failed to write capture line: {:?}", e);
+        }
+    }
+}
+
+// Pool lines seed the registry wherever they are in the file (firehose pools are
+// captured after their first log). Returns the seeded pool count and the logs in file order
+fn read_capture(path: &Path, registry: &PoolRegistry) -> Result<(usize, Vec<Log>)> {
+    let contents = std::fs::read_to_string(path).wrap_err_with(|| format!("Failed to read {}", path.display()))?;
+    let mut pools = 0;
+    let mut logs = Vec::new();
+
+    for (i, line) in contents.lines().enumerate().filter(|(_, l)| !l.trim().is_empty()) {
+        let parsed: CaptureLine = serde_json::from_str(line).wrap_err_with(|| format!("{}:{}: invalid line", path.display(), i + 1))?;
+        match parsed {
+            CaptureLine::Pool(p) => {
+                let meta = PoolMeta {
+                    token0: p.token0,
+                    token1: p.token1,
+                    decimals: PoolDecimals { token0: p.decimals0, token1: p.decimals1 },
+                    fee: p.fee,
+                    tick_spacing: p.tick_spacing,
+                    token0_symbol: p.token0_symbol,
+                    token1_symbol: p.token1_symbol,
+                    resolved: true,
+                };
+                registry.seed(parse_pool_spec(&p.pool)?.pool, meta);
+                pools += 1;
+            }
+            CaptureLine::Minimal(log) => logs.push(log.into()),
+            CaptureLine::Log(log) => logs.push(*log),
+        }
+    }
+    Ok((pools, logs))
+}
+
+// --replay: the captured logs go through the same LogHandler as the live stream,
+// without any RPC. Rows go to the sink (SINK), with --stdout to stdout too or instead
+pub async fn replay(config: Arc<IndexerConfig>, path: &Path, stdout: Option<StdoutMode>) -> Result<()> {
+    let registry = Arc::new(PoolRegistry::offline(&config));
+    let (seeded, logs) = read_capture(path, &registry)?;
+    info!("⏮️ Replaying {} log(s) of {} pool(s) from {}", logs.len(), seeded, path.display());
+
+    let gate = Arc::new(LiquidityGate::new(&config)?);
+    let block_times = Arc::new(BlockTimes::offline(config.block_cache_size));
+
+    let (tx, rx) = mpsc::channel::<IndexedEvent>(config.channel_capacity);
+    let mut secondaries = Vec::new();
+    let database = match stdout {
+        Some(StdoutMode::Only) => Database::Stdout,
+        _ => {
+            for kind in &config.secondary_sinks {
+                secondaries.push(Secondary::connect(kind, &config).await?);
+            }
+            Database::connect(&config.sink, &config).await?
+        }
+    };
+    database.spawn_dead_letter(config.tables.get("indexer_errors").to_string(), config.chain_id);
+    let settings = WriterSettings { stdout: stdout == Some(StdoutMode::Also), ..WriterSettings::new(&config) };
+    let derived = Derived::new(&config);
+    let writer = tokio::spawn(run_writers(database, secondaries, rx, settings, derived, None, config.channel_capacity));
+
+    let mut handler = LogHandler::offline(&config, &registry, &gate, &block_times, tx);
+    let mut pools = HashMap::new();
+    for log in logs {
+        if !handler.handle(&mut pools, log).await {
+            eyre::bail!("Writer channel closed during replay");
+        }
+    }
+
+    // Firehose swaps may still be in their tasks, the writer ends once they are done
+    drop(handler);
+    writer.await??;
+    info!("✅ Replay done");
+    Ok(())
+}
@@ -0,0 +1,39 @@
+use alloy::{
+    providers::{DynProvider, Provider, ProviderBuilder},
+    rpc::client::ClientBuilder,
+    transports::layers::{RetryBackoffLayer, ThrottleLayer},
+};
+use eyre::Result;
+use std::sync::OnceLock;
+
+// 429 / -32005 responses are retried with exponential backoff starting here
+pub const MAX_RATE_LIMIT_RETRIES: u32 = 10;
+pub const INITIAL_BACKOFF_MS: u64 = 500;
+// Average compute units per call, how most providers express their budget
+const AVG_CALL_COST: u64 = 20;
+
+// RPC_RATE_LIMIT: one budget shared by every HTTP provider in the process, so
+// backfill workers and enrichment tasks can't exceed it together
+static THROTTLE: OnceLock<(u32, ThrottleLayer)> = OnceLock::new();
+
+pub fn set_rate_limit(requests_per_second: u32) {
+    THROTTLE.get_or_init(|| (requests_per_second, ThrottleLayer::new(requests_per_second)));
+}
+
+// HTTP provider with the shared throttle and rate-limit retries, used for all eth_calls,
+// receipts, block headers and get_logs
+pub fn http_provider(url: &str) -> Result<DynProvider> {
+    let url = url.parse()?;
+    let client = match THROTTLE.get() {
+        Some((rps, throttle)) => {
+            let retry = RetryBackoffLayer::new(MAX_RATE_LIMIT_RETRIES, INITIAL_BACKOFF_MS, *rps as u64 * AVG_CALL_COST);
+            let throttle = ThrottleLayer { throttle: throttle.throttle.clone() };
+            ClientBuilder::default().layer(retry).layer(throttle).http(url)
+        }
+        None => {
+            let retry = RetryBackoffLayer::new(MAX_RATE_LIMIT_RETRIES, INITIAL_BACKOFF_MS, u64::MAX);
+            ClientBuilder::default().layer(retry).http(url)
+        }
+    };
+    Ok(ProviderBuilder::new().connect_client(client).erased())
+}
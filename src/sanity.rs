@@ -0,0 +1,107 @@
+use std::collections::VecDeque;
+use tracing::warn;
+
+use crate::metrics;
+use crate::records::{IndexedEvent, SuspectSwapRecord, SwapRecord};
+
+// Default for PRICE_MEDIAN_WINDOW
+pub const DEFAULT_MEDIAN_WINDOW: usize = 50;
+
+// Prices a pool needs before the median applies, its first swaps only face the bounds
+pub const MIN_HISTORY: usize = 5;
+
+// Limits a swap's price_usd is held to, rows outside them go to suspect_swaps.
+// Everything is off by default
+#[derive(Debug, Clone, Default)]
+pub struct PriceSanity {
+    // PRICE_MIN / PRICE_MAX, absolute bounds
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+    // PRICE_MAX_DEVIATION_PCT as a fraction, from the median of the pool's recent prices
+    pub max_deviation: Option<f64>,
+    // PRICE_MEDIAN_WINDOW, swaps the median is taken over
+    pub window: usize,
+}
+
+impl PriceSanity {
+    pub fn enabled(&self) -> bool {
+        self.min.is_some() || self.max.is_some() || self.max_deviation.is_some()
+    }
+
+    // Why the price is rejected, None when it passes. Every price joins the history,
+    // the median shrugs off the outliers and still follows a real move
+    pub fn check(&self, history: &mut VecDeque<f64>, price: f64) -> Option<String> {
+        let reason = if let Some(min) = self.min && price < min {
+            Some(format!("below PRICE_MIN {}", min))
+        } else if let Some(max) = self.max && price > max {
+            Some(format!("above PRICE_MAX {}", max))
+        } else {
+            self.deviation(history, price)
+        };
+
+        if self.max_deviation.is_some() {
+            if history.len() == self.window {
+                history.pop_front();
+            }
+            history.push_back(price);
+        }
+        reason
+    }
+
+    fn deviation(&self, history: &VecDeque<f64>, price: f64) -> Option<String> {
+        let max_deviation = self.max_deviation?;
+        if history.len() < MIN_HISTORY {
+            return None;
+        }
+        let median = median(history);
+        if median <= 0.0 {
+            return None;
+        }
+        let deviation = (price - median).abs() / median;
+        (deviation > max_deviation)
+            .then(|| format!("{:.1}% from median {} of the last {} swaps", deviation * 100.0, median, history.len()))
+    }
+}
+
+pub fn median(values: &VecDeque<f64>) -> f64 {
+    let mut sorted: Vec<f64> = values.iter().copied().collect();
+    sorted.sort_by(f64::total_cmp);
+    let mid = sorted.len() / 2;
+    match sorted.len() {
+        0 => 0.0,
+        n if n % 2 == 0 => (sorted[mid - 1] + sorted[mid]) / 2.0,
+        _ => sorted[mid],
+    }
+}
+
+// The swap as it goes to the writer: unchanged, or quarantined in suspect_swaps.
+// Swaps without a price (unresolved pools) aren't checked
+pub fn screen(sanity: &PriceSanity, history: &mut VecDeque<f64>, record: Box<SwapRecord>) -> IndexedEvent {
+    let Some(price) = record.price_usd.filter(|_| sanity.enabled()) else {
+        return IndexedEvent::Swap(record);
+    };
+    let Some(reason) = sanity.check(history, price) else {
+        return IndexedEvent::Swap(record);
+    };
+
+    metrics::SUSPECT_SWAPS.inc();
+    warn!("🚧 Swap {}#{} quarantined, price {} {}", record.tx_hash, record.log_index, price, reason);
+    IndexedEvent::Suspect(SuspectSwapRecord {
+        chain_id: record.chain_id,
+        schema_version: record.schema_version,
+        timestamp: record.timestamp.timestamp_millis(),
+        block_number: record.block_number,
+        block_hash: record.block_hash,
+        tx_hash: record.tx_hash,
+        log_index: record.log_index,
+        pool_address: record.pool_address,
+        price_usd: price,
+        price_exact: record.price_exact.unwrap_or_default(),
+        sqrt_price_x96: record.sqrt_price_x96,
+        tick: record.tick,
+        amount0_raw: record.amount0_raw,
+        amount1_raw: record.amount1_raw,
+        decimals_shift: record.decimals_shift,
+        reason,
+    })
+}
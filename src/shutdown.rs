@@ -0,0 +1,51 @@
+use eyre::{Result, WrapErr};
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tracing::{error, info, warn};
+
+use crate::metrics;
+
+// Default for SHUTDOWN_TIMEOUT_SECONDS, Kubernetes' default grace period
+pub const DEFAULT_SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(30);
+
+// Resolves on Ctrl-C or SIGTERM
+pub async fn signal() {
+    #[cfg(unix)]
+    {
+        let mut term = match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(term) => term,
+            Err(e) => {
+                warn!("⚠️ Can't listen for SIGTERM, only Ctrl-C stops cleanly: {:?}", e);
+                let _ = tokio::signal::ctrl_c().await;
+                return;
+            }
+        };
+        tokio::select! {
+            _ = tokio::signal::ctrl_c() => info!("🛑 Ctrl-C received"),
+            _ = term.recv() => info!("🛑 SIGTERM received"),
+        }
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = tokio::signal::ctrl_c().await;
+        info!("🛑 Ctrl-C received");
+    }
+}
+
+// Waits for the writer once every sender is dropped: the confirmation buffer forwards what it
+// holds, the writer inserts its last batch and saves the checkpoints. Past the deadline the
+// process exits anyway, with an error so the exit code shows the drain didn't complete
+pub async fn drain(writer: JoinHandle<Result<()>>, timeout: Duration) -> Result<()> {
+    info!("⏳ Draining the writer (up to {:?})...", timeout);
+    match tokio::time::timeout(timeout, writer).await {
+        Ok(result) => {
+            result.wrap_err("Writer task panicked")??;
+            info!("✅ Drained, {} rows inserted since startup", metrics::ROWS_INSERTED.get());
+            Ok(())
+        }
+        Err(_) => {
+            error!("❌ Writer still busy after {:?}, exiting without its last rows", timeout);
+            eyre::bail!("Shutdown timed out after {:?}, buffered rows may be lost", timeout)
+        }
+    }
+}
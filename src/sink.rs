@@ -0,0 +1,257 @@
+use clickhouse::{Client, RowOwned, RowWrite};
+use eyre::Result;
+use serde::Serialize;
+use std::future::Future;
+use tokio::sync::mpsc;
+use tracing::{error, info};
+
+use crate::checkpoint::{CheckpointDb, Checkpoints};
+use crate::config::{IndexerConfig, TableNames};
+use crate::dead_letter;
+use crate::derived::Derived;
+use crate::failover::Endpoints;
+use crate::file_sink::{self, FileSettings, FileSink};
+use crate::migrations::migrate;
+use crate::postgres::{self, Postgres, PostgresSettings};
+use crate::records::IndexedEvent;
+use crate::stdout;
+use crate::storage::{self, connect_endpoints, AsyncInsert, WriterSettings};
+
+// SINK: where the tables are written to
+#[derive(Debug, Clone)]
+pub enum SinkKind {
+    ClickHouse,
+    Postgres(PostgresSettings),
+    File(FileSettings),
+}
+
+impl SinkKind {
+    // As listed in SINK
+    pub fn name(&self) -> &'static str {
+        match self {
+            SinkKind::ClickHouse => "clickhouse",
+            SinkKind::Postgres(_) => "postgres",
+            SinkKind::File(_) => "file",
+        }
+    }
+}
+
+// Where a batch's rows go. The writer (batching, retries, row isolation, spill replay and
+// checkpoints) is the same for every sink, only the insert differs
+pub trait Sink: Clone + Send + Sync + 'static {
+    // One insert attempt of `rows` into `table` (its TABLE_NAMES name)
+    fn write_rows<T: RowOwned + RowWrite + Serialize + Sync>(
+        &self,
+        table: &str,
+        rows: &[T],
+        dedup_token: Option<&str>,
+        async_insert: Option<AsyncInsert>,
+    ) -> impl Future<Output = Result<()>> + Send;
+
+    // Whether an insert failed on its rows, so some of them could still go in
+    fn is_row_error(&self, e: &eyre::Report) -> bool;
+
+    // Once the writer flushed its last batch
+    fn close(&self) -> impl Future<Output = Result<()>> + Send {
+        std::future::ready(Ok(()))
+    }
+}
+
+impl Sink for Endpoints {
+    fn write_rows<T: RowOwned + RowWrite + Serialize + Sync>(
+        &self,
+        table: &str,
+        rows: &[T],
+        dedup_token: Option<&str>,
+        async_insert: Option<AsyncInsert>,
+    ) -> impl Future<Output = Result<()>> + Send {
+        Endpoints::write_rows(self, table, rows, dedup_token, async_insert)
+    }
+
+    fn is_row_error(&self, e: &eyre::Report) -> bool {
+        storage::is_row_error(e)
+    }
+}
+
+// ASYNC_INSERT is refused with SINK=postgres, deduplication is the upsert's
+impl Sink for Postgres {
+    fn write_rows<T: RowOwned + RowWrite + Serialize + Sync>(
+        &self,
+        table: &str,
+        rows: &[T],
+        _dedup_token: Option<&str>,
+        _async_insert: Option<AsyncInsert>,
+    ) -> impl Future<Output = Result<()>> + Send {
+        Postgres::write_rows(self, table, rows)
+    }
+
+    fn is_row_error(&self, e: &eyre::Report) -> bool {
+        postgres::is_row_error(e)
+    }
+}
+
+// Rows are on disk once the call returns, nothing to await
+impl Sink for FileSink {
+    fn write_rows<T: RowOwned + RowWrite + Serialize + Sync>(
+        &self,
+        table: &str,
+        rows: &[T],
+        _dedup_token: Option<&str>,
+        _async_insert: Option<AsyncInsert>,
+    ) -> impl Future<Output = Result<()>> + Send {
+        std::future::ready(FileSink::write_rows(self, table, rows))
+    }
+
+    fn is_row_error(&self, e: &eyre::Report) -> bool {
+        file_sink::is_row_error(e)
+    }
+
+    // The open files are only complete once closed
+    fn close(&self) -> impl Future<Output = Result<()>> + Send {
+        std::future::ready(FileSink::close(self))
+    }
+}
+
+// The connected sink, picked by SINK. Stdout is --stdout=only, no database at all
+#[derive(Clone)]
+pub enum Database {
+    ClickHouse(Endpoints),
+    Postgres(Postgres),
+    File(FileSink),
+    Stdout,
+}
+
+impl Database {
+    pub async fn connect(kind: &SinkKind, config: &IndexerConfig) -> Result<Self> {
+        Ok(match kind {
+            SinkKind::ClickHouse => Database::ClickHouse(connect_endpoints(&config.clickhouse).await?),
+            SinkKind::Postgres(settings) => Database::Postgres(Postgres::connect(settings, &config.tables).await?),
+            SinkKind::File(settings) => Database::File(FileSink::new(settings, &config.tables)?),
+        })
+    }
+
+    // For what only ClickHouse has: POOLS_TABLE and the verify commands
+    pub fn clickhouse(&self) -> Option<Client> {
+        match self {
+            Database::ClickHouse(endpoints) => Some(endpoints.client()),
+            Database::Postgres(_) | Database::File(_) | Database::Stdout => None,
+        }
+    }
+
+    pub async fn migrate(&self, tables: &TableNames, create: bool) -> Result<()> {
+        match self {
+            Database::ClickHouse(endpoints) => migrate(&endpoints.client(), tables, create).await,
+            Database::Postgres(postgres) => postgres.migrate(create).await,
+            Database::File(files) => {
+                files.check_leftovers();
+                Ok(())
+            }
+            Database::Stdout => Ok(()),
+        }
+    }
+
+    // CHECKPOINT_STORE=clickhouse or postgres, the checkpoints live next to the rows
+    pub fn checkpoint_db(&self) -> Option<CheckpointDb> {
+        match self {
+            Database::ClickHouse(endpoints) => Some(CheckpointDb::ClickHouse(Box::new(endpoints.client()))),
+            Database::Postgres(postgres) => Some(CheckpointDb::Postgres(postgres.clone())),
+            Database::File(_) | Database::Stdout => None,
+        }
+    }
+
+    pub fn spawn_dead_letter(&self, table: String, chain_id: u64) {
+        match self {
+            Database::ClickHouse(endpoints) => dead_letter::spawn(endpoints.clone(), table, chain_id),
+            Database::Postgres(postgres) => dead_letter::spawn(postgres.clone(), table, chain_id),
+            Database::File(files) => dead_letter::spawn(files.clone(), table, chain_id),
+            // Errors would mix with the rows, they are only counted
+            Database::Stdout => {}
+        }
+    }
+
+    pub async fn run_writer(
+        self,
+        rx: mpsc::Receiver<IndexedEvent>,
+        settings: WriterSettings,
+        derived: Derived,
+        checkpoints: Option<Checkpoints>,
+    ) -> Result<()> {
+        match self {
+            Database::ClickHouse(endpoints) => storage::run_writer(endpoints, rx, settings, derived, checkpoints).await,
+            Database::Postgres(postgres) => storage::run_writer(postgres, rx, settings, derived, checkpoints).await,
+            Database::File(files) => storage::run_writer(files, rx, settings, derived, checkpoints).await,
+            Database::Stdout => stdout::run_writer(rx, derived, settings.tables).await,
+        }
+    }
+}
+
+// A sink of SINK past the first: no checkpoints, dead letters, Kafka, NATS or --stdout, and
+// its own spill directory (SPILL_DIR/<sink>) so its rows are replayed into it
+pub struct Secondary {
+    pub name: &'static str,
+    pub database: Database,
+    pub settings: WriterSettings,
+    pub derived: Derived,
+}
+
+impl Secondary {
+    pub async fn connect(kind: &SinkKind, config: &IndexerConfig) -> Result<Self> {
+        let database = Database::connect(kind, config).await?;
+        let mut settings = WriterSettings::new(config);
+        settings.spill.path = settings.spill.path.join(kind.name());
+        Ok(Self { name: kind.name(), database, settings, derived: Derived::new(config) })
+    }
+}
+
+// The writer of the first sink and, with more in SINK, one per secondary sink, each behind
+// a queue of `capacity` records. Every sink batches, retries and spills on its own: a slow or
+// failing one holds the others up only once its queue is full. Returns the first failure
+pub async fn run_writers(
+    primary: Database,
+    secondaries: Vec<Secondary>,
+    rx: mpsc::Receiver<IndexedEvent>,
+    settings: WriterSettings,
+    derived: Derived,
+    checkpoints: Option<Checkpoints>,
+    capacity: usize,
+) -> Result<()> {
+    if secondaries.is_empty() {
+        return primary.run_writer(rx, settings, derived, checkpoints).await;
+    }
+    let (tx, primary_rx) = mpsc::channel(capacity);
+    let mut queues = vec![("primary", tx)];
+    let mut writers = vec![tokio::spawn(primary.run_writer(primary_rx, settings, derived, checkpoints))];
+    for secondary in secondaries {
+        info!("🔀 Also writing to SINK={}", secondary.name);
+        let (tx, rx) = mpsc::channel(capacity);
+        queues.push((secondary.name, tx));
+        writers.push(tokio::spawn(secondary.database.run_writer(rx, secondary.settings, secondary.derived, None)));
+    }
+    fan_out(rx, queues).await;
+
+    let mut result = Ok(());
+    for writer in writers {
+        let finished = writer.await?;
+        result = result.and(finished);
+    }
+    result
+}
+
+// Hands every record to each queue, in order. A queue whose writer stopped gets no more
+// records; once every sender of `rx` is gone so are the queues, and their writers finish
+pub async fn fan_out(mut rx: mpsc::Receiver<IndexedEvent>, mut queues: Vec<(&'static str, mpsc::Sender<IndexedEvent>)>) {
+    while let Some(event) = rx.recv().await {
+        let mut i = 0;
+        while i < queues.len() {
+            if queues[i].1.send(event.clone()).await.is_ok() {
+                i += 1;
+            } else {
+                let (name, _) = queues.remove(i);
+                error!("❌ The {} writer stopped, it gets no more records", name);
+            }
+        }
+        if queues.is_empty() {
+            return;
+        }
+    }
+}
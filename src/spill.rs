@@ -0,0 +1,147 @@
+use eyre::{Result, WrapErr};
+use serde::{Deserialize, Serialize};
+use serde_json::value::RawValue;
+use std::fs;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use tracing::{error, warn};
+
+use crate::metrics;
+
+// Defaults for SPILL_DIR / SPILL_MAX_MB
+pub const DEFAULT_SPILL_DIR: &str = "spill";
+pub const DEFAULT_SPILL_MAX_BYTES: u64 = 1024 * 1024 * 1024;
+
+// SPILL_FULL_POLICY: what happens to a batch that doesn't fit under SPILL_MAX_MB
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpillPolicy {
+    // Keep what is spilled, the new rows are lost (indexer_rows_lost_total)
+    Stop,
+    // Delete the oldest files until the new batch fits
+    DropOldest,
+}
+
+// SPILL_DIR: batches whose inserts ran out of retries, one JSON lines file per batch of
+// {"table", "row"} lines (the --stdout format, default table names). The writer
+// replays them oldest first once an insert succeeds again, deleting each after its inserts
+#[derive(Debug, Clone)]
+pub struct SpillDir {
+    pub path: PathBuf,
+    pub max_bytes: u64,
+    pub policy: SpillPolicy,
+}
+
+// Files written by this process in the same millisecond still sort in write order
+static SEQUENCE: AtomicU64 = AtomicU64::new(0);
+
+impl SpillDir {
+    // Spilled batches, oldest first. Half-written .tmp files are ignored
+    pub fn files(&self) -> Result<Vec<PathBuf>> {
+        let entries = match fs::read_dir(&self.path) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e).wrap_err_with(|| format!("Failed to list {}", self.path.display())),
+        };
+        let mut files: Vec<PathBuf> = entries
+            .filter_map(|e| e.ok().map(|e| e.path()))
+            .filter(|p| p.extension().is_some_and(|ext| ext == "jsonl"))
+            .collect();
+        files.sort();
+        Ok(files)
+    }
+
+    pub fn size(&self) -> Result<u64> {
+        Ok(self.files()?.iter().filter_map(|p| fs::metadata(p).ok()).map(|m| m.len()).sum())
+    }
+
+    // Writes one batch's lines as a new file, None when the size cap kept it out. The file only
+    // gets its .jsonl name once it is complete, a crash mid-write leaves a .tmp behind
+    pub fn write(&self, lines: &[String]) -> Result<Option<PathBuf>> {
+        let bytes: u64 = lines.iter().map(|l| l.len() as u64 + 1).sum();
+        if !self.make_room(bytes)? {
+            return Ok(None);
+        }
+
+        fs::create_dir_all(&self.path).wrap_err_with(|| format!("Failed to create {}", self.path.display()))?;
+        let name = format!(
+            "{:013}-{:06}",
+            chrono::Utc::now().timestamp_millis(),
+            SEQUENCE.fetch_add(1, Ordering::Relaxed) % 1_000_000
+        );
+        let tmp = self.path.join(format!("{}.tmp", name));
+        let file = self.path.join(format!("{}.jsonl", name));
+
+        let mut out = BufWriter::new(fs::File::create(&tmp).wrap_err_with(|| format!("Failed to create {}", tmp.display()))?);
+        for line in lines {
+            writeln!(out, "{}", line)?;
+        }
+        out.into_inner().map_err(|e| e.into_error())?.sync_all()?;
+        fs::rename(&tmp, &file)?;
+        Ok(Some(file))
+    }
+
+    fn make_room(&self, bytes: u64) -> Result<bool> {
+        let mut size = self.size()?;
+        if size + bytes <= self.max_bytes {
+            return Ok(true);
+        }
+        match self.policy {
+            SpillPolicy::Stop => {
+                error!(
+                    "❌ {} is full ({} of {} bytes), not spilling {} more bytes",
+                    self.path.display(), size, self.max_bytes, bytes
+                );
+                Ok(false)
+            }
+            SpillPolicy::DropOldest => {
+                for file in self.files()? {
+                    if size + bytes <= self.max_bytes {
+                        break;
+                    }
+                    let len = fs::metadata(&file).map(|m| m.len()).unwrap_or(0);
+                    let rows = read_lines(&file).map(|l| l.len()).unwrap_or(0);
+                    fs::remove_file(&file)?;
+                    metrics::ROWS_LOST.inc_by(rows as u64);
+                    warn!("⚠️ {} is full, dropped the oldest spill {} ({} rows)", self.path.display(), file.display(), rows);
+                    size = size.saturating_sub(len);
+                }
+                Ok(size + bytes <= self.max_bytes)
+            }
+        }
+    }
+}
+
+// One spill (or --stdout) line. Serialized straight to text: a serde_json::Value can't hold the
+// u128 and i128 columns past the u64 range
+pub fn spill_line<T: Serialize>(table: &str, row: &T) -> String {
+    let row = serde_json::to_string(row).expect("Records serialize to JSON");
+    format!("{{\"table\":{},\"row\":{}}}", serde_json::Value::from(table), row)
+}
+
+#[derive(Deserialize)]
+struct SpilledLine {
+    table: String,
+    row: Box<RawValue>,
+}
+
+// A spilled batch's rows grouped by table, tables in the order they first appear. Rows stay
+// raw JSON until they are read into their record, for the same reason
+pub fn read_spill(path: &Path) -> Result<Vec<(String, Vec<Box<RawValue>>)>> {
+    let mut tables: Vec<(String, Vec<Box<RawValue>>)> = Vec::new();
+    for line in read_lines(path)? {
+        let SpilledLine { table, row } =
+            serde_json::from_str(&line).wrap_err_with(|| format!("Invalid line in {}", path.display()))?;
+        match tables.iter_mut().find(|(t, _)| *t == table) {
+            Some((_, rows)) => rows.push(row),
+            None => tables.push((table, vec![row])),
+        }
+    }
+    Ok(tables)
+}
+
+fn read_lines(path: &Path) -> Result<Vec<String>> {
+    let file = fs::File::open(path).wrap_err_with(|| format!("Failed to open {}", path.display()))?;
+    let lines: std::io::Result<Vec<String>> = BufReader::new(file).lines().filter(|l| l.as_ref().map_or(true, |l| !l.is_empty())).collect();
+    Ok(lines?)
+}
@@ -0,0 +1,71 @@
+use eyre::Result;
+use std::io::Write;
+use std::str::FromStr;
+use tokio::sync::mpsc;
+
+use crate::config::TableNames;
+use crate::derived::Derived;
+use crate::records::IndexedEvent;
+use crate::spill::spill_line;
+
+// --stdout[=only|also]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StdoutMode {
+    // Rows go to stdout as they arrive, nothing is written to the sink
+    Only,
+    // Each batch is printed before it goes to the sink
+    Also,
+}
+
+impl FromStr for StdoutMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "only" => Ok(StdoutMode::Only),
+            "also" => Ok(StdoutMode::Also),
+            _ => Err(format!("expected only or also, got '{}'", s)),
+        }
+    }
+}
+
+// One {"table", "row"} object, the row's fields named like the table's columns (the spill
+// format). None for what has no table
+pub fn json_line(tables: &TableNames, event: &IndexedEvent) -> Option<String> {
+    let table = tables.get(event.table()?);
+    Some(match event {
+        IndexedEvent::Swap(r) => spill_line(table, r),
+        IndexedEvent::Mint(r) => spill_line(table, r),
+        IndexedEvent::Burn(r) => spill_line(table, r),
+        IndexedEvent::Collect(r) => spill_line(table, r),
+        IndexedEvent::Flash(r) => spill_line(table, r),
+        IndexedEvent::Initialize(r) => spill_line(table, r),
+        IndexedEvent::Pool(r) => spill_line(table, r),
+        IndexedEvent::Position(r) => spill_line(table, r),
+        IndexedEvent::ProtocolFee(r) => spill_line(table, r),
+        IndexedEvent::Reorged(r) => spill_line(table, r),
+        IndexedEvent::Suspect(r) => spill_line(table, r),
+        IndexedEvent::Twap(r) => spill_line(table, r),
+        IndexedEvent::Candle(r) => spill_line(table, r),
+        IndexedEvent::Reverted { .. } => return None,
+    })
+}
+
+// Stdout is line-buffered (even into a pipe), every row is out once its line is written
+pub fn print(tables: &TableNames, events: &[IndexedEvent]) -> Result<()> {
+    let mut out = std::io::stdout().lock();
+    for line in events.iter().filter_map(|event| json_line(tables, event)) {
+        writeln!(out, "{}", line)?;
+    }
+    Ok(())
+}
+
+// --stdout=only: rows are printed as they arrive, no batching and no checkpoints
+pub async fn run_writer(mut rx: mpsc::Receiver<IndexedEvent>, mut derived: Derived, tables: TableNames) -> Result<()> {
+    while let Some(event) = rx.recv().await {
+        let mut rows = vec![event];
+        derived.apply(&mut rows);
+        print(&tables, &rows)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,615 @@
+use clickhouse::inserter::Quantities;
+use clickhouse::{Client, RowOwned, RowWrite};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::value::RawValue;
+use std::path::Path;
+use std::time::{Duration, Instant};
+use eyre::{Result, WrapErr};
+use tokio::sync::mpsc;
+use tracing::{error, info, warn};
+
+use crate::batch::{stamp_insert_version, Batch};
+use crate::checkpoint::Checkpoints;
+use crate::config::{parse_pool_specs, ClickhouseSettings, IndexerConfig, PoolsTable, TableNames};
+use crate::dead_letter;
+use crate::derived::Derived;
+use crate::failover::Endpoints;
+use crate::health::{self, HEALTH};
+use crate::kafka::{KafkaMode, KafkaSink};
+use crate::metrics;
+use crate::nats::NatsSink;
+use crate::pool::PoolSpec;
+use crate::records::{
+    BurnRecord, CandleRecord, CollectRecord, FlashRecord, IndexedEvent, InitializeRecord, MintRecord, PoolRecord, PoolTwapRecord,
+    PositionEventRecord, ProtocolFeeRecord, ReorgedSwapRecord, SuspectSwapRecord, SwapRecord,
+};
+use crate::sink::Sink;
+use crate::stdout;
+use crate::spill::{read_spill, spill_line, SpillDir};
+use crate::transport::{http_client, tls_cause};
+use crate::workers::run_insert_workers;
+
+// ClickHouse
+// Client for one of the CLICKHOUSE_URL endpoints
+pub fn get_clickhouse_client(settings: &ClickhouseSettings, url: &str) -> Result<Client> {
+    let client = http_client(settings, url)?.with_compression(settings.compression);
+    let client = settings.headers.iter().fold(client, |client, (name, value)| client.with_header(name, value));
+    Ok(client
+        .with_url(url)
+        .with_user(&settings.user)
+        .with_password(&settings.password)
+        .with_database(&settings.database))
+}
+
+// Clients for the CLICKHOUSE_* settings, each endpoint checked with a ping so a wrong URL,
+// password or certificate fails startup instead of the first insert. With several endpoints
+// only all of them failing does; the unreachable ones start out unhealthy
+pub async fn connect_endpoints(settings: &ClickhouseSettings) -> Result<Endpoints> {
+    let mut clients = Vec::new();
+    let mut down = Vec::new();
+    let mut first_error = None;
+    for url in &settings.urls {
+        let client = get_clickhouse_client(settings, url)?;
+        match ping(&client, settings, url).await {
+            Ok(()) => info!("🗄️ Connected to ClickHouse at {} ({}, compression {:?})", url, settings.database, settings.compression),
+            Err(e) if settings.urls.len() > 1 => {
+                warn!("⚠️ {:#}", e);
+                down.push(clients.len());
+                first_error.get_or_insert(e);
+            }
+            Err(e) => return Err(e),
+        }
+        clients.push((url.clone(), client));
+    }
+    if let Some(e) = first_error.filter(|_| down.len() == clients.len()) {
+        return Err(e.wrap_err("No ClickHouse endpoint in CLICKHOUSE_URL is reachable"));
+    }
+
+    let endpoints = Endpoints::new(clients, &settings.failover);
+    for index in down {
+        endpoints.mark_down(index);
+    }
+    Ok(endpoints)
+}
+
+// A single client, the first reachable endpoint, for the commands that only read
+pub async fn connect_clickhouse(settings: &ClickhouseSettings) -> Result<Client> {
+    Ok(connect_endpoints(settings).await?.client())
+}
+
+async fn ping(client: &Client, settings: &ClickhouseSettings, url: &str) -> Result<()> {
+    let Err(e) = client.query("SELECT 1").fetch_one::<u8>().await else { return Ok(()) };
+    // The TLS cause sits at the bottom of the hyper error chain, name it in the message
+    let tls = tls_cause(&e);
+    let hint = match &tls {
+        Some(_) => "check CLICKHOUSE_CA_FILE, or CLICKHOUSE_INSECURE_SKIP_VERIFY for a self-signed dev server",
+        None => "check CLICKHOUSE_URL/USER/PASSWORD/DATABASE",
+    };
+    let tls = tls.map(|cause| format!(", TLS error: {}", cause)).unwrap_or_default();
+    Err(eyre::Report::new(e).wrap_err(format!(
+        "ClickHouse at {} (database {}, user {}) is unreachable{}, {}",
+        url, settings.database, settings.user, tls, hint
+    )))
+}
+
+// Pool specs from POOLS_TABLE, same syntax as POOL_ADDRESSES
+pub async fn load_tracked_pools(client: &Client, source: &PoolsTable) -> Result<Vec<PoolSpec>> {
+    let rows: Vec<String> = client
+        .query(&format!("SELECT DISTINCT toString({}) FROM {}", source.column, source.table))
+        .fetch_all()
+        .await
+        .wrap_err_with(|| format!("Failed to read pools from {}", source.table))?;
+
+    parse_pool_specs(&rows.join(","))
+}
+
+// Defaults for INSERT_RETRIES / INSERT_RETRY_DELAY_MS
+pub const DEFAULT_INSERT_RETRIES: u32 = 5;
+pub const DEFAULT_INSERT_RETRY_DELAY: Duration = Duration::from_millis(500);
+// Longest wait between two attempts
+const MAX_INSERT_RETRY_DELAY: Duration = Duration::from_secs(30);
+
+// A failed insert is retried with exponential backoff; rows of an insert that never succeeds
+// go to the spill directory
+#[derive(Debug, Clone)]
+pub struct InsertRetry {
+    pub retries: u32,
+    pub base_delay: Duration,
+}
+
+impl InsertRetry {
+    // Wait before retry number `attempt` (0-based)
+    pub fn delay(&self, attempt: u32) -> Duration {
+        self.base_delay.saturating_mul(2u32.saturating_pow(attempt)).min(MAX_INSERT_RETRY_DELAY)
+    }
+}
+
+// ASYNC_INSERT: ClickHouse buffers the rows server-side (async_insert=1). With wait an insert
+// returns once the buffer reached the table; without, as soon as the server queued it, so a
+// flush failing later never shows up here and the checkpoints stay where they are
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AsyncInsert {
+    pub wait: bool,
+}
+
+// Split the batch by record type, one insert per table. A failed table doesn't
+// stop the others, the first error is returned. Each table is retried on its own, so the
+// rows that made it aren't inserted twice; the rows of tables that ran out of retries are
+// spilled together, one file for the batch
+pub async fn flush_batch<S: Sink>(sink: &S, settings: &WriterSettings, batch: &mut Vec<IndexedEvent>) -> Result<()> {
+    // A closed pipe (`| head`) stops the printing, not the inserts
+    if settings.stdout && let Err(e) = stdout::print(&settings.tables, batch) {
+        warn!("⚠️ Failed to print {} rows to stdout: {}", batch.len(), e);
+    }
+    let _timer = metrics::FLUSH_SECONDS.start_timer();
+    let mut spilled = Vec::new();
+    let mut swaps = Vec::new();
+    let mut mints = Vec::new();
+    let mut burns = Vec::new();
+    let mut collects = Vec::new();
+    let mut flashes = Vec::new();
+    let mut initializations = Vec::new();
+    let mut pools = Vec::new();
+    let mut positions = Vec::new();
+    let mut protocol_fees = Vec::new();
+    let mut reorged = Vec::new();
+    let mut suspect = Vec::new();
+    let mut twaps = Vec::new();
+    let mut candles = Vec::new();
+
+    for event in batch.drain(..) {
+        match event {
+            IndexedEvent::Swap(r) => swaps.push(*r),
+            IndexedEvent::Mint(r) => mints.push(r),
+            IndexedEvent::Burn(r) => burns.push(r),
+            IndexedEvent::Collect(r) => collects.push(r),
+            IndexedEvent::Flash(r) => flashes.push(r),
+            IndexedEvent::Initialize(r) => initializations.push(r),
+            IndexedEvent::Pool(r) => pools.push(r),
+            IndexedEvent::Position(r) => positions.push(r),
+            IndexedEvent::ProtocolFee(r) => protocol_fees.push(r),
+            IndexedEvent::Reorged(r) => reorged.push(r),
+            IndexedEvent::Suspect(r) => suspect.push(r),
+            IndexedEvent::Twap(r) => twaps.push(r),
+            IndexedEvent::Candle(r) => candles.push(r),
+            IndexedEvent::Reverted { .. } => {}
+        }
+    }
+
+    // Swaps reach NATS and Kafka once per batch: spill replays and insert retries don't publish again
+    if let Some(nats) = &settings.nats {
+        nats.publish(&swaps).await;
+    }
+    if let Some(kafka) = &settings.kafka {
+        kafka.publish(&swaps);
+        if kafka.mode == KafkaMode::Instead {
+            swaps.clear();
+        }
+    }
+
+    let results = [
+        write_rows_retrying(sink, settings, "uniswap_swaps", &mut spilled, &swaps).await,
+        write_rows_retrying(sink, settings, "uniswap_mints", &mut spilled, &mints).await,
+        write_rows_retrying(sink, settings, "uniswap_burns", &mut spilled, &burns).await,
+        write_rows_retrying(sink, settings, "uniswap_collects", &mut spilled, &collects).await,
+        write_rows_retrying(sink, settings, "uniswap_flashes", &mut spilled, &flashes).await,
+        write_rows_retrying(sink, settings, "pool_initializations", &mut spilled, &initializations).await,
+        write_rows_retrying(sink, settings, "pools", &mut spilled, &pools).await,
+        write_rows_retrying(sink, settings, "positions_events", &mut spilled, &positions).await,
+        write_rows_retrying(sink, settings, "protocol_fees", &mut spilled, &protocol_fees).await,
+        write_rows_retrying(sink, settings, "reorged_swaps", &mut spilled, &reorged).await,
+        write_rows_retrying(sink, settings, "suspect_swaps", &mut spilled, &suspect).await,
+        write_rows_retrying(sink, settings, "pool_twaps", &mut spilled, &twaps).await,
+        write_rows_retrying(sink, settings, "uniswap_candles_1m", &mut spilled, &candles).await,
+    ];
+    if !spilled.is_empty() {
+        spill(&settings.spill, &spilled);
+    }
+    results.into_iter().collect()
+}
+
+// Writes a batch's given-up rows to SPILL_DIR, counting them as lost when that fails too
+fn spill(dir: &SpillDir, lines: &[String]) {
+    match dir.write(lines) {
+        Ok(Some(file)) => {
+            metrics::ROWS_SPILLED.inc_by(lines.len() as u64);
+            error!("❌ Spilled {} rows to {}, replayed once ClickHouse takes inserts again", lines.len(), file.display());
+        }
+        Ok(None) => metrics::ROWS_LOST.inc_by(lines.len() as u64),
+        Err(e) => {
+            metrics::ROWS_LOST.inc_by(lines.len() as u64);
+            error!("❌ Failed to spill {} rows to {}: {:?}", lines.len(), dir.path.display(), e);
+        }
+    }
+}
+
+// While this retries the writer doesn't read the channel: it fills up, then the ordering stage
+// in front of it, and the log handler waits. Memory stays at CHANNEL_CAPACITY, the batch and
+// twice ENRICH_WINDOW (the stage's queue and window), plus with CONFIRMATIONS another channel
+// and the records of the unconfirmed blocks. Rows ClickHouse refuses don't count as a
+// failed attempt, they go to indexer_errors and the rest is inserted
+async fn write_rows_retrying<S: Sink, T: RowOwned + RowWrite + Serialize + Sync>(
+    sink: &S,
+    settings: &WriterSettings,
+    table: &'static str,
+    spilled: &mut Vec<String>,
+    rows: &[T],
+) -> Result<()> {
+    let name = settings.tables.get(table);
+    let retry = &settings.retry;
+    let mut attempt = 0;
+    let mut rest = rows;
+    loop {
+        match write_rows_isolating(sink, settings, table, rest).await {
+            Ok(()) => {
+                HEALTH.insert_succeeded();
+                return Ok(());
+            }
+            Err((done, _)) if attempt < retry.retries => {
+                metrics::INSERT_FAILURES.with_label_values(&[name]).inc();
+                HEALTH.insert_failed(health::now());
+                rest = &rest[done..];
+                let delay = retry.delay(attempt);
+                attempt += 1;
+                metrics::INSERT_RETRIES.inc();
+                warn!("⚠️ Insert of {} rows into {} failed (attempt {}), retrying in {:?}", rest.len(), name, attempt, delay);
+                tokio::time::sleep(delay).await;
+            }
+            Err((done, e)) => {
+                metrics::INSERT_FAILURES.with_label_values(&[name]).inc();
+                HEALTH.insert_failed(health::now());
+                rest = &rest[done..];
+                error!("❌ Gave up on {} rows for {} after {} retries", rest.len(), name, retry.retries);
+                // Spilled under the default name, TABLE_NAMES applies again on replay
+                spilled.extend(rest.iter().map(|row| spill_line(table, row)));
+                return Err(e);
+            }
+        }
+    }
+}
+
+// An insert refused for its data is split in halves until the bad rows are alone, those are
+// dead-lettered. Any other error stops it, with the number of leading rows already inserted
+// or dead-lettered so the retry only sends the rest
+async fn write_rows_isolating<S: Sink, T: RowOwned + RowWrite + Serialize + Sync>(
+    sink: &S,
+    settings: &WriterSettings,
+    table: &'static str,
+    rows: &[T],
+) -> std::result::Result<(), (usize, eyre::Report)> {
+    let name = settings.tables.get(table);
+    match sink.write_rows(name, rows, None, settings.async_insert).await {
+        Ok(()) => Ok(()),
+        Err(e) if !sink.is_row_error(&e) => Err((0, e)),
+        Err(e) if rows.len() == 1 => {
+            error!("❌ {} refused a row, sent to indexer_errors: {:#}", name, e);
+            dead_letter::report(dead_letter::rejected_row(table, &rows[0], &e));
+            Ok(())
+        }
+        Err(_) => {
+            let mid = rows.len() / 2;
+            warn!("⚠️ {} refused rows of a {}-row insert, splitting it", name, rows.len());
+            Box::pin(write_rows_isolating(sink, settings, table, &rows[..mid])).await?;
+            Box::pin(write_rows_isolating(sink, settings, table, &rows[mid..])).await.map_err(|(done, e)| (mid + done, e))
+        }
+    }
+}
+
+// ClickHouse exceptions about the data rather than the table, user or connection: parse and
+// conversion failures, type mismatches, out-of-range values, NULL into a plain column, constraints
+const ROW_ERROR_CODES: [u32; 9] = [6, 27, 33, 53, 69, 70, 117, 349, 469];
+
+// Whether an insert failed on its rows, so some of them could still go in
+pub fn is_row_error(e: &eyre::Report) -> bool {
+    e.chain().any(|cause| match cause.downcast_ref::<clickhouse::error::Error>() {
+        Some(clickhouse::error::Error::BadResponse(message)) => error_code(message).is_some_and(|code| ROW_ERROR_CODES.contains(&code)),
+        // A row serde couldn't write as RowBinary
+        Some(clickhouse::error::Error::Custom(_)) => true,
+        _ => false,
+    })
+}
+
+// "Code: 53. DB::Exception: ..." -> 53
+pub fn error_code(message: &str) -> Option<u32> {
+    let rest = &message[message.find("Code: ")? + "Code: ".len()..];
+    rest[..rest.find(|c: char| !c.is_ascii_digit()).unwrap_or(rest.len())].parse().ok()
+}
+
+// Spilled batches, oldest first, until one fails: that one and the newer ones wait for the
+// next successful flush. Each insert carries the file and table as insert_deduplication_token,
+// so a replay cut short by a crash doesn't double the rows on tables with a deduplication window.
+// Returns whether the directory is empty now
+pub async fn replay_spilled<S: Sink>(sink: &S, settings: &WriterSettings) -> bool {
+    let files = match settings.spill.files() {
+        Ok(files) => files,
+        Err(e) => {
+            warn!("⚠️ {:?}", e);
+            return false;
+        }
+    };
+    for file in files {
+        if let Err(e) = replay_spill_file(sink, &settings.tables, &file).await {
+            warn!("⚠️ Replaying {} failed, retried after the next insert: {:?}", file.display(), e);
+            return false;
+        }
+        if let Err(e) = std::fs::remove_file(&file) {
+            // Replayed again next time, the tokens keep that from duplicating
+            warn!("⚠️ Failed to delete replayed {}: {:?}", file.display(), e);
+            return false;
+        }
+        metrics::SPILL_FILES_REPLAYED.inc();
+        info!("♻️ Replayed and deleted {}", file.display());
+    }
+    true
+}
+
+async fn replay_spill_file<S: Sink>(sink: &S, tables: &TableNames, file: &Path) -> Result<()> {
+    let stem = file.file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default();
+    for (table, rows) in read_spill(file)? {
+        let token = format!("spill-{}-{}", stem, table);
+        let name = tables.get(&table);
+        match table.as_str() {
+            "uniswap_swaps" => write_spilled::<_, SwapRecord>(sink, name, rows, &token).await?,
+            "uniswap_mints" => write_spilled::<_, MintRecord>(sink, name, rows, &token).await?,
+            "uniswap_burns" => write_spilled::<_, BurnRecord>(sink, name, rows, &token).await?,
+            "uniswap_collects" => write_spilled::<_, CollectRecord>(sink, name, rows, &token).await?,
+            "uniswap_flashes" => write_spilled::<_, FlashRecord>(sink, name, rows, &token).await?,
+            "pool_initializations" => write_spilled::<_, InitializeRecord>(sink, name, rows, &token).await?,
+            "pools" => write_spilled::<_, PoolRecord>(sink, name, rows, &token).await?,
+            "positions_events" => write_spilled::<_, PositionEventRecord>(sink, name, rows, &token).await?,
+            "protocol_fees" => write_spilled::<_, ProtocolFeeRecord>(sink, name, rows, &token).await?,
+            "reorged_swaps" => write_spilled::<_, ReorgedSwapRecord>(sink, name, rows, &token).await?,
+            "suspect_swaps" => write_spilled::<_, SuspectSwapRecord>(sink, name, rows, &token).await?,
+            "pool_twaps" => write_spilled::<_, PoolTwapRecord>(sink, name, rows, &token).await?,
+            "uniswap_candles_1m" => write_spilled::<_, CandleRecord>(sink, name, rows, &token).await?,
+            other => eyre::bail!("Unknown table {} in {}", other, file.display()),
+        }
+    }
+    Ok(())
+}
+
+async fn write_spilled<S: Sink, T: RowOwned + RowWrite + DeserializeOwned + Serialize + Sync>(
+    sink: &S,
+    table: &str,
+    rows: Vec<Box<RawValue>>,
+    token: &str,
+) -> Result<()> {
+    let rows: Vec<T> = rows.iter().map(|row| serde_json::from_str(row.get())).collect::<Result<_, _>>().wrap_err_with(|| format!("Spilled {} row doesn't match the record", table))?;
+    // Synchronous, the token only deduplicates inserts that reach the table directly
+    sink.write_rows(table, &rows, Some(token), None).await
+}
+
+pub async fn write_rows<T: RowOwned + RowWrite>(
+    client: &Client,
+    table: &str,
+    rows: &[T],
+    dedup_token: Option<&str>,
+    async_insert: Option<AsyncInsert>,
+) -> Result<()> {
+    if rows.is_empty() {
+        return Ok(());
+    }
+
+    // One INSERT per table and batch, ended right away; the batch thresholds are the writer's
+    let result: Result<Quantities> = async {
+        let mut inserter = client.inserter::<T>(table);
+        if let Some(token) = dedup_token {
+            inserter = inserter.with_option("insert_deduplication_token", token);
+        }
+        if let Some(AsyncInsert { wait }) = async_insert {
+            inserter = inserter.with_option("async_insert", "1").with_option("wait_for_async_insert", if wait { "1" } else { "0" });
+        }
+        for r in rows {
+            inserter.write(r).await.wrap_err("Write error")?;
+        }
+        inserter.end().await.wrap_err("ClickHouse End Error")
+    }
+    .await;
+
+    match &result {
+        Ok(written) => {
+            metrics::ROWS_INSERTED.inc_by(written.rows);
+            metrics::BYTES_INSERTED.inc_by(written.bytes);
+            info!("💾 Saved {} rows ({} bytes) to {}", written.rows, written.bytes, table)
+        }
+        Err(e) => error!("❌ {} ({}): {:?}", e, table, e.root_cause()),
+    }
+    result.map(|_| ()).wrap_err_with(|| format!("Insert into {} failed", table))
+}
+
+// Records arrive slightly out of order (several subscriptions, gap fills replaying a block).
+// Stable, full ties keep their arrival order
+pub fn sort_batch(batch: &mut [IndexedEvent]) {
+    batch.sort_by_key(IndexedEvent::sort_key);
+}
+
+// Flush, then move the checkpoints of the pools in the batch. A pool's checkpoint only
+// moves to the last block the batch completes (Checkpoints::advance), and only once every
+// table's insert succeeded; a failed flush freezes it instead
+async fn flush_and_checkpoint<S: Sink>(
+    sink: &S,
+    settings: &WriterSettings,
+    batch: &mut Vec<IndexedEvent>,
+    derived: &mut Derived,
+    checkpoints: Option<&mut Checkpoints>,
+) -> Result<()> {
+    sort_batch(batch);
+    derived.apply(batch);
+    stamp_insert_version(batch);
+    let touched = checkpoints.is_some().then(|| Checkpoints::touched(batch));
+    let result = flush_batch(sink, settings, batch).await;
+
+    if let (Some(checkpoints), Some(touched)) = (checkpoints, touched) {
+        match &result {
+            Ok(_) if settings.acknowledged() => checkpoints.advance(touched).await,
+            Ok(_) => {}
+            Err(_) => checkpoints.freeze(touched),
+        }
+    }
+    result
+}
+
+// Adds a record from the channel; a reorged swap's audit row drops the swap if it is still here
+pub fn push_record(batch: &mut Batch, record: IndexedEvent) {
+    if let IndexedEvent::Reorged(r) = &record {
+        drop_reorged(&mut batch.events, r);
+    }
+    batch.push(record, Instant::now());
+}
+
+// A reorged swap still in the batch never reaches uniswap_swaps, the audit row is kept either way
+pub fn drop_reorged(batch: &mut Vec<IndexedEvent>, reorged: &ReorgedSwapRecord) {
+    batch.retain(|event| match event {
+        IndexedEvent::Swap(s) => {
+            let same = s.tx_hash == reorged.tx_hash && s.log_index == reorged.log_index && s.block_hash == reorged.block_hash;
+            if same {
+                info!("🗑️ Dropped reorged swap {}#{} before it was written", s.tx_hash, s.log_index);
+            }
+            !same
+        }
+        _ => true,
+    });
+}
+
+// What the writer task needs from the config
+#[derive(Debug, Clone)]
+pub struct WriterSettings {
+    // BATCH_SIZE, rows buffered before an insert
+    pub batch_size: usize,
+    // BATCH_MAX_AGE_SECONDS, how long a partial batch may wait
+    pub max_age: Duration,
+    pub retry: InsertRetry,
+    pub spill: SpillDir,
+    // TABLE_NAMES
+    pub tables: TableNames,
+    // INSERT_WORKERS, inserts running at once
+    pub workers: usize,
+    pub async_insert: Option<AsyncInsert>,
+    // KAFKA_BROKERS, set by main once the producer is up
+    pub kafka: Option<KafkaSink>,
+    // NATS_URL / NATS_DRY_RUN, likewise
+    pub nats: Option<NatsSink>,
+    // --stdout=also: each batch is printed as JSON lines too
+    pub stdout: bool,
+}
+
+impl WriterSettings {
+    pub fn new(config: &IndexerConfig) -> Self {
+        Self {
+            batch_size: config.batch_size,
+            max_age: config.batch_max_age,
+            retry: config.insert_retry.clone(),
+            spill: config.spill.clone(),
+            tables: config.tables.clone(),
+            workers: config.insert_workers.max(1),
+            async_insert: config.async_insert,
+            kafka: None,
+            nats: None,
+            stdout: false,
+        }
+    }
+
+    // Whether a successful insert means the rows are in the table, the checkpoints move only then
+    pub fn acknowledged(&self) -> bool {
+        self.async_insert.is_none_or(|a| a.wait)
+    }
+
+    pub fn log_mode(&self) {
+        match self.async_insert {
+            Some(AsyncInsert { wait: true }) => {
+                info!("📨 async_insert: ClickHouse buffers the rows, inserts wait for its flush (up to {} rows each)", self.batch_size)
+            }
+            Some(AsyncInsert { wait: false }) => warn!(
+                "⚠️ async_insert without wait_for_async_insert: inserts return before the rows are written, checkpoints stay put"
+            ),
+            None => info!("📦 Batching up to {} rows or {:?} per insert", self.batch_size, self.max_age),
+        }
+    }
+}
+
+// Background task: buffer records and flush them to the sink (SINK) once the batch is full or
+// its oldest row is BATCH_MAX_AGE_SECONDS old, both through the same flush. Failed batches
+// are logged and skipped; once every sender is gone the partial batch is flushed, the sink
+// closed and the first failure, if any, is returned
+pub async fn run_writer<S: Sink>(
+    sink: S,
+    rx: mpsc::Receiver<IndexedEvent>,
+    settings: WriterSettings,
+    derived: Derived,
+    checkpoints: Option<Checkpoints>,
+) -> Result<()> {
+    settings.log_mode();
+    let result = match settings.workers > 1 {
+        true => run_insert_workers(sink.clone(), rx, settings, derived, checkpoints).await,
+        false => run_batches(&sink, rx, settings, derived, checkpoints).await,
+    };
+    let closed = sink.close().await;
+    result.and(closed)
+}
+
+async fn run_batches<S: Sink>(
+    sink: &S,
+    mut rx: mpsc::Receiver<IndexedEvent>,
+    settings: WriterSettings,
+    mut derived: Derived,
+    mut checkpoints: Option<Checkpoints>,
+) -> Result<()> {
+    let mut batch = Batch::new(settings.batch_size, settings.max_age);
+    let mut failed = None;
+
+    // Left over from an earlier run, or spilled since: replayed after each successful flush
+    let mut spill_pending = settings.spill.files().is_ok_and(|files| !files.is_empty());
+    if spill_pending {
+        spill_pending = !replay_spilled(sink, &settings).await;
+    }
+
+    loop {
+        let deadline = batch.deadline();
+        let due = async {
+            match deadline {
+                Some(deadline) => tokio::time::sleep_until(deadline.into()).await,
+                None => std::future::pending().await,
+            }
+        };
+
+        tokio::select! {
+            record = rx.recv() => {
+                let Some(record) = record else { break };
+                push_record(&mut batch, record);
+                // Whatever else is queued joins this batch, ASYNC_INSERT writes right away
+                while !batch.is_full() && let Ok(record) = rx.try_recv() {
+                    push_record(&mut batch, record);
+                }
+            }
+            _ = due => {}
+        }
+
+        if batch.is_due(Instant::now()) {
+            // Partial batches going out on the timer, how often the age limit is what flushes
+            if !batch.is_full() && !settings.max_age.is_zero() {
+                metrics::TIMED_FLUSHES.inc();
+            }
+            let mut rows = batch.take();
+            match flush_and_checkpoint(sink, &settings, &mut rows, &mut derived, checkpoints.as_mut()).await {
+                Ok(()) if spill_pending => spill_pending = !replay_spilled(sink, &settings).await,
+                Ok(()) => {}
+                Err(e) => {
+                    spill_pending = true;
+                    failed.get_or_insert(e);
+                }
+            }
+        }
+    }
+
+    let mut rows = batch.take();
+    if !rows.is_empty() && let Err(e) = flush_and_checkpoint(sink, &settings, &mut rows, &mut derived, checkpoints.as_mut()).await {
+        failed.get_or_insert(e);
+    } else if spill_pending {
+        replay_spilled(sink, &settings).await;
+    }
+    if let Some(checkpoints) = checkpoints.as_mut() {
+        checkpoints.finish().await;
+    }
+    failed.map_or(Ok(()), Err)
+}
@@ -0,0 +1,195 @@
+use clickhouse::Client;
+use eyre::{Result, WrapErr};
+use hyper::rt::{Read, ReadBufCursor, Write};
+use hyper::Uri;
+use hyper_rustls::{ConfigBuilderExt, HttpsConnectorBuilder};
+use hyper_util::client::legacy::connect::{Connected, Connection, HttpConnector};
+use hyper_util::client::legacy::Client as HyperClient;
+use hyper_util::rt::TokioExecutor;
+use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+use rustls::{DigitallySignedStruct, SignatureScheme};
+use rustls_pki_types::pem::PemObject;
+use rustls_pki_types::{CertificateDer, ServerName, UnixTime};
+use std::future::Future;
+use std::io::IoSlice;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tower_service::Service;
+use tracing::warn;
+
+use crate::config::ClickhouseSettings;
+use crate::metrics;
+
+// Same keepalive and idle timeout as the clickhouse crate's own client
+const TCP_KEEPALIVE: Duration = Duration::from_secs(60);
+const POOL_IDLE_TIMEOUT: Duration = Duration::from_secs(2);
+
+// https:// URLs are verified against the webpki roots unless CLICKHOUSE_CA_FILE or
+// CLICKHOUSE_INSECURE_SKIP_VERIFY say otherwise
+fn tls_config(settings: &ClickhouseSettings, url: &str) -> Result<rustls::ClientConfig> {
+    if (settings.ca_file.is_some() || settings.insecure_skip_verify) && !url.starts_with("https://") {
+        warn!("⚠️ CLICKHOUSE_CA_FILE / CLICKHOUSE_INSECURE_SKIP_VERIFY have no effect on {}", url);
+    }
+
+    let provider = Arc::new(rustls::crypto::aws_lc_rs::default_provider());
+    let builder = rustls::ClientConfig::builder_with_provider(provider.clone())
+        .with_safe_default_protocol_versions()
+        .wrap_err("Failed to set up TLS")?;
+
+    if settings.insecure_skip_verify {
+        if settings.ca_file.is_some() {
+            warn!("⚠️ CLICKHOUSE_INSECURE_SKIP_VERIFY is set, CLICKHOUSE_CA_FILE is ignored");
+        }
+        warn!("⚠️ Not verifying the certificate of ClickHouse at {} (CLICKHOUSE_INSECURE_SKIP_VERIFY)", url);
+        let verifier = Arc::new(SkipVerify(provider));
+        return Ok(builder.dangerous().with_custom_certificate_verifier(verifier).with_no_client_auth());
+    }
+
+    let Some(path) = &settings.ca_file else { return Ok(builder.with_webpki_roots().with_no_client_auth()) };
+    let mut roots = rustls::RootCertStore::empty();
+    for cert in CertificateDer::pem_file_iter(path).wrap_err_with(|| format!("Failed to read CLICKHOUSE_CA_FILE {}", path.display()))? {
+        let cert = cert.wrap_err_with(|| format!("Invalid certificate in CLICKHOUSE_CA_FILE {}", path.display()))?;
+        roots.add(cert).wrap_err_with(|| format!("Invalid certificate in CLICKHOUSE_CA_FILE {}", path.display()))?;
+    }
+    if roots.is_empty() {
+        eyre::bail!("No certificates in CLICKHOUSE_CA_FILE {}", path.display());
+    }
+    Ok(builder.with_root_certificates(roots).with_no_client_auth())
+}
+
+// The HTTP client under a ClickHouse client, plain or TLS depending on the URL
+pub fn http_client(settings: &ClickhouseSettings, url: &str) -> Result<Client> {
+    let mut connector = HttpConnector::new();
+    connector.set_keepalive(Some(TCP_KEEPALIVE));
+    connector.enforce_http(false);
+    let connector = HttpsConnectorBuilder::new()
+        .with_tls_config(tls_config(settings, url)?)
+        .https_or_http()
+        .enable_http1()
+        .wrap_connector(Counting(connector));
+    Ok(Client::with_http_client(HyperClient::builder(TokioExecutor::new()).pool_idle_timeout(POOL_IDLE_TIMEOUT).build(connector)))
+}
+
+// TCP connections that count what they send, TLS records included: the egress
+// (indexer_clickhouse_bytes_sent_total) next to the uncompressed indexer_bytes_inserted_total
+#[derive(Clone)]
+struct Counting<C>(C);
+
+impl<C> Service<Uri> for Counting<C>
+where
+    C: Service<Uri> + Send,
+    C::Future: Send + 'static,
+{
+    type Response = Counting<C::Response>;
+    type Error = C::Error;
+    type Future = Pin<Box<dyn Future<Output = std::result::Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<std::result::Result<(), Self::Error>> {
+        self.0.poll_ready(cx)
+    }
+
+    fn call(&mut self, uri: Uri) -> Self::Future {
+        let connecting = self.0.call(uri);
+        Box::pin(async move { connecting.await.map(Counting) })
+    }
+}
+
+impl<T: Read + Unpin> Read for Counting<T> {
+    fn poll_read(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: ReadBufCursor<'_>) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.0).poll_read(cx, buf)
+    }
+}
+
+impl<T: Write + Unpin> Write for Counting<T> {
+    fn poll_write(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<std::io::Result<usize>> {
+        let written = Pin::new(&mut self.0).poll_write(cx, buf);
+        if let Poll::Ready(Ok(n)) = written {
+            metrics::CLICKHOUSE_BYTES_SENT.inc_by(n as u64);
+        }
+        written
+    }
+
+    fn poll_write_vectored(mut self: Pin<&mut Self>, cx: &mut Context<'_>, bufs: &[IoSlice<'_>]) -> Poll<std::io::Result<usize>> {
+        let written = Pin::new(&mut self.0).poll_write_vectored(cx, bufs);
+        if let Poll::Ready(Ok(n)) = written {
+            metrics::CLICKHOUSE_BYTES_SENT.inc_by(n as u64);
+        }
+        written
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        self.0.is_write_vectored()
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.0).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.0).poll_shutdown(cx)
+    }
+}
+
+impl<T: Connection> Connection for Counting<T> {
+    fn connected(&self) -> Connected {
+        self.0.connected()
+    }
+}
+
+// CLICKHOUSE_INSECURE_SKIP_VERIFY: any certificate for any name is accepted, the handshake
+// signatures are still checked so the connection is at least encrypted to whoever answered
+#[derive(Debug)]
+struct SkipVerify(Arc<rustls::crypto::CryptoProvider>);
+
+impl ServerCertVerifier for SkipVerify {
+    fn verify_server_cert(
+        &self,
+        _end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _server_name: &ServerName<'_>,
+        _ocsp_response: &[u8],
+        _now: UnixTime,
+    ) -> std::result::Result<ServerCertVerified, rustls::Error> {
+        Ok(ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> std::result::Result<HandshakeSignatureValid, rustls::Error> {
+        rustls::crypto::verify_tls12_signature(message, cert, dss, &self.0.signature_verification_algorithms)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> std::result::Result<HandshakeSignatureValid, rustls::Error> {
+        rustls::crypto::verify_tls13_signature(message, cert, dss, &self.0.signature_verification_algorithms)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.0.signature_verification_algorithms.supported_schemes()
+    }
+}
+
+// The rustls error behind a failed request, if that is what failed
+pub fn tls_cause(e: &(dyn std::error::Error + 'static)) -> Option<String> {
+    let mut source = Some(e);
+    while let Some(e) = source {
+        if let Some(e) = e.downcast_ref::<rustls::Error>() {
+            return Some(e.to_string());
+        }
+        // io::Error::source() skips the error it wraps
+        source = match e.downcast_ref::<std::io::Error>().and_then(|io| io.get_ref()) {
+            Some(inner) => Some(inner),
+            None => e.source(),
+        };
+    }
+    None
+}
@@ -0,0 +1,136 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use crate::records::{IndexedEvent, PoolTwapRecord, SCHEMA_VERSION};
+
+// Gap filled with carried-forward windows at most, a longer silence restarts the windows
+pub const MAX_CARRIED_WINDOWS: i64 = 1440;
+
+// One window size of one pool: price x ms accumulated since `start`, up to `cursor`
+#[derive(Debug)]
+struct Window {
+    size: i64,
+    start: i64,
+    cursor: i64,
+    weighted: f64,
+    covered: i64,
+    swaps: u64,
+}
+
+impl Window {
+    fn new(size: i64, at: i64) -> Self {
+        Self { size, start: at - at.rem_euclid(size), cursor: at, weighted: 0.0, covered: 0, swaps: 0 }
+    }
+
+    // Weighs `price` up to `to`, closing every window that ends on the way
+    fn advance(&mut self, to: i64, price: f64, mut close: impl FnMut(&Window, f64)) {
+        if to >= self.start + self.size * (MAX_CARRIED_WINDOWS + 1) {
+            self.close(price, &mut close);
+            *self = Window::new(self.size, to);
+            return;
+        }
+        while to >= self.start + self.size {
+            self.close(price, &mut close);
+        }
+        self.weighted += price * (to - self.cursor) as f64;
+        self.covered += to - self.cursor;
+        self.cursor = to;
+    }
+
+    fn close(&mut self, price: f64, close: &mut impl FnMut(&Window, f64)) {
+        let end = self.start + self.size;
+        self.weighted += price * (end - self.cursor) as f64;
+        self.covered += end - self.cursor;
+        if self.covered > 0 {
+            close(self, self.weighted / self.covered as f64);
+        }
+        *self = Window { size: self.size, start: end, cursor: end, weighted: 0.0, covered: 0, swaps: 0 };
+    }
+}
+
+#[derive(Debug)]
+struct PoolTwap {
+    chain_id: u64,
+    // (block_number, log_index) of the last swap counted
+    position: (u64, u64),
+    price: f64,
+    windows: Vec<Window>,
+}
+
+// TWAP_WINDOWS: time-weighted price_usd per pool, on the block time of the ordered swap stream.
+// The latest swap time closes the windows of every pool, quiet ones carry their last price
+// forward. A swap at or behind a pool's last one (a stream replaying after a reconnect) isn't
+// counted again. Pools without a price_usd get no windows
+#[derive(Debug, Default)]
+pub struct Twaps {
+    sizes: Vec<i64>,
+    pools: HashMap<String, PoolTwap>,
+    now: i64,
+}
+
+impl Twaps {
+    pub fn new(windows: &[Duration]) -> Self {
+        Self { sizes: windows.iter().map(|w| w.as_millis() as i64).filter(|w| *w > 0).collect(), ..Self::default() }
+    }
+
+    pub fn enabled(&self) -> bool {
+        !self.sizes.is_empty()
+    }
+
+    // One pool_twaps row per window closed by the batch's swaps
+    pub fn apply(&mut self, batch: &[IndexedEvent]) -> Vec<IndexedEvent> {
+        let mut rows = Vec::new();
+        if !self.enabled() {
+            return rows;
+        }
+
+        for event in batch {
+            let IndexedEvent::Swap(swap) = event else { continue };
+            let Some(price) = swap.price_usd else { continue };
+            let position = (swap.block_number, swap.log_index);
+            let at = swap.timestamp.timestamp_millis();
+
+            let Some(pool) = self.pools.get_mut(&swap.pool_address) else {
+                let windows = self.sizes.iter().map(|size| Window { swaps: 1, ..Window::new(*size, at) }).collect();
+                self.pools.insert(swap.pool_address.clone(), PoolTwap { chain_id: swap.chain_id, position, price, windows });
+                self.now = self.now.max(at);
+                continue;
+            };
+            if position <= pool.position || pool.windows.iter().any(|w| at < w.cursor) {
+                continue;
+            }
+
+            let last = pool.price;
+            for window in &mut pool.windows {
+                window.advance(at, last, |w, twap| rows.push(twap_row(pool.chain_id, &swap.pool_address, w, twap)));
+                window.swaps += 1;
+            }
+            pool.position = position;
+            pool.price = price;
+            self.now = self.now.max(at);
+        }
+
+        // Windows that ended without a swap of their pool
+        for (address, pool) in &mut self.pools {
+            for window in &mut pool.windows {
+                if self.now >= window.start + window.size {
+                    window.advance(self.now, pool.price, |w, twap| rows.push(twap_row(pool.chain_id, address, w, twap)));
+                }
+            }
+        }
+        rows
+    }
+}
+
+fn twap_row(chain_id: u64, pool: &str, window: &Window, twap: f64) -> IndexedEvent {
+    IndexedEvent::Twap(PoolTwapRecord {
+        chain_id,
+        schema_version: SCHEMA_VERSION,
+        pool_address: pool.to_string(),
+        window_seconds: (window.size / 1000) as u32,
+        window_start: window.start,
+        window_end: window.start + window.size,
+        twap_price: twap,
+        n_swaps: window.swaps,
+    })
+}
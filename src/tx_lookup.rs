@@ -0,0 +1,110 @@
+use alloy::{
+    primitives::{Address, B256},
+    providers::{DynProvider, Provider},
+};
+use eyre::Result;
+use lru::LruCache;
+use std::num::NonZeroUsize;
+use std::sync::{Arc, Mutex};
+use tokio::sync::{OnceCell, Semaphore};
+use tracing::warn;
+
+use crate::metrics;
+use crate::records::SwapRecord;
+use crate::rpc::http_provider;
+
+// Receipts kept around for swaps later in the same tx
+pub const TX_CACHE_SIZE: usize = 4096;
+// In-flight requests against the HTTP RPC
+pub const TX_LOOKUP_CONCURRENCY: usize = 8;
+
+#[derive(Debug, Clone, Copy)]
+pub struct TxGas {
+    pub gas_used: u64,
+    pub gas_price_gwei: f64,
+}
+
+type Cache<T> = Arc<Mutex<LruCache<B256, Arc<OnceCell<Option<T>>>>>>;
+
+// Per-transaction data for swap rows, fetched once per tx hash
+#[derive(Clone)]
+pub struct TxLookup {
+    provider: DynProvider,
+    permits: Arc<Semaphore>,
+    receipts: Cache<TxGas>,
+    // None when FETCH_TX_FROM is off
+    senders: Option<Cache<Address>>,
+}
+
+impl TxLookup {
+    pub fn new(http_url: &str, fetch_tx_from: bool) -> Result<Self> {
+        let provider = http_provider(http_url)?;
+        let size = NonZeroUsize::new(TX_CACHE_SIZE).unwrap();
+
+        Ok(Self {
+            provider,
+            permits: Arc::new(Semaphore::new(TX_LOOKUP_CONCURRENCY)),
+            receipts: Arc::new(Mutex::new(LruCache::new(size))),
+            senders: fetch_tx_from.then(|| Arc::new(Mutex::new(LruCache::new(size)))),
+        })
+    }
+
+    // Missing data stays NULL, the row is still written
+    pub async fn enrich(&self, record: &mut SwapRecord, tx_hash: B256) {
+        if let Some(gas) = self.gas(tx_hash).await {
+            record.gas_used = Some(gas.gas_used);
+            record.gas_price_gwei = Some(gas.gas_price_gwei);
+        }
+        record.tx_from = self.tx_from(tx_hash).await.map(|a| a.to_string());
+    }
+
+    // tx.origin, the Swap's sender is usually a router
+    pub async fn tx_from(&self, tx_hash: B256) -> Option<Address> {
+        let senders = self.senders.as_ref()?;
+        let cell = senders.lock().unwrap().get_or_insert(tx_hash, Default::default).clone();
+
+        *cell
+            .get_or_init(|| async {
+                let _permit = self.permits.acquire().await.ok()?;
+                match self.provider.get_transaction_by_hash(tx_hash).await {
+                    Ok(Some(tx)) => Some(tx.inner.signer()),
+                    Ok(None) => {
+                        warn!("⚠️ Transaction {:?} not found", tx_hash);
+                        None
+                    }
+                    Err(e) => {
+                        metrics::RPC_ERRORS.with_label_values(&["transaction"]).inc();
+                        warn!("⚠️ Failed to fetch transaction {:?}: {:?}", tx_hash, e);
+                        None
+                    }
+                }
+            })
+            .await
+    }
+
+    pub async fn gas(&self, tx_hash: B256) -> Option<TxGas> {
+        // Swaps in the same tx share the cell, so only the first one hits the RPC
+        let cell = self.receipts.lock().unwrap().get_or_insert(tx_hash, Default::default).clone();
+
+        *cell
+            .get_or_init(|| async {
+                let _permit = self.permits.acquire().await.ok()?;
+                match self.provider.get_transaction_receipt(tx_hash).await {
+                    Ok(Some(receipt)) => Some(TxGas {
+                        gas_used: receipt.gas_used,
+                        gas_price_gwei: receipt.effective_gas_price as f64 / 1e9,
+                    }),
+                    Ok(None) => {
+                        warn!("⚠️ No receipt for tx {:?}", tx_hash);
+                        None
+                    }
+                    Err(e) => {
+                        metrics::RPC_ERRORS.with_label_values(&["receipt"]).inc();
+                        warn!("⚠️ Failed to fetch receipt for tx {:?}: {:?}", tx_hash, e);
+                        None
+                    }
+                }
+            })
+            .await
+    }
+}
@@ -0,0 +1,144 @@
+use alloy::{providers::Provider, rpc::types::Log};
+use clickhouse::Client;
+use eyre::{Result, WrapErr};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use tracing::{info, warn};
+
+use crate::backfill::{logs_in_range, BackfillRange};
+use crate::config::IndexerConfig;
+use crate::indexer::{is_swap, pool_filters, LogHandler};
+use crate::pool::{PoolInfo, PoolRef};
+
+// A block whose swap count on chain differs from its rows in ClickHouse
+#[derive(Debug, Clone, Copy)]
+pub struct Mismatch {
+    pub block: u64,
+    pub logs: u64,
+    pub rows: u64,
+}
+
+#[derive(Debug, Default)]
+pub struct VerifyReport {
+    pub mismatches: Vec<Mismatch>,
+    // Swap logs of mismatched blocks without a row, what --repair re-ingests
+    pub missing: Vec<Log>,
+}
+
+impl VerifyReport {
+    // Blocks with more swaps in ClickHouse than on chain (reorged-out rows), re-ingesting
+    // doesn't fix them
+    pub fn repairable(&self) -> bool {
+        self.mismatches.iter().all(|m| m.rows < m.logs)
+    }
+}
+
+// Rows of the indexed pools in from..=to, all pools in firehose mode
+fn swaps_query(
+    client: &Client,
+    select: &str,
+    tail: &str,
+    config: &IndexerConfig,
+    pools: &[String],
+    from: u64,
+    to: u64,
+) -> clickhouse::query::Query {
+    let pool_clause = if config.firehose { "" } else { " AND pool_address IN ?" };
+    let query = client
+        .query(&format!(
+            "SELECT {} FROM {} WHERE chain_id = ? AND block_number BETWEEN ? AND ?{} {}",
+            select, config.tables.get("uniswap_swaps"), pool_clause, tail
+        ))
+        .bind(config.chain_id)
+        .bind(from)
+        .bind(to);
+    if config.firehose { query } else { query.bind(pools) }
+}
+
+// Swap logs from eth_getLogs against uniswap_swaps rows per block, one chunk at a time
+pub async fn verify<P: Provider>(
+    provider: &P,
+    client: &Client,
+    pools: &HashMap<PoolRef, PoolInfo>,
+    config: &IndexerConfig,
+    range: BackfillRange,
+) -> Result<VerifyReport> {
+    let to = match range.to {
+        Some(to) => to,
+        None => provider.get_block_number().await?,
+    };
+    if !config.watchlist.is_empty() {
+        warn!("⚠️ WATCHLIST is set, swaps of other wallets have no rows and show up as missing");
+    }
+    if config.price_sanity.enabled() {
+        warn!("⚠️ Price sanity check is on, swaps quarantined in suspect_swaps show up as missing");
+    }
+
+    let filters = pool_filters(pools, config);
+    let pool_list: Vec<String> = pools.keys().map(PoolRef::to_string).collect();
+    let chunk_size = range.chunk_size.max(1);
+    let mut report = VerifyReport::default();
+
+    info!("🔍 Verifying swaps in blocks {}..={}", range.from, to);
+    let mut start = range.from;
+    while start <= to {
+        let end = start.saturating_add(chunk_size - 1).min(to);
+
+        let logs: Vec<Log> = logs_in_range(provider, &filters, start, end).await?.into_iter().filter(is_swap).collect();
+        let mut on_chain: BTreeMap<u64, u64> = BTreeMap::new();
+        for log in &logs {
+            *on_chain.entry(log.block_number.unwrap_or_default()).or_default() += 1;
+        }
+
+        // Distinct swaps: copies of one (tx_hash, log_index) are the same row once uniswap_swaps merges
+        let rows: HashMap<u64, u64> = swaps_query(client, "block_number, uniqExact(tx_hash, log_index)", "GROUP BY block_number", config, &pool_list, start, end)
+            .fetch_all::<(u64, u64)>()
+            .await
+            .wrap_err_with(|| format!("Failed to count rows in {}", config.tables.get("uniswap_swaps")))?
+            .into_iter()
+            .collect();
+
+        let blocks: BTreeSet<u64> = on_chain.keys().chain(rows.keys()).copied().collect();
+        let mut short = HashSet::new();
+        for block in blocks {
+            let (logs, rows) = (on_chain.get(&block).copied().unwrap_or(0), rows.get(&block).copied().unwrap_or(0));
+            if logs != rows {
+                warn!("❗ Block {}: {} swap log(s), {} row(s)", block, logs, rows);
+                report.mismatches.push(Mismatch { block, logs, rows });
+                short.extend((rows < logs).then_some(block));
+            }
+        }
+
+        // Only the logs that really have no row, so a repair doesn't duplicate the others
+        if !short.is_empty() {
+            let existing: HashSet<(String, u64)> = swaps_query(client, "tx_hash, log_index", "", config, &pool_list, start, end)
+                .fetch_all::<(String, u64)>()
+                .await
+                .wrap_err_with(|| format!("Failed to read rows from {}", config.tables.get("uniswap_swaps")))?
+                .into_iter()
+                .collect();
+            report.missing.extend(logs.into_iter().filter(|log| {
+                short.contains(&log.block_number.unwrap_or_default())
+                    && !existing.contains(&(log.transaction_hash.unwrap_or_default().to_string(), log.log_index.unwrap_or_default()))
+            }));
+        }
+
+        start = end.saturating_add(1);
+    }
+
+    info!("🔍 {} mismatched block(s), {} swap(s) missing", report.mismatches.len(), report.missing.len());
+    Ok(report)
+}
+
+// --repair: the missing swaps go through the backfill handler, in chain order
+pub async fn repair(handler: &mut LogHandler, pools: &mut HashMap<PoolRef, PoolInfo>, missing: Vec<Log>) -> Result<u64> {
+    handler.block_times.prefetch(&missing).await;
+    let mut handled = 0;
+    for log in missing {
+        if !handler.handle(pools, log).await {
+            eyre::bail!("Writer channel closed during repair");
+        }
+        handled += 1;
+    }
+    info!("🩹 Re-ingested {} swap(s)", handled);
+    Ok(handled)
+}
@@ -0,0 +1,34 @@
+use alloy::primitives::Address;
+use std::collections::HashSet;
+use std::str::FromStr;
+use std::time::Duration;
+use tracing::info;
+
+use crate::metrics;
+use crate::records::SwapRecord;
+
+// How often the matched/dropped counts are logged
+pub const WATCHLIST_REPORT_INTERVAL: Duration = Duration::from_secs(60);
+
+// WATCHLIST: a swap is kept when its sender, recipient or tx_from is on the list
+pub fn matches(watchlist: &HashSet<Address>, record: &SwapRecord) -> bool {
+    let listed = |s: &str| Address::from_str(s).is_ok_and(|addr| watchlist.contains(&addr));
+    let hit = listed(&record.sender) || listed(&record.recipient) || record.tx_from.as_deref().is_some_and(listed);
+
+    if hit {
+        metrics::WATCHLIST_MATCHED.inc();
+    } else {
+        metrics::WATCHLIST_DROPPED.inc();
+    }
+    hit
+}
+
+// Counts since startup, so it's visible that the filter lets something through
+pub async fn report(every: Duration) {
+    let mut interval = tokio::time::interval(every);
+    interval.tick().await;
+    loop {
+        interval.tick().await;
+        info!("👀 Watchlist: {} swaps matched, {} dropped", metrics::WATCHLIST_MATCHED.get(), metrics::WATCHLIST_DROPPED.get());
+    }
+}
@@ -0,0 +1,45 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::pool::PoolRef;
+
+// Highest (block, log_index) emitted per pool. After a resubscribe the new stream
+// may replay blocks already written; a pool's logs at or below its watermark are
+// dropped until the stream passes it, then the pool is back to normal flow
+#[derive(Debug, Default)]
+pub struct Watermarks {
+    emitted: HashMap<PoolRef, (u64, u64)>,
+    guarded: HashSet<PoolRef>,
+}
+
+impl Watermarks {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // A new stream starts: every pool with a watermark is guarded
+    pub fn resubscribed(&mut self) {
+        self.guarded = self.emitted.keys().copied().collect();
+    }
+
+    pub fn is_guarded(&self, pool: &PoolRef) -> bool {
+        self.guarded.contains(pool)
+    }
+
+    // false for a replayed log of a guarded pool
+    pub fn admit(&mut self, pool: PoolRef, block: u64, log_index: u64) -> bool {
+        let position = (block, log_index);
+        let watermark = self.emitted.get(&pool).copied();
+
+        if self.guarded.contains(&pool) {
+            if watermark.is_some_and(|w| position <= w) {
+                return false;
+            }
+            self.guarded.remove(&pool);
+        }
+
+        if watermark.is_none_or(|w| position > w) {
+            self.emitted.insert(pool, position);
+        }
+        true
+    }
+}
@@ -0,0 +1,304 @@
+use eyre::{Result, WrapErr};
+use serde::Deserialize;
+use std::collections::{HashMap, VecDeque};
+use std::path::Path;
+use std::sync::{Mutex, OnceLock};
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tracing::{error, info, warn};
+
+use crate::metrics;
+use crate::records::SwapRecord;
+
+// Alerts waiting for delivery, past that they are dropped (and counted as failed)
+const CAPACITY: usize = 1_000;
+// Attempts per alert, the delay doubling from RETRY_DELAY
+const ATTEMPTS: u32 = 4;
+const RETRY_DELAY: Duration = Duration::from_secs(1);
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+// Window of the reconnects condition
+const RECONNECT_WINDOW_MS: i64 = 3_600_000;
+
+fn default_cooldown() -> u64 {
+    60
+}
+
+// One entry of WEBHOOK_RULES_FILE, a JSON array of them
+#[derive(Debug, Clone, Deserialize)]
+pub struct Rule {
+    pub name: String,
+    #[serde(flatten)]
+    pub condition: Condition,
+    // POSTed {"rule": <name>, "text": <template filled in>}
+    pub url: String,
+    // {rule} and {chain_id}, {pool}, {pair}, {tx_hash}, {block} with {usd} (swap_usd) or
+    // {percent}, {minutes}, {price} (price_move); {count} for reconnects
+    #[serde(default)]
+    pub template: Option<String>,
+    // At most one delivery per rule in this many seconds, matches in between are counted and dropped
+    #[serde(default = "default_cooldown")]
+    pub cooldown_seconds: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(tag = "condition", rename_all = "snake_case")]
+pub enum Condition {
+    // A swap's volume_usd at or above min_usd
+    SwapUsd { min_usd: f64 },
+    // A pool's price (price_usd, token0 in token1 without one) `percent` away from its low or
+    // high of the last `minutes` of block time
+    PriceMove { percent: f64, minutes: u64 },
+    // More than `count` stream reconnects within the last hour
+    Reconnects { count: usize },
+}
+
+impl Condition {
+    fn default_template(&self) -> &'static str {
+        match self {
+            Condition::SwapUsd { .. } => "{rule}: ${usd} swap on {pair} ({pool}), tx {tx_hash}",
+            Condition::PriceMove { .. } => "{rule}: {pair} ({pool}) moved {percent}% within {minutes} min, now {price}",
+            Condition::Reconnects { .. } => "{rule}: {count} reconnects in the last hour",
+        }
+    }
+}
+
+pub fn parse_rules(json: &str) -> Result<Vec<Rule>> {
+    let rules: Vec<Rule> = serde_json::from_str(json)?;
+    for (i, rule) in rules.iter().enumerate() {
+        if rule.name.trim().is_empty() || rules[..i].iter().any(|r| r.name == rule.name) {
+            eyre::bail!("Rule names must be set and unique, got '{}'", rule.name);
+        }
+        if !rule.url.starts_with("http://") && !rule.url.starts_with("https://") {
+            eyre::bail!("Rule {} needs an http(s) url", rule.name);
+        }
+        let valid = match rule.condition {
+            Condition::SwapUsd { min_usd } => min_usd.is_finite() && min_usd > 0.0,
+            Condition::PriceMove { percent, minutes } => percent.is_finite() && percent > 0.0 && minutes > 0,
+            Condition::Reconnects { count } => count > 0,
+        };
+        if !valid {
+            eyre::bail!("Rule {} has an invalid threshold", rule.name);
+        }
+    }
+    Ok(rules)
+}
+
+pub fn read_rules_file(path: &Path) -> Result<Vec<Rule>> {
+    let json = std::fs::read_to_string(path).wrap_err_with(|| format!("Failed to read {}", path.display()))?;
+    parse_rules(&json)
+}
+
+// A rule that matched, ready to POST
+#[derive(Debug, Clone, PartialEq)]
+pub struct Alert {
+    pub rule: String,
+    pub url: String,
+    pub body: String,
+}
+
+// The rules and what they remember: the price windows per pool, recent reconnects and when
+// each rule last fired. `now` is wall-clock Unix millis, the cooldowns run on it
+pub struct Rules {
+    rules: Vec<Rule>,
+    last_fired: Vec<Option<i64>>,
+    // Per pool (block time millis, price), oldest first, as long as the longest price_move window
+    prices: HashMap<String, VecDeque<(i64, f64)>>,
+    window_ms: i64,
+    reconnects: VecDeque<i64>,
+}
+
+impl Rules {
+    pub fn new(rules: Vec<Rule>) -> Self {
+        let window_ms = rules
+            .iter()
+            .filter_map(|r| match r.condition {
+                Condition::PriceMove { minutes, .. } => Some(minutes as i64 * 60_000),
+                _ => None,
+            })
+            .max()
+            .unwrap_or(0);
+        Self { last_fired: vec![None; rules.len()], rules, prices: HashMap::new(), window_ms, reconnects: VecDeque::new() }
+    }
+
+    // After pricing, for every swap that is kept
+    pub fn on_swap(&mut self, swap: &SwapRecord, now: i64) -> Vec<Alert> {
+        let time = swap.timestamp.timestamp_millis();
+        let price = swap.price_usd.or(swap.price_token0_in_token1).filter(|p| p.is_finite() && *p > 0.0);
+        if price.is_some() && self.window_ms > 0 {
+            let samples = self.prices.entry(swap.pool_address.clone()).or_default();
+            let newest = samples.back().map_or(time, |(t, _)| time.max(*t));
+            while samples.front().is_some_and(|(t, _)| *t < newest - self.window_ms) {
+                samples.pop_front();
+            }
+        }
+        let samples = self.prices.get(&swap.pool_address);
+
+        let mut matched = Vec::new();
+        for (i, rule) in self.rules.iter().enumerate() {
+            let values = match rule.condition {
+                Condition::SwapUsd { min_usd } => match swap.volume_usd {
+                    Some(usd) if usd >= min_usd => swap_values(swap, vec![("usd", format!("{:.2}", usd))]),
+                    _ => continue,
+                },
+                Condition::PriceMove { percent, minutes } => {
+                    let (Some(price), Some(samples)) = (price, samples) else { continue };
+                    let since = time - minutes as i64 * 60_000;
+                    let window = samples.iter().filter(|(t, _)| *t >= since).map(|(_, p)| *p);
+                    let (low, high) = window.fold((f64::MAX, f64::MIN), |(low, high), p| (low.min(p), high.max(p)));
+                    if low > high {
+                        continue;
+                    }
+                    let moved = ((price / low - 1.0) * 100.0).max((1.0 - price / high) * 100.0);
+                    if moved < percent {
+                        continue;
+                    }
+                    swap_values(
+                        swap,
+                        vec![("percent", format!("{:.2}", moved)), ("minutes", minutes.to_string()), ("price", price.to_string())],
+                    )
+                }
+                Condition::Reconnects { .. } => continue,
+            };
+            matched.push((i, values));
+        }
+        if let Some(price) = price
+            && self.window_ms > 0
+        {
+            self.prices.entry(swap.pool_address.clone()).or_default().push_back((time, price));
+        }
+        self.fire(matched, now)
+    }
+
+    // After each reconnect of the live stream
+    pub fn on_reconnect(&mut self, now: i64) -> Vec<Alert> {
+        self.reconnects.push_back(now);
+        while self.reconnects.front().is_some_and(|t| *t < now - RECONNECT_WINDOW_MS) {
+            self.reconnects.pop_front();
+        }
+        let count = self.reconnects.len();
+        let matched = self
+            .rules
+            .iter()
+            .enumerate()
+            .filter(|(_, rule)| matches!(rule.condition, Condition::Reconnects { count: max } if count > max))
+            .map(|(i, _)| (i, vec![("count", count.to_string())]))
+            .collect();
+        self.fire(matched, now)
+    }
+
+    fn fire(&mut self, matched: Vec<(usize, Vec<(&'static str, String)>)>, now: i64) -> Vec<Alert> {
+        let mut alerts = Vec::new();
+        for (i, mut values) in matched {
+            let rule = &self.rules[i];
+            if self.last_fired[i].is_some_and(|last| now - last < rule.cooldown_seconds as i64 * 1000) {
+                metrics::WEBHOOKS.with_label_values(&[&rule.name, "suppressed"]).inc();
+                continue;
+            }
+            self.last_fired[i] = Some(now);
+            values.push(("rule", rule.name.clone()));
+            let text = render(rule.template.as_deref().unwrap_or(rule.condition.default_template()), &values);
+            let body = serde_json::json!({ "rule": rule.name, "text": text }).to_string();
+            alerts.push(Alert { rule: rule.name.clone(), url: rule.url.clone(), body });
+        }
+        alerts
+    }
+}
+
+// The values every swap alert has: {chain_id}, {pool}, {pair}, {tx_hash}, {block}
+fn swap_values(swap: &SwapRecord, mut values: Vec<(&'static str, String)>) -> Vec<(&'static str, String)> {
+    values.extend([
+        ("chain_id", swap.chain_id.to_string()),
+        ("pool", swap.pool_address.clone()),
+        ("pair", swap.pair.clone()),
+        ("tx_hash", swap.tx_hash.clone()),
+        ("block", swap.block_number.to_string()),
+    ]);
+    values
+}
+
+// Unknown {placeholders} stay as they are
+pub fn render(template: &str, values: &[(&str, String)]) -> String {
+    let mut text = template.to_string();
+    for (key, value) in values {
+        text = text.replace(&format!("{{{}}}", key), value);
+    }
+    text
+}
+
+struct Hook {
+    rules: Mutex<Rules>,
+    tx: mpsc::Sender<Alert>,
+}
+
+static HOOK: OnceLock<Hook> = OnceLock::new();
+
+fn now() -> i64 {
+    chrono::Utc::now().timestamp_millis()
+}
+
+fn send(hook: &Hook, alerts: Vec<Alert>) {
+    for alert in alerts {
+        info!("🔔 Webhook rule {} matched", alert.rule);
+        let rule = alert.rule.clone();
+        if hook.tx.try_send(alert).is_err() {
+            metrics::WEBHOOKS.with_label_values(&[&rule, "failed"]).inc();
+        }
+    }
+}
+
+// Called for each swap next to latest_price::report. Never waits on a delivery
+pub fn report_swap(swap: &SwapRecord) {
+    let Some(hook) = HOOK.get() else { return };
+    let alerts = hook.rules.lock().unwrap().on_swap(swap, now());
+    send(hook, alerts);
+}
+
+pub fn report_reconnect() {
+    let Some(hook) = HOOK.get() else { return };
+    let alerts = hook.rules.lock().unwrap().on_reconnect(now());
+    send(hook, alerts);
+}
+
+// Starts the delivery task, once per process
+pub fn start(rules: Vec<Rule>) -> Result<()> {
+    let client = alloy::transports::http::reqwest::Client::builder().timeout(REQUEST_TIMEOUT).build()?;
+    let (tx, rx) = mpsc::channel(CAPACITY);
+    info!("🔔 {} webhook rule(s): {}", rules.len(), rules.iter().map(|r| r.name.as_str()).collect::<Vec<_>>().join(", "));
+    if HOOK.set(Hook { rules: Mutex::new(Rules::new(rules)), tx }).is_err() {
+        return Ok(());
+    }
+    tokio::spawn(deliver_all(client, rx));
+    Ok(())
+}
+
+async fn deliver_all(client: alloy::transports::http::reqwest::Client, mut rx: mpsc::Receiver<Alert>) {
+    while let Some(alert) = rx.recv().await {
+        // Each alert retries on its own, a dead endpoint doesn't hold up the other rules
+        tokio::spawn(deliver(client.clone(), alert));
+    }
+}
+
+async fn deliver(client: alloy::transports::http::reqwest::Client, alert: Alert) {
+    let mut delay = RETRY_DELAY;
+    for attempt in 1..=ATTEMPTS {
+        let response = client.post(&alert.url).header("content-type", "application/json").body(alert.body.clone()).send().await;
+        let failure = match response {
+            Ok(response) if response.status().is_success() => {
+                metrics::WEBHOOKS.with_label_values(&[&alert.rule, "delivered"]).inc();
+                info!("🔔 Webhook {} delivered (attempt {})", alert.rule, attempt);
+                return;
+            }
+            Ok(response) => format!("HTTP {}", response.status()),
+            Err(e) => e.to_string(),
+        };
+        if attempt == ATTEMPTS {
+            metrics::WEBHOOKS.with_label_values(&[&alert.rule, "failed"]).inc();
+            error!("❌ Gave up on webhook {} after {} attempts: {}", alert.rule, ATTEMPTS, failure);
+            return;
+        }
+        metrics::WEBHOOKS.with_label_values(&[&alert.rule, "retried"]).inc();
+        warn!("⚠️ Webhook {} failed (attempt {}): {}, retrying in {:?}", alert.rule, attempt, failure, delay);
+        tokio::time::sleep(delay).await;
+        delay *= 2;
+    }
+}
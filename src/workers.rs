@@ -0,0 +1,185 @@
+use eyre::Result;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+use std::time::Instant;
+use tokio::sync::{mpsc, Mutex};
+use tracing::info;
+
+use crate::batch::{stamp_insert_version, Batch};
+use crate::checkpoint::Checkpoints;
+use crate::derived::Derived;
+use crate::metrics;
+use crate::records::IndexedEvent;
+use crate::sink::Sink;
+use crate::storage::{flush_batch, push_record, replay_spilled, sort_batch, WriterSettings};
+
+// Batches handed out in sequence and finished in any order. A result is released only once
+// every earlier batch has finished, so checkpoints move in batch order: a pool's checkpoint
+// never passes a block of an earlier batch that is still being inserted
+#[derive(Debug)]
+pub struct FlushOrder<T> {
+    next_seq: u64,
+    // Oldest batch not released yet
+    next_release: u64,
+    finished: BTreeMap<u64, T>,
+}
+
+impl<T> FlushOrder<T> {
+    pub fn new() -> Self {
+        Self { next_seq: 0, next_release: 0, finished: BTreeMap::new() }
+    }
+
+    pub fn start(&mut self) -> u64 {
+        self.next_seq += 1;
+        self.next_seq - 1
+    }
+
+    // The results now releasable, oldest first
+    pub fn finish(&mut self, seq: u64, result: T) -> Vec<T> {
+        self.finished.insert(seq, result);
+        let mut released = Vec::new();
+        while let Some(result) = self.finished.remove(&self.next_release) {
+            released.push(result);
+            self.next_release += 1;
+        }
+        released
+    }
+
+    // Batches started but not released
+    pub fn in_flight(&self) -> u64 {
+        self.next_seq - self.next_release
+    }
+}
+
+impl<T> Default for FlushOrder<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+struct Job {
+    seq: u64,
+    rows: Vec<IndexedEvent>,
+}
+
+struct Done {
+    seq: u64,
+    touched: HashMap<String, u64>,
+    ok: bool,
+}
+
+// INSERT_WORKERS > 1: batches are built, sorted and run through the derived state here, in
+// stream order, then inserted by whichever worker is free. Checkpoints follow FlushOrder.
+// With every worker busy the job queue is full, this task stops reading the channel and the
+// log handler waits, as with a single writer
+pub async fn run_insert_workers<S: Sink>(
+    sink: S,
+    mut rx: mpsc::Receiver<IndexedEvent>,
+    settings: WriterSettings,
+    mut derived: Derived,
+    mut checkpoints: Option<Checkpoints>,
+) -> Result<()> {
+    let settings = Arc::new(settings);
+    let (jobs_tx, jobs_rx) = mpsc::channel::<Job>(settings.workers);
+    let jobs_rx = Arc::new(Mutex::new(jobs_rx));
+    let (done_tx, mut done_rx) = mpsc::unbounded_channel::<Done>();
+
+    info!("👷 Inserting with {} workers", settings.workers);
+    for worker in 0..settings.workers {
+        let (sink, settings, jobs_rx, done_tx) = (sink.clone(), settings.clone(), jobs_rx.clone(), done_tx.clone());
+        tokio::spawn(async move {
+            let label = worker.to_string();
+            loop {
+                let job = jobs_rx.lock().await.recv().await;
+                let Some(Job { seq, mut rows }) = job else { break };
+                let touched = Checkpoints::touched(&rows);
+                let count = rows.len() as u64;
+                let ok = flush_batch(&sink, &settings, &mut rows).await.is_ok();
+                if ok {
+                    metrics::WORKER_ROWS.with_label_values(&[&label]).inc_by(count);
+                }
+                metrics::WORKER_BATCHES.with_label_values(&[&label]).inc();
+                if done_tx.send(Done { seq, touched, ok }).is_err() {
+                    break;
+                }
+            }
+        });
+    }
+    drop(done_tx);
+
+    let mut batch = Batch::new(settings.batch_size, settings.max_age);
+    let mut order = FlushOrder::new();
+    let mut failed = false;
+    let mut spill_pending = settings.spill.files().is_ok_and(|files| !files.is_empty());
+    if spill_pending {
+        spill_pending = !replay_spilled(&sink, &settings).await;
+    }
+
+    let mut open = true;
+    while open || order.in_flight() > 0 {
+        let deadline = batch.deadline();
+        let due = async {
+            match deadline {
+                Some(deadline) => tokio::time::sleep_until(deadline.into()).await,
+                None => std::future::pending().await,
+            }
+        };
+
+        tokio::select! {
+            record = rx.recv(), if open => match record {
+                Some(record) => {
+                    push_record(&mut batch, record);
+                    while !batch.is_full() && let Ok(record) = rx.try_recv() {
+                        push_record(&mut batch, record);
+                    }
+                }
+                None => open = false,
+            },
+            _ = due => {}
+            done = done_rx.recv() => {
+                let Some(Done { seq, touched, ok }) = done else { break };
+                let mut all_ok = true;
+                for (touched, ok) in order.finish(seq, (touched, ok)) {
+                    all_ok &= ok;
+                    if let Some(checkpoints) = checkpoints.as_mut() {
+                        match ok {
+                            true if settings.acknowledged() => checkpoints.advance(touched).await,
+                            true => {}
+                            false => checkpoints.freeze(touched),
+                        }
+                    }
+                }
+                failed |= !ok;
+                if !ok {
+                    spill_pending = true;
+                } else if all_ok && spill_pending {
+                    spill_pending = !replay_spilled(&sink, &settings).await;
+                }
+            }
+        }
+
+        // Once the channel is closed the partial batch goes out right away
+        if batch.is_due(Instant::now()) || (!open && !batch.events.is_empty()) {
+            if !batch.is_full() && open && !settings.max_age.is_zero() {
+                metrics::TIMED_FLUSHES.inc();
+            }
+            let mut rows = batch.take();
+            sort_batch(&mut rows);
+            derived.apply(&mut rows);
+            stamp_insert_version(&mut rows);
+            let seq = order.start();
+            if jobs_tx.send(Job { seq, rows }).await.is_err() {
+                eyre::bail!("Insert workers are gone");
+            }
+        }
+    }
+
+    drop(jobs_tx);
+    if let Some(checkpoints) = checkpoints.as_mut() {
+        checkpoints.finish().await;
+    }
+    match failed {
+        true => Err(eyre::eyre!("Some batches failed to insert, see the log")),
+        false => Ok(()),
+    }
+}
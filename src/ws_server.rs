@@ -0,0 +1,203 @@
+use eyre::Result;
+use futures_util::{SinkExt, StreamExt};
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::broadcast::{self, error::RecvError};
+use tokio_tungstenite::tungstenite::handshake::server::{Callback, ErrorResponse, Request, Response};
+use tokio_tungstenite::tungstenite::http::StatusCode;
+use tokio_tungstenite::tungstenite::{Message, Utf8Bytes};
+use tracing::{error, info, warn};
+
+use crate::metrics;
+use crate::records::SwapRecord;
+
+// Defaults for WS_SERVER_MAX_CLIENTS / WS_SERVER_SEND_TIMEOUT_SECONDS / WS_SERVER_BUFFER
+pub const DEFAULT_WS_MAX_CLIENTS: usize = 100;
+pub const DEFAULT_WS_SEND_TIMEOUT: Duration = Duration::from_secs(5);
+pub const DEFAULT_WS_BUFFER: usize = 1_024;
+
+// The only path served
+const PATH: &str = "/swaps";
+
+// WS_SERVER_ADDR and the rest
+#[derive(Debug, Clone)]
+pub struct WsServerSettings {
+    pub addr: SocketAddr,
+    // Past that, new connections get a 503
+    pub max_clients: usize,
+    // A client that takes longer to accept one message is disconnected
+    pub send_timeout: Duration,
+    // Swaps a client can fall behind before it loses the oldest
+    pub buffer: usize,
+}
+
+// ?pool=0x…&min_usd=…: pool can repeat (or be a comma list), any case
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct WsFilter {
+    pub pools: Vec<String>,
+    pub min_usd: Option<f64>,
+}
+
+impl WsFilter {
+    pub fn parse(query: Option<&str>) -> Result<Self, String> {
+        let mut filter = WsFilter::default();
+        for (key, value) in url::form_urlencoded::parse(query.unwrap_or_default().as_bytes()) {
+            match key.as_ref() {
+                "pool" => filter.pools.extend(value.split(',').map(str::trim).filter(|p| !p.is_empty()).map(str::to_string)),
+                "min_usd" => match value.trim().parse::<f64>() {
+                    Ok(usd) if usd.is_finite() => filter.min_usd = Some(usd),
+                    _ => return Err(format!("Invalid min_usd '{}'", value)),
+                },
+                other => return Err(format!("Unknown parameter '{}'", other)),
+            }
+        }
+        Ok(filter)
+    }
+
+    // A min_usd needs a priced swap
+    pub fn matches(&self, pool: &str, volume_usd: Option<f64>) -> bool {
+        (self.pools.is_empty() || self.pools.iter().any(|p| p.eq_ignore_ascii_case(pool)))
+            && self.min_usd.is_none_or(|min| volume_usd.is_some_and(|usd| usd >= min))
+    }
+}
+
+// A swap as every client gets it, serialized once
+#[derive(Debug, Clone)]
+struct Frame {
+    pool: String,
+    volume_usd: Option<f64>,
+    json: Utf8Bytes,
+}
+
+static TX: OnceLock<broadcast::Sender<Arc<Frame>>> = OnceLock::new();
+
+// Called for each swap next to latest_price::report. Never waits: a client that fell behind
+// loses its oldest swaps
+pub fn report(swap: &SwapRecord) {
+    let Some(tx) = TX.get() else { return };
+    if tx.receiver_count() == 0 {
+        return;
+    }
+    let Ok(json) = serde_json::to_string(swap) else { return };
+    // Only fails without clients
+    let _ = tx.send(Arc::new(Frame { pool: swap.pool_address.clone(), volume_usd: swap.volume_usd, json: json.into() }));
+}
+
+// Binds WS_SERVER_ADDR (port 0 picks one) and serves ws://<addr>/swaps in the background, once
+// per process. Returns the bound address
+pub async fn start(settings: &WsServerSettings) -> Result<SocketAddr> {
+    let listener = TcpListener::bind(settings.addr).await.map_err(|e| eyre::eyre!("Failed to bind WS_SERVER_ADDR {}: {}", settings.addr, e))?;
+    let addr = listener.local_addr()?;
+    let (tx, _) = broadcast::channel(settings.buffer.max(1));
+    if TX.set(tx.clone()).is_err() {
+        eyre::bail!("The WebSocket server is already running");
+    }
+    info!("📣 Swaps on ws://{}{} (up to {} clients)", addr, PATH, settings.max_clients);
+    tokio::spawn(accept_all(listener, tx, settings.clone()));
+    Ok(addr)
+}
+
+async fn accept_all(listener: TcpListener, tx: broadcast::Sender<Arc<Frame>>, settings: WsServerSettings) {
+    let clients = Arc::new(AtomicUsize::new(0));
+    loop {
+        match listener.accept().await {
+            Ok((stream, peer)) => {
+                tokio::spawn(serve(stream, peer, tx.clone(), clients.clone(), settings.clone()));
+            }
+            Err(e) => {
+                error!("❌ WebSocket accept failed: {}", e);
+                tokio::time::sleep(Duration::from_secs(1)).await;
+            }
+        }
+    }
+}
+
+fn reject(status: StatusCode, reason: String) -> ErrorResponse {
+    let mut response = ErrorResponse::new(Some(reason));
+    *response.status_mut() = status;
+    response
+}
+
+// Counted in indexer_ws_clients from the handshake until the client is gone
+struct Client(Arc<AtomicUsize>);
+
+impl Drop for Client {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+        metrics::WS_CLIENTS.dec();
+    }
+}
+
+// Checks the path and the client limit, reads the filter. Admitted clients are counted
+struct Handshake<'a> {
+    clients: &'a Arc<AtomicUsize>,
+    max_clients: usize,
+    filter: &'a mut WsFilter,
+    client: &'a mut Option<Client>,
+}
+
+impl Callback for Handshake<'_> {
+    fn on_request(self, request: &Request, response: Response) -> Result<Response, ErrorResponse> {
+        if request.uri().path() != PATH {
+            return Err(reject(StatusCode::NOT_FOUND, format!("Swaps are served on {}", PATH)));
+        }
+        *self.filter = WsFilter::parse(request.uri().query()).map_err(|e| reject(StatusCode::BAD_REQUEST, e))?;
+        if self.clients.fetch_add(1, Ordering::SeqCst) >= self.max_clients {
+            self.clients.fetch_sub(1, Ordering::SeqCst);
+            return Err(reject(StatusCode::SERVICE_UNAVAILABLE, format!("Already serving {} clients", self.max_clients)));
+        }
+        metrics::WS_CLIENTS.inc();
+        *self.client = Some(Client(self.clients.clone()));
+        Ok(response)
+    }
+}
+
+async fn serve(stream: TcpStream, peer: SocketAddr, tx: broadcast::Sender<Arc<Frame>>, clients: Arc<AtomicUsize>, settings: WsServerSettings) {
+    let mut filter = WsFilter::default();
+    let mut client = None;
+    let callback = Handshake { clients: &clients, max_clients: settings.max_clients, filter: &mut filter, client: &mut client };
+    let handshake = tokio_tungstenite::accept_hdr_async(stream, callback);
+    let ws = match tokio::time::timeout(settings.send_timeout, handshake).await {
+        Ok(Ok(ws)) => ws,
+        Ok(Err(e)) => {
+            warn!("⚠️ WebSocket handshake from {} failed: {}", peer, e);
+            return;
+        }
+        Err(_) => {
+            warn!("⚠️ WebSocket handshake from {} timed out", peer);
+            return;
+        }
+    };
+    let Some(_client) = client else { return };
+    let mut rx = tx.subscribe();
+    info!("📣 WebSocket client {} connected ({} pool(s), min_usd {:?})", peer, filter.pools.len(), filter.min_usd);
+
+    let (mut sink, mut incoming) = ws.split();
+    let reason = loop {
+        tokio::select! {
+            frame = rx.recv() => match frame {
+                Ok(frame) if filter.matches(&frame.pool, frame.volume_usd) => {
+                    match tokio::time::timeout(settings.send_timeout, sink.send(Message::Text(frame.json.clone()))).await {
+                        Ok(Ok(())) => {}
+                        Ok(Err(e)) => break e.to_string(),
+                        Err(_) => break format!("send took over {:?}", settings.send_timeout),
+                    }
+                }
+                Ok(_) => {}
+                Err(RecvError::Lagged(n)) => metrics::WS_DROPPED.inc_by(n),
+                Err(RecvError::Closed) => break "server stopped".to_string(),
+            },
+            // Clients only talk to close; pings are answered by the next write
+            message = incoming.next() => match message {
+                Some(Ok(Message::Close(_))) | None => break "closed".to_string(),
+                Some(Ok(_)) => {}
+                Some(Err(e)) => break e.to_string(),
+            },
+        }
+    };
+    let _ = tokio::time::timeout(settings.send_timeout, sink.close()).await;
+    info!("📣 WebSocket client {} disconnected: {}", peer, reason);
+}
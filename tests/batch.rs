@@ -0,0 +1,95 @@
+use std::time::{Duration, Instant};
+use uniswap_indexer::batch::{next_insert_version, stamp_insert_version, Batch};
+use uniswap_indexer::records::{IndexedEvent, SwapRecord};
+
+fn reverted(block_number: u64) -> IndexedEvent {
+    IndexedEvent::Reverted { block_number, block_hash: String::new() }
+}
+
+// When each flush happens and how many rows it carries, for rows arriving at the given
+// offsets. Mirrors the writer: a deadline that passes between two rows flushes at the deadline
+fn flushes(batch: &mut Batch, start: Instant, arrivals: impl IntoIterator<Item = Duration>) -> Vec<(Duration, usize)> {
+    let mut flushed = Vec::new();
+    for (i, at) in arrivals.into_iter().enumerate() {
+        let now = start + at;
+        if let Some(deadline) = batch.deadline() && deadline <= now {
+            flushed.push((deadline - start, batch.take().len()));
+        }
+        batch.push(reverted(i as u64), now);
+        if batch.is_due(now) {
+            flushed.push((at, batch.take().len()));
+        }
+    }
+    if let Some(deadline) = batch.deadline() {
+        flushed.push((deadline - start, batch.take().len()));
+    }
+    flushed
+}
+
+// 1000 swaps/s for 10s: full batches every half second, the timer never fires
+#[test]
+fn sustained_load_flushes_on_size() {
+    let start = Instant::now();
+    let mut batch = Batch::new(500, Duration::from_secs(5));
+    let flushed = flushes(&mut batch, start, (0..10_000).map(Duration::from_millis));
+
+    assert_eq!(flushed.len(), 20);
+    assert!(flushed.iter().all(|(_, rows)| *rows == 500));
+    assert_eq!(flushed[0].0, Duration::from_millis(499));
+    assert_eq!(flushed[19].0, Duration::from_millis(9999));
+}
+
+// 1 swap/hour: every swap goes out on its own once it's max_age old, not an hour later
+#[test]
+fn quiet_pool_flushes_on_age() {
+    let start = Instant::now();
+    let mut batch = Batch::new(500, Duration::from_secs(5));
+    let flushed = flushes(&mut batch, start, (0..4).map(|h| Duration::from_secs(h * 3600)));
+
+    let expected: Vec<(Duration, usize)> = (0..4).map(|h| (Duration::from_secs(h * 3600 + 5), 1)).collect();
+    assert_eq!(flushed, expected);
+}
+
+#[test]
+fn age_restarts_after_a_flush() {
+    let start = Instant::now();
+    let mut batch = Batch::new(10, Duration::from_secs(5));
+    assert_eq!(batch.deadline(), None);
+
+    batch.push(reverted(1), start);
+    batch.push(reverted(2), start + Duration::from_secs(3));
+    assert!(!batch.is_due(start + Duration::from_secs(4)));
+    assert!(batch.is_due(start + Duration::from_secs(5)));
+    assert_eq!(batch.take().len(), 2);
+
+    assert_eq!(batch.deadline(), None);
+    batch.push(reverted(3), start + Duration::from_secs(6));
+    assert_eq!(batch.deadline(), Some(start + Duration::from_secs(11)));
+}
+
+#[test]
+fn insert_versions_only_go_up() {
+    let versions: Vec<u64> = (0..1000).map(|_| next_insert_version()).collect();
+    assert!(versions.windows(2).all(|w| w[0] < w[1]));
+    assert!(versions[0] > 1_700_000_000_000_000);
+}
+
+// One version per batch, later batches win the ReplacingMergeTree merge
+#[test]
+fn batches_are_stamped_in_order() {
+    let swap = || IndexedEvent::Swap(Box::default());
+    let version = |event: &IndexedEvent| match event {
+        IndexedEvent::Swap(swap) => swap.insert_version,
+        _ => unreachable!(),
+    };
+
+    let mut first = vec![swap(), reverted(1), swap()];
+    let mut second = vec![swap()];
+    stamp_insert_version(&mut first);
+    stamp_insert_version(&mut second);
+
+    assert_eq!(version(&first[0]), version(&first[2]));
+    assert!(version(&second[0]) > version(&first[0]));
+    let default: SwapRecord = Default::default();
+    assert_eq!(default.insert_version, 0);
+}
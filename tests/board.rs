@@ -0,0 +1,34 @@
+use std::time::Duration;
+use uniswap_indexer::board::PriceBoard;
+
+const MAX_AGE: Duration = Duration::from_secs(300);
+const T: i64 = 1_700_000_000_000;
+
+#[test]
+fn empty_board_has_no_price() {
+    assert_eq!(PriceBoard::new().get(T, MAX_AGE), None);
+}
+
+#[test]
+fn price_goes_stale_after_max_age() {
+    let board = PriceBoard::new();
+    board.update(2500.0, T);
+
+    assert_eq!(board.get(T + 300_000, MAX_AGE), Some(2500.0));
+    assert_eq!(board.get(T + 300_001, MAX_AGE), None);
+    assert_eq!(board.get(T - 300_001, MAX_AGE), None);
+}
+
+// A backfilled reference swap doesn't roll the live price back, junk prices are ignored
+#[test]
+fn only_newer_prices_replace_the_board() {
+    let board = PriceBoard::new();
+    board.update(2500.0, T);
+    board.update(1800.0, T - 60_000);
+    board.update(f64::NAN, T + 1);
+    board.update(0.0, T + 1);
+    assert_eq!(board.get(T, MAX_AGE), Some(2500.0));
+
+    board.update(2510.0, T + 12_000);
+    assert_eq!(board.get(T + 12_000, MAX_AGE), Some(2510.0));
+}
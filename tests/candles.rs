@@ -0,0 +1,70 @@
+mod common;
+
+use common::swap;
+use std::time::Duration;
+use uniswap_indexer::candles::{CandleSettings, Candles};
+use uniswap_indexer::records::{CandleRecord, IndexedEvent, SwapRecord};
+
+// amount0 of the pool's token at price, the other side paid in full
+fn trade(pool: &str, seconds: i64, price: f64, amount0: f64) -> IndexedEvent {
+    let swap = swap().pool(pool).at(seconds).price(price).volume((amount0 * price).abs()).record();
+    IndexedEvent::Swap(Box::new(SwapRecord { amount0: Some(amount0), amount1: Some(-amount0 * price), ..swap }))
+}
+
+fn candles(fill_gaps: bool) -> Candles {
+    Candles::new(CandleSettings { enabled: true, grace: Duration::from_secs(10), fill_gaps })
+}
+
+fn rows(events: Vec<IndexedEvent>) -> Vec<CandleRecord> {
+    events
+        .into_iter()
+        .map(|e| match e {
+            IndexedEvent::Candle(c) => c,
+            other => panic!("not a candle: {:?}", other),
+        })
+        .collect()
+}
+
+#[test]
+fn minute_closes_after_the_grace_period() {
+    let mut c = candles(false);
+    assert!(c.apply(&[trade("a", 0, 10.0, 1.0), trade("a", 20, 12.0, -2.0), trade("a", 40, 9.0, 1.0)]).is_empty());
+
+    // Next minute started, but we're still inside the grace period: a late log gets in
+    assert!(c.apply(&[trade("a", 65, 11.0, 1.0), trade("a", 50, 8.0, 1.0)]).is_empty());
+
+    let done = rows(c.apply(&[trade("a", 71, 11.0, 1.0)]));
+    assert_eq!(done.len(), 1);
+    let m = &done[0];
+    assert_eq!((m.minute, m.open, m.high, m.low, m.close, m.swaps), (0, 10.0, 12.0, 8.0, 8.0, 4));
+    assert_eq!(m.volume0, 5.0);
+    assert_eq!(m.volume_usd, Some(10.0 + 24.0 + 9.0 + 8.0));
+
+    // Too late now
+    assert!(c.apply(&[trade("a", 59, 100.0, 1.0)]).is_empty());
+    let next = rows(c.apply(&[trade("a", 200, 11.0, 1.0)]));
+    assert_eq!(next.iter().map(|c| (c.minute, c.swaps)).collect::<Vec<_>>(), vec![(60_000, 2)]);
+}
+
+#[test]
+fn gaps_are_filled_only_when_asked() {
+    let mut gapless = candles(true);
+    let mut sparse = candles(false);
+    for c in [&mut gapless, &mut sparse] {
+        c.apply(&[trade("a", 0, 10.0, 1.0)]);
+    }
+
+    let batch = [trade("a", 250, 20.0, 1.0)];
+    let filled = rows(gapless.apply(&batch));
+    assert_eq!(filled.iter().map(|c| (c.minute / 1000, c.close, c.swaps)).collect::<Vec<_>>(), vec![(0, 10.0, 1), (60, 10.0, 0), (120, 10.0, 0), (180, 10.0, 0)]);
+    assert_eq!(filled[1].volume_usd, None);
+
+    let plain = rows(sparse.apply(&batch));
+    assert_eq!(plain.iter().map(|c| c.minute).collect::<Vec<_>>(), vec![0]);
+}
+
+#[test]
+fn off_by_default() {
+    let mut c = Candles::new(CandleSettings::default());
+    assert!(c.apply(&[trade("a", 0, 1.0, 1.0), trade("a", 600, 1.0, 1.0)]).is_empty());
+}
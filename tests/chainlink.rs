@@ -0,0 +1,30 @@
+use alloy::primitives::{address, Address};
+use bigdecimal::BigDecimal;
+use std::time::Duration;
+use uniswap_indexer::chainlink::{FeedPrice, PriceCache};
+use uniswap_indexer::config::parse_chainlink_feeds;
+
+const WETH: Address = address!("c02aaa39b223fe8d0a0e5c4f27ead9083c756cc2");
+const ETH_USD: Address = address!("5f4ec3df9cbd43714fe2740f5e3616155c5b8419");
+
+#[test]
+fn stale_rounds_count_as_missing() {
+    let cache = PriceCache::new(Duration::from_secs(3600));
+    cache.insert(WETH, FeedPrice { usd: BigDecimal::from(2500), updated_at: 1_700_000_000 });
+
+    assert_eq!(cache.get(WETH, 1_700_000_000 + 3600), Some(BigDecimal::from(2500)));
+    assert_eq!(cache.get(WETH, 1_700_000_000 + 3601), None);
+    // A backfilled swap long before the round doesn't get today's price either
+    assert_eq!(cache.get(WETH, 1_600_000_000), None);
+    assert_eq!(cache.get(ETH_USD, 1_700_000_000), None);
+}
+
+#[test]
+fn feeds_parse_symbols_and_addresses() {
+    let feeds = parse_chainlink_feeds(&format!("WETH={}, {}={}", ETH_USD, ETH_USD, WETH)).unwrap();
+    assert_eq!(feeds.get(&WETH), Some(&ETH_USD));
+    assert_eq!(feeds.get(&ETH_USD), Some(&WETH));
+
+    assert!(parse_chainlink_feeds("WETH").is_err());
+    assert!(parse_chainlink_feeds("WETH=0x12").is_err());
+}
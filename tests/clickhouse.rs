@@ -0,0 +1,91 @@
+// Against a live ClickHouse (docker compose up, CLICKHOUSE_* from the environment):
+//   cargo test --test clickhouse -- --ignored
+use bigdecimal::BigDecimal;
+use clickhouse::Row;
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+use uniswap_indexer::config::clickhouse_from_env;
+use uniswap_indexer::migrations::schema;
+use uniswap_indexer::price::{format_price_exact, to_decimal_38_18};
+use uniswap_indexer::storage::connect_clickhouse;
+
+#[derive(Debug, Serialize, Deserialize, Row)]
+struct PriceRow {
+    id: u32,
+    price_usd_decimal: Option<i128>,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize, Row)]
+struct LiquidityRow {
+    id: u32,
+    liquidity: Option<u128>,
+}
+
+fn swap_column_type(column: &str) -> &'static str {
+    schema("uniswap_swaps").unwrap().columns.iter().find(|(c, _)| *c == column).unwrap().1
+}
+
+// What ClickHouse stores for a price_usd_decimal is what price_exact says
+#[tokio::test]
+#[ignore]
+async fn decimal_prices_round_trip() {
+    let client = connect_clickhouse(&clickhouse_from_env()).await.unwrap();
+    let table = format!("test_price_decimal_{}", std::process::id());
+    let ty = swap_column_type("price_usd_decimal");
+    client
+        .query(&format!("CREATE TABLE {} (id UInt32, price_usd_decimal {}) ENGINE = Memory", table, ty))
+        .execute()
+        .await
+        .unwrap();
+
+    let prices = ["3133.65", "0.0000000000000000025", "0.000000000001234567", "99999999999999999999.999999999999999999", "-42.1"];
+    let mut insert = client.insert::<PriceRow>(&table).await.unwrap();
+    for (id, price) in prices.iter().enumerate() {
+        let price = to_decimal_38_18(&BigDecimal::from_str(price).unwrap());
+        insert.write(&PriceRow { id: id as u32, price_usd_decimal: price }).await.unwrap();
+    }
+    insert.write(&PriceRow { id: prices.len() as u32, price_usd_decimal: None }).await.unwrap();
+    insert.end().await.unwrap();
+
+    let stored: Vec<String> = client
+        .query(&format!("SELECT ifNull(toString(price_usd_decimal), 'NULL') FROM {} ORDER BY id", table))
+        .fetch_all()
+        .await
+        .unwrap();
+    client.query(&format!("DROP TABLE {}", table)).execute().await.unwrap();
+
+    let mut expected: Vec<String> = prices
+        .iter()
+        .map(|p| format_price_exact(&BigDecimal::from_str(p).unwrap()).trim_end_matches('0').trim_end_matches('.').to_string())
+        .collect();
+    expected.push("NULL".to_string());
+    assert_eq!(stored, expected);
+}
+
+// uint128 liquidity comes back exactly, up to u128::MAX
+#[tokio::test]
+#[ignore]
+async fn liquidity_round_trips_at_the_top_of_u128() {
+    let client = connect_clickhouse(&clickhouse_from_env()).await.unwrap();
+    let table = format!("test_liquidity_{}", std::process::id());
+    client
+        .query(&format!("CREATE TABLE {} (id UInt32, liquidity {}) ENGINE = Memory", table, swap_column_type("liquidity")))
+        .execute()
+        .await
+        .unwrap();
+
+    let rows = [Some(u128::MAX), Some(u128::MAX - 1), Some(1 << 64), Some(0), None];
+    let mut insert = client.insert::<LiquidityRow>(&table).await.unwrap();
+    for (id, liquidity) in rows.iter().enumerate() {
+        insert.write(&LiquidityRow { id: id as u32, liquidity: *liquidity }).await.unwrap();
+    }
+    insert.end().await.unwrap();
+
+    let stored: Vec<LiquidityRow> = client.query(&format!("SELECT ?fields FROM {} ORDER BY id", table)).fetch_all().await.unwrap();
+    let text: String = client.query(&format!("SELECT toString(liquidity) FROM {} WHERE id = 0", table)).fetch_one().await.unwrap();
+    client.query(&format!("DROP TABLE {}", table)).execute().await.unwrap();
+
+    let expected: Vec<LiquidityRow> = rows.iter().enumerate().map(|(id, liquidity)| LiquidityRow { id: id as u32, liquidity: *liquidity }).collect();
+    assert_eq!(stored, expected);
+    assert_eq!(text, u128::MAX.to_string());
+}
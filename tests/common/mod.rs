@@ -0,0 +1,74 @@
+// Shared by the integration tests, each file pulls it in with `mod common;` and uses what it needs
+#![allow(dead_code)]
+
+use chrono::DateTime;
+use uniswap_indexer::records::{IndexedEvent, SwapRecord};
+
+// A swap row with only what the test cares about set: chain 1, pool 0xpool, the rest default.
+// Columns without a setter go through struct update, `SwapRecord { tick: Some(1), ..swap().record() }`
+#[derive(Debug, Clone)]
+pub struct SwapBuilder(SwapRecord);
+
+pub fn swap() -> SwapBuilder {
+    SwapBuilder(SwapRecord { chain_id: 1, pool_address: "0xpool".to_string(), ..Default::default() })
+}
+
+impl SwapBuilder {
+    pub fn chain(mut self, chain_id: u64) -> Self {
+        self.0.chain_id = chain_id;
+        self
+    }
+
+    pub fn pool(mut self, pool: &str) -> Self {
+        self.0.pool_address = pool.to_string();
+        self
+    }
+
+    pub fn block(mut self, block_number: u64) -> Self {
+        self.0.block_number = block_number;
+        self
+    }
+
+    pub fn log_index(mut self, log_index: u64) -> Self {
+        self.0.log_index = log_index;
+        self
+    }
+
+    pub fn block_hash(mut self, block_hash: &str) -> Self {
+        self.0.block_hash = block_hash.to_string();
+        self
+    }
+
+    pub fn tx(mut self, tx_hash: &str) -> Self {
+        self.0.tx_hash = tx_hash.to_string();
+        self
+    }
+
+    // Block time, Unix seconds
+    pub fn at(self, seconds: i64) -> Self {
+        self.at_millis(seconds * 1000)
+    }
+
+    pub fn at_millis(mut self, millis: i64) -> Self {
+        self.0.timestamp = DateTime::from_timestamp_millis(millis).unwrap();
+        self
+    }
+
+    pub fn price(mut self, price_usd: f64) -> Self {
+        self.0.price_usd = Some(price_usd);
+        self
+    }
+
+    pub fn volume(mut self, volume_usd: f64) -> Self {
+        self.0.volume_usd = Some(volume_usd);
+        self
+    }
+
+    pub fn record(self) -> SwapRecord {
+        self.0
+    }
+
+    pub fn event(self) -> IndexedEvent {
+        IndexedEvent::Swap(Box::new(self.0))
+    }
+}
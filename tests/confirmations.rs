@@ -0,0 +1,66 @@
+mod common;
+
+use common::swap;
+use futures_util::FutureExt;
+use std::time::Duration;
+use tokio::sync::{mpsc, oneshot, watch};
+use uniswap_indexer::confirmations::run_confirmer;
+use uniswap_indexer::indexer::{run_ordered, Pending, ENRICH_WINDOW};
+use uniswap_indexer::records::IndexedEvent;
+
+fn reverted(block_number: u64, block_hash: &str) -> IndexedEvent {
+    IndexedEvent::Reverted { block_number, block_hash: block_hash.to_string() }
+}
+
+fn ready(event: IndexedEvent) -> Pending {
+    Box::pin(std::future::ready(Some(event)))
+}
+
+async fn drain(mut rx: mpsc::Receiver<IndexedEvent>) -> Vec<(u64, String)> {
+    let mut out = Vec::new();
+    while let Some(event) = rx.recv().await {
+        out.push((event.block_number(), event.block_hash().unwrap_or_default().to_string()));
+    }
+    out
+}
+
+// The block that replaced a reverted one at the same height keeps its records
+#[tokio::test]
+async fn only_the_reverted_hash_is_evicted() {
+    let (tx, rx) = mpsc::channel(16);
+    let (out_tx, out_rx) = mpsc::channel(16);
+    let (_heads_tx, heads_rx) = watch::channel(0);
+    let confirmer = tokio::spawn(run_confirmer(rx, out_tx, 3, heads_rx));
+
+    tx.send(swap().block(10).block_hash("0xa").log_index(0).event()).await.unwrap();
+    tx.send(swap().block(10).block_hash("0xb").log_index(0).event()).await.unwrap();
+    tx.send(reverted(10, "0xa")).await.unwrap();
+    tx.send(swap().block(11).block_hash("0xc").log_index(0).event()).await.unwrap();
+    drop(tx);
+    confirmer.await.unwrap().unwrap();
+
+    assert_eq!(drain(out_rx).await, vec![(10, "0xb".to_string()), (11, "0xc".to_string())]);
+}
+
+// A swap still waiting on its receipt when its block is reverted is evicted all the same:
+// the revert queues behind it
+#[tokio::test]
+async fn a_revert_waits_for_the_swaps_in_flight() {
+    let (pending_tx, pending_rx) = mpsc::channel(ENRICH_WINDOW);
+    let (tx, rx) = mpsc::channel(16);
+    let (out_tx, out_rx) = mpsc::channel(16);
+    let (_heads_tx, heads_rx) = watch::channel(0);
+    tokio::spawn(run_ordered(pending_rx, tx));
+    let confirmer = tokio::spawn(run_confirmer(rx, out_tx, 3, heads_rx));
+
+    let (receipt_tx, receipt_rx) = oneshot::channel::<()>();
+    pending_tx.send(Box::pin(receipt_rx.map(|_| Some(swap().block(10).block_hash("0xa").log_index(0).event())))).await.unwrap();
+    pending_tx.send(ready(reverted(10, "0xa"))).await.unwrap();
+    pending_tx.send(ready(swap().block(10).block_hash("0xb").log_index(1).event())).await.unwrap();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    receipt_tx.send(()).unwrap();
+    drop(pending_tx);
+    confirmer.await.unwrap().unwrap();
+
+    assert_eq!(drain(out_rx).await, vec![(10, "0xb".to_string())]);
+}
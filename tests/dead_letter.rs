@@ -0,0 +1,49 @@
+use alloy::primitives::{address, b256, Bytes, Log as PrimitiveLog};
+use alloy::rpc::types::Log;
+use uniswap_indexer::dead_letter::{decode_failure, rejected_row};
+use uniswap_indexer::records::SwapRecord;
+
+#[test]
+fn decode_failures_keep_the_raw_log() {
+    let topic = b256!("c42079f94a6350d7e6235f29174924f928cc2ac818eb64fed8004e115fbcca67");
+    let log = Log {
+        inner: PrimitiveLog::new_unchecked(
+            address!("88e6a0c2ddd26feeb64f039a2c41296fcb3f5640"),
+            vec![topic],
+            Bytes::from(vec![0xde, 0xad]),
+        ),
+        block_number: Some(17_000_000),
+        transaction_hash: Some(b256!("1111111111111111111111111111111111111111111111111111111111111111")),
+        log_index: Some(7),
+        ..Default::default()
+    };
+
+    let record = decode_failure(&log, "Swap(...): buffer overrun".to_string());
+    assert_eq!(record.category, "decode");
+    assert_eq!((record.block_number, record.log_index), (17_000_000, 7));
+    assert_eq!(record.address, "0x88e6a0c2ddd26feeb64f039a2c41296fcb3f5640");
+    assert_eq!(record.topics, vec![format!("{:?}", topic)]);
+    assert_eq!(record.data, "0xdead");
+    assert!(record.target_table.is_empty());
+}
+
+// The row is kept whole as JSON, u128 columns included
+#[test]
+fn rejected_rows_keep_the_row_and_its_log() {
+    let swap = SwapRecord {
+        chain_id: 42161,
+        block_number: 9,
+        log_index: 3,
+        tx_hash: "0xabc".to_string(),
+        pool_address: "0xpool".to_string(),
+        liquidity: Some(u128::MAX),
+        ..Default::default()
+    };
+    let record = rejected_row("uniswap_swaps", &swap, &eyre::eyre!("Code: 53. DB::Exception: Type mismatch"));
+    assert_eq!(record.category, "insert");
+    assert_eq!(record.target_table, "uniswap_swaps");
+    assert_eq!((record.chain_id, record.block_number, record.log_index), (42161, 9, 3));
+    assert_eq!((record.tx_hash.as_str(), record.address.as_str()), ("0xabc", "0xpool"));
+    assert!(record.data.contains(&format!("\"liquidity\":{}", u128::MAX)));
+    assert!(record.error.contains("Type mismatch"));
+}
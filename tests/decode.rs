@@ -0,0 +1,27 @@
+use alloy::primitives::{address, Bytes, Log as PrimitiveLog};
+use alloy::rpc::types::Log;
+use uniswap_indexer::decode::log_position;
+
+fn log(block_number: Option<u64>, transaction_index: Option<u64>, log_index: Option<u64>) -> Log {
+    Log {
+        inner: PrimitiveLog::new_unchecked(address!("88e6a0c2ddd26feeb64f039a2c41296fcb3f5640"), vec![], Bytes::new()),
+        block_number,
+        transaction_index,
+        log_index,
+        ..Default::default()
+    }
+}
+
+#[test]
+fn mined_logs_have_a_position() {
+    let at = log_position(&log(Some(17_000_000), Some(3), Some(7)), "Mint").unwrap();
+    assert_eq!((at.block_number, at.transaction_index, at.log_index), (17_000_000, 3, 7));
+}
+
+// Pending logs get no row rather than block 0, log index 0
+#[test]
+fn pending_logs_are_skipped() {
+    assert!(log_position(&log(None, None, None), "Mint").is_none());
+    assert!(log_position(&log(Some(17_000_000), None, Some(7)), "Burn").is_none());
+    assert!(log_position(&log(Some(17_000_000), Some(3), None), "Collect").is_none());
+}
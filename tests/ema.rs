@@ -0,0 +1,72 @@
+mod common;
+
+use common::swap;
+use std::collections::HashMap;
+use std::time::Duration;
+use uniswap_indexer::config::parse_half_lives;
+use uniswap_indexer::ema::{EmaHalfLives, PriceEma};
+use uniswap_indexer::records::IndexedEvent;
+
+const HALF_LIFE: Duration = Duration::from_secs(60);
+
+fn emas(batch: &[IndexedEvent]) -> Vec<Option<f64>> {
+    batch
+        .iter()
+        .map(|e| match e {
+            IndexedEvent::Swap(s) => s.price_ema,
+            _ => None,
+        })
+        .collect()
+}
+
+fn every_pool(half_life: Duration) -> PriceEma {
+    PriceEma::new(EmaHalfLives { default: Some(half_life), pools: HashMap::new() })
+}
+
+// One half-life apart the EMA goes halfway, two apart three quarters, same time it stays put
+#[test]
+fn hand_computed_sequence() {
+    let mut ema = every_pool(HALF_LIFE);
+    let mut batch = vec![
+        swap().pool("a").block(1).at(0).price(100.0).event(),  // starts at the first price
+        swap().pool("a").block(2).at(60).price(200.0).event(), // 100 + 0.5 * 100
+        swap().pool("a").block(3).at(60).price(400.0).event(), // dt 0: unchanged
+        swap().pool("a").block(4).at(180).price(50.0).event(), // 150 + 0.75 * (50 - 150)
+    ];
+    ema.apply(&mut batch);
+    assert_eq!(emas(&batch), vec![Some(100.0), Some(150.0), Some(150.0), Some(75.0)]);
+}
+
+// After a day idle with a one minute half-life, the old value is gone
+#[test]
+fn long_idle_gap_takes_the_new_price() {
+    let mut ema = every_pool(HALF_LIFE);
+    let mut batch = vec![swap().pool("a").block(1).at(0).price(100.0).event(), swap().pool("a").block(2).at(86_400).price(3000.0).event()];
+    ema.apply(&mut batch);
+    assert_eq!(emas(&batch)[1], Some(3000.0));
+}
+
+// Replayed swaps get nothing and don't move it, other pools have their own
+#[test]
+fn replays_and_pools_are_kept_apart() {
+    let mut ema = every_pool(HALF_LIFE);
+    ema.apply(&mut [swap().pool("a").block(1).at(0).price(100.0).event(), swap().pool("a").block(2).at(60).price(200.0).event()]);
+
+    let mut batch = vec![swap().pool("a").block(2).at(60).price(200.0).event(), swap().pool("b").block(3).at(60).price(10.0).event(), swap().pool("a").block(4).at(120).price(150.0).event()];
+    ema.apply(&mut batch);
+    assert_eq!(emas(&batch), vec![None, Some(10.0), Some(150.0)]);
+}
+
+#[test]
+fn per_pool_half_lives() {
+    let pool = "0x88e6A0c2dDD26FEEb64F039a2c41296FcB3f5640";
+    let half_lives = parse_half_lives(&format!("5m, {}=30s", pool)).unwrap();
+    assert_eq!(half_lives.default, Some(Duration::from_secs(300)));
+    assert_eq!(half_lives.pools.get(pool), Some(&Duration::from_secs(30)));
+    assert!(parse_half_lives(&format!("{}=", pool)).is_err());
+
+    let mut ema = PriceEma::new(EmaHalfLives { default: None, pools: half_lives.pools });
+    let mut batch = vec![swap().pool(pool).block(1).at(0).price(1.0).event(), swap().pool("other").block(2).at(0).price(1.0).event()];
+    ema.apply(&mut batch);
+    assert_eq!(emas(&batch), vec![Some(1.0), None]);
+}
@@ -0,0 +1,57 @@
+use std::time::{Duration, Instant};
+use uniswap_indexer::failover::{FailoverMode, FailoverSettings, Health};
+
+fn settings(mode: FailoverMode) -> FailoverSettings {
+    FailoverSettings { mode, unhealthy_after: 2, recheck: Duration::from_secs(30) }
+}
+
+#[test]
+fn ordered_mode_prefers_the_first_endpoint() {
+    let mut health = Health::new(3, &settings(FailoverMode::Ordered));
+    let now = Instant::now();
+    assert_eq!(health.candidates(now), vec![0, 1, 2]);
+    assert_eq!(health.candidates(now), vec![0, 1, 2]);
+}
+
+#[test]
+fn round_robin_starts_one_further_each_time() {
+    let mut health = Health::new(3, &settings(FailoverMode::RoundRobin));
+    let now = Instant::now();
+    assert_eq!(health.candidates(now), vec![0, 1, 2]);
+    assert_eq!(health.candidates(now), vec![1, 2, 0]);
+    assert_eq!(health.candidates(now), vec![2, 0, 1]);
+    assert_eq!(health.candidates(now), vec![0, 1, 2]);
+}
+
+// Down after consecutive failures, skipped until the recheck interval, back on a success
+#[test]
+fn unhealthy_endpoints_are_skipped_until_rechecked() {
+    let mut health = Health::new(2, &settings(FailoverMode::Ordered));
+    let now = Instant::now();
+    assert_eq!(health.record(0, false, now), None);
+    assert_eq!(health.record(0, true, now), None);
+    assert_eq!(health.record(0, false, now), None);
+    assert_eq!(health.record(0, false, now), Some(false));
+    assert!(!health.is_healthy(0));
+    assert_eq!(health.candidates(now + Duration::from_secs(10)), vec![1]);
+
+    // A failed recheck pushes the next one out again
+    let recheck = now + Duration::from_secs(30);
+    assert_eq!(health.candidates(recheck), vec![0, 1]);
+    assert_eq!(health.record(0, false, recheck), None);
+    assert_eq!(health.candidates(recheck + Duration::from_secs(10)), vec![1]);
+
+    let recheck = recheck + Duration::from_secs(30);
+    assert_eq!(health.record(0, true, recheck), Some(true));
+    assert!(health.is_healthy(0));
+    assert_eq!(health.candidates(recheck), vec![0, 1]);
+}
+
+#[test]
+fn every_endpoint_is_tried_when_all_are_down() {
+    let mut health = Health::new(2, &settings(FailoverMode::Ordered));
+    let now = Instant::now();
+    health.mark_down(0, now);
+    health.mark_down(1, now);
+    assert_eq!(health.candidates(now), vec![0, 1]);
+}
@@ -0,0 +1,160 @@
+mod common;
+
+use serde_json::value::RawValue;
+use std::fs;
+use std::path::{Path, PathBuf};
+use uniswap_indexer::config::TableNames;
+use uniswap_indexer::file_sink::{cell, decimal_string, is_row_error, parquet_schema, FileFormat, FileRotation, FileSettings, FileSink};
+use uniswap_indexer::migrations::schema;
+use uniswap_indexer::records::{MintRecord, SwapRecord};
+
+// The columns with a formatting edge: millis, a negative decimal, u128::MAX and a quoted comma
+fn row(block_number: u64) -> SwapRecord {
+    let swap = common::swap().block(block_number).tx(&format!("0x{:064x}", block_number)).at_millis(1_700_000_000_123).price(1.5).record();
+    SwapRecord {
+        price_usd_decimal: Some(-1_500_000_000_000_000_000),
+        liquidity: Some(u128::MAX),
+        direction: "buy, \"quoted\"".to_string(),
+        ..swap
+    }
+}
+
+fn dir(test: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("indexer-file-sink-{}-{}", test, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    dir
+}
+
+fn files(dir: &Path) -> Vec<String> {
+    let mut names: Vec<String> =
+        fs::read_dir(dir).map(|e| e.filter_map(|e| e.ok()).map(|e| e.file_name().to_string_lossy().into_owned()).collect()).unwrap_or_default();
+    names.sort();
+    names
+}
+
+fn raw(json: &str) -> Box<RawValue> {
+    RawValue::from_string(json.to_string()).unwrap()
+}
+
+#[test]
+fn cells_match_what_clickhouse_reads() {
+    assert_eq!(cell("DateTime64(3)", &raw("1700000000123")).unwrap().unwrap(), "2023-11-14 22:13:20.123");
+    assert_eq!(cell("Nullable(Decimal(38, 18))", &raw("-1500000000000000000")).unwrap().unwrap(), "-1.500000000000000000");
+    assert_eq!(cell("Nullable(Float64)", &raw("null")).unwrap(), None);
+    assert_eq!(cell("LowCardinality(String)", &raw("\"a\\\"b\"")).unwrap().unwrap(), "a\"b");
+    assert_eq!(cell("Nullable(UInt128)", &raw("340282366920938463463374607431768211455")).unwrap().unwrap(), u128::MAX.to_string());
+    assert!(is_row_error(&cell("DateTime64(3)", &raw("\"soon\"")).unwrap_err()));
+    assert!(!is_row_error(&eyre::eyre!("No space left on device")));
+}
+
+#[test]
+fn decimals_keep_their_scale() {
+    assert_eq!(decimal_string("5", 3).as_deref(), Some("0.005"));
+    assert_eq!(decimal_string("-1500", 3).as_deref(), Some("-1.500"));
+    assert_eq!(decimal_string("42", 0).as_deref(), Some("42"));
+    assert_eq!(decimal_string("1e5", 2), None);
+}
+
+#[test]
+fn parquet_schema_keeps_widths_and_nullability() {
+    let schema = parquet_schema(schema("uniswap_swaps").unwrap());
+    assert!(schema.starts_with("message uniswap_swaps {"));
+    assert!(schema.contains("REQUIRED INT64 block_number (INTEGER(64,false));"));
+    assert!(schema.contains("REQUIRED INT64 timestamp (TIMESTAMP(MILLIS,true));"));
+    assert!(schema.contains("OPTIONAL DOUBLE price_usd;"));
+    assert!(schema.contains("OPTIONAL BYTE_ARRAY price_usd_decimal (STRING);"));
+    assert!(schema.contains("OPTIONAL BYTE_ARRAY liquidity (STRING);"));
+    assert!(schema.contains("REQUIRED INT32 schema_version (INTEGER(16,false));"));
+}
+
+#[test]
+fn csv_files_are_renamed_once_closed() {
+    let dir = dir("csv");
+    let settings = FileSettings { dir: dir.clone(), format: FileFormat::Csv, rotation: FileRotation::Hour };
+    let sink = FileSink::new(&settings, &TableNames::default()).unwrap();
+    sink.write_rows("uniswap_swaps", &[row(10), row(11)]).unwrap();
+    sink.write_rows("uniswap_swaps", &[row(12)]).unwrap();
+
+    let swaps = dir.join("uniswap_swaps");
+    let open = files(&swaps);
+    assert_eq!(open.len(), 1);
+    assert!(open[0].starts_with("uniswap_swaps-") && open[0].ends_with(".csv.tmp"));
+
+    sink.close().unwrap();
+    let closed = files(&swaps);
+    assert_eq!(closed, vec![open[0].trim_end_matches(".tmp").to_string()]);
+
+    let mut reader = csv::Reader::from_path(swaps.join(&closed[0])).unwrap();
+    let headers = reader.headers().unwrap().clone();
+    let rows: Vec<csv::StringRecord> = reader.records().map(Result::unwrap).collect();
+    assert_eq!(rows.len(), 3);
+    let column = |name: &str| headers.iter().position(|h| h == name).unwrap();
+    assert_eq!(&rows[0][column("timestamp")], "2023-11-14 22:13:20.123");
+    assert_eq!(&rows[0][column("price_usd_decimal")], "-1.500000000000000000");
+    assert_eq!(&rows[0][column("direction")], "buy, \"quoted\"");
+    assert_eq!(&rows[0][column("tx_from")], "");
+    assert_eq!(&rows[2][column("block_number")], "12");
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn files_rotate_past_the_size_limit() {
+    let dir = dir("rotate");
+    let settings = FileSettings { dir: dir.clone(), format: FileFormat::Csv, rotation: FileRotation::Size(1) };
+    let sink = FileSink::new(&settings, &TableNames::default()).unwrap();
+    let mint = MintRecord {
+        chain_id: 1,
+        schema_version: 0,
+        timestamp: 1_700_000_000_000,
+        block_number: 10,
+        log_index: 0,
+        block_hash: String::new(),
+        tx_hash: String::new(),
+        pool_address: "0xpool".to_string(),
+        owner: String::new(),
+        tick_lower: -10,
+        tick_upper: 10,
+        amount: "1".to_string(),
+        amount0: "2".to_string(),
+        amount1: "3".to_string(),
+    };
+    for _ in 0..3 {
+        sink.write_rows("uniswap_mints", std::slice::from_ref(&mint)).unwrap();
+    }
+
+    // Two complete files, the third is still being written
+    let names = files(&dir.join("uniswap_mints"));
+    assert_eq!(names.iter().filter(|n| n.ends_with(".csv")).count(), 2);
+    assert_eq!(names.iter().filter(|n| n.ends_with(".csv.tmp")).count(), 1);
+    sink.close().unwrap();
+    assert_eq!(files(&dir.join("uniswap_mints")).iter().filter(|n| n.ends_with(".csv")).count(), 3);
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[cfg(feature = "parquet")]
+#[test]
+fn parquet_files_read_back() {
+    use parquet::file::reader::{FileReader, SerializedFileReader};
+
+    let dir = dir("parquet");
+    let settings = FileSettings { dir: dir.clone(), format: FileFormat::Parquet, rotation: FileRotation::Hour };
+    let sink = FileSink::new(&settings, &TableNames::default()).unwrap();
+    sink.write_rows("uniswap_swaps", &[row(10), row(11)]).unwrap();
+    sink.write_rows("uniswap_swaps", &[row(12)]).unwrap();
+    sink.close().unwrap();
+
+    let swaps = dir.join("uniswap_swaps");
+    let names = files(&swaps);
+    assert_eq!(names.len(), 1);
+    let reader = SerializedFileReader::new(fs::File::open(swaps.join(&names[0])).unwrap()).unwrap();
+    assert_eq!(reader.metadata().file_metadata().num_rows(), 3);
+    assert_eq!(reader.metadata().num_row_groups(), 2);
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[cfg(not(feature = "parquet"))]
+#[test]
+fn parquet_needs_the_feature() {
+    let settings = FileSettings { dir: dir("no-parquet"), format: FileFormat::Parquet, rotation: FileRotation::Hour };
+    assert!(FileSink::new(&settings, &TableNames::default()).is_err());
+}
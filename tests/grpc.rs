@@ -0,0 +1,66 @@
+#![cfg(feature = "grpc")]
+
+mod common;
+
+use common::swap;
+use futures_util::StreamExt;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::broadcast;
+use uniswap_indexer::grpc::proto::swap_stream_client::SwapStreamClient;
+use uniswap_indexer::grpc::proto::{SwapEvent, SwapFilter};
+use uniswap_indexer::grpc::{matches, report, start, swap_event, swap_stream, GrpcSettings};
+use uniswap_indexer::metrics;
+
+const POOL: &str = "0x88e6A0c2dDD26FEEb64F039a2c41296FcB3f5640";
+
+#[test]
+fn filters_select_pools_and_a_minimum_size() {
+    let event = swap_event(&swap().block(1).pool(POOL).volume(50_000.0).record());
+    assert!(matches(&SwapFilter::default(), &event));
+    assert!(matches(&SwapFilter { pools: vec![POOL.to_lowercase()], min_usd: 50_000.0 }, &event));
+    assert!(!matches(&SwapFilter { pools: vec!["0xother".to_string()], min_usd: 0.0 }, &event));
+    assert!(!matches(&SwapFilter { pools: vec![], min_usd: 50_000.01 }, &event));
+
+    let unpriced = swap_event(&swap().block(1).pool(POOL).record());
+    assert!(matches(&SwapFilter::default(), &unpriced));
+    assert!(!matches(&SwapFilter { pools: vec![], min_usd: 1.0 }, &unpriced));
+}
+
+// The sender never waits: a subscriber that fell behind loses the oldest and is told how many
+#[tokio::test]
+async fn a_slow_subscriber_loses_the_oldest_swaps() {
+    let (tx, rx) = broadcast::channel(2);
+    let dropped_before = metrics::GRPC_DROPPED.get();
+    let mut stream = Box::pin(swap_stream(rx, SwapFilter::default()));
+    for block in 1..=5 {
+        tx.send(Arc::new(swap_event(&swap().block(block).pool(POOL).record()))).unwrap();
+    }
+    drop(tx);
+
+    let events: Vec<SwapEvent> = stream.by_ref().collect().await;
+    let received: Vec<(u64, u64)> = events.iter().map(|e| (e.block_number, e.dropped)).collect();
+    assert_eq!(received, vec![(4, 3), (5, 0)]);
+    assert_eq!(metrics::GRPC_DROPPED.get() - dropped_before, 3);
+}
+
+#[tokio::test]
+async fn subscribers_get_their_swaps_over_grpc() {
+    let addr = start(&GrpcSettings { addr: "127.0.0.1:0".parse().unwrap(), buffer: 16 }).await.unwrap();
+    let mut client = SwapStreamClient::connect(format!("http://{}", addr)).await.unwrap();
+    let filter = SwapFilter { pools: vec![POOL.to_string()], min_usd: 1_000.0 };
+    let mut stream = client.subscribe_swaps(filter).await.unwrap().into_inner();
+
+    // Swaps are only sent while someone listens, keep reporting until the subscription is in
+    let reporter = tokio::spawn(async {
+        loop {
+            report(&swap().block(1).pool("0xother").volume(5_000.0).record());
+            report(&swap().block(2).pool(POOL).volume(10.0).record());
+            report(&swap().block(3).pool(POOL).volume(5_000.0).record());
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+    });
+    let event = tokio::time::timeout(Duration::from_secs(5), stream.message()).await.unwrap().unwrap().unwrap();
+    reporter.abort();
+    assert_eq!((event.block_number, event.pool_address.as_str(), event.volume_usd), (3, POOL, Some(5_000.0)));
+}
@@ -0,0 +1,72 @@
+use std::time::Duration;
+use uniswap_indexer::health::{Health, Readiness, ReadinessSettings};
+
+const T0: i64 = 1_700_000_000;
+
+fn settings() -> ReadinessSettings {
+    ReadinessSettings {
+        max_disconnected: Duration::from_secs(60),
+        max_idle: Some(Duration::from_secs(600)),
+        max_insert_failing: Duration::from_secs(30),
+    }
+}
+
+fn reasons(readiness: Readiness) -> Vec<String> {
+    assert_eq!(readiness.ready, readiness.reasons.is_empty());
+    readiness.reasons
+}
+
+// Not connected yet counts as down, with the same grace as a reconnect
+#[test]
+fn the_stream_gets_a_grace_period_to_connect() {
+    let health = Health::new(T0);
+    assert!(health.readiness(&settings(), true, T0 + 60).ready);
+    assert_eq!(reasons(health.readiness(&settings(), true, T0 + 61)), vec!["WebSocket subscription down for 61s"]);
+
+    health.connected(T0 + 61);
+    assert!(health.readiness(&settings(), true, T0 + 62).ready);
+}
+
+// Down since the first disconnect, however many reconnects fail after it
+#[test]
+fn the_outage_runs_from_the_first_disconnect() {
+    let health = Health::new(T0);
+    health.connected(T0);
+    health.disconnected(T0 + 10);
+    health.disconnected(T0 + 50);
+    assert_eq!(reasons(health.readiness(&settings(), true, T0 + 80)), vec!["WebSocket subscription down for 70s"]);
+}
+
+#[test]
+fn a_connected_stream_without_events_goes_stale() {
+    let health = Health::new(T0);
+    health.connected(T0);
+    health.event(T0 + 100);
+    assert!(health.readiness(&settings(), true, T0 + 700).ready);
+    assert_eq!(reasons(health.readiness(&settings(), true, T0 + 701)), vec!["No logs or blocks for 601s"]);
+
+    let quiet = ReadinessSettings { max_idle: None, ..settings() };
+    assert!(health.readiness(&quiet, true, T0 + 10_000).ready);
+}
+
+#[test]
+fn failing_inserts_turn_it_unready_until_one_goes_through() {
+    let health = Health::new(T0);
+    health.connected(T0);
+    health.insert_failed(T0);
+    health.insert_failed(T0 + 20);
+    assert!(health.readiness(&settings(), true, T0 + 30).ready);
+    assert_eq!(reasons(health.readiness(&settings(), true, T0 + 31)), vec!["Inserts failing for 31s"]);
+
+    health.insert_succeeded();
+    assert!(health.readiness(&settings(), true, T0 + 31).ready);
+}
+
+// Backfills have no stream, only their inserts count
+#[test]
+fn without_the_stream_only_inserts_count() {
+    let health = Health::new(T0);
+    assert!(health.readiness(&settings(), false, T0 + 10_000).ready);
+    health.insert_failed(T0);
+    assert_eq!(reasons(health.readiness(&settings(), false, T0 + 10_000)), vec!["Inserts failing for 10000s"]);
+}
@@ -0,0 +1,137 @@
+mod common;
+
+use alloy::primitives::aliases::{I24, U160};
+use alloy::primitives::{Address, Log as PrimitiveLog, B256, I256, U256};
+use alloy::rpc::types::Log;
+use alloy::sol_types::SolEvent;
+use common::swap;
+use futures_util::FutureExt;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{mpsc, oneshot};
+use uniswap_indexer::abi::{uniswap_v2, Swap};
+use uniswap_indexer::blocks::BlockTimes;
+use uniswap_indexer::config::IndexerConfig;
+use uniswap_indexer::indexer::{pool_filters, run_ordered, LogHandler, Pending, ENRICH_WINDOW};
+use uniswap_indexer::pool::{Dex, PoolInfo, PoolMeta, PoolRef, PoolSpec, Protocol};
+use uniswap_indexer::liquidity::LiquidityGate;
+use uniswap_indexer::records::IndexedEvent;
+use uniswap_indexer::registry::PoolRegistry;
+
+fn ready(event: IndexedEvent) -> Pending {
+    Box::pin(std::future::ready(Some(event)))
+}
+
+// FIREHOSE=true with room for 3 pools. Every test setting the environment sets the same values
+fn firehose_config() -> Arc<IndexerConfig> {
+    // SAFETY: nothing in this test binary reads the environment differently
+    unsafe {
+        std::env::set_var("RPC_URL", "ws://localhost:8546");
+        std::env::set_var("RPC_HTTP_URL", "http://localhost:8545");
+        std::env::set_var("FIREHOSE", "true");
+        std::env::set_var("POOL_CACHE_SIZE", "3");
+    }
+    Arc::new(IndexerConfig::from_env())
+}
+
+// A V3 Swap of `pool`, one per block
+fn swap_log(pool: Address, block_number: u64) -> Log {
+    let swap = Swap {
+        sender: Address::ZERO,
+        recipient: Address::ZERO,
+        amount0: I256::try_from(1_000).unwrap(),
+        amount1: I256::try_from(-1_000).unwrap(),
+        sqrtPriceX96: U160::from(1u128 << 96),
+        liquidity: 1_000_000,
+        tick: I24::ZERO,
+    };
+    Log {
+        inner: PrimitiveLog { address: pool, data: swap.encode_log_data() },
+        block_number: Some(block_number),
+        block_timestamp: Some(1_700_000_000 + block_number),
+        transaction_hash: Some(B256::from(U256::from(block_number))),
+        transaction_index: Some(0),
+        log_index: Some(0),
+        ..Default::default()
+    }
+}
+
+// A swap waiting on its receipt holds back what was handled after it, however fast that is
+#[tokio::test]
+async fn records_leave_in_the_order_they_were_handled() {
+    let (pending_tx, pending_rx) = mpsc::channel(ENRICH_WINDOW);
+    let (tx, mut rx) = mpsc::channel(16);
+    tokio::spawn(run_ordered(pending_rx, tx));
+
+    let (receipt_tx, receipt_rx) = oneshot::channel::<()>();
+    pending_tx.send(Box::pin(receipt_rx.map(|_| Some(swap().block(10).event())))).await.unwrap();
+    pending_tx.send(ready(swap().block(11).event())).await.unwrap();
+    // Left out by the watchlist
+    pending_tx.send(Box::pin(std::future::ready(None))).await.unwrap();
+    pending_tx.send(ready(swap().block(12).event())).await.unwrap();
+
+    assert!(tokio::time::timeout(Duration::from_millis(100), rx.recv()).await.is_err());
+    receipt_tx.send(()).unwrap();
+    drop(pending_tx);
+
+    let mut blocks = Vec::new();
+    while let Some(event) = rx.recv().await {
+        blocks.push(event.block_number());
+    }
+    assert_eq!(blocks, vec![10, 11, 12]);
+}
+
+// A full window stops the handler instead of piling records up
+#[tokio::test]
+async fn a_stuck_swap_stops_the_handler_once_the_window_is_full() {
+    let (pending_tx, pending_rx) = mpsc::channel(ENRICH_WINDOW);
+    let (tx, _rx) = mpsc::channel(16);
+    tokio::spawn(run_ordered(pending_rx, tx));
+
+    let (_receipt_tx, receipt_rx) = oneshot::channel::<()>();
+    pending_tx.send(Box::pin(receipt_rx.map(|_| Some(swap().block(1).event())))).await.unwrap();
+    let mut sent = 0;
+    while tokio::time::timeout(Duration::from_millis(50), pending_tx.send(ready(swap().block(2).event()))).await.is_ok() {
+        sent += 1;
+        assert!(sent <= 2 * ENRICH_WINDOW, "the handler never had to wait");
+    }
+}
+
+// However many pools the chain has, the firehose holds POOL_CACHE_SIZE of them, an evicted one
+// is resolved again on its next log
+#[tokio::test]
+async fn firehose_pools_stay_within_the_cache_size() {
+    let config = firehose_config();
+    let registry = Arc::new(PoolRegistry::offline(&config));
+    let gate = Arc::new(LiquidityGate::new(&config).unwrap());
+    let block_times = Arc::new(BlockTimes::offline(config.block_cache_size));
+    let (tx, mut rx) = mpsc::channel(16);
+    tokio::spawn(async move { while rx.recv().await.is_some() {} });
+    let mut handler = LogHandler::offline(&config, &registry, &gate, &block_times, tx);
+
+    let mut pools = HashMap::new();
+    for n in 1..=10u8 {
+        assert!(handler.handle(&mut pools, swap_log(Address::repeat_byte(n), n.into())).await);
+        assert!(handler.firehose_pools() <= 3);
+    }
+    assert!(handler.handle(&mut pools, swap_log(Address::repeat_byte(1), 11)).await);
+    assert_eq!(handler.firehose_pools(), 3);
+    // The configured map is left to the configured pools
+    assert!(pools.is_empty());
+}
+
+// Configured pools keep their own filter next to the chain-wide Swap one, with all their events
+#[test]
+fn firehose_keeps_the_configured_pools_filter() {
+    let config = firehose_config();
+    let pool = Address::repeat_byte(0x88);
+    let spec = PoolSpec { protocol: Protocol::V2, dex: Dex::Uniswap, pool: PoolRef::Address(pool) };
+    let pools = HashMap::from([(spec.pool, PoolInfo::new(spec, Arc::new(PoolMeta::unresolved()), &config))]);
+
+    let filters = pool_filters(&pools, &config);
+    assert_eq!(filters.len(), 2);
+    assert!(filters[0].address.is_empty());
+    assert!(filters[1].address.contains(&pool));
+    assert!(filters[1].topics[0].contains(&uniswap_v2::Sync::SIGNATURE_HASH));
+}
@@ -0,0 +1,69 @@
+mod common;
+
+use common::swap;
+use serde_json::Value;
+use tokio::sync::mpsc;
+use uniswap_indexer::kafka::{connect_schema, parse_kafka_config, KafkaFormat, KafkaMode, KafkaSink};
+use uniswap_indexer::metrics;
+use uniswap_indexer::records::SwapRecord;
+
+#[test]
+fn connect_schema_maps_clickhouse_types() {
+    let schema = connect_schema(
+        "uniswap_swaps",
+        &[
+            ("chain_id", "UInt64"),
+            ("timestamp", "DateTime64(3)"),
+            ("price_usd", "Nullable(Float64)"),
+            ("liquidity", "Nullable(UInt128)"),
+            ("direction", "LowCardinality(String)"),
+        ],
+    );
+    let fields = schema["fields"].as_array().unwrap();
+    let field = |name: &str| fields.iter().find(|f| f["field"] == name).unwrap();
+
+    assert_eq!((field("chain_id")["type"].as_str(), field("chain_id")["optional"].as_bool()), (Some("int64"), Some(false)));
+    assert_eq!(field("timestamp")["name"], "org.apache.kafka.connect.data.Timestamp");
+    assert_eq!((field("price_usd")["type"].as_str(), field("price_usd")["optional"].as_bool()), (Some("double"), Some(true)));
+    assert_eq!(field("liquidity")["name"], "org.apache.kafka.connect.data.Decimal");
+    assert_eq!(field("liquidity")["optional"], true);
+    assert_eq!(field("direction")["type"], "string");
+}
+
+// Every column of the payload has its field in the schema
+#[test]
+fn schema_envelope_covers_the_swap() {
+    let (tx, _rx) = mpsc::channel(1);
+    let sink = KafkaSink::new(tx, KafkaFormat::Schema, KafkaMode::Also);
+    let message = sink.encode(&SwapRecord { liquidity: Some(u128::MAX), ..swap().record() });
+    assert!(message.contains(&format!("\"liquidity\":{}", u128::MAX)));
+
+    let envelope: Value = serde_json::from_str(&message).unwrap();
+    let fields: Vec<&str> = envelope["schema"]["fields"].as_array().unwrap().iter().map(|f| f["field"].as_str().unwrap()).collect();
+    let payload = envelope["payload"].as_object().unwrap();
+    assert_eq!(fields.len(), payload.len());
+    assert!(payload.keys().all(|k| fields.contains(&k.as_str())));
+}
+
+#[test]
+fn full_buffer_drops_instead_of_waiting() {
+    let (tx, mut rx) = mpsc::channel(2);
+    let sink = KafkaSink::new(tx, KafkaFormat::Json, KafkaMode::Also);
+    let before = metrics::KAFKA_DROPPED.get();
+
+    sink.publish(&[swap().pool("0xa").record(), swap().pool("0xb").record(), swap().pool("0xc").record()]);
+    assert_eq!(metrics::KAFKA_DROPPED.get() - before, 1);
+
+    let (key, payload) = rx.try_recv().unwrap();
+    assert_eq!(key, "0xa");
+    assert!(payload.starts_with("{\"chain_id\":1,"));
+    assert_eq!(rx.try_recv().unwrap().0, "0xb");
+}
+
+#[test]
+fn kafka_config_is_name_value_pairs() {
+    let config = parse_kafka_config("acks=all, sasl.password=a=b,").unwrap();
+    assert_eq!(config, vec![("acks".to_string(), "all".to_string()), ("sasl.password".to_string(), "a=b".to_string())]);
+    assert!(parse_kafka_config("acks").is_err());
+    assert!(parse_kafka_config("bad name=1").is_err());
+}
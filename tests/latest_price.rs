@@ -0,0 +1,28 @@
+mod common;
+
+use uniswap_indexer::latest_price::{price_update, report};
+use uniswap_indexer::metrics;
+use uniswap_indexer::records::SwapRecord;
+
+// A WETH/USDC swap with both prices set
+fn priced() -> SwapRecord {
+    let swap = common::swap().pool("0x88e6a0c2ddd26feeb64f039a2c41296fcb3f5640").block(19_000_000).log_index(12).at_millis(1_700_000_000_000).price(2034.5).record();
+    SwapRecord { price_exact: Some("2034.5".to_string()), ..swap }
+}
+
+#[test]
+fn key_and_blob_of_a_swap() {
+    let update = price_update("price", &priced());
+    assert_eq!(update.key, "price:1:0x88e6a0c2ddd26feeb64f039a2c41296fcb3f5640");
+    assert_eq!(
+        serde_json::to_string(&update.price).unwrap(),
+        r#"{"price":2034.5,"price_exact":"2034.5","block":19000000,"log_index":12,"timestamp":1700000000000}"#
+    );
+}
+
+// Nothing to do and nothing counted when REDIS_URL isn't set
+#[test]
+fn report_without_redis_is_a_no_op() {
+    report(&priced());
+    assert_eq!(metrics::REDIS_FAILED.get(), 0);
+}
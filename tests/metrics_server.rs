@@ -0,0 +1,71 @@
+use alloy::transports::http::reqwest;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use uniswap_indexer::health::ReadinessSettings;
+use uniswap_indexer::metrics::{self, encode, observe_block, track_channel};
+use uniswap_indexer::metrics_server::start;
+use uniswap_indexer::records::IndexedEvent;
+
+#[test]
+fn the_last_block_only_moves_forward() {
+    observe_block(20_000_000);
+    let moved_at = metrics::LAST_BLOCK_TIME.get();
+    observe_block(19_999_990);
+    assert_eq!(metrics::LAST_BLOCK.get(), 20_000_000);
+    assert!(moved_at > 0);
+    observe_block(20_000_001);
+    assert_eq!(metrics::LAST_BLOCK.get(), 20_000_001);
+}
+
+// Read at scrape time, without keeping the channel open
+#[test]
+fn channel_depth_is_what_waits_for_the_writer() {
+    let (tx, _rx) = mpsc::channel(10);
+    track_channel(&tx);
+    for _ in 0..3 {
+        tx.try_send(IndexedEvent::Swap(Box::default())).unwrap();
+    }
+    assert!(encode().contains("indexer_channel_depth 3\n"));
+}
+
+#[tokio::test]
+async fn metrics_are_served_on_get_metrics() {
+    metrics::SWAPS_DECODED.with_label_values(&["0xpool"]).inc();
+    let addr = start("127.0.0.1:0".parse().unwrap(), ReadinessSettings::default(), false).await.unwrap();
+
+    let response = reqwest::get(format!("http://{}/metrics", addr)).await.unwrap();
+    assert_eq!(response.status(), 200);
+    assert!(response.headers()["content-type"].to_str().unwrap().starts_with("text/plain"));
+    let body = response.text().await.unwrap();
+    assert!(body.contains("indexer_swaps_decoded_total{pool=\"0xpool\"} 1"), "{}", body);
+
+    assert_eq!(reqwest::get(format!("http://{}/", addr)).await.unwrap().status(), 404);
+}
+
+// A one-shot run has no stream to wait for, and nothing has failed yet
+#[tokio::test]
+async fn probes_are_served_next_to_metrics() {
+    let addr = start("127.0.0.1:0".parse().unwrap(), ReadinessSettings::default(), false).await.unwrap();
+
+    let response = reqwest::get(format!("http://{}/healthz", addr)).await.unwrap();
+    assert_eq!(response.status(), 200);
+    let response = reqwest::get(format!("http://{}/readyz", addr)).await.unwrap();
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.headers()["content-type"], "application/json");
+    assert_eq!(response.text().await.unwrap(), r#"{"ready":true,"reasons":[]}"#);
+}
+
+// A live run whose stream never connected is alive but not ready
+#[tokio::test]
+async fn readyz_is_503_with_its_reasons() {
+    let settings = ReadinessSettings { max_disconnected: Duration::ZERO, ..Default::default() };
+    let addr = start("127.0.0.1:0".parse().unwrap(), settings, true).await.unwrap();
+    tokio::time::sleep(Duration::from_millis(1_100)).await;
+
+    assert_eq!(reqwest::get(format!("http://{}/healthz", addr)).await.unwrap().status(), 200);
+    let response = reqwest::get(format!("http://{}/readyz", addr)).await.unwrap();
+    assert_eq!(response.status(), 503);
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["ready"], false);
+    assert!(body["reasons"][0].as_str().unwrap().starts_with("WebSocket subscription down for"), "{}", body);
+}
@@ -0,0 +1,82 @@
+use uniswap_indexer::migrations::{
+    latest_migrations, migrations, schema, schema_drift, timestamp_conversion, Drift, SCHEMAS, TABLES,
+};
+
+fn live(columns: &[(&str, &str)]) -> Vec<(String, String)> {
+    columns.iter().map(|(name, ty)| (name.to_string(), ty.to_string())).collect()
+}
+
+#[test]
+fn schemas_cover_every_table() {
+    let tables: Vec<&str> = SCHEMAS.iter().map(|s| s.table).collect();
+    assert_eq!(tables, TABLES);
+}
+
+// A table created from SCHEMAS and one brought up by the ALTERs end up the same
+#[test]
+fn migrations_end_at_the_schema_types() {
+    let all = migrations();
+    for schema in &SCHEMAS {
+        for m in latest_migrations(&all, schema.table) {
+            let ty = schema.columns.iter().find(|(column, _)| *column == m.column).map(|(_, ty)| *ty);
+            assert_eq!(ty, Some(m.ty), "{}.{} (v{})", m.table, m.column, m.version);
+        }
+    }
+}
+
+#[test]
+fn later_migrations_replace_earlier_types() {
+    let all = migrations();
+    let latest = latest_migrations(&all, "uniswap_swaps");
+    let amount0 = latest.iter().find(|m| m.column == "amount0").unwrap();
+    assert_eq!((amount0.version, amount0.ty), (3, "Nullable(Float64)"));
+    assert_eq!(latest.iter().filter(|m| m.column == "amount0").count(), 1);
+}
+
+#[test]
+fn create_statement_uses_the_mapped_name() {
+    let ddl = schema("uniswap_swaps").unwrap().create_statement("swaps_arbitrum");
+    assert!(ddl.starts_with("CREATE TABLE IF NOT EXISTS swaps_arbitrum (chain_id UInt64, "));
+    assert!(ddl.ends_with(
+        "ENGINE = ReplacingMergeTree(insert_version) PARTITION BY toYYYYMM(timestamp) ORDER BY (chain_id, pool_address, tx_hash, log_index)"
+    ));
+
+    let ddl = schema("pools").unwrap().create_statement("pools");
+    assert!(!ddl.contains("PARTITION BY"));
+}
+
+#[test]
+fn drift_reports_missing_wrong_and_extra_columns() {
+    let schema = schema("reorged_swaps").unwrap();
+    assert_eq!(schema_drift(schema, &[]), vec![Drift::MissingTable]);
+    assert_eq!(schema_drift(schema, &live(schema.columns)), vec![]);
+
+    let mut columns = live(schema.columns);
+    columns.retain(|(name, _)| name != "pool_address");
+    columns[0].1 = "UInt32".to_string();
+    columns.push(("note".to_string(), "String".to_string()));
+    let drift = schema_drift(schema, &columns);
+    assert_eq!(
+        drift,
+        vec![
+            Drift::WrongType { column: "chain_id".into(), expected: "UInt64".into(), live: "UInt32".into() },
+            Drift::MissingColumn { column: "pool_address".into(), expected: "String".into() },
+            Drift::ExtraColumn { column: "note".into(), live: "String".into() },
+        ]
+    );
+    assert_eq!(drift.iter().filter(|d| d.is_breaking()).count(), 2);
+}
+
+// Int64 millis columns are converted, columns the old table lacks are left to their defaults
+#[test]
+fn int64_timestamps_are_converted_on_copy() {
+    let schema = schema("uniswap_swaps").unwrap();
+    assert_eq!(timestamp_conversion(schema, &live(schema.columns), "uniswap_swaps", "copy"), None);
+
+    let old = live(&[("timestamp", "Int64"), ("tx_hash", "String"), ("pool_address", "String"), ("price_usd", "Float64")]);
+    assert_eq!(
+        timestamp_conversion(schema, &old, "uniswap_swaps", "uniswap_swaps_converted").unwrap(),
+        "INSERT INTO uniswap_swaps_converted (timestamp, tx_hash, pool_address, price_usd) \
+         SELECT fromUnixTimestamp64Milli(timestamp), tx_hash, pool_address, price_usd FROM uniswap_swaps"
+    );
+}
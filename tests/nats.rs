@@ -0,0 +1,39 @@
+mod common;
+
+use common::swap;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use uniswap_indexer::nats::{nats_message, start, NatsSettings, NatsSink};
+
+#[test]
+fn subject_names_the_chain_and_pool() {
+    let message = nats_message("md.swaps", &swap().chain(8453).pool("0x88e6a0c2ddd26feeb64f039a2c41296fcb3f5640").tx("0xabc").log_index(4).record());
+    assert_eq!(message.subject, "md.swaps.8453.0x88e6a0c2ddd26feeb64f039a2c41296fcb3f5640");
+    assert_eq!(message.id, "8453:0xabc:4");
+    assert!(message.payload.starts_with("{\"chain_id\":8453,"));
+}
+
+// A full buffer holds the batch up instead of dropping swaps
+#[tokio::test]
+async fn full_buffer_makes_the_writer_wait() {
+    let (tx, mut rx) = mpsc::channel(1);
+    let sink = NatsSink::new(tx, "swaps");
+    let swaps = [swap().chain(8453).pool("0xa").log_index(0).record(), swap().chain(8453).pool("0xb").log_index(1).record()];
+
+    let publish = sink.publish(&swaps);
+    tokio::pin!(publish);
+    assert!(tokio::time::timeout(Duration::from_millis(50), &mut publish).await.is_err());
+
+    assert_eq!(rx.recv().await.unwrap().subject, "swaps.8453.0xa");
+    publish.await;
+    assert_eq!(rx.recv().await.unwrap().subject, "swaps.8453.0xb");
+}
+
+#[tokio::test]
+async fn dry_run_needs_no_server() {
+    let settings = NatsSettings { dry_run: true, ..Default::default() };
+    let (sink, task) = start(&settings).await.unwrap();
+    sink.publish(&[swap().chain(8453).pool("0xa").log_index(0).record()]).await;
+    drop(sink);
+    task.await.unwrap();
+}
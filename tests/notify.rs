@@ -0,0 +1,97 @@
+mod common;
+
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use tokio::sync::mpsc;
+use uniswap_indexer::config::parse_alert_thresholds;
+use uniswap_indexer::notify::{compact_usd, message, price_text, run, AlertThresholds, LargeSwaps, Notifier, DEFAULT_ALERT_MIN_USD};
+use uniswap_indexer::records::SwapRecord;
+
+const POOL: &str = "0x88e6A0c2dDD26FEEb64F039a2c41296FcB3f5640";
+
+// One WETH sold for USDC, worth usd
+fn sell(tx_hash: &str, pool: &str, usd: f64) -> SwapRecord {
+    let swap = common::swap().tx(tx_hash).pool(pool).price(3412.25).volume(usd).record();
+    SwapRecord {
+        amount0_raw: "-3412000000".to_string(),
+        amount1_raw: "1000000000000000000".to_string(),
+        token0_symbol: "USDC".to_string(),
+        token1_symbol: "WETH".to_string(),
+        ..swap
+    }
+}
+
+#[test]
+fn message_names_the_sold_token_first() {
+    let whale = sell("0xabcdef0123", POOL, 2_340_000.0);
+    assert_eq!(message(&whale, 2_340_000.0), "🐳 $2.3M WETH→USDC on 0x88e6… at $3,412, tx 0xabcd…");
+
+    let other_way = SwapRecord { amount0_raw: "5000000".to_string(), amount1_raw: "-1".to_string(), price_usd: None, ..whale };
+    assert_eq!(message(&other_way, 850_000.0), "🐳 $850.0K USDC→WETH on 0x88e6…, tx 0xabcd…");
+}
+
+#[test]
+fn amounts_and_prices_read_short() {
+    assert_eq!(compact_usd(1_250_000_000.0), "1.2B");
+    assert_eq!(compact_usd(999.4), "999");
+    assert_eq!(price_text(1_234_567.8), "1,234,568");
+    assert_eq!(price_text(412.0), "412");
+    assert_eq!(price_text(1.5), "1.50");
+    assert_eq!(price_text(0.00012345), "0.0001234");
+}
+
+#[test]
+fn pools_can_have_their_own_threshold() {
+    let thresholds = parse_alert_thresholds(&format!("250000, {}=5000000", POOL)).unwrap();
+    assert_eq!(thresholds.default, 250_000.0);
+    let mut swaps = LargeSwaps::new(thresholds);
+    assert!(swaps.check(&sell("0x01", POOL, 1_000_000.0)).is_none());
+    assert!(swaps.check(&sell("0x02", POOL, 5_000_000.0)).is_some());
+    assert!(swaps.check(&sell("0x03", "0xother", 250_000.0)).is_some());
+    assert!(swaps.check(&sell("0x04", "0xother", 249_999.0)).is_none());
+
+    assert_eq!(parse_alert_thresholds("").unwrap().default, DEFAULT_ALERT_MIN_USD);
+    assert!(parse_alert_thresholds("-5").is_err());
+    assert!(parse_alert_thresholds(&format!("{}=", POOL)).is_err());
+    assert!(parse_alert_thresholds("0xnope=5").is_err());
+}
+
+// A routed trade through several pools alerts once
+#[test]
+fn one_alert_per_transaction() {
+    let mut swaps = LargeSwaps::new(AlertThresholds { default: 1_000.0, ..Default::default() });
+    assert!(swaps.check(&sell("0xrouted", POOL, 500.0)).is_none());
+    assert!(swaps.check(&sell("0xrouted", POOL, 2_000.0)).is_some());
+    assert!(swaps.check(&sell("0xrouted", "0xsecond-leg", 2_000.0)).is_none());
+    assert!(swaps.check(&sell("0xnext", POOL, 2_000.0)).is_some());
+}
+
+// Fails every other message
+struct Flaky {
+    sent: Arc<Mutex<Vec<String>>>,
+}
+
+impl Notifier for Flaky {
+    fn name(&self) -> &'static str {
+        "flaky"
+    }
+
+    fn send(&self, text: &str) -> impl Future<Output = eyre::Result<()>> + Send {
+        let mut sent = self.sent.lock().unwrap();
+        let result = if sent.len().is_multiple_of(2) { Ok(()) } else { Err(eyre::eyre!("HTTP 429 Too Many Requests")) };
+        sent.push(text.to_string());
+        std::future::ready(result)
+    }
+}
+
+#[tokio::test]
+async fn failed_sends_dont_stop_the_notifier() {
+    let sent = Arc::new(Mutex::new(Vec::new()));
+    let (tx, rx) = mpsc::channel(10);
+    for text in ["a", "b", "c"] {
+        tx.send(text.to_string()).await.unwrap();
+    }
+    drop(tx);
+    run(Flaky { sent: sent.clone() }, rx).await;
+    assert_eq!(*sent.lock().unwrap(), vec!["a", "b", "c"]);
+}
@@ -0,0 +1,284 @@
+use alloy::primitives::U256;
+use alloy::primitives::I256;
+use bigdecimal::{BigDecimal, ToPrimitive};
+use num_bigint::BigInt;
+use num_traits::Zero;
+use std::str::FromStr;
+use uniswap_indexer::pool::QuoteSide;
+use uniswap_indexer::price::{
+    adjust_signed_amount, calculate_pair_prices, calculate_pair_prices_v2, calculate_price, calculate_price_v2,
+    exec_price, format_price_exact, price_from_tick, price_impact_bps, price_matches_tick, sqrt_pair_prices_bigdecimal, sqrt_pair_prices_integer, swap_fee, to_decimal_38_18, to_f64_rounded, PriceError, PRICE_DECIMAL_MAX, MAX_SQRT_RATIO, MIN_SQRT_RATIO, Q96_STR,
+};
+
+fn assert_close(actual: &BigDecimal, expected: f64) {
+    let actual = actual.to_f64().unwrap();
+    assert!(((actual - expected) / expected).abs() < 1e-9, "expected {}, got {}", expected, actual);
+}
+
+fn sqrt_price(s: &str) -> U256 {
+    U256::from_str(s).unwrap()
+}
+
+// USDC (6) / WETH (18) at 2500 USDC per WETH: sqrtPriceX96 = 20000 * 2^96
+#[test]
+fn usdc_weth_both_orientations() {
+    let prices = calculate_pair_prices(sqrt_price("1584563250285286751870879006720000"), 6 - 18).unwrap();
+
+    assert_eq!(prices.token1_in_token0, BigDecimal::from(2500));
+    assert_eq!(prices.token0_in_token1, BigDecimal::from_str("0.0004").unwrap());
+}
+
+// WETH (18) / USDT (6) at 2500 USDT per WETH
+#[test]
+fn weth_usdt_both_orientations() {
+    let prices = calculate_pair_prices(sqrt_price("3961408125713216879677197"), 18 - 6).unwrap();
+
+    assert_close(&prices.token0_in_token1, 2500.0);
+    assert_close(&prices.token1_in_token0, 0.0004);
+}
+
+#[test]
+fn calculate_price_matches_token1_in_token0() {
+    let sqrt = sqrt_price("1584563250285286751870879006720000");
+    assert_eq!(calculate_price(sqrt, -12).unwrap(), calculate_pair_prices(sqrt, -12).unwrap().token1_in_token0);
+}
+
+#[test]
+fn v2_reserves_both_orientations() {
+    let e18 = U256::from(10u64).pow(U256::from(18));
+
+    // 2500 USDC against 1 WETH
+    let usdc_weth = calculate_pair_prices_v2(U256::from(2_500_000_000u64), e18, 6 - 18).unwrap();
+    assert_eq!(usdc_weth.token1_in_token0, BigDecimal::from(2500));
+    assert_eq!(usdc_weth.token0_in_token1, BigDecimal::from_str("0.0004").unwrap());
+
+    // 1 WETH against 2500 USDT
+    let weth_usdt = calculate_pair_prices_v2(e18, U256::from(2_500_000_000u64), 18 - 6).unwrap();
+    assert_eq!(weth_usdt.token0_in_token1, BigDecimal::from(2500));
+    assert_eq!(weth_usdt.token1_in_token0, BigDecimal::from_str("0.0004").unwrap());
+}
+
+#[test]
+fn zero_sqrt_price_is_an_error() {
+    assert_eq!(calculate_pair_prices(U256::ZERO, 0), Err(PriceError::ZeroSqrtPrice));
+    assert_eq!(calculate_price(U256::ZERO, -12), Err(PriceError::ZeroSqrtPrice));
+}
+
+#[test]
+fn sqrt_price_outside_the_tick_range_is_an_error() {
+    for sqrt in [MIN_SQRT_RATIO - U256::from(1u64), MAX_SQRT_RATIO + U256::from(1u64), U256::MAX] {
+        assert_eq!(calculate_price(sqrt, 0), Err(PriceError::SqrtPriceOutOfRange(sqrt)));
+    }
+    assert!(calculate_price(MIN_SQRT_RATIO, 0).is_ok());
+    assert!(calculate_price(MAX_SQRT_RATIO, 0).is_ok());
+}
+
+// reserve0 = 0 has nothing to divide by, reserve1 = 0 has nothing to invert
+#[test]
+fn empty_reserves_are_an_error() {
+    let one = U256::from(1u64);
+    for (reserve0, reserve1) in [(U256::ZERO, one), (one, U256::ZERO), (U256::ZERO, U256::ZERO)] {
+        assert_eq!(calculate_price_v2(reserve0, reserve1, 0), Err(PriceError::ZeroReserve { reserve0, reserve1 }));
+    }
+}
+
+#[test]
+fn price_exact_is_fixed_scale_plain_decimal() {
+    assert_eq!(format_price_exact(&BigDecimal::from(2500)), "2500.000000000000000000");
+    assert_eq!(format_price_exact(&BigDecimal::from_str("4e-4").unwrap()), "0.000400000000000000");
+    assert_eq!(format_price_exact(&BigDecimal::from_str("1e30").unwrap()), format!("1{}.{}", "0".repeat(30), "0".repeat(18)));
+}
+
+// A raw price of 1 shifted by the decimals: exactly 10^diff, far past what a u128 holds
+#[test]
+fn decimal_shift_for_any_diff() {
+    let q96 = sqrt_price(Q96_STR);
+    for diff in [0, 12, -12, 18, -18, 39, -39, 60, -60] {
+        let prices = calculate_pair_prices(q96, diff).unwrap();
+        assert_eq!(prices.token0_in_token1, BigDecimal::new(BigInt::from(1), -(diff as i64)), "diff {}", diff);
+        assert_eq!(prices.token1_in_token0, BigDecimal::new(BigInt::from(1), diff as i64), "diff {}", diff);
+    }
+}
+
+#[test]
+fn zero_diff_keeps_the_raw_price() {
+    let sqrt = sqrt_price("1584563250285286751870879006720000");
+    assert_eq!(calculate_pair_prices(sqrt, 0).unwrap().token0_in_token1, BigDecimal::from(400_000_000u64));
+}
+
+#[test]
+fn max_values_convert_exactly() {
+    assert!(calculate_price(MAX_SQRT_RATIO, 0).unwrap() > BigDecimal::zero());
+    assert_eq!(adjust_signed_amount(I256::MIN, 0).to_string(), I256::MIN.to_string());
+    assert_eq!(adjust_signed_amount(I256::try_from(-1500).unwrap(), 3), BigDecimal::from_str("-1.5").unwrap());
+}
+
+// 4e8 raw (2500 USDC per WETH) sits at tick 198,079
+#[test]
+fn sqrt_price_agrees_with_its_tick() {
+    let prices = calculate_pair_prices(sqrt_price("1584563250285286751870879006720000"), -12).unwrap();
+    assert!(price_matches_tick(&prices, 198_079, -12));
+    assert!(price_matches_tick(&prices, 198_080, -12));
+    assert!(!price_matches_tick(&prices, 198_078, -12));
+    assert!(!price_matches_tick(&prices, 198_090, -12));
+    assert!((price_from_tick(198_079, 6, 18).to_f64().unwrap() / 2500.0 - 1.0).abs() < 1e-4);
+}
+
+// The wrong decimals orientation is 24 orders of magnitude off the tick
+#[test]
+fn flipped_decimals_fail_the_tick_check() {
+    let prices = calculate_pair_prices(sqrt_price("1584563250285286751870879006720000"), 12).unwrap();
+    assert!(!price_matches_tick(&prices, 198_079, -12));
+}
+
+#[test]
+fn tick_check_covers_the_full_range() {
+    assert!(price_matches_tick(&calculate_pair_prices(MIN_SQRT_RATIO, 60).unwrap(), -887_272, 60));
+    assert!(price_matches_tick(&calculate_pair_prices(MAX_SQRT_RATIO, -60).unwrap(), 887_271, -60));
+}
+
+// Raw price for equal decimals, so the tick alone moves it
+#[test]
+fn price_from_tick_at_zero_and_one() {
+    assert_eq!(price_from_tick(0, 18, 18), BigDecimal::from(1));
+    assert_eq!(price_from_tick(-1, 18, 18), BigDecimal::from_str("1.0001").unwrap());
+    assert_eq!(price_from_tick(0, 6, 18), BigDecimal::from_str("1e12").unwrap());
+}
+
+// calculate_price quotes token0 per token1, so it falls as the tick rises
+#[test]
+fn price_from_tick_is_monotonic_over_the_full_range() {
+    let ticks: Vec<i32> = (-887_272..=887_272).step_by(7_919).chain([887_272]).collect();
+    let prices: Vec<BigDecimal> = ticks.iter().map(|t| price_from_tick(*t, 6, 18)).collect();
+    for (i, pair) in prices.windows(2).enumerate() {
+        assert!(pair[0] > pair[1], "not decreasing between ticks {} and {}", ticks[i], ticks[i + 1]);
+    }
+    assert!(price_from_tick(-887_272, 0, 24) > BigDecimal::zero());
+    assert!(price_from_tick(887_272, 24, 0) > BigDecimal::zero());
+}
+
+// TickMath rounds the sqrt ratio, calculate_price lies within one tick of the tick's price
+#[test]
+fn price_from_tick_agrees_with_calculate_price() {
+    let cases = [
+        (sqrt_price("1584563250285286751870879006720000"), 198_079, 6, 18),
+        (sqrt_price("3961408125713216879677197"), -198_080, 18, 6),
+        (MIN_SQRT_RATIO, -887_272, 18, 18),
+        (MAX_SQRT_RATIO, 887_272, 18, 18),
+    ];
+    let step = BigDecimal::from_str("1.0001").unwrap();
+    for (sqrt, tick, d0, d1) in cases {
+        let price = calculate_price(sqrt, d0 as i32 - d1 as i32).unwrap();
+        let at_tick = price_from_tick(tick, d0, d1);
+        assert!(price < &at_tick * &step && price > &at_tick / &step, "tick {}: {} vs {}", tick, price, at_tick);
+    }
+}
+
+// Spread over the whole sqrt range: both paths give the same stored values
+#[test]
+fn integer_path_matches_bigdecimal_path() {
+    let mut state = 0x2545f4914f6cdd1d_u64;
+    let mut next = || {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        state
+    };
+
+    let mut compared = 0;
+    for _ in 0..2_000 {
+        let bits = 33 + next() % 127;
+        let random = U256::from(next()) | (U256::from(next()) << 64usize) | (U256::from(next()) << 128usize);
+        let sqrt: U256 = random >> (192 - bits as usize);
+        let sqrt = sqrt.clamp(MIN_SQRT_RATIO, MAX_SQRT_RATIO);
+        let diff = (next() % 61) as i32 - 30;
+
+        let Some(fast) = sqrt_pair_prices_integer(sqrt, diff) else { continue };
+        let exact = sqrt_pair_prices_bigdecimal(sqrt, diff);
+        for (fast, exact) in [(&fast.token0_in_token1, &exact.token0_in_token1), (&fast.token1_in_token0, &exact.token1_in_token0)] {
+            assert_eq!(format_price_exact(fast), format_price_exact(exact), "sqrt {} diff {}", sqrt, diff);
+            assert_eq!(to_f64_rounded(fast), to_f64_rounded(exact), "sqrt {} diff {}", sqrt, diff);
+        }
+        compared += 1;
+    }
+    assert!(compared > 500, "only {} inputs took the integer path", compared);
+}
+
+// Too few digits left at the fixed scale: the BigDecimal path takes over
+#[test]
+fn integer_path_falls_back_on_extreme_prices() {
+    assert!(sqrt_pair_prices_integer(MIN_SQRT_RATIO, 0).is_none());
+    assert!(sqrt_pair_prices_integer(sqrt_price(Q96_STR), 60).is_none());
+    assert!(calculate_price(MIN_SQRT_RATIO, 0).unwrap() > BigDecimal::zero());
+}
+
+// Outputs of the BigDecimal path recorded before its constants became statics,
+// compared digit for digit including the scale
+#[test]
+fn bigdecimal_path_matches_recorded_corpus() {
+    let corpus = include_str!("fixtures/sqrt_prices.csv");
+    let mut rows = 0;
+    for line in corpus.lines().skip(1) {
+        let fields: Vec<&str> = line.split(',').collect();
+        let (sqrt, diff) = (sqrt_price(fields[0]), fields[1].parse().unwrap());
+
+        let prices = sqrt_pair_prices_bigdecimal(sqrt, diff);
+        assert_eq!(prices.token0_in_token1.to_string(), fields[2], "sqrt {} diff {}", sqrt, diff);
+        assert_eq!(prices.token1_in_token0.to_string(), fields[3], "sqrt {} diff {}", sqrt, diff);
+        rows += 1;
+    }
+    assert_eq!(rows, 100);
+}
+
+// 1 WETH in, 2490 USDC out against a 2500 pool price: 40 bps worse than the pool
+#[test]
+fn exec_price_and_impact_from_amounts() {
+    let amount0 = adjust_signed_amount(I256::from_str("1000000000000000000").unwrap(), 18);
+    let amount1 = adjust_signed_amount(I256::from_str("-2490000000").unwrap(), 6);
+
+    let exec = exec_price(&amount0, &amount1).unwrap();
+    assert_eq!(exec, BigDecimal::from(2490));
+    let impact = price_impact_bps(&exec, &BigDecimal::from(2500)).unwrap();
+    assert!((impact + 40.0).abs() < 1e-9, "{}", impact);
+
+    assert_eq!(exec_price(&BigDecimal::zero(), &amount1), None);
+    assert_eq!(price_impact_bps(&exec, &BigDecimal::zero()), None);
+}
+
+// 0.05% of what went into the pool, whichever side that was
+#[test]
+fn fee_is_taken_from_the_input_leg() {
+    let weth_in = adjust_signed_amount(I256::from_str("2000000000000000000").unwrap(), 18);
+    let usdc_out = adjust_signed_amount(I256::from_str("-4980000000").unwrap(), 6);
+
+    assert_eq!(swap_fee(&weth_in, &usdc_out, 500), Some((QuoteSide::Token0, BigDecimal::from_str("0.001").unwrap())));
+    assert_eq!(swap_fee(&-usdc_out.clone(), &-weth_in.clone(), 3000), Some((QuoteSide::Token0, BigDecimal::from_str("14.94").unwrap())));
+    assert_eq!(swap_fee(&usdc_out, &weth_in, 10_000), Some((QuoteSide::Token1, BigDecimal::from_str("0.02").unwrap())));
+    assert_eq!(swap_fee(&BigDecimal::zero(), &usdc_out, 500), None);
+}
+
+// Decimal(38, 18) keeps what an f64 can't and rounds the 19th digit like price_exact
+#[test]
+fn decimal_price_rounds_at_the_18th_digit() {
+    let decimal = |s: &str| to_decimal_38_18(&BigDecimal::from_str(s).unwrap());
+
+    assert_eq!(decimal("3133.65"), Some(3_133_650_000_000_000_000_000));
+    assert_eq!(decimal("0.000000000001234567"), Some(1_234_567));
+    // Half-even at the boundary, same as format_price_exact
+    assert_eq!(decimal("0.0000000000000000015"), Some(2));
+    assert_eq!(decimal("0.0000000000000000025"), Some(2));
+    assert_eq!(decimal("0.0000000000000000004"), Some(0));
+    assert_eq!(format_price_exact(&BigDecimal::from_str("0.0000000000000000025").unwrap()), "0.000000000000000002");
+    assert_eq!(decimal("-1.5"), Some(-1_500_000_000_000_000_000));
+}
+
+#[test]
+fn decimal_price_overflow_is_none() {
+    let decimal = |s: &str| to_decimal_38_18(&BigDecimal::from_str(s).unwrap());
+
+    assert_eq!(decimal("99999999999999999999.999999999999999999"), Some(PRICE_DECIMAL_MAX));
+    // Rounds up past the last representable value
+    assert_eq!(decimal("99999999999999999999.9999999999999999995"), None);
+    assert_eq!(decimal("100000000000000000000"), None);
+    assert_eq!(decimal("1e60"), None);
+}
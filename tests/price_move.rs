@@ -0,0 +1,50 @@
+mod common;
+
+use uniswap_indexer::price_move::{price_move_bps, PriceMoves};
+use uniswap_indexer::records::{IndexedEvent, SwapRecord};
+
+const Q96: f64 = 79228162514264337593543950336.0;
+
+fn at_sqrt(pool: &str, block_number: u64, log_index: u64, sqrt: f64) -> IndexedEvent {
+    let swap = common::swap().pool(pool).block(block_number).log_index(log_index).record();
+    IndexedEvent::Swap(Box::new(SwapRecord { sqrt_price_x96: Some(format!("{:.0}", sqrt)), ..swap }))
+}
+
+fn moves(batch: &[IndexedEvent]) -> Vec<Option<f64>> {
+    batch
+        .iter()
+        .map(|e| match e {
+            IndexedEvent::Swap(s) => s.price_move_bps.map(|m| (m * 1000.0).round() / 1000.0),
+            _ => None,
+        })
+        .collect()
+}
+
+#[test]
+fn a_one_percent_sqrt_move_is_about_201_bps() {
+    assert!((price_move_bps(Q96, Q96 * 1.01) - 201.0).abs() < 1e-6);
+    assert!((price_move_bps(Q96, Q96 * 0.99) + 199.0).abs() < 1e-6);
+}
+
+// Interleaved pools keep their own previous price, the first swap of each only seeds it
+#[test]
+fn moves_are_per_pool_and_in_chain_order() {
+    let mut state = PriceMoves::new();
+    let mut batch = vec![at_sqrt("a", 10, 0, Q96), at_sqrt("b", 10, 1, 2.0 * Q96), at_sqrt("a", 10, 2, Q96 * 1.01), at_sqrt("b", 11, 0, 2.0 * Q96)];
+    state.apply(&mut batch);
+    assert_eq!(moves(&batch), vec![None, None, Some(201.0), Some(0.0)]);
+
+    // The next batch picks up where this one stopped
+    let mut batch = vec![at_sqrt("a", 12, 0, Q96)];
+    state.apply(&mut batch);
+    assert!(moves(&batch)[0].unwrap() < -196.0);
+}
+
+// A late swap from behind the last one gets no move and doesn't replace the previous price
+#[test]
+fn older_swaps_are_left_alone() {
+    let mut state = PriceMoves::new();
+    let mut batch = vec![at_sqrt("a", 20, 0, Q96), at_sqrt("a", 19, 5, 3.0 * Q96), at_sqrt("a", 21, 0, Q96)];
+    state.apply(&mut batch);
+    assert_eq!(moves(&batch), vec![None, None, Some(0.0)]);
+}
@@ -0,0 +1,97 @@
+use alloy::primitives::{address, Address, I256};
+use bigdecimal::BigDecimal;
+use std::str::FromStr;
+use uniswap_indexer::config::{default_stablecoins, parse_pool_refs, parse_quote_tokens};
+use uniswap_indexer::decode::{swap_direction, Direction};
+use uniswap_indexer::pool::{PoolDecimals, PoolMeta, PoolRef, QuoteSide};
+use uniswap_indexer::price::{pair_prices, quoted_price};
+
+const USDC: Address = address!("a0b86991c6218b36c1d19d4a2e9eb0ce3606eb48");
+const WETH: Address = address!("c02aaa39b223fe8d0a0e5c4f27ead9083c756cc2");
+const WBTC: Address = address!("2260fac5e5542a773aa44fbcfedf7c193bc2c599");
+const POOL: Address = address!("88e6a0c2ddd26feeb64f039a2c41296fcb3f5640");
+
+fn meta(token0: (Address, &str), token1: (Address, &str)) -> PoolMeta {
+    PoolMeta {
+        token0: token0.0,
+        token1: token1.0,
+        decimals: PoolDecimals { token0: 6, token1: 18 },
+        fee: 500,
+        tick_spacing: 10,
+        token0_symbol: token0.1.to_string(),
+        token1_symbol: token1.1.to_string(),
+        resolved: true,
+    }
+}
+
+#[test]
+fn every_default_chain_has_usdc() {
+    assert!(default_stablecoins(1).contains(&USDC));
+    for chain in [10, 56, 137, 8453, 42161] {
+        assert!(default_stablecoins(chain).len() >= 2, "chain {}", chain);
+    }
+    assert!(default_stablecoins(31337).is_empty());
+}
+
+// pool=token beats the bare entries, which are tried in order
+#[test]
+fn pool_override_wins_over_the_global_list() {
+    let quote = parse_quote_tokens(&format!("WETH, {}=WBTC", POOL)).unwrap();
+    let pool = meta((WBTC, "WBTC"), (WETH, "WETH"));
+
+    assert_eq!(quote.side(PoolRef::Address(POOL), &pool), Some(QuoteSide::Token0));
+    assert_eq!(quote.side(PoolRef::Address(USDC), &pool), Some(QuoteSide::Token1));
+    assert_eq!(quote.side(PoolRef::Address(USDC), &meta((USDC, "USDC"), (WBTC, "WBTC"))), None);
+}
+
+// An override naming neither token falls back to the bare entries
+#[test]
+fn override_for_a_foreign_token_is_ignored() {
+    let quote = parse_quote_tokens(&format!("{}=DAI,weth", POOL)).unwrap();
+    let pool = meta((USDC, "USDC"), (WETH, "WETH"));
+    assert_eq!(quote.side(PoolRef::Address(POOL), &pool), Some(QuoteSide::Token1));
+}
+
+#[test]
+fn v4_pool_ids_and_bad_specs() {
+    let id = "0x21c67e77068de97969ba93d4aab21826d33ca12bb9f565d8496e8fda8a82ca27";
+    let quote = parse_quote_tokens(&format!("v4:{}=USDC", id)).unwrap();
+    assert!(quote.pools.contains_key(&PoolRef::Id(id.parse().unwrap())));
+
+    assert!(parse_quote_tokens("0x1234=USDC").is_err());
+}
+
+// 1 WETH = 2500 USDC: the price is in USDC whichever side it sits on
+#[test]
+fn price_is_expressed_in_the_quote_token() {
+    let prices = pair_prices(BigDecimal::from(2500), 0);
+    assert_eq!(quoted_price(&prices, Some(QuoteSide::Token1)), Some(&BigDecimal::from(2500)));
+    assert_eq!(quoted_price(&prices, Some(QuoteSide::Token0)), Some(&BigDecimal::from_str("0.0004").unwrap()));
+    assert_eq!(quoted_price(&prices, None), None);
+}
+
+#[test]
+fn direction_and_pair_follow_the_base_token() {
+    let out = I256::from_str("-1").unwrap();
+    let inn = I256::from_str("1").unwrap();
+    assert_eq!(swap_direction(inn, out, Some(QuoteSide::Token0)), Direction::Buy);
+    assert_eq!(swap_direction(inn, out, Some(QuoteSide::Token1)), Direction::Sell);
+    assert_eq!(swap_direction(inn, out, None), Direction::Buy);
+
+    let pool = meta((USDC, "USDC"), (WETH, "WETH"));
+    assert_eq!(pool.pair(Some(QuoteSide::Token0)), "WETH/USDC 0.05%");
+    assert_eq!(pool.pair(Some(QuoteSide::Token1)), "USDC/WETH 0.05%");
+}
+
+#[test]
+fn invert_price_takes_pool_specs() {
+    let id = "0x21c67e77068de97969ba93d4aab21826d33ca12bb9f565d8496e8fda8a82ca27";
+    let pools = parse_pool_refs(&format!("{}, v4:{}", POOL, id)).unwrap();
+    assert!(pools.contains(&PoolRef::Address(POOL)));
+    assert!(pools.contains(&PoolRef::Id(id.parse().unwrap())));
+    assert!(parse_pool_refs("").unwrap().is_empty());
+    assert!(parse_pool_refs("v5:0x12").is_err());
+
+    assert_eq!(QuoteSide::Token0.other(), QuoteSide::Token1);
+    assert_eq!(QuoteSide::Token1.other(), QuoteSide::Token0);
+}
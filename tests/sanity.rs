@@ -0,0 +1,46 @@
+use std::collections::VecDeque;
+use uniswap_indexer::sanity::{PriceSanity, MIN_HISTORY};
+
+fn sanity(min: Option<f64>, max: Option<f64>, pct: Option<f64>) -> PriceSanity {
+    PriceSanity { min, max, max_deviation: pct.map(|p| p / 100.0), window: 10 }
+}
+
+// A pool's first swaps have no median yet, only the bounds apply
+#[test]
+fn first_swaps_only_face_the_bounds() {
+    let sanity = sanity(None, Some(1e12), Some(10.0));
+    let mut history = VecDeque::new();
+
+    assert!(sanity.check(&mut history, 2500.0).is_none());
+    assert!(sanity.check(&mut history, 9000.0).is_none());
+    assert!(sanity.check(&mut history, 3.7e31).is_some());
+    assert_eq!(history.len(), 3);
+}
+
+#[test]
+fn outlier_against_the_median_is_rejected() {
+    let sanity = sanity(None, None, Some(10.0));
+    let mut history = VecDeque::new();
+    for _ in 0..MIN_HISTORY {
+        assert!(sanity.check(&mut history, 2500.0).is_none());
+    }
+
+    assert!(sanity.check(&mut history, 3.7e31).is_some());
+    assert!(sanity.check(&mut history, 2600.0).is_none());
+    assert!(sanity.check(&mut history, 2000.0).is_some());
+}
+
+// Outliers share the window, a lasting move takes over the median and passes again
+#[test]
+fn median_follows_a_real_move() {
+    let sanity = sanity(None, None, Some(10.0));
+    let mut history = VecDeque::new();
+    for _ in 0..10 {
+        sanity.check(&mut history, 100.0);
+    }
+
+    let rejected = (0..10).filter(|_| sanity.check(&mut history, 200.0).is_some()).count();
+    assert!(rejected < 10);
+    assert!(sanity.check(&mut history, 200.0).is_none());
+    assert_eq!(history.len(), 10);
+}
@@ -0,0 +1,22 @@
+use std::time::Duration;
+use uniswap_indexer::shutdown::drain;
+
+#[tokio::test]
+async fn drain_reports_the_writer_result() {
+    let done = tokio::spawn(async { eyre::Ok(()) });
+    assert!(drain(done, Duration::from_secs(1)).await.is_ok());
+
+    let failed = tokio::spawn(async { Err(eyre::eyre!("insert failed")) });
+    assert!(drain(failed, Duration::from_secs(1)).await.is_err());
+}
+
+// A writer stuck on ClickHouse doesn't keep the process alive past the deadline
+#[tokio::test]
+async fn drain_gives_up_after_the_deadline() {
+    let stuck = tokio::spawn(async {
+        std::future::pending::<()>().await;
+        eyre::Ok(())
+    });
+    let err = drain(stuck, Duration::from_millis(20)).await.unwrap_err();
+    assert!(err.to_string().contains("timed out"), "{}", err);
+}
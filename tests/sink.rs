@@ -0,0 +1,280 @@
+mod common;
+
+use clickhouse::{RowOwned, RowWrite};
+use common::swap;
+use futures_util::FutureExt;
+use serde::Serialize;
+use serde_json::Value;
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::{mpsc, oneshot, Semaphore};
+use uniswap_indexer::checkpoint::{CheckpointStore, Checkpoints};
+use uniswap_indexer::config::TableNames;
+use uniswap_indexer::derived::Derived;
+use uniswap_indexer::indexer::{run_ordered, Pending, ENRICH_WINDOW};
+use uniswap_indexer::records::IndexedEvent;
+use uniswap_indexer::sink::{fan_out, Sink};
+use uniswap_indexer::spill::{SpillDir, SpillPolicy};
+use uniswap_indexer::storage::{run_writer, AsyncInsert, InsertRetry, WriterSettings};
+
+// Table and rows of each insert
+type Inserts = Arc<Mutex<Vec<(String, Vec<Value>)>>>;
+
+// Keeps every insert in memory. `down` fails them, `gate` holds each one until it gets a permit
+#[derive(Clone, Default)]
+struct MockSink {
+    inserts: Inserts,
+    down: Arc<AtomicBool>,
+    gate: Option<Arc<Semaphore>>,
+    closed: Arc<AtomicUsize>,
+}
+
+impl MockSink {
+    fn blocks(&self, table: &str) -> Vec<u64> {
+        let inserts = self.inserts.lock().unwrap();
+        inserts.iter().filter(|(t, _)| t == table).flat_map(|(_, rows)| rows.iter().map(|r| r["block_number"].as_u64().unwrap())).collect()
+    }
+
+    fn insert_sizes(&self, table: &str) -> Vec<usize> {
+        self.inserts.lock().unwrap().iter().filter(|(t, _)| t == table).map(|(_, rows)| rows.len()).collect()
+    }
+}
+
+impl Sink for MockSink {
+    fn write_rows<T: RowOwned + RowWrite + Serialize + Sync>(
+        &self,
+        table: &str,
+        rows: &[T],
+        _dedup_token: Option<&str>,
+        _async_insert: Option<AsyncInsert>,
+    ) -> impl Future<Output = eyre::Result<()>> + Send {
+        let rows: Vec<Value> = rows.iter().map(|row| serde_json::to_value(row).unwrap()).collect();
+        let (table, sink) = (table.to_string(), self.clone());
+        async move {
+            if rows.is_empty() {
+                return Ok(());
+            }
+            if let Some(gate) = &sink.gate {
+                gate.acquire().await.unwrap().forget();
+            }
+            if sink.down.load(Ordering::SeqCst) {
+                eyre::bail!("mock sink is down");
+            }
+            sink.inserts.lock().unwrap().push((table, rows));
+            Ok(())
+        }
+    }
+
+    fn is_row_error(&self, _e: &eyre::Report) -> bool {
+        false
+    }
+
+    fn close(&self) -> impl Future<Output = eyre::Result<()>> + Send {
+        self.closed.fetch_add(1, Ordering::SeqCst);
+        std::future::ready(Ok(()))
+    }
+}
+
+fn ready(event: IndexedEvent) -> Pending {
+    Box::pin(std::future::ready(Some(event)))
+}
+
+fn checkpoint_file(test: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("indexer-checkpoints-{}-{}.txt", test, std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    path
+}
+
+// What a restart would resume 0xpool after
+fn stored(path: &Path) -> Option<u64> {
+    let contents = std::fs::read_to_string(path).ok()?;
+    contents.lines().find_map(|line| line.strip_prefix("0xpool ")?.parse().ok())
+}
+
+fn settings(test: &str, batch_size: usize) -> WriterSettings {
+    WriterSettings {
+        batch_size,
+        max_age: Duration::from_secs(3600),
+        retry: InsertRetry { retries: 0, base_delay: Duration::ZERO },
+        spill: SpillDir {
+            path: std::env::temp_dir().join(format!("indexer-sink-{}-{}", test, std::process::id())),
+            max_bytes: 0,
+            policy: SpillPolicy::Stop,
+        },
+        tables: TableNames::default(),
+        workers: 1,
+        async_insert: None,
+        kafka: None,
+        nats: None,
+        stdout: false,
+    }
+}
+
+async fn eventually(what: impl Fn() -> bool) {
+    tokio::time::timeout(Duration::from_secs(5), async {
+        while !what() {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .expect("timed out");
+}
+
+#[tokio::test]
+async fn full_batches_go_out_and_shutdown_flushes_the_rest() {
+    let sink = MockSink::default();
+    let (tx, rx) = mpsc::channel(100);
+    for block in 1..=5 {
+        tx.send(swap().block(block).event()).await.unwrap();
+    }
+    drop(tx);
+    run_writer(sink.clone(), rx, settings("batches", 2), Derived::default(), None).await.unwrap();
+
+    assert_eq!(sink.insert_sizes("uniswap_swaps"), vec![2, 2, 1]);
+    assert_eq!(sink.closed.load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test]
+async fn rows_are_written_in_block_order() {
+    let sink = MockSink::default();
+    let (tx, rx) = mpsc::channel(100);
+    for block in [3, 1, 2, 6, 4, 5] {
+        tx.send(swap().block(block).event()).await.unwrap();
+    }
+    drop(tx);
+    run_writer(sink.clone(), rx, settings("order", 3), Derived::default(), None).await.unwrap();
+
+    // Sorted within a batch, batches in arrival order
+    assert_eq!(sink.blocks("uniswap_swaps"), vec![1, 2, 3, 4, 5, 6]);
+}
+
+#[tokio::test]
+async fn a_failing_sink_doesnt_stop_the_others() {
+    let (healthy, failing) = (MockSink::default(), MockSink::default());
+    failing.down.store(true, Ordering::SeqCst);
+    let (tx, rx) = mpsc::channel(100);
+    let (healthy_tx, healthy_rx) = mpsc::channel(4);
+    let (failing_tx, failing_rx) = mpsc::channel(4);
+    let writers = [
+        tokio::spawn(run_writer(healthy.clone(), healthy_rx, settings("healthy", 2), Derived::default(), None)),
+        tokio::spawn(run_writer(failing.clone(), failing_rx, settings("failing", 2), Derived::default(), None)),
+    ];
+    let fan = tokio::spawn(fan_out(rx, vec![("healthy", healthy_tx), ("failing", failing_tx)]));
+    for block in 1..=10 {
+        tx.send(swap().block(block).event()).await.unwrap();
+    }
+    drop(tx);
+    fan.await.unwrap();
+
+    let [healthy_result, failing_result] = writers;
+    healthy_result.await.unwrap().unwrap();
+    assert!(failing_result.await.unwrap().is_err());
+    assert_eq!(healthy.blocks("uniswap_swaps"), (1..=10).collect::<Vec<_>>());
+    assert_eq!(failing.closed.load(Ordering::SeqCst), 1);
+    let _ = std::fs::remove_dir_all(settings("failing", 2).spill.path);
+}
+
+// A sink that hangs holds the stream up once its queue is full, not before, and catches up after
+#[tokio::test]
+async fn a_stuck_sink_holds_the_others_up_only_past_its_queue() {
+    let gate = Arc::new(Semaphore::new(0));
+    let (healthy, stuck) = (MockSink::default(), MockSink { gate: Some(gate.clone()), ..Default::default() });
+    let (tx, rx) = mpsc::channel(100);
+    let (healthy_tx, healthy_rx) = mpsc::channel(2);
+    let (stuck_tx, stuck_rx) = mpsc::channel(2);
+    let writers = [
+        tokio::spawn(run_writer(healthy.clone(), healthy_rx, settings("healthy-stuck", 1), Derived::default(), None)),
+        tokio::spawn(run_writer(stuck.clone(), stuck_rx, settings("stuck", 1), Derived::default(), None)),
+    ];
+    let fan = tokio::spawn(fan_out(rx, vec![("healthy", healthy_tx), ("stuck", stuck_tx)]));
+    for block in 1..=10 {
+        tx.send(swap().block(block).event()).await.unwrap();
+    }
+    drop(tx);
+
+    // One swap in the stuck insert, two in its queue, the fourth waits for room
+    eventually(|| healthy.blocks("uniswap_swaps").len() == 4).await;
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert_eq!(healthy.blocks("uniswap_swaps"), vec![1, 2, 3, 4]);
+
+    gate.add_permits(100);
+    fan.await.unwrap();
+    for writer in writers {
+        writer.await.unwrap().unwrap();
+    }
+    assert_eq!(healthy.blocks("uniswap_swaps"), (1..=10).collect::<Vec<_>>());
+    assert_eq!(stuck.blocks("uniswap_swaps"), (1..=10).collect::<Vec<_>>());
+}
+
+// The first queue's writer is gone: the others still get everything
+#[tokio::test]
+async fn a_stopped_writer_loses_only_its_own_copy() {
+    let sink = MockSink::default();
+    let (tx, rx) = mpsc::channel(100);
+    let (gone_tx, gone_rx) = mpsc::channel(2);
+    drop(gone_rx);
+    let (sink_tx, sink_rx) = mpsc::channel(2);
+    let writer = tokio::spawn(run_writer(sink.clone(), sink_rx, settings("stopped", 1), Derived::default(), None));
+    let fan = tokio::spawn(fan_out(rx, vec![("gone", gone_tx), ("sink", sink_tx)]));
+    for block in 1..=3 {
+        tx.send(swap().block(block).event()).await.unwrap();
+    }
+    drop(tx);
+    fan.await.unwrap();
+    writer.await.unwrap().unwrap();
+    assert_eq!(sink.blocks("uniswap_swaps"), vec![1, 2, 3]);
+}
+
+// A swap waiting on its receipt keeps the later blocks and their checkpoint back, and a row of
+// an already flushed block arriving late is still ahead of the checkpoint
+async fn late_rows_stay_ahead_of_the_checkpoint(test: &str, workers: usize) {
+    let sink = MockSink::default();
+    let path = checkpoint_file(test);
+    let checkpoints = Checkpoints::load(CheckpointStore::File(path.clone()), None, 1).await.unwrap();
+    let (pending_tx, pending_rx) = mpsc::channel(ENRICH_WINDOW);
+    let (tx, rx) = mpsc::channel(100);
+    tokio::spawn(run_ordered(pending_rx, tx));
+    let writer = tokio::spawn(run_writer(sink.clone(), rx, WriterSettings { workers, ..settings(test, 1) }, Derived::default(), Some(checkpoints)));
+
+    let (receipt_tx, receipt_rx) = oneshot::channel::<()>();
+    pending_tx.send(Box::pin(receipt_rx.map(|_| Some(swap().block(10).event())))).await.unwrap();
+    pending_tx.send(ready(swap().block(11).event())).await.unwrap();
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert!(sink.blocks("uniswap_swaps").is_empty());
+    assert_eq!(stored(&path), None);
+
+    receipt_tx.send(()).unwrap();
+    eventually(|| sink.blocks("uniswap_swaps").len() == 2).await;
+    // Block 11 went out but may still have rows coming
+    eventually(|| stored(&path) == Some(10)).await;
+
+    pending_tx.send(ready(swap().block(11).log_index(1).event())).await.unwrap();
+    eventually(|| sink.blocks("uniswap_swaps").len() == 3).await;
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert_eq!(stored(&path), Some(10));
+
+    pending_tx.send(ready(swap().block(12).event())).await.unwrap();
+    eventually(|| stored(&path) == Some(11)).await;
+
+    // Shut down: nothing can join block 12 any more
+    drop(pending_tx);
+    writer.await.unwrap().unwrap();
+    let mut blocks = sink.blocks("uniswap_swaps");
+    blocks.sort();
+    assert_eq!(blocks, vec![10, 11, 11, 12]);
+    assert_eq!(stored(&path), Some(12));
+    let _ = std::fs::remove_file(path);
+}
+
+#[tokio::test]
+async fn late_rows_stay_ahead_of_the_checkpoint_with_one_writer() {
+    late_rows_stay_ahead_of_the_checkpoint("late", 1).await;
+}
+
+#[tokio::test]
+async fn late_rows_stay_ahead_of_the_checkpoint_with_insert_workers() {
+    late_rows_stay_ahead_of_the_checkpoint("late-workers", 2).await;
+}
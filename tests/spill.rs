@@ -0,0 +1,86 @@
+use std::path::PathBuf;
+use uniswap_indexer::records::SwapRecord;
+use uniswap_indexer::spill::{read_spill, spill_line, SpillDir, SpillPolicy};
+
+fn spill_dir(name: &str, max_bytes: u64, policy: SpillPolicy) -> SpillDir {
+    let path: PathBuf = std::env::temp_dir().join(format!("spill_{}_{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&path);
+    SpillDir { path, max_bytes, policy }
+}
+
+fn batch(block: u64) -> Vec<String> {
+    vec![
+        spill_line("uniswap_swaps", &serde_json::json!({ "block_number": block, "log_index": 0 })),
+        spill_line("pool_twaps", &serde_json::json!({ "n_swaps": 1 })),
+        spill_line("uniswap_swaps", &serde_json::json!({ "block_number": block, "log_index": 1 })),
+    ]
+}
+
+// One file per batch, listed oldest first, rows grouped by table as they were written
+#[test]
+fn batches_round_trip_in_order() {
+    let dir = spill_dir("order", u64::MAX, SpillPolicy::Stop);
+    assert!(dir.files().unwrap().is_empty());
+
+    let first = dir.write(&batch(1)).unwrap().unwrap();
+    let second = dir.write(&batch(2)).unwrap().unwrap();
+    assert_eq!(dir.files().unwrap(), vec![first.clone(), second]);
+
+    let tables = read_spill(&first).unwrap();
+    assert_eq!(tables.len(), 2);
+    assert_eq!(tables[0].0, "uniswap_swaps");
+    assert_eq!(tables[0].1.len(), 2);
+    assert_eq!(tables[0].1[1].get(), r#"{"block_number":1,"log_index":1}"#);
+    assert_eq!(tables[1].0, "pool_twaps");
+    std::fs::remove_dir_all(&dir.path).unwrap();
+}
+
+#[test]
+fn a_full_directory_stops_spilling() {
+    let one = batch(1).iter().map(|l| l.len() as u64 + 1).sum::<u64>();
+    let dir = spill_dir("stop", one * 2, SpillPolicy::Stop);
+
+    assert!(dir.write(&batch(1)).unwrap().is_some());
+    assert!(dir.write(&batch(2)).unwrap().is_some());
+    assert!(dir.write(&batch(3)).unwrap().is_none());
+    assert_eq!(dir.files().unwrap().len(), 2);
+    std::fs::remove_dir_all(&dir.path).unwrap();
+}
+
+#[test]
+fn drop_oldest_makes_room_for_the_new_batch() {
+    let one = batch(1).iter().map(|l| l.len() as u64 + 1).sum::<u64>();
+    let dir = spill_dir("drop", one * 2, SpillPolicy::DropOldest);
+
+    let first = dir.write(&batch(1)).unwrap().unwrap();
+    let second = dir.write(&batch(2)).unwrap().unwrap();
+    let third = dir.write(&batch(3)).unwrap().unwrap();
+    assert_eq!(dir.files().unwrap(), vec![second, third]);
+    assert!(!first.exists());
+    std::fs::remove_dir_all(&dir.path).unwrap();
+}
+
+// Swap timestamps are DateTime64(3) columns but spill as Unix millis; they and a u128
+// liquidity past the u64 range read back the same
+#[test]
+fn swap_rows_round_trip_exactly() {
+    let dir = spill_dir("swap", u64::MAX, SpillPolicy::Stop);
+    let timestamp = chrono::DateTime::from_timestamp_millis(1_700_000_000_123).unwrap();
+    let swap = SwapRecord {
+        timestamp,
+        ingested_at: timestamp,
+        liquidity: Some(u128::MAX),
+        price_usd_decimal: Some(-i128::MAX),
+        ..Default::default()
+    };
+    let line = spill_line("uniswap_swaps", &swap);
+    assert!(line.starts_with(r#"{"table":"uniswap_swaps","row":{"chain_id":0,"schema_version":0,"timestamp":1700000000123,"#));
+
+    let file = dir.write(&[line]).unwrap().unwrap();
+    let tables = read_spill(&file).unwrap();
+    let back: SwapRecord = serde_json::from_str(tables[0].1[0].get()).unwrap();
+    assert_eq!(back.timestamp, timestamp);
+    assert_eq!(back.liquidity, Some(u128::MAX));
+    assert_eq!(back.price_usd_decimal, Some(-i128::MAX));
+    std::fs::remove_dir_all(&dir.path).unwrap();
+}
@@ -0,0 +1,36 @@
+mod common;
+
+use clap::Parser;
+use common::swap;
+use serde_json::Value;
+use uniswap_indexer::cli::Cli;
+use uniswap_indexer::config::{parse_table_names, TableNames};
+use uniswap_indexer::migrations::schema;
+use uniswap_indexer::stdout::{json_line, StdoutMode};
+
+// Downstream scripts read the same names as the ClickHouse columns
+#[test]
+fn rows_carry_the_column_names() {
+    let line: Value = serde_json::from_str(&json_line(&TableNames::default(), &swap().event()).unwrap()).unwrap();
+    assert_eq!(line["table"], "uniswap_swaps");
+    let mut fields: Vec<&str> = line["row"].as_object().unwrap().keys().map(String::as_str).collect();
+    let mut columns: Vec<&str> = schema("uniswap_swaps").unwrap().columns.iter().map(|(column, _)| *column).collect();
+    fields.sort();
+    columns.sort();
+    assert_eq!(fields, columns);
+}
+
+#[test]
+fn lines_use_table_names() {
+    let tables = parse_table_names("uniswap_swaps=swaps").unwrap();
+    let line: Value = serde_json::from_str(&json_line(&tables, &swap().event()).unwrap()).unwrap();
+    assert_eq!(line["table"], "swaps");
+}
+
+#[test]
+fn stdout_is_only_unless_also() {
+    assert_eq!(Cli::try_parse_from(["uniswap-indexer"]).unwrap().stdout, None);
+    assert_eq!(Cli::try_parse_from(["uniswap-indexer", "--stdout"]).unwrap().stdout, Some(StdoutMode::Only));
+    assert_eq!(Cli::try_parse_from(["uniswap-indexer", "--stdout=also", "backfill", "--from-block", "1"]).unwrap().stdout, Some(StdoutMode::Also));
+    assert!(Cli::try_parse_from(["uniswap-indexer", "--stdout=sometimes"]).is_err());
+}
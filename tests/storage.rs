@@ -0,0 +1,172 @@
+use clickhouse::Compression;
+use std::time::Duration;
+use uniswap_indexer::config::{parse_clickhouse_headers, parse_table_names, ClickhouseSettings, TableNames};
+use uniswap_indexer::spill::{SpillDir, SpillPolicy};
+use uniswap_indexer::failover::FailoverSettings;
+use uniswap_indexer::records::{IndexedEvent, MintRecord, ReorgedSwapRecord};
+use uniswap_indexer::storage::{error_code, get_clickhouse_client, is_row_error, sort_batch, AsyncInsert, InsertRetry, WriterSettings};
+
+fn mint(block_number: u64, log_index: u64, timestamp: i64) -> IndexedEvent {
+    IndexedEvent::Mint(MintRecord {
+        chain_id: 1,
+        schema_version: 0,
+        timestamp,
+        block_number,
+        log_index,
+        block_hash: String::new(),
+        tx_hash: String::new(),
+        pool_address: String::new(),
+        owner: String::new(),
+        tick_lower: 0,
+        tick_upper: 0,
+        amount: String::new(),
+        amount0: String::new(),
+        amount1: String::new(),
+    })
+}
+
+fn reorged(block_number: u64, log_index: u64) -> IndexedEvent {
+    IndexedEvent::Reorged(ReorgedSwapRecord {
+        chain_id: 1,
+        schema_version: 0,
+        detected_at: 0,
+        block_number,
+        block_hash: String::new(),
+        tx_hash: String::new(),
+        log_index,
+        pool_address: String::new(),
+    })
+}
+
+#[test]
+fn shuffled_batch_comes_out_in_chain_order() {
+    let mut batch = vec![mint(12, 4, 0), reorged(10, 7), mint(11, 0, 0), mint(10, 2, 0), reorged(12, 1), mint(10, 9, 0)];
+    sort_batch(&mut batch);
+
+    let keys: Vec<(u64, u64)> = batch.iter().map(|e| (e.block_number(), e.sort_key().1)).collect();
+    assert_eq!(keys, vec![(10, 2), (10, 7), (10, 9), (11, 0), (12, 1), (12, 4)]);
+}
+
+// Same position: the block time decides, then arrival order
+#[test]
+fn ties_fall_back_to_timestamp() {
+    let mut batch = vec![mint(5, 0, 300), mint(5, 0, 100), mint(5, 0, 200)];
+    sort_batch(&mut batch);
+
+    let times: Vec<i64> = batch.iter().map(|e| e.sort_key().2).collect();
+    assert_eq!(times, vec![100, 200, 300]);
+}
+
+#[test]
+fn table_names_rename_only_the_listed_tables() {
+    let tables = parse_table_names("uniswap_swaps=swaps_arbitrum, pool_twaps = twaps_arbitrum").unwrap();
+    assert_eq!(tables.get("uniswap_swaps"), "swaps_arbitrum");
+    assert_eq!(tables.get("pool_twaps"), "twaps_arbitrum");
+    assert_eq!(tables.get("uniswap_mints"), "uniswap_mints");
+    assert!(tables.is_renamed("uniswap_swaps") && !tables.is_renamed("uniswap_mints"));
+
+    assert!(parse_table_names("swaps=swaps_arbitrum").is_err());
+    assert!(parse_table_names("uniswap_swaps").is_err());
+    assert!(parse_table_names("uniswap_swaps=swaps; DROP TABLE pools").is_err());
+    assert!(parse_table_names("uniswap_swaps=other_db.swaps").is_err());
+    // Mints can't land in the swaps table
+    assert!(parse_table_names("uniswap_mints=uniswap_swaps").is_err());
+    assert!(parse_table_names("uniswap_swaps=shared,uniswap_burns=shared").is_err());
+}
+
+#[test]
+fn clickhouse_headers_parse_and_stay_out_of_the_log() {
+    let headers = parse_clickhouse_headers("X-Api-Key=c2VjcmV0==, X-Team = data").unwrap();
+    assert_eq!(
+        headers,
+        vec![("X-Api-Key".to_string(), "c2VjcmV0==".to_string()), ("X-Team".to_string(), "data".to_string())]
+    );
+    assert_eq!(parse_clickhouse_headers("").unwrap(), vec![]);
+
+    assert!(parse_clickhouse_headers("X-Api-Key").is_err());
+    assert!(parse_clickhouse_headers("X Api=1").is_err());
+    assert!(parse_clickhouse_headers("=1").is_err());
+    assert!(parse_clickhouse_headers("X-Team=a,x-team=b").is_err());
+
+    let settings = clickhouse("https://clickhouse.example:8443", headers);
+    let debug = format!("{:?}", settings);
+    assert!(debug.contains("X-Api-Key: <redacted>") && !debug.contains("c2VjcmV0"));
+}
+
+fn clickhouse(url: &str, headers: Vec<(String, String)>) -> ClickhouseSettings {
+    ClickhouseSettings {
+        urls: vec![url.to_string()],
+        user: "default".to_string(),
+        password: String::new(),
+        database: "crypto_db".to_string(),
+        ca_file: None,
+        insecure_skip_verify: false,
+        headers,
+        failover: FailoverSettings::default(),
+        compression: Compression::None,
+    }
+}
+
+// A bad CLICKHOUSE_CA_FILE fails while building the client, before any request
+#[test]
+fn unreadable_ca_files_are_rejected() {
+    let mut settings = clickhouse("https://clickhouse.example:8443", vec![]);
+    assert!(get_clickhouse_client(&settings, &settings.urls[0]).is_ok());
+
+    settings.ca_file = Some(std::env::temp_dir().join("no-such-ca.pem"));
+    assert!(get_clickhouse_client(&settings, &settings.urls[0]).is_err());
+
+    let empty = std::env::temp_dir().join(format!("empty-ca-{}.pem", std::process::id()));
+    std::fs::write(&empty, "").unwrap();
+    settings.ca_file = Some(empty.clone());
+    let result = get_clickhouse_client(&settings, &settings.urls[0]);
+    std::fs::remove_file(&empty).unwrap();
+    assert!(result.is_err());
+
+    settings.insecure_skip_verify = true;
+    assert!(get_clickhouse_client(&settings, &settings.urls[0]).is_ok());
+}
+
+#[test]
+fn insert_retries_back_off_up_to_a_cap() {
+    let retry = InsertRetry { retries: 5, base_delay: Duration::from_millis(500) };
+    assert_eq!(retry.delay(0), Duration::from_millis(500));
+    assert_eq!(retry.delay(3), Duration::from_secs(4));
+    assert_eq!(retry.delay(10), Duration::from_secs(30));
+    assert_eq!(retry.delay(40), Duration::from_secs(30));
+}
+
+// Fire-and-forget async inserts don't say the rows are in, the checkpoints can't follow them
+#[test]
+fn only_acknowledged_inserts_move_checkpoints() {
+    let mut settings = WriterSettings {
+        batch_size: 10,
+        max_age: Duration::from_secs(5),
+        retry: InsertRetry { retries: 0, base_delay: Duration::ZERO },
+        spill: SpillDir { path: "spill".into(), max_bytes: 0, policy: SpillPolicy::Stop },
+        tables: TableNames::default(),
+        workers: 1,
+        async_insert: None,
+        kafka: None,
+        nats: None,
+        stdout: false,
+    };
+    assert!(settings.acknowledged());
+    settings.async_insert = Some(AsyncInsert { wait: true });
+    assert!(settings.acknowledged());
+    settings.async_insert = Some(AsyncInsert { wait: false });
+    assert!(!settings.acknowledged());
+}
+
+// Only exceptions about the data split an insert, a missing table or bad password fails it whole
+#[test]
+fn row_errors_are_told_from_insert_errors() {
+    assert_eq!(error_code("Code: 53. DB::Exception: Type mismatch in IN or VALUES section"), Some(53));
+    assert_eq!(error_code("connection reset"), None);
+
+    let rejected = |message: &str| eyre::Report::new(clickhouse::error::Error::BadResponse(message.to_string())).wrap_err("Insert into uniswap_swaps failed");
+    assert!(is_row_error(&rejected("Code: 70. DB::Exception: Value out of range")));
+    assert!(!is_row_error(&rejected("Code: 60. DB::Exception: Table crypto_db.uniswap_swaps does not exist")));
+    assert!(!is_row_error(&rejected("Code: 516. DB::Exception: default: Authentication failed")));
+    assert!(!is_row_error(&eyre::eyre!("network error")));
+}
@@ -0,0 +1,68 @@
+mod common;
+
+use common::swap;
+use std::time::Duration;
+use uniswap_indexer::records::IndexedEvent;
+use uniswap_indexer::twap::Twaps;
+
+// (pool, window_start s, twap, n_swaps)
+fn rows(events: Vec<IndexedEvent>) -> Vec<(String, i64, f64, u64)> {
+    let mut rows: Vec<_> = events
+        .into_iter()
+        .map(|e| match e {
+            IndexedEvent::Twap(r) => (r.pool_address, r.window_start / 1000, r.twap_price, r.n_swaps),
+            other => panic!("not a twap row: {:?}", other),
+        })
+        .collect();
+    rows.sort_by(|a, b| (&a.0, a.1).cmp(&(&b.0, b.1)));
+    rows
+}
+
+#[test]
+fn windows_are_time_weighted_and_gaps_carry_the_price() {
+    let mut twaps = Twaps::new(&[Duration::from_secs(60)]);
+    assert!(twaps.apply(&[swap().pool("a").block(1).at(0).price(100.0).event(), swap().pool("a").block(2).at(30).price(200.0).event()]).is_empty());
+
+    // 100 for 30s, 200 for 30s
+    assert_eq!(rows(twaps.apply(&[swap().pool("a").block(3).at(90).price(300.0).event()])), vec![("a".into(), 0, 150.0, 2)]);
+
+    // [60, 120) is 200 then 300, [120, 180) has no swap and keeps 300
+    assert_eq!(
+        rows(twaps.apply(&[swap().pool("a").block(4).at(200).price(400.0).event()])),
+        vec![("a".into(), 60, 250.0, 1), ("a".into(), 120, 300.0, 0)]
+    );
+}
+
+// Another pool's swaps move the clock, a quiet pool still gets its carried-forward windows
+#[test]
+fn quiet_pools_close_on_the_stream_clock() {
+    let mut twaps = Twaps::new(&[Duration::from_secs(60)]);
+    twaps.apply(&[swap().pool("a").block(1).at(10).price(5.0).event(), swap().pool("b").block(2).at(20).price(7.0).event()]);
+
+    let closed = rows(twaps.apply(&[swap().pool("b").block(3).at(70).price(9.0).event()]));
+    assert_eq!(closed, vec![("a".into(), 0, 5.0, 1), ("b".into(), 0, 7.0, 1)]);
+}
+
+// A reconnect replays swaps already counted: they change nothing
+#[test]
+fn replayed_swaps_are_not_counted_twice() {
+    let mut twaps = Twaps::new(&[Duration::from_secs(60), Duration::from_secs(300)]);
+    twaps.apply(&[swap().pool("a").block(1).at(0).price(100.0).event(), swap().pool("a").block(2).at(30).price(200.0).event()]);
+    assert!(twaps.apply(&[swap().pool("a").block(1).at(0).price(100.0).event(), swap().pool("a").block(2).at(30).price(200.0).event()]).is_empty());
+
+    assert_eq!(rows(twaps.apply(&[swap().pool("a").block(3).at(60).price(100.0).event()])), vec![("a".into(), 0, 150.0, 2)]);
+}
+
+#[test]
+fn disabled_without_windows() {
+    let mut twaps = Twaps::new(&[]);
+    assert!(twaps.apply(&[swap().pool("a").block(1).at(0).price(1.0).event(), swap().pool("a").block(2).at(600).price(1.0).event()]).is_empty());
+}
+
+#[test]
+fn window_sizes_parse_with_units() {
+    let windows = uniswap_indexer::config::parse_windows("1m, 5m,30m,90s,3600").unwrap();
+    assert_eq!(windows.iter().map(Duration::as_secs).collect::<Vec<_>>(), vec![60, 300, 1800, 90, 3600]);
+    assert!(uniswap_indexer::config::parse_windows("0m").is_err());
+    assert!(uniswap_indexer::config::parse_windows("5d").is_err());
+}
@@ -0,0 +1,70 @@
+use alloy::primitives::{address, Address};
+use uniswap_indexer::pool::PoolRef;
+use uniswap_indexer::watermark::Watermarks;
+
+const POOL_A: Address = address!("88e6a0c2ddd26feeb64f039a2c41296fcb3f5640");
+const POOL_B: Address = address!("8ad599c3a0ff1de082011efddc58f1908eb6e6d8");
+
+// (pool, block, log_index) as a stream delivers them
+type Segment = [(Address, u64, u64)];
+
+fn run(watermarks: &mut Watermarks, segment: &Segment, out: &mut Vec<(Address, u64, u64)>) {
+    for &(pool, block, log_index) in segment {
+        if watermarks.admit(PoolRef::Address(pool), block, log_index) {
+            out.push((pool, block, log_index));
+        }
+    }
+}
+
+// The second segment replays the tail of the first after a reconnect
+#[test]
+fn overlapping_segments_emit_each_log_once() {
+    let mut watermarks = Watermarks::new();
+    let mut out = Vec::new();
+
+    let first = [(POOL_A, 100, 0), (POOL_A, 100, 3), (POOL_B, 100, 5), (POOL_A, 101, 1)];
+    run(&mut watermarks, &first, &mut out);
+
+    watermarks.resubscribed();
+    let second = [(POOL_A, 100, 3), (POOL_B, 100, 5), (POOL_A, 101, 1), (POOL_A, 102, 0), (POOL_B, 102, 2)];
+    run(&mut watermarks, &second, &mut out);
+
+    assert_eq!(
+        out,
+        vec![
+            (POOL_A, 100, 0),
+            (POOL_A, 100, 3),
+            (POOL_B, 100, 5),
+            (POOL_A, 101, 1),
+            (POOL_A, 102, 0),
+            (POOL_B, 102, 2),
+        ]
+    );
+}
+
+// Past the watermark the pool is back to normal flow
+#[test]
+fn guard_lifts_once_the_stream_passes_the_watermark() {
+    let mut watermarks = Watermarks::new();
+    let pool = PoolRef::Address(POOL_A);
+
+    assert!(watermarks.admit(pool, 200, 4));
+    watermarks.resubscribed();
+    assert!(watermarks.is_guarded(&pool));
+
+    assert!(!watermarks.admit(pool, 200, 4));
+    assert!(!watermarks.admit(pool, 199, 9));
+    assert!(watermarks.admit(pool, 200, 5));
+    assert!(!watermarks.is_guarded(&pool));
+}
+
+// Pools without anything emitted yet aren't held back after a resubscribe
+#[test]
+fn new_pools_are_not_guarded() {
+    let mut watermarks = Watermarks::new();
+    assert!(watermarks.admit(PoolRef::Address(POOL_A), 300, 0));
+
+    watermarks.resubscribed();
+    assert!(!watermarks.is_guarded(&PoolRef::Address(POOL_B)));
+    assert!(watermarks.admit(PoolRef::Address(POOL_B), 10, 0));
+}
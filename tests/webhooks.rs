@@ -0,0 +1,113 @@
+mod common;
+
+use serde_json::Value;
+use uniswap_indexer::metrics;
+use uniswap_indexer::records::SwapRecord;
+use uniswap_indexer::webhooks::{parse_rules, render, Alert, Condition, Rules};
+
+const MINUTE: i64 = 60_000;
+
+// A WETH/USDC swap `minute` minutes in, one block a minute
+fn at_minute(minute: i64, price: f64, usd: f64) -> SwapRecord {
+    let swap = common::swap().block(19_000_000 + minute as u64).tx("0xtx").at_millis(1_700_000_000_000 + minute * MINUTE).price(price).volume(usd).record();
+    SwapRecord { pair: "WETH/USDC".to_string(), ..swap }
+}
+
+fn rules(json: &str) -> Rules {
+    Rules::new(parse_rules(json).unwrap())
+}
+
+fn text(alert: &Alert) -> String {
+    let body: Value = serde_json::from_str(&alert.body).unwrap();
+    assert_eq!(body["rule"], alert.rule);
+    body["text"].as_str().unwrap().to_string()
+}
+
+#[test]
+fn rules_parse_with_defaults() {
+    let rules = parse_rules(
+        r#"[
+            {"name": "whale", "condition": "swap_usd", "min_usd": 1000000, "url": "https://hooks.example/a"},
+            {"name": "jump", "condition": "price_move", "percent": 5, "minutes": 10, "url": "http://localhost/b",
+             "template": "{pair} {percent}", "cooldown_seconds": 0}
+        ]"#,
+    )
+    .unwrap();
+    assert_eq!(rules[0].condition, Condition::SwapUsd { min_usd: 1_000_000.0 });
+    assert_eq!((rules[0].template.as_deref(), rules[0].cooldown_seconds), (None, 60));
+    assert_eq!(rules[1].condition, Condition::PriceMove { percent: 5.0, minutes: 10 });
+    assert_eq!((rules[1].template.as_deref(), rules[1].cooldown_seconds), (Some("{pair} {percent}"), 0));
+}
+
+#[test]
+fn invalid_rules_are_rejected() {
+    for json in [
+        r#"[{"name": "a", "condition": "swap_usd", "min_usd": 1, "url": "ftp://x"}]"#,
+        r#"[{"name": "a", "condition": "swap_usd", "min_usd": 0, "url": "https://x"}]"#,
+        r#"[{"name": "a", "condition": "price_move", "percent": 5, "minutes": 0, "url": "https://x"}]"#,
+        r#"[{"name": "a", "condition": "reconnects", "count": 0, "url": "https://x"}]"#,
+        r#"[{"name": "a", "condition": "gas_price", "url": "https://x"}]"#,
+        r#"[{"name": "a", "condition": "reconnects", "count": 1, "url": "https://x"},
+            {"name": "a", "condition": "reconnects", "count": 2, "url": "https://x"}]"#,
+    ] {
+        assert!(parse_rules(json).is_err(), "{}", json);
+    }
+}
+
+#[test]
+fn swaps_at_or_above_the_threshold_alert() {
+    let mut rules = rules(r#"[{"name": "whale", "condition": "swap_usd", "min_usd": 1000, "url": "https://x", "cooldown_seconds": 0}]"#);
+    assert!(rules.on_swap(&at_minute(0, 2000.0, 999.99), 0).is_empty());
+    let alerts = rules.on_swap(&at_minute(1, 2000.0, 1000.0), 0);
+    assert_eq!(alerts.len(), 1);
+    assert_eq!(alerts[0].url, "https://x");
+    assert_eq!(text(&alerts[0]), "whale: $1000.00 swap on WETH/USDC (0xpool), tx 0xtx");
+}
+
+// The window is block time: a slow climb doesn't alert, the same move within `minutes` does
+#[test]
+fn price_moves_within_the_window_alert() {
+    let mut rules = rules(
+        r#"[{"name": "jump", "condition": "price_move", "percent": 5, "minutes": 10, "url": "https://x",
+             "cooldown_seconds": 0, "template": "{percent}% in {minutes} min, now {price}"}]"#,
+    );
+    for (minute, price) in [(0, 100.0), (8, 103.0), (16, 106.0), (24, 109.0)] {
+        assert!(rules.on_swap(&at_minute(minute, price, 1.0), 0).is_empty(), "minute {}", minute);
+    }
+    let alerts = rules.on_swap(&at_minute(25, 104.0, 1.0), 0);
+    assert!(alerts.is_empty());
+    let alerts = rules.on_swap(&at_minute(26, 112.0, 1.0), 0);
+    assert_eq!(alerts.iter().map(text).collect::<Vec<_>>(), vec!["7.69% in 10 min, now 112"]);
+
+    // Falls count too
+    let alerts = rules.on_swap(&at_minute(27, 100.0, 1.0), 0);
+    assert_eq!(alerts.iter().map(text).collect::<Vec<_>>(), vec!["10.71% in 10 min, now 100"]);
+}
+
+#[test]
+fn more_reconnects_than_the_count_within_an_hour_alert() {
+    let mut rules = rules(r#"[{"name": "flaky", "condition": "reconnects", "count": 2, "url": "https://x", "cooldown_seconds": 0}]"#);
+    assert!(rules.on_reconnect(0).is_empty());
+    assert!(rules.on_reconnect(10 * MINUTE).is_empty());
+    // The first is out of the hour by now
+    assert!(rules.on_reconnect(61 * MINUTE).is_empty());
+    let alerts = rules.on_reconnect(62 * MINUTE);
+    assert_eq!(alerts.iter().map(text).collect::<Vec<_>>(), vec!["flaky: 3 reconnects in the last hour"]);
+}
+
+#[test]
+fn matches_within_the_cooldown_are_suppressed() {
+    let mut rules = rules(r#"[{"name": "cooled", "condition": "swap_usd", "min_usd": 1, "url": "https://x", "cooldown_seconds": 60}]"#);
+    let suppressed = || metrics::WEBHOOKS.with_label_values(&["cooled", "suppressed"]).get();
+    assert_eq!(rules.on_swap(&at_minute(0, 1.0, 5.0), 0).len(), 1);
+    assert!(rules.on_swap(&at_minute(1, 1.0, 5.0), 59_999).is_empty());
+    assert_eq!(suppressed(), 1);
+    assert_eq!(rules.on_swap(&at_minute(2, 1.0, 5.0), 60_000).len(), 1);
+    assert_eq!(suppressed(), 1);
+}
+
+#[test]
+fn render_fills_known_placeholders_only() {
+    let values = [("rule", "r".to_string()), ("usd", "12.50".to_string())];
+    assert_eq!(render("{rule}: ${usd} {unknown}", &values), "r: $12.50 {unknown}");
+}
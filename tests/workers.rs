@@ -0,0 +1,25 @@
+use uniswap_indexer::workers::FlushOrder;
+
+// Batch 1 finishing first waits for batch 0, then both are released in order
+#[test]
+fn results_are_released_in_batch_order() {
+    let mut order = FlushOrder::new();
+    let (a, b, c) = (order.start(), order.start(), order.start());
+    assert_eq!(order.in_flight(), 3);
+
+    assert!(order.finish(b, "b").is_empty());
+    assert_eq!(order.finish(a, "a"), vec!["a", "b"]);
+    assert_eq!(order.in_flight(), 1);
+    assert_eq!(order.finish(c, "c"), vec!["c"]);
+    assert_eq!(order.in_flight(), 0);
+}
+
+// With one worker batches finish in order and are released one by one, like a single writer
+#[test]
+fn in_order_finishes_release_immediately() {
+    let mut order = FlushOrder::new();
+    for i in 0..5 {
+        let seq = order.start();
+        assert_eq!(order.finish(seq, i), vec![i]);
+    }
+}
@@ -0,0 +1,81 @@
+mod common;
+
+use common::swap;
+use futures_util::StreamExt;
+use serde_json::Value;
+use std::time::Duration;
+use tokio_tungstenite::tungstenite::{Error, Message};
+use uniswap_indexer::metrics;
+use uniswap_indexer::ws_server::{report, start, WsFilter, WsServerSettings};
+
+const POOL: &str = "0x88e6A0c2dDD26FEEb64F039a2c41296FcB3f5640";
+
+#[test]
+fn filters_come_from_the_query() {
+    assert_eq!(WsFilter::parse(None).unwrap(), WsFilter::default());
+    let filter = WsFilter::parse(Some("pool=0xa,0xb&pool=0xc&min_usd=2500.5")).unwrap();
+    assert_eq!(filter.pools, vec!["0xa", "0xb", "0xc"]);
+    assert_eq!(filter.min_usd, Some(2500.5));
+    assert!(WsFilter::parse(Some("min_usd=lots")).is_err());
+    assert!(WsFilter::parse(Some("pools=0xa")).is_err());
+}
+
+#[test]
+fn filters_match_pools_in_any_case_and_priced_swaps_from_min_usd() {
+    let filter = WsFilter { pools: vec![POOL.to_lowercase()], min_usd: Some(1_000.0) };
+    assert!(filter.matches(POOL, Some(1_000.0)));
+    assert!(!filter.matches(POOL, Some(999.0)));
+    assert!(!filter.matches(POOL, None));
+    assert!(!filter.matches("0xother", Some(5_000.0)));
+    assert!(WsFilter::default().matches("0xother", None));
+}
+
+async fn eventually(what: impl Fn() -> bool) {
+    tokio::time::timeout(Duration::from_secs(5), async {
+        while !what() {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .expect("timed out");
+}
+
+fn status(result: Result<impl Sized, Error>) -> u16 {
+    match result {
+        Err(Error::Http(response)) => response.status().as_u16(),
+        Err(e) => panic!("expected an HTTP error, got {}", e),
+        Ok(_) => panic!("expected an HTTP error, got a connection"),
+    }
+}
+
+#[tokio::test]
+async fn clients_get_their_swaps_up_to_the_client_limit() {
+    let settings = WsServerSettings { addr: "127.0.0.1:0".parse().unwrap(), max_clients: 1, send_timeout: Duration::from_secs(5), buffer: 16 };
+    let addr = start(&settings).await.unwrap();
+
+    let url = format!("ws://{}/swaps?pool={}&min_usd=1000", addr, POOL.to_lowercase());
+    let (mut client, _) = tokio_tungstenite::connect_async(url.as_str()).await.unwrap();
+    assert_eq!(metrics::WS_CLIENTS.get(), 1);
+    assert_eq!(status(tokio_tungstenite::connect_async(url.as_str()).await), 503);
+    assert_eq!(status(tokio_tungstenite::connect_async(format!("ws://{}/prices", addr)).await), 404);
+    assert_eq!(status(tokio_tungstenite::connect_async(format!("ws://{}/swaps?min_usd=x", addr)).await), 400);
+
+    // Swaps are only serialized while someone listens, keep reporting until the client is subscribed
+    let reporter = tokio::spawn(async {
+        loop {
+            report(&swap().block(1).pool("0xother").volume(5_000.0).record());
+            report(&swap().block(2).pool(POOL).volume(10.0).record());
+            report(&swap().block(3).pool(POOL).volume(5_000.0).record());
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+    });
+    let message = tokio::time::timeout(Duration::from_secs(5), client.next()).await.unwrap().unwrap().unwrap();
+    reporter.abort();
+    let Message::Text(text) = message else { panic!("expected text, got {:?}", message) };
+    let row: Value = serde_json::from_str(&text).unwrap();
+    assert_eq!((row["block_number"].as_u64(), row["pool_address"].as_str()), (Some(3), Some(POOL)));
+
+    client.close(None).await.unwrap();
+    eventually(|| metrics::WS_CLIENTS.get() == 0).await;
+    let (_client, _) = tokio_tungstenite::connect_async(url.as_str()).await.unwrap();
+}